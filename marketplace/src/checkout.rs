@@ -0,0 +1,508 @@
+use crate::{
+    constants::NO_DEPOSIT,
+    external::nft_contract,
+    listing::{
+        id::ListingIdJson, sale::Sale, status::ListingStatus,
+    },
+    *,
+};
+use near_sdk::{
+    json_types::{U128, U64},
+    PromiseResult,
+};
+
+pub const CHECKOUT_ITEMS_MAX: usize = 8;
+pub const SWEEP_SCAN_MAX: usize = 50; // floor entries looked at by a single sweep
+
+#[cfg(test)]
+#[path = "checkout_tests.rs"]
+mod checkout_tests;
+
+const NFT_MINT_GAS: Gas = Gas(15_000_000_000_000); // TODO: measure
+const NFT_TRANSFER_GAS: Gas = Gas(15_000_000_000_000); // TODO: measure
+const CHECKOUT_COMPLETION_BASE_GAS: Gas = Gas(5_000_000_000_000); // TODO: measure
+const CHECKOUT_COMPLETION_ITEM_GAS: Gas = Gas(5_000_000_000_000); // TODO: measure
+
+// one token from a primary listing (minted) or the token of a secondary listing
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct CartItem {
+    pub listing_id: ListingIdJson,
+    pub max_price_yocto: U128, // the price the buyer was presented with
+}
+
+// what the completion needs to settle an item, in cart order
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct CheckoutItem {
+    pub listing_id: ListingIdJson,
+    pub round: Option<U64>, // sale round of a primary listing
+    pub seller_id: AccountId,
+    pub price_yocto: U128,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct CheckoutItemResult {
+    pub listing_id: ListingIdJson,
+    pub token_id: Option<NftId>, // None if the purchase failed and its price was refunded
+    pub price_yocto: U128,       // paid to the seller, 0 if the purchase failed
+}
+
+#[near_bindgen]
+impl MarketplaceContract {
+    // buys the items at their buy now prices in one go; the deposit must cover all of them (plus
+    // 1yN per secondary item for nft_transfer) and the buyer's storage deposit the worst case
+    // storage of the primary mints; mints and transfers run in parallel and whatever fails is
+    // refunded, the rest goes to the sellers; presale phases are not supported here
+    // tokens go to receiver_id (the caller if missing), the caller pays and gets the refunds
+    #[payable]
+    pub fn buy_many(&mut self, items: Vec<CartItem>, receiver_id: Option<AccountId>) -> Promise {
+        self.internal_checkout(items, receiver_id)
+    }
+
+    // buys up to count of the cheapest running buy-now secondary listings of the NFT contract,
+    // or of one of its collections, as long as their total price stays within max_total_yocto;
+    // the listings are picked from the floor index and bought the same way buy_many does, so
+    // the deposit must cover the picked prices plus 1yN each and unused deposit is refunded
+    #[payable]
+    pub fn sweep(
+        &mut self,
+        nft_contract_id: AccountId,
+        collection_id: Option<U64>,
+        count: u64,
+        max_total_yocto: U128,
+        receiver_id: Option<AccountId>,
+    ) -> Promise {
+        assert!(
+            count > 0 && count as usize <= CHECKOUT_ITEMS_MAX,
+            "Count must be between 1 and {}",
+            CHECKOUT_ITEMS_MAX
+        );
+
+        let buyer_id = env::predecessor_account_id();
+        let receiver_id = receiver_id.unwrap_or_else(|| buyer_id.clone());
+
+        let mut items: Vec<CartItem> = Vec::new();
+        let mut total_yocto: Balance = 0;
+        // floor entries come in price order, primary listings are skipped; stale entries cost
+        // gas as well, so only the first few are looked at
+        let floor = self
+            .internal_floor(&nft_contract_id, collection_id.map(|c| c.0))
+            .take(SWEEP_SCAN_MAX);
+        for (_, listing_id) in floor {
+            if items.len() as u64 == count {
+                break;
+            }
+            let listing_id = match listing_id {
+                ListingId::Secondary(listing_id) => listing_id,
+                ListingId::Primary(_) | ListingId::Bundle(_) => continue,
+            };
+            let mut listing = self.secondary_listings_by_id.get(&listing_id).unwrap();
+            listing.update_status();
+            if listing.status != ListingStatus::Running
+                || listing.seller_id == buyer_id
+                || listing.seller_id == receiver_id
+            {
+                continue;
+            }
+            // the floor is price ordered, nothing further fits either
+            let price_yocto = listing.price_yocto.unwrap();
+            if total_yocto + price_yocto > max_total_yocto.0 {
+                break;
+            }
+            total_yocto += price_yocto;
+            items.push(CartItem {
+                listing_id: ListingIdJson::Secondary {
+                    nft_contract_id: listing_id.nft_contract_id,
+                    token_id: listing_id.token_id,
+                },
+                max_price_yocto: U128(price_yocto),
+            });
+        }
+        assert!(!items.is_empty(), "No listings within the price limit");
+
+        self.internal_checkout(items, Some(receiver_id))
+    }
+}
+
+impl MarketplaceContract {
+    fn internal_checkout(
+        &mut self,
+        items: Vec<CartItem>,
+        receiver_id: Option<AccountId>,
+    ) -> Promise {
+        assert!(
+            !items.is_empty() && items.len() <= CHECKOUT_ITEMS_MAX,
+            "Number of items must be between 1 and {}",
+            CHECKOUT_ITEMS_MAX
+        );
+
+        let buyer_id = env::predecessor_account_id();
+        let receiver_id = receiver_id.unwrap_or_else(|| buyer_id.clone());
+
+        // the same primary listing may come several times, one token each
+        let mut quantities_by_primary_listing: HashMap<(AccountId, NftCollectionId), u64> =
+            HashMap::new();
+        let mut secondary_listing_ids: Vec<SecondaryListingId> = Vec::new();
+        let mut checkout_items: Vec<CheckoutItem> = Vec::new();
+        let mut purchases: Vec<Promise> = Vec::new();
+        let mut required_deposit: Balance = 0;
+        let nft_worst_case_storage_cost =
+            self.config.storage_limits.nft_mint_storage_max as Balance * env::storage_byte_cost();
+
+        for item in items {
+            let max_price_yocto = item.max_price_yocto.0;
+            match item.listing_id {
+                ListingIdJson::Primary {
+                    nft_contract_id,
+                    collection_id,
+                } => {
+                    let listing_id = PrimaryListingId {
+                        nft_contract_id,
+                        collection_id: collection_id.0,
+                    };
+                    let mut listing = self
+                        .primary_listings_by_id
+                        .get(&listing_id)
+                        .expect("Could not find NFT listing");
+                    listing.update_status();
+
+                    assert!(
+                        listing.current_phase_index().is_none(),
+                        "Presale phase is active, use primary_listing_buy"
+                    );
+                    assert!(
+                        listing.status == ListingStatus::Running,
+                        "This listing is {}",
+                        listing.status.as_str()
+                    );
+                    assert!(buyer_id != listing.seller_id, "Cannot buy from yourself");
+                    let price_yocto = listing
+                        .price_yocto
+                        .expect("Buy Now is not possible for this listing");
+                    assert!(
+                        price_yocto <= max_price_yocto,
+                        "Price of {} exceeds the maximum price of {}",
+                        price_yocto,
+                        max_price_yocto
+                    );
+
+                    // every token is reserved as it's added, so the checks see the earlier
+                    // tokens of the same listing; the completion releases failed mints
+                    *quantities_by_primary_listing
+                        .entry((listing_id.nft_contract_id.clone(), listing_id.collection_id))
+                        .or_insert(0) += 1;
+                    let supply_available = listing.supply_available();
+                    assert!(
+                        supply_available >= 1,
+                        "Only {} NFTs left",
+                        supply_available
+                    );
+                    if let Some(max_per_account) = listing.max_per_account {
+                        let purchased = listing.purchases_by_account.get(&buyer_id).unwrap_or(0);
+                        assert!(
+                            purchased + 1 <= max_per_account,
+                            "Limit is {} per account, you have already bought {}",
+                            max_per_account,
+                            purchased
+                        );
+                    }
+                    self.internal_primary_listing_reserve(&mut listing, &buyer_id, 1, None);
+                    self.primary_listings_by_id.insert(&listing_id, &listing);
+
+                    required_deposit += price_yocto;
+                    purchases.push(nft_contract::mint(
+                        U64(listing_id.collection_id),
+                        receiver_id.clone(),
+                        None, // perpetual royalties
+                        listing_id.nft_contract_id.clone(),
+                        nft_worst_case_storage_cost,
+                        NFT_MINT_GAS,
+                    ));
+                    checkout_items.push(CheckoutItem {
+                        listing_id: ListingIdJson::Primary {
+                            nft_contract_id: listing_id.nft_contract_id,
+                            collection_id,
+                        },
+                        round: Some(U64(listing.round)),
+                        seller_id: listing.seller_id,
+                        price_yocto: U128(price_yocto),
+                    });
+                }
+                ListingIdJson::Secondary {
+                    nft_contract_id,
+                    token_id,
+                } => {
+                    let listing_id = SecondaryListingId {
+                        nft_contract_id,
+                        token_id,
+                    };
+                    assert!(
+                        !secondary_listing_ids
+                            .iter()
+                            .any(|id| id.nft_contract_id == listing_id.nft_contract_id
+                                && id.token_id == listing_id.token_id),
+                        "Token {} is in the cart more than once",
+                        listing_id.token_id
+                    );
+                    let mut listing = self
+                        .secondary_listings_by_id
+                        .get(&listing_id)
+                        .expect("Could not find NFT listing");
+                    listing.update_status();
+                    self.secondary_listings_by_id.insert(&listing_id, &listing);
+
+                    assert!(
+                        listing.status == ListingStatus::Running,
+                        "This listing is {}",
+                        listing.status.as_str()
+                    );
+                    assert!(buyer_id != listing.seller_id, "Cannot buy from yourself");
+                    assert!(
+                        receiver_id != listing.seller_id,
+                        "Cannot buy for the seller"
+                    );
+                    let price_yocto = listing
+                        .price_yocto
+                        .expect("Buy Now is not possible for this listing");
+                    assert!(
+                        price_yocto <= max_price_yocto,
+                        "Price of {} exceeds the maximum price of {}",
+                        price_yocto,
+                        max_price_yocto
+                    );
+
+                    // the marketplace owns the token in custody, no approval involved
+                    let approval_id = if listing.is_in_custody {
+                        None
+                    } else {
+                        Some(listing.approval_id)
+                    };
+                    required_deposit += price_yocto + 1; // 1yN required by nft_transfer
+                    purchases.push(nft_contract::nft_transfer(
+                        receiver_id.clone(),
+                        listing_id.token_id.clone(),
+                        approval_id,
+                        None,
+                        listing_id.nft_contract_id.clone(),
+                        1,
+                        NFT_TRANSFER_GAS,
+                    ));
+                    checkout_items.push(CheckoutItem {
+                        listing_id: ListingIdJson::Secondary {
+                            nft_contract_id: listing_id.nft_contract_id.clone(),
+                            token_id: listing_id.token_id.clone(),
+                        },
+                        round: None,
+                        seller_id: listing.seller_id,
+                        price_yocto: U128(price_yocto),
+                    });
+                    secondary_listing_ids.push(listing_id);
+                }
+                ListingIdJson::Bundle { .. } => {
+                    env::panic_str("Bundles cannot be bought with buy_many")
+                }
+            }
+        }
+
+        let attached_deposit = env::attached_deposit();
+        assert!(
+            attached_deposit >= required_deposit,
+            "Attached deposit of {} is insufficient to pay the total of {}",
+            attached_deposit,
+            required_deposit
+        );
+
+        let mints_count: u64 = quantities_by_primary_listing.values().sum();
+        let total_worst_case_storage_cost = nft_worst_case_storage_cost * mints_count as u128;
+        let current_deposit: Balance = self.storage_deposits.get(&buyer_id).unwrap_or(0);
+        assert!(
+            current_deposit >= total_worst_case_storage_cost,
+            "Your storage deposit is too low. Must be {} yN to process transaction. Please increase your deposit.",
+            total_worst_case_storage_cost
+        );
+
+        let completion_gas = Gas(CHECKOUT_COMPLETION_BASE_GAS.0
+            + CHECKOUT_COMPLETION_ITEM_GAS.0 * checkout_items.len() as u64);
+        let mut purchases = purchases.into_iter();
+        let first_purchase = purchases.next().unwrap();
+        purchases
+            .fold(first_purchase, |all, purchase| all.and(purchase))
+            .then(ext_self_nft::buy_many_completion(
+                buyer_id,
+                receiver_id,
+                U128(attached_deposit),
+                checkout_items,
+                env::current_account_id(),
+                NO_DEPOSIT,
+                completion_gas,
+            ))
+    }
+}
+
+#[ext_contract(ext_self_nft)]
+trait CheckoutCallback {
+    fn buy_many_completion(
+        &mut self,
+        buyer_id: AccountId,
+        receiver_id: AccountId,
+        attached_deposit: U128,
+        items: Vec<CheckoutItem>,
+    ) -> Vec<CheckoutItemResult>;
+}
+
+trait CheckoutCallback {
+    fn buy_many_completion(
+        &mut self,
+        buyer_id: AccountId,
+        receiver_id: AccountId,
+        attached_deposit: U128,
+        items: Vec<CheckoutItem>,
+    ) -> Vec<CheckoutItemResult>;
+}
+
+#[near_bindgen]
+impl CheckoutCallback for MarketplaceContract {
+    // one promise result per item, in cart order; nothing here may panic, it'd revert the
+    // payments and refunds of the whole cart: failed items are refunded and the listings may have
+    // been changed or removed while the mints and transfers were in flight
+    #[private]
+    fn buy_many_completion(
+        &mut self,
+        buyer_id: AccountId,
+        receiver_id: AccountId,
+        attached_deposit: U128,
+        items: Vec<CheckoutItem>,
+    ) -> Vec<CheckoutItemResult> {
+        let results_count = env::promise_results_count();
+        let mut paid: Balance = 0;
+        let mut results: Vec<CheckoutItemResult> = Vec::new();
+        for (result_index, item) in items.into_iter().enumerate() {
+            let price_yocto = item.price_yocto.0;
+            let result = if (result_index as u64) < results_count {
+                env::promise_result(result_index as u64)
+            } else {
+                PromiseResult::Failed
+            };
+            // an unexpected mint value counts as a failed mint
+            let minted = match (&result, &item.listing_id) {
+                (PromiseResult::Successful(val), ListingIdJson::Primary { .. }) => {
+                    near_sdk::serde_json::from_slice::<(NftId, U64)>(val).ok()
+                }
+                _ => None,
+            };
+            let token_id = match (result, &item.listing_id, minted) {
+                (
+                    _,
+                    ListingIdJson::Primary {
+                        nft_contract_id,
+                        collection_id,
+                    },
+                    Some((token_id, token_storage_bytes)),
+                ) => {
+                    let (_, storage_shortfall) = self.internal_primary_listing_record_mints(
+                        &PrimaryListingId {
+                            nft_contract_id: nft_contract_id.clone(),
+                            collection_id: collection_id.0,
+                        },
+                        item.round.map_or(0, |r| r.0),
+                        &item.seller_id,
+                        &buyer_id,
+                        &receiver_id,
+                        price_yocto,
+                        1,
+                        &[token_id.clone()],
+                        token_storage_bytes.0,
+                        None,
+                    );
+                    let seller_proceeds = price_yocto.saturating_sub(storage_shortfall);
+                    if seller_proceeds > 0 {
+                        Promise::new(item.seller_id.clone()).transfer(seller_proceeds);
+                    }
+                    paid += price_yocto;
+                    Some(token_id)
+                }
+                // failed mint, the reservation is released and the price stays in the refund
+                (
+                    _,
+                    ListingIdJson::Primary {
+                        nft_contract_id,
+                        collection_id,
+                    },
+                    None,
+                ) => {
+                    self.internal_primary_listing_record_mints(
+                        &PrimaryListingId {
+                            nft_contract_id: nft_contract_id.clone(),
+                            collection_id: collection_id.0,
+                        },
+                        item.round.map_or(0, |r| r.0),
+                        &item.seller_id,
+                        &buyer_id,
+                        &receiver_id,
+                        price_yocto,
+                        1,
+                        &[],
+                        0,
+                        None,
+                    );
+                    None
+                }
+                (
+                    PromiseResult::Successful(_),
+                    ListingIdJson::Secondary {
+                        nft_contract_id,
+                        token_id,
+                    },
+                    _,
+                ) => {
+                    let listing_id = SecondaryListingId {
+                        nft_contract_id: nft_contract_id.clone(),
+                        token_id: token_id.clone(),
+                    };
+                    Promise::new(item.seller_id.clone()).transfer(price_yocto);
+                    paid += price_yocto + 1; // 1yN went with nft_transfer
+
+                    // standing bids are refunded, the listing storage goes back to the seller;
+                    // a listing made since by someone else is left alone
+                    let collection_id = match self.secondary_listings_by_id.get(&listing_id) {
+                        Some(listing) if listing.seller_id == item.seller_id => {
+                            self.internal_close_secondary_listing(&listing_id)
+                                .collection_id
+                        }
+                        _ => None,
+                    };
+                    self.internal_record_sale(
+                        Sale {
+                            listing_id: Some(ListingId::Secondary(listing_id)),
+                            token_id: token_id.clone(),
+                            buyer_id: receiver_id.clone(),
+                            seller_id: item.seller_id.clone(),
+                            price_yocto,
+                            timestamp: env::block_timestamp(),
+                        },
+                        nft_contract_id,
+                        collection_id,
+                    );
+                    Some(token_id.clone())
+                }
+                // failed transfer, the price stays in the refund
+                _ => None,
+            };
+
+            results.push(CheckoutItemResult {
+                listing_id: item.listing_id,
+                price_yocto: U128(if token_id.is_some() { price_yocto } else { 0 }),
+                token_id,
+            });
+        }
+
+        let refund = attached_deposit.0.saturating_sub(paid);
+        if refund > 0 {
+            Promise::new(buyer_id).transfer(refund);
+        }
+
+        results
+    }
+}
@@ -0,0 +1,373 @@
+#[cfg(test)]
+mod checkout_tests {
+    use super::super::{
+        CartItem, CheckoutCallback, CheckoutItem, CheckoutItemResult, SWEEP_SCAN_MAX,
+    };
+    use crate::{listing::id::ListingIdJson, test_utils::*, *};
+    use near_sdk::json_types::{U128, U64};
+    use near_sdk::PromiseResult;
+
+    const TOKEN_ID: &str = "1:1";
+
+    fn primary_id() -> ListingIdJson {
+        ListingIdJson::Primary {
+            nft_contract_id: account(NFT_CONTRACT_ID),
+            collection_id: U64(0),
+        }
+    }
+
+    fn secondary_id() -> ListingIdJson {
+        ListingIdJson::Secondary {
+            nft_contract_id: account(NFT_CONTRACT_ID),
+            token_id: TOKEN_ID.to_string(),
+        }
+    }
+
+    // two tokens of the primary listing and the token of the secondary one, which has a bid
+    fn setup() -> (MarketplaceContract, PrimaryListingId, SecondaryListingId) {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        let primary_listing_id =
+            add_primary_listing(&mut marketplace, 0, 3, Some(PRICE_YOCTO), None, None);
+        let secondary_listing_id = add_secondary_listing(
+            &mut marketplace,
+            TOKEN_ID,
+            Some(PRICE_YOCTO),
+            Some(MIN_BID_YOCTO),
+            false,
+        );
+        set_context(BIDDER_ACCOUNT_ID, NOW, MIN_BID_YOCTO);
+        marketplace.secondary_listing_place_bid(
+            account(NFT_CONTRACT_ID),
+            TOKEN_ID.to_string(),
+            U128(MIN_BID_YOCTO),
+        );
+
+        set_context(BUYER_ACCOUNT_ID, NOW, 3 * PRICE_YOCTO + 1);
+        marketplace.buy_many(
+            vec![
+                CartItem {
+                    listing_id: primary_id(),
+                    max_price_yocto: U128(PRICE_YOCTO),
+                },
+                CartItem {
+                    listing_id: primary_id(),
+                    max_price_yocto: U128(PRICE_YOCTO),
+                },
+                CartItem {
+                    listing_id: secondary_id(),
+                    max_price_yocto: U128(PRICE_YOCTO),
+                },
+            ],
+            None,
+        );
+        (marketplace, primary_listing_id, secondary_listing_id)
+    }
+
+    fn checkout_items() -> Vec<CheckoutItem> {
+        vec![
+            CheckoutItem {
+                listing_id: primary_id(),
+                round: Some(U64(0)),
+                seller_id: account(SELLER_ACCOUNT_ID),
+                price_yocto: U128(PRICE_YOCTO),
+            },
+            CheckoutItem {
+                listing_id: primary_id(),
+                round: Some(U64(0)),
+                seller_id: account(SELLER_ACCOUNT_ID),
+                price_yocto: U128(PRICE_YOCTO),
+            },
+            CheckoutItem {
+                listing_id: secondary_id(),
+                round: None,
+                seller_id: account(SELLER_ACCOUNT_ID),
+                price_yocto: U128(PRICE_YOCTO),
+            },
+        ]
+    }
+
+    fn complete(
+        marketplace: &mut MarketplaceContract,
+        promise_results: Vec<PromiseResult>,
+    ) -> Vec<CheckoutItemResult> {
+        set_callback_context(NOW, promise_results);
+        marketplace.buy_many_completion(
+            account(BUYER_ACCOUNT_ID),
+            account(BUYER_ACCOUNT_ID),
+            U128(3 * PRICE_YOCTO + 1),
+            checkout_items(),
+        )
+    }
+
+    fn token_ids(results: &Vec<CheckoutItemResult>) -> Vec<Option<String>> {
+        results
+            .iter()
+            .map(|result| result.token_id.clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_buy_many_reserves_primary_supply() {
+        let (marketplace, primary_listing_id, _) = setup();
+
+        let listing = marketplace
+            .primary_listings_by_id
+            .get(&primary_listing_id)
+            .unwrap();
+        assert_eq!(listing.supply_pending, 2);
+        assert_eq!(listing.supply_left, 3);
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Only 0 NFTs left"#)]
+    fn test_buy_many_cannot_overshoot_supply() {
+        let (mut marketplace, _, _) = setup();
+
+        set_context(BUYER2_ACCOUNT_ID, NOW, 2 * PRICE_YOCTO);
+        marketplace.buy_many(
+            vec![
+                CartItem {
+                    listing_id: primary_id(),
+                    max_price_yocto: U128(PRICE_YOCTO),
+                },
+                CartItem {
+                    listing_id: primary_id(),
+                    max_price_yocto: U128(PRICE_YOCTO),
+                },
+            ],
+            None,
+        );
+    }
+
+    #[test]
+    fn test_buy_many_completion() {
+        let (mut marketplace, primary_listing_id, secondary_listing_id) = setup();
+
+        let results = complete(
+            &mut marketplace,
+            vec![
+                mint_result("0:1", 800),
+                mint_result("0:2", 800),
+                PromiseResult::Successful(vec![]),
+            ],
+        );
+
+        assert_eq!(
+            token_ids(&results),
+            vec![
+                Some("0:1".to_string()),
+                Some("0:2".to_string()),
+                Some(TOKEN_ID.to_string())
+            ]
+        );
+        let listing = marketplace
+            .primary_listings_by_id
+            .get(&primary_listing_id)
+            .unwrap();
+        assert_eq!(listing.supply_left, 1);
+        assert_eq!(listing.supply_pending, 0);
+        // the secondary listing is closed and its bid refunded
+        assert!(marketplace
+            .secondary_listings_by_id
+            .get(&secondary_listing_id)
+            .is_none());
+        assert!(marketplace
+            .bids_by_account(account(BIDDER_ACCOUNT_ID), None, None)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_buy_many_completion_refunds_failed_items() {
+        let (mut marketplace, primary_listing_id, secondary_listing_id) = setup();
+
+        let results = complete(
+            &mut marketplace,
+            vec![
+                mint_result("0:1", 800),
+                PromiseResult::Failed,
+                PromiseResult::Failed,
+            ],
+        );
+
+        assert_eq!(
+            token_ids(&results),
+            vec![Some("0:1".to_string()), None, None]
+        );
+        assert_eq!(results[1].price_yocto, U128(0));
+        let listing = marketplace
+            .primary_listings_by_id
+            .get(&primary_listing_id)
+            .unwrap();
+        assert_eq!(listing.supply_left, 2);
+        assert_eq!(listing.supply_pending, 0);
+        assert!(marketplace
+            .secondary_listings_by_id
+            .get(&secondary_listing_id)
+            .is_some());
+    }
+
+    #[test]
+    fn test_buy_many_completion_tolerates_unexpected_results() {
+        let (mut marketplace, primary_listing_id, _) = setup();
+
+        // garbage from the first mint, the result of the last item is missing
+        let results = complete(
+            &mut marketplace,
+            vec![
+                PromiseResult::Successful(b"\"0:1\"".to_vec()),
+                mint_result("0:2", 800),
+            ],
+        );
+
+        assert_eq!(
+            token_ids(&results),
+            vec![None, Some("0:2".to_string()), None]
+        );
+        let listing = marketplace
+            .primary_listings_by_id
+            .get(&primary_listing_id)
+            .unwrap();
+        assert_eq!(listing.supply_left, 2);
+        assert_eq!(listing.supply_pending, 0);
+    }
+
+    #[test]
+    fn test_buy_many_completion_tolerates_removed_listings() {
+        let (mut marketplace, primary_listing_id, secondary_listing_id) = setup();
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        marketplace.primary_listing_conclude(account(NFT_CONTRACT_ID), 0);
+        marketplace.internal_close_secondary_listing(&secondary_listing_id);
+
+        let results = complete(
+            &mut marketplace,
+            vec![
+                mint_result("0:1", 800),
+                mint_result("0:2", 800),
+                PromiseResult::Successful(vec![]),
+            ],
+        );
+
+        assert_eq!(
+            token_ids(&results),
+            vec![
+                Some("0:1".to_string()),
+                Some("0:2".to_string()),
+                Some(TOKEN_ID.to_string())
+            ]
+        );
+        assert!(marketplace
+            .primary_listings_by_id
+            .get(&primary_listing_id)
+            .is_none());
+    }
+
+    #[test]
+    fn test_buy_many_completion_leaves_new_listing_alone() {
+        let (mut marketplace, _, secondary_listing_id) = setup();
+        // the token was relisted by someone else while the transfer was in flight
+        let mut listing = marketplace
+            .secondary_listings_by_id
+            .get(&secondary_listing_id)
+            .unwrap();
+        listing.seller_id = account(BUYER2_ACCOUNT_ID);
+        marketplace
+            .secondary_listings_by_id
+            .insert(&secondary_listing_id, &listing);
+
+        complete(
+            &mut marketplace,
+            vec![
+                mint_result("0:1", 800),
+                mint_result("0:2", 800),
+                PromiseResult::Successful(vec![]),
+            ],
+        );
+
+        assert!(marketplace
+            .secondary_listings_by_id
+            .get(&secondary_listing_id)
+            .is_some());
+    }
+
+    /* sweep */
+
+    // running buy-now listings of the seller priced 2N and 3N, and an ended one at 1N
+    fn sweep_setup() -> MarketplaceContract {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        let ended_listing_id =
+            add_secondary_listing(&mut marketplace, "2:1", Some(ONE_NEAR), None, false);
+        let mut listing = marketplace
+            .secondary_listings_by_id
+            .get(&ended_listing_id)
+            .unwrap();
+        listing.end_timestamp = Some(NOW as i64);
+        marketplace
+            .secondary_listings_by_id
+            .insert(&ended_listing_id, &listing);
+        add_secondary_listing(&mut marketplace, "2:2", Some(2 * ONE_NEAR), None, false);
+        add_secondary_listing(&mut marketplace, "2:3", Some(3 * ONE_NEAR), None, false);
+        marketplace
+    }
+
+    // nothing is attached, the deposit check tells the total of the picked listings
+    fn sweep(marketplace: &mut MarketplaceContract, count: u64, max_total_yocto: Balance) {
+        set_context(BUYER_ACCOUNT_ID, NOW, 0);
+        marketplace.sweep(
+            account(NFT_CONTRACT_ID),
+            None,
+            count,
+            U128(max_total_yocto),
+            None,
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = r#"insufficient to pay the total of 5000000000000000000000002"#)]
+    fn test_sweep_picks_cheapest_running_listings() {
+        let mut marketplace = sweep_setup();
+        sweep(&mut marketplace, 3, 10 * ONE_NEAR);
+    }
+
+    #[test]
+    #[should_panic(expected = r#"insufficient to pay the total of 2000000000000000000000001"#)]
+    fn test_sweep_stops_at_max_total() {
+        let mut marketplace = sweep_setup();
+        sweep(&mut marketplace, 2, 4 * ONE_NEAR);
+    }
+
+    #[test]
+    #[should_panic(expected = r#"No listings within the price limit"#)]
+    fn test_sweep_below_floor() {
+        let mut marketplace = sweep_setup();
+        sweep(&mut marketplace, 1, ONE_NEAR);
+    }
+
+    #[test]
+    #[should_panic(expected = r#"No listings within the price limit"#)]
+    fn test_sweep_gives_up_after_scan_limit() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        for index in 0..SWEEP_SCAN_MAX {
+            let listing_id = add_secondary_listing(
+                &mut marketplace,
+                &format!("2:{}", index),
+                Some(ONE_NEAR),
+                None,
+                false,
+            );
+            let mut listing = marketplace
+                .secondary_listings_by_id
+                .get(&listing_id)
+                .unwrap();
+            listing.end_timestamp = Some(NOW as i64);
+            marketplace
+                .secondary_listings_by_id
+                .insert(&listing_id, &listing);
+        }
+        add_secondary_listing(&mut marketplace, "3:1", Some(2 * ONE_NEAR), None, false);
+
+        sweep(&mut marketplace, 1, 10 * ONE_NEAR);
+    }
+}
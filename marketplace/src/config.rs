@@ -0,0 +1,365 @@
+use crate::{
+    events::{ConfigUpdateLog, EventLog, EventLogVariant},
+    listing::{
+        constants::*,
+        bundle::config::*,
+        primary::config::*,
+        rules::{JsonListingRules, ListingRules},
+        secondary::config::*,
+    },
+    *,
+};
+use near_sdk::json_types::{I64, U128, U64};
+
+const ONE_MILLINEAR: u128 = 1_000_000_000_000_000_000_000;
+const ONE_NEAR: u128 = 1_000_000_000_000_000_000_000_000;
+
+#[cfg(test)]
+#[path = "config_tests.rs"]
+mod config_tests;
+
+// limits within which sellers may set the rules of their listings
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct ListingRulesBounds {
+    pub min_price_yocto: u128, // lowest minimum price a listing may have
+    pub price_step_min_yocto: u128,
+    pub price_step_max_yocto: u128,
+    pub min_bid_yocto: u128, // lowest minimum bid a listing may have
+    pub bid_step_min_yocto: u128,
+    pub bid_step_max_yocto: u128,
+    pub bid_increment_rate_max: u8, // percent
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct JsonListingRulesBounds {
+    pub min_price_yocto: U128,
+    pub price_step_min_yocto: U128,
+    pub price_step_max_yocto: U128,
+    pub min_bid_yocto: U128,
+    pub bid_step_min_yocto: U128,
+    pub bid_step_max_yocto: U128,
+    pub bid_increment_rate_max: u8,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct ListingLimits {
+    pub title_len_max: u32,
+    pub total_supply_max: u64, // of a primary listing
+    pub primary_listing_min_duration_nano: i64,
+    pub primary_listing_max_duration_nano: i64, // only applies to bid-accepting listings
+    pub secondary_listing_min_duration_nano: i64,
+    pub secondary_listing_max_duration_nano: i64, // only applies to bid-accepting listings
+    pub secondary_listing_bids_max: u64,          // bids held at once, the lowest is refunded
+    pub bundle_listing_bids_max: u64,             // same for bundle listings
+    pub revoke_fee_rate: u8,                      // percent of a revoked bid kept as a fee
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct JsonListingLimits {
+    pub title_len_max: u32,
+    pub total_supply_max: U64,
+    pub primary_listing_min_duration_nano: I64,
+    pub primary_listing_max_duration_nano: I64,
+    pub secondary_listing_min_duration_nano: I64,
+    pub secondary_listing_max_duration_nano: I64,
+    pub secondary_listing_bids_max: U64,
+    pub bundle_listing_bids_max: U64,
+    pub revoke_fee_rate: u8,
+}
+
+// worst case storage (in bytes) the seller's or buyer's deposit must cover before a call goes
+// ahead, raise them if the measured usage grows
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct StorageLimits {
+    pub nft_make_collection_storage_max: u64,
+    pub nft_mint_storage_max: u64,
+    pub primary_listing_add_storage_max: u64,
+    pub secondary_listing_add_storage_max: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct JsonStorageLimits {
+    pub nft_make_collection_storage_max: U64,
+    pub nft_mint_storage_max: U64,
+    pub primary_listing_add_storage_max: U64,
+    pub secondary_listing_add_storage_max: U64,
+}
+
+// marketplace settings the owner can change without redeploying the contract
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct MarketplaceConfig {
+    pub listing_rules_default: ListingRules, // for listings which don't set their own
+    pub listing_rules_bounds: ListingRulesBounds,
+    pub listing_limits: ListingLimits,
+    pub storage_limits: StorageLimits,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct JsonMarketplaceConfig {
+    pub listing_rules_default: JsonListingRules,
+    pub listing_rules_bounds: JsonListingRulesBounds,
+    pub listing_limits: JsonListingLimits,
+    pub storage_limits: JsonStorageLimits,
+}
+
+impl Default for MarketplaceConfig {
+    fn default() -> Self {
+        Self {
+            listing_rules_default: ListingRules {
+                min_price_yocto: MIN_PRICE_YOCTO,
+                price_step_yocto: PRICE_STEP_YOCTO,
+                min_bid_yocto: MIN_BID_YOCTO,
+                bid_step_yocto: BID_STEP_YOCTO,
+                bid_increment_rate: 0,
+            },
+            listing_rules_bounds: ListingRulesBounds {
+                min_price_yocto: ONE_MILLINEAR,
+                price_step_min_yocto: ONE_MILLINEAR,
+                price_step_max_yocto: 10 * ONE_NEAR,
+                min_bid_yocto: ONE_MILLINEAR,
+                bid_step_min_yocto: ONE_MILLINEAR,
+                bid_step_max_yocto: 10 * ONE_NEAR,
+                bid_increment_rate_max: 50,
+            },
+            listing_limits: ListingLimits {
+                title_len_max: MAX_LISTING_TITLE_LEN,
+                total_supply_max: TOTAL_SUPPLY_MAX,
+                primary_listing_min_duration_nano: PRIMARY_LISTING_MIN_DURATION_NANO,
+                primary_listing_max_duration_nano: PRIMARY_LISTING_MAX_DURATION_NANO,
+                secondary_listing_min_duration_nano: SECONDARY_LISTING_MIN_DURATION_NANO,
+                secondary_listing_max_duration_nano: SECONDARY_LISTING_MAX_DURATION_NANO,
+                secondary_listing_bids_max: SECONDARY_LISTING_BIDS_MAX,
+                bundle_listing_bids_max: BUNDLE_LISTING_BIDS_MAX,
+                revoke_fee_rate: PROPOSAL_REVOKE_FEE_RATE,
+            },
+            storage_limits: StorageLimits {
+                nft_make_collection_storage_max: NFT_MAKE_COLLECTION_STORAGE_MAX,
+                nft_mint_storage_max: NFT_MINT_STORAGE_MAX,
+                primary_listing_add_storage_max: PRIMARY_LISTING_ADD_STORAGE_MAX,
+                secondary_listing_add_storage_max: SECONDARY_LISTING_ADD_STORAGE_MAX,
+            },
+        }
+    }
+}
+
+impl MarketplaceConfig {
+    fn from_json(config: &JsonMarketplaceConfig) -> Self {
+        let bounds = &config.listing_rules_bounds;
+        let limits = &config.listing_limits;
+        let storage = &config.storage_limits;
+        Self {
+            listing_rules_default: ListingRules::from_json(&config.listing_rules_default),
+            listing_rules_bounds: ListingRulesBounds {
+                min_price_yocto: bounds.min_price_yocto.0,
+                price_step_min_yocto: bounds.price_step_min_yocto.0,
+                price_step_max_yocto: bounds.price_step_max_yocto.0,
+                min_bid_yocto: bounds.min_bid_yocto.0,
+                bid_step_min_yocto: bounds.bid_step_min_yocto.0,
+                bid_step_max_yocto: bounds.bid_step_max_yocto.0,
+                bid_increment_rate_max: bounds.bid_increment_rate_max,
+            },
+            listing_limits: ListingLimits {
+                title_len_max: limits.title_len_max,
+                total_supply_max: limits.total_supply_max.0,
+                primary_listing_min_duration_nano: limits.primary_listing_min_duration_nano.0,
+                primary_listing_max_duration_nano: limits.primary_listing_max_duration_nano.0,
+                secondary_listing_min_duration_nano: limits.secondary_listing_min_duration_nano.0,
+                secondary_listing_max_duration_nano: limits.secondary_listing_max_duration_nano.0,
+                secondary_listing_bids_max: limits.secondary_listing_bids_max.0,
+                bundle_listing_bids_max: limits.bundle_listing_bids_max.0,
+                revoke_fee_rate: limits.revoke_fee_rate,
+            },
+            storage_limits: StorageLimits {
+                nft_make_collection_storage_max: storage.nft_make_collection_storage_max.0,
+                nft_mint_storage_max: storage.nft_mint_storage_max.0,
+                primary_listing_add_storage_max: storage.primary_listing_add_storage_max.0,
+                secondary_listing_add_storage_max: storage.secondary_listing_add_storage_max.0,
+            },
+        }
+    }
+
+    fn to_json(&self) -> JsonMarketplaceConfig {
+        let bounds = &self.listing_rules_bounds;
+        let limits = &self.listing_limits;
+        let storage = &self.storage_limits;
+        JsonMarketplaceConfig {
+            listing_rules_default: self.listing_rules_default.to_json(),
+            listing_rules_bounds: JsonListingRulesBounds {
+                min_price_yocto: U128(bounds.min_price_yocto),
+                price_step_min_yocto: U128(bounds.price_step_min_yocto),
+                price_step_max_yocto: U128(bounds.price_step_max_yocto),
+                min_bid_yocto: U128(bounds.min_bid_yocto),
+                bid_step_min_yocto: U128(bounds.bid_step_min_yocto),
+                bid_step_max_yocto: U128(bounds.bid_step_max_yocto),
+                bid_increment_rate_max: bounds.bid_increment_rate_max,
+            },
+            listing_limits: JsonListingLimits {
+                title_len_max: limits.title_len_max,
+                total_supply_max: U64(limits.total_supply_max),
+                primary_listing_min_duration_nano: I64(limits.primary_listing_min_duration_nano),
+                primary_listing_max_duration_nano: I64(limits.primary_listing_max_duration_nano),
+                secondary_listing_min_duration_nano: I64(limits.secondary_listing_min_duration_nano),
+                secondary_listing_max_duration_nano: I64(limits.secondary_listing_max_duration_nano),
+                secondary_listing_bids_max: U64(limits.secondary_listing_bids_max),
+                bundle_listing_bids_max: U64(limits.bundle_listing_bids_max),
+                revoke_fee_rate: limits.revoke_fee_rate,
+            },
+            storage_limits: JsonStorageLimits {
+                nft_make_collection_storage_max: U64(storage.nft_make_collection_storage_max),
+                nft_mint_storage_max: U64(storage.nft_mint_storage_max),
+                primary_listing_add_storage_max: U64(storage.primary_listing_add_storage_max),
+                secondary_listing_add_storage_max: U64(storage.secondary_listing_add_storage_max),
+            },
+        }
+    }
+
+    fn assert_valid(&self) {
+        let bounds = &self.listing_rules_bounds;
+        // zero prices and steps would break the floor lookups and the step arithmetic
+        assert!(
+            bounds.min_price_yocto > 0
+                && bounds.price_step_min_yocto > 0
+                && bounds.min_bid_yocto > 0
+                && bounds.bid_step_min_yocto > 0,
+            "Listing rules bounds must be positive"
+        );
+        assert!(
+            bounds.price_step_min_yocto <= bounds.price_step_max_yocto
+                && bounds.bid_step_min_yocto <= bounds.bid_step_max_yocto,
+            "Minimum step cannot exceed maximum step"
+        );
+        assert!(
+            bounds.bid_increment_rate_max <= 100,
+            "Bid increment rate cannot exceed 100%"
+        );
+        self.assert_listing_rules(&self.listing_rules_default);
+
+        let limits = &self.listing_limits;
+        assert!(
+            limits.title_len_max > 0,
+            "Title length limit must be positive"
+        );
+        assert!(
+            limits.total_supply_max > 0,
+            "Total supply limit must be positive"
+        );
+        assert!(
+            limits.primary_listing_min_duration_nano > 0
+                && limits.primary_listing_min_duration_nano
+                    <= limits.primary_listing_max_duration_nano,
+            "Primary listing duration limits are invalid"
+        );
+        assert!(
+            limits.secondary_listing_min_duration_nano > 0
+                && limits.secondary_listing_min_duration_nano
+                    <= limits.secondary_listing_max_duration_nano,
+            "Secondary listing duration limits are invalid"
+        );
+        assert!(
+            limits.secondary_listing_bids_max > 0,
+            "Secondary listing bids limit must be positive"
+        );
+        assert!(
+            limits.bundle_listing_bids_max > 0,
+            "Bundle listing bids limit must be positive"
+        );
+        assert!(
+            limits.revoke_fee_rate <= 100,
+            "Revoke fee rate cannot exceed 100%"
+        );
+
+        // a zero maximum would let calls through without any storage deposit
+        let storage = &self.storage_limits;
+        assert!(
+            storage.nft_make_collection_storage_max > 0
+                && storage.nft_mint_storage_max > 0
+                && storage.primary_listing_add_storage_max > 0
+                && storage.secondary_listing_add_storage_max > 0,
+            "Storage limits must be positive"
+        );
+    }
+
+    fn assert_listing_rules(&self, rules: &ListingRules) {
+        let bounds = &self.listing_rules_bounds;
+        assert!(
+            rules.price_step_yocto >= bounds.price_step_min_yocto
+                && rules.price_step_yocto <= bounds.price_step_max_yocto,
+            "Price step must be between {} and {} yoctoNear",
+            bounds.price_step_min_yocto,
+            bounds.price_step_max_yocto
+        );
+        assert!(
+            rules.min_price_yocto >= bounds.min_price_yocto,
+            "Minimum price cannot be lower than {} yoctoNear",
+            bounds.min_price_yocto
+        );
+        assert!(
+            rules.min_price_yocto % rules.price_step_yocto == 0,
+            "Minimum price must be integer multiple of the price step"
+        );
+        assert!(
+            rules.bid_step_yocto >= bounds.bid_step_min_yocto
+                && rules.bid_step_yocto <= bounds.bid_step_max_yocto,
+            "Bid step must be between {} and {} yoctoNear",
+            bounds.bid_step_min_yocto,
+            bounds.bid_step_max_yocto
+        );
+        assert!(
+            rules.min_bid_yocto >= bounds.min_bid_yocto,
+            "Minimum bid cannot be lower than {} yoctoNear",
+            bounds.min_bid_yocto
+        );
+        assert!(
+            rules.min_bid_yocto % rules.bid_step_yocto == 0,
+            "Minimum bid must be integer multiple of the bid step"
+        );
+        assert!(
+            rules.bid_increment_rate <= bounds.bid_increment_rate_max,
+            "Bid increment rate cannot exceed {}%",
+            bounds.bid_increment_rate_max
+        );
+    }
+
+    // rules for a new listing, the defaults if the seller doesn't set any
+    pub(crate) fn listing_rules(&self, rules: Option<JsonListingRules>) -> ListingRules {
+        rules.map_or_else(
+            || self.listing_rules_default.clone(),
+            |rules| {
+                let rules = ListingRules::from_json(&rules);
+                self.assert_listing_rules(&rules);
+                rules
+            },
+        )
+    }
+}
+
+#[near_bindgen]
+impl MarketplaceContract {
+    // the whole config is replaced, take the current one from config() and change what's needed
+    // listings keep the rules they were created with, new bounds only apply to new listings;
+    // limits are checked whenever a listing is added or updated
+    pub fn set_config(&mut self, config: JsonMarketplaceConfig) {
+        assert_eq!(
+            env::predecessor_account_id(),
+            self.owner_id,
+            "Only the owner can change the config"
+        );
+        let config = MarketplaceConfig::from_json(&config);
+        config.assert_valid();
+        self.config = config;
+
+        let config_update_log = EventLog::new(EventLogVariant::ConfigUpdate(ConfigUpdateLog {
+            updated_by: env::predecessor_account_id(),
+            config: self.config.to_json(),
+        }));
+        env::log_str(&config_update_log.to_string());
+    }
+
+    pub fn config(&self) -> JsonMarketplaceConfig {
+        self.config.to_json()
+    }
+}
@@ -0,0 +1,155 @@
+#[cfg(test)]
+mod config_tests {
+    use crate::{
+        listing::{date::DateInput, rules::JsonListingRules},
+        test_utils::*,
+        *,
+    };
+    use near_sdk::json_types::{U128, U64};
+
+    const STEP_YOCTO: Balance = ONE_NEAR / 10;
+
+    fn rules() -> JsonListingRules {
+        JsonListingRules {
+            min_price_yocto: U128(ONE_NEAR),
+            price_step_yocto: U128(ONE_NEAR / 2),
+            min_bid_yocto: U128(2 * STEP_YOCTO),
+            bid_step_yocto: U128(STEP_YOCTO),
+            bid_increment_rate: 10,
+        }
+    }
+
+    // raises the default minimum bid, offers must follow it
+    fn raise_default_min_bid(marketplace: &mut MarketplaceContract) {
+        let mut config = marketplace.config();
+        config.listing_rules_default.min_bid_yocto = U128(ONE_NEAR);
+        set_context(MARKETPLACE_ACCOUNT_ID, NOW, 0);
+        marketplace.set_config(config);
+    }
+
+    /* listing rules */
+
+    #[test]
+    fn test_listing_rules_default() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let marketplace = marketplace();
+
+        let rules = marketplace.config.listing_rules(None);
+
+        assert_eq!(
+            rules.min_bid_yocto,
+            marketplace.config.listing_rules_default.min_bid_yocto
+        );
+        assert_eq!(
+            rules.bid_step_yocto,
+            marketplace.config.listing_rules_default.bid_step_yocto
+        );
+    }
+
+    #[test]
+    fn test_listing_rules_custom() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let marketplace = marketplace();
+
+        let rules = marketplace.config.listing_rules(Some(rules()));
+
+        assert_eq!(rules.min_price_yocto, ONE_NEAR);
+        assert_eq!(rules.price_step_yocto, ONE_NEAR / 2);
+        assert_eq!(rules.bid_increment_rate, 10);
+    }
+
+    #[test]
+    #[should_panic(expected = "Price step must be between")]
+    fn test_listing_rules_price_step_too_high() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let marketplace = marketplace();
+
+        marketplace.config.listing_rules(Some(JsonListingRules {
+            min_price_yocto: U128(20 * ONE_NEAR),
+            price_step_yocto: U128(20 * ONE_NEAR),
+            ..rules()
+        }));
+    }
+
+    #[test]
+    #[should_panic(expected = "Minimum price must be integer multiple of the price step")]
+    fn test_listing_rules_min_price_not_multiple_of_step() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let marketplace = marketplace();
+
+        marketplace.config.listing_rules(Some(JsonListingRules {
+            min_price_yocto: U128(ONE_NEAR + STEP_YOCTO),
+            ..rules()
+        }));
+    }
+
+    #[test]
+    #[should_panic(expected = "Bid step must be between")]
+    fn test_listing_rules_bid_step_too_low() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let marketplace = marketplace();
+
+        marketplace.config.listing_rules(Some(JsonListingRules {
+            bid_step_yocto: U128(1),
+            ..rules()
+        }));
+    }
+
+    #[test]
+    #[should_panic(expected = "Minimum bid must be integer multiple of the bid step")]
+    fn test_listing_rules_min_bid_not_multiple_of_step() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let marketplace = marketplace();
+
+        marketplace.config.listing_rules(Some(JsonListingRules {
+            min_bid_yocto: U128(2 * STEP_YOCTO + STEP_YOCTO / 2),
+            ..rules()
+        }));
+    }
+
+    #[test]
+    #[should_panic(expected = "Bid increment rate cannot exceed")]
+    fn test_listing_rules_increment_rate_too_high() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let marketplace = marketplace();
+
+        marketplace.config.listing_rules(Some(JsonListingRules {
+            bid_increment_rate: 90,
+            ..rules()
+        }));
+    }
+
+    /* offers follow the default rules */
+
+    #[test]
+    #[should_panic(expected = "Offer cannot be lower than 1000000000000000000000000 yoctoNear")]
+    fn test_collection_offer_below_default_min_bid() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        raise_default_min_bid(&mut marketplace);
+
+        set_context(BUYER_ACCOUNT_ID, NOW, MIN_BID_YOCTO);
+        marketplace.collection_offer_place(
+            account(NFT_CONTRACT_ID),
+            None,
+            U128(MIN_BID_YOCTO),
+            U64(1),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Offer cannot be lower than 1000000000000000000000000 yoctoNear")]
+    fn test_token_offer_below_default_min_bid() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        raise_default_min_bid(&mut marketplace);
+
+        set_context(BUYER_ACCOUNT_ID, NOW, MIN_BID_YOCTO);
+        marketplace.token_offer_place(
+            account(NFT_CONTRACT_ID),
+            "0:1".to_string(),
+            U128(MIN_BID_YOCTO),
+            DateInput::DurationMs(U64(3_600_000)),
+        );
+    }
+}
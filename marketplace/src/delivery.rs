@@ -0,0 +1,115 @@
+use crate::{constants::NO_DEPOSIT, external::nft_contract, *};
+use near_sdk::PromiseResult;
+
+pub const NFT_DELIVERY_GAS: Gas = Gas(15_000_000_000_000); // TODO: measure
+pub const NFT_DELIVERY_COMPLETION_GAS: Gas = Gas(5_000_000_000_000); // TODO: measure
+
+#[cfg(test)]
+#[path = "delivery_tests.rs"]
+mod delivery_tests;
+
+// tokens held by the marketplace (swaps, bundle sales) are passed on to the receiver once the
+// trade is settled; the trade cannot be reverted at that point, so a failed transfer is recorded
+// and the token can be claimed again later
+#[near_bindgen]
+impl MarketplaceContract {
+    // retries the transfer of an undelivered token to its receiver; anyone can call it, the
+    // token only ever goes to the recorded receiver; 1yN must be attached for nft_transfer
+    #[payable]
+    pub fn token_delivery_retry(&mut self, nft_contract_id: AccountId, token_id: NftId) -> Promise {
+        assert_eq!(
+            env::attached_deposit(),
+            1,
+            "Requires attached deposit of exactly 1 yoctoNEAR"
+        );
+        assert!(
+            env::prepaid_gas() >= NFT_DELIVERY_GAS + NFT_DELIVERY_COMPLETION_GAS,
+            "Attach at least {} gas",
+            (NFT_DELIVERY_GAS + NFT_DELIVERY_COMPLETION_GAS).0
+        );
+        let receiver_id = self
+            .undelivered_tokens
+            .remove(&(nft_contract_id.clone(), token_id.clone()))
+            .expect("This token is not waiting for delivery");
+        self.internal_deliver_token(&nft_contract_id, &token_id, &receiver_id)
+    }
+
+    // the account the token is waiting to be delivered to, if any
+    pub fn undelivered_token(&self, nft_contract_id: AccountId, token_id: NftId) -> Option<AccountId> {
+        self.undelivered_tokens.get(&(nft_contract_id, token_id))
+    }
+}
+
+impl MarketplaceContract {
+    // transfers a token the marketplace holds, 1yN must be available to the caller
+    pub(crate) fn internal_deliver_token(
+        &self,
+        nft_contract_id: &AccountId,
+        token_id: &NftId,
+        receiver_id: &AccountId,
+    ) -> Promise {
+        nft_contract::nft_transfer(
+            receiver_id.clone(),
+            token_id.clone(),
+            None,
+            None,
+            nft_contract_id.clone(),
+            1,
+            NFT_DELIVERY_GAS,
+        )
+        .then(ext_self_delivery::token_delivery_completion(
+            nft_contract_id.clone(),
+            token_id.clone(),
+            receiver_id.clone(),
+            env::current_account_id(),
+            NO_DEPOSIT,
+            NFT_DELIVERY_COMPLETION_GAS,
+        ))
+    }
+}
+
+#[ext_contract(ext_self_delivery)]
+trait TokenDeliveryCallback {
+    fn token_delivery_completion(
+        &mut self,
+        nft_contract_id: AccountId,
+        token_id: NftId,
+        receiver_id: AccountId,
+    ) -> bool;
+}
+
+trait TokenDeliveryCallback {
+    fn token_delivery_completion(
+        &mut self,
+        nft_contract_id: AccountId,
+        token_id: NftId,
+        receiver_id: AccountId,
+    ) -> bool;
+}
+
+#[near_bindgen]
+impl TokenDeliveryCallback for MarketplaceContract {
+    // returns true if the token was delivered; the record of a failed delivery is paid for by
+    // the marketplace
+    #[private]
+    fn token_delivery_completion(
+        &mut self,
+        nft_contract_id: AccountId,
+        token_id: NftId,
+        receiver_id: AccountId,
+    ) -> bool {
+        assert_eq!(env::promise_results_count(), 1, "Too many data receipts");
+        match env::promise_result(0) {
+            PromiseResult::Successful(_) => true,
+            _ => {
+                env::log_str(&format!(
+                    "Could not deliver token {}.{} to {}, call token_delivery_retry",
+                    nft_contract_id, token_id, receiver_id
+                ));
+                self.undelivered_tokens
+                    .insert(&(nft_contract_id, token_id), &receiver_id);
+                false
+            }
+        }
+    }
+}
@@ -0,0 +1,76 @@
+#[cfg(test)]
+mod delivery_tests {
+    use super::super::TokenDeliveryCallback;
+    use crate::{test_utils::*, *};
+    use near_sdk::PromiseResult;
+
+    const TOKEN_ID: &str = "0:1";
+
+    fn complete_delivery(marketplace: &mut MarketplaceContract, result: PromiseResult) -> bool {
+        set_callback_context(NOW, vec![result]);
+        marketplace.token_delivery_completion(
+            account(NFT_CONTRACT_ID),
+            TOKEN_ID.to_string(),
+            account(BUYER_ACCOUNT_ID),
+        )
+    }
+
+    #[test]
+    fn test_token_delivery_completion() {
+        let mut marketplace = marketplace();
+
+        assert!(complete_delivery(
+            &mut marketplace,
+            PromiseResult::Successful(vec![])
+        ));
+
+        assert!(marketplace
+            .undelivered_token(account(NFT_CONTRACT_ID), TOKEN_ID.to_string())
+            .is_none());
+    }
+
+    #[test]
+    fn test_token_delivery_completion_failed() {
+        let mut marketplace = marketplace();
+
+        assert!(!complete_delivery(&mut marketplace, PromiseResult::Failed));
+
+        assert_eq!(
+            marketplace.undelivered_token(account(NFT_CONTRACT_ID), TOKEN_ID.to_string()),
+            Some(account(BUYER_ACCOUNT_ID))
+        );
+    }
+
+    #[test]
+    fn test_token_delivery_retry() {
+        let mut marketplace = marketplace();
+        complete_delivery(&mut marketplace, PromiseResult::Failed);
+
+        // anyone can retry, the token goes to the recorded receiver
+        set_context(BUYER2_ACCOUNT_ID, NOW, 1);
+        marketplace.token_delivery_retry(account(NFT_CONTRACT_ID), TOKEN_ID.to_string());
+
+        assert!(marketplace
+            .undelivered_token(account(NFT_CONTRACT_ID), TOKEN_ID.to_string())
+            .is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = r#"This token is not waiting for delivery"#)]
+    fn test_token_delivery_retry_not_undelivered() {
+        let mut marketplace = marketplace();
+
+        set_context(BUYER_ACCOUNT_ID, NOW, 1);
+        marketplace.token_delivery_retry(account(NFT_CONTRACT_ID), TOKEN_ID.to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Requires attached deposit of exactly 1 yoctoNEAR"#)]
+    fn test_token_delivery_retry_without_deposit() {
+        let mut marketplace = marketplace();
+        complete_delivery(&mut marketplace, PromiseResult::Failed);
+
+        set_context(BUYER_ACCOUNT_ID, NOW, 0);
+        marketplace.token_delivery_retry(account(NFT_CONTRACT_ID), TOKEN_ID.to_string());
+    }
+}
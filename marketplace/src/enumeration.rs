@@ -0,0 +1,68 @@
+use crate::{
+    listing::id::{ListingId, ListingIdJson},
+    *,
+};
+use near_sdk::json_types::{U128, U64};
+
+#[cfg(test)]
+#[path = "enumeration_tests.rs"]
+mod enumeration_tests;
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct JsonAccountBid {
+    pub listing_id: ListingIdJson,
+    pub bid_id: U64,
+    pub amount_yocto: U128,     // effective amount
+    pub max_amount_yocto: U128, // escrowed for the bid, above amount_yocto for a proxy bid
+    pub rank: U64,              // position among the listing bids, 0 is the best one
+}
+
+// view-only methods spanning all listing types
+
+#[near_bindgen]
+impl MarketplaceContract {
+    // open bids of the account on primary, secondary and bundle listings, results are paginated
+    pub fn bids_by_account(
+        &self,
+        account_id: AccountId,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<JsonAccountBid> {
+        let bids = self.bids_by_account.get(&account_id);
+        if bids.is_none() {
+            return vec![];
+        }
+        let bids = bids.unwrap();
+
+        //where to start pagination - if we have a from_index, we'll use that - otherwise start from 0 index
+        let start = u128::from(from_index.unwrap_or(U128(0))) as usize;
+        let count = limit.unwrap_or(10) as usize;
+
+        bids.iter()
+            .skip(start) //skip to the index we specified in the start variable
+            .take(count) // return "limit" elements or 0 if missing
+            .filter_map(|(listing_id, bid_id)| {
+                // an entry whose listing or bid is gone is skipped, the page may come back short
+                let listing_bids = match &listing_id {
+                    ListingId::Primary(primary_listing_id) => {
+                        self.primary_listings_by_id.get(primary_listing_id)?.bids
+                    }
+                    ListingId::Secondary(secondary_listing_id) => {
+                        self.secondary_listings_by_id.get(secondary_listing_id)?.bids
+                    }
+                    ListingId::Bundle(bundle_id) => self.bundle_listings_by_id.get(bundle_id)?.bids,
+                };
+                let bid = listing_bids.get(bid_id)?;
+                let rank = listing_bids.rank(&bid)?;
+                Some(JsonAccountBid {
+                    listing_id: listing_id.to_json(),
+                    bid_id: U64(bid_id),
+                    amount_yocto: U128(bid.amount_yocto),
+                    max_amount_yocto: U128(bid.max_amount_yocto),
+                    rank: U64(rank),
+                })
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,176 @@
+#[cfg(test)]
+mod enumeration_tests {
+    use super::JsonAccountBid;
+    use crate::{listing::id::ListingIdJson, test_utils::*, *};
+    use near_sdk::json_types::{U128, U64};
+
+    const TOKEN_ID: &str = "0:1";
+
+    // an auction of each listing type, all of the seller
+    fn setup() -> (MarketplaceContract, U64) {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        add_primary_listing(
+            &mut marketplace,
+            0,
+            2,
+            Some(10 * ONE_NEAR),
+            Some(MIN_BID_YOCTO),
+            None,
+        );
+        add_secondary_listing(
+            &mut marketplace,
+            TOKEN_ID,
+            Some(10 * ONE_NEAR),
+            Some(MIN_BID_YOCTO),
+            false,
+        );
+        let bundle_id = add_bundle_listing(
+            &mut marketplace,
+            &["1:1", "1:2"],
+            Some(10 * ONE_NEAR),
+            Some(MIN_BID_YOCTO),
+        );
+        (marketplace, U64(bundle_id))
+    }
+
+    fn bid_on_secondary(
+        marketplace: &mut MarketplaceContract,
+        bidder_id: &str,
+        amount_yocto: Balance,
+    ) -> U64 {
+        set_context(bidder_id, NOW, amount_yocto);
+        marketplace.secondary_listing_place_bid(
+            account(NFT_CONTRACT_ID),
+            TOKEN_ID.to_string(),
+            U128(amount_yocto),
+        )
+    }
+
+    fn bid_on_all(marketplace: &mut MarketplaceContract, bundle_id: U64) {
+        set_context(BIDDER_ACCOUNT_ID, NOW, MIN_BID_YOCTO);
+        marketplace.primary_listing_place_bid(
+            account(NFT_CONTRACT_ID),
+            U64(0),
+            U128(MIN_BID_YOCTO),
+            None,
+        );
+        bid_on_secondary(marketplace, BIDDER_ACCOUNT_ID, MIN_BID_YOCTO);
+        set_context(BIDDER_ACCOUNT_ID, NOW, MIN_BID_YOCTO);
+        marketplace.bundle_listing_place_bid(bundle_id, U128(MIN_BID_YOCTO));
+    }
+
+    fn account_bids(marketplace: &MarketplaceContract, account_id: &str) -> Vec<JsonAccountBid> {
+        marketplace.bids_by_account(account(account_id), None, Some(100))
+    }
+
+    /* bids by account */
+
+    #[test]
+    fn test_bids_by_account_covers_all_listing_types() {
+        let (mut marketplace, bundle_id) = setup();
+        bid_on_all(&mut marketplace, bundle_id);
+
+        let bids = account_bids(&marketplace, BIDDER_ACCOUNT_ID);
+        assert_eq!(bids.len(), 3);
+        assert!(bids.iter().all(|bid| bid.amount_yocto.0 == MIN_BID_YOCTO
+            && bid.max_amount_yocto.0 == MIN_BID_YOCTO
+            && bid.rank.0 == 0));
+        assert!(matches!(bids[0].listing_id, ListingIdJson::Primary { .. }));
+        assert!(matches!(
+            bids[1].listing_id,
+            ListingIdJson::Secondary { .. }
+        ));
+        assert!(matches!(bids[2].listing_id, ListingIdJson::Bundle { .. }));
+        assert!(account_bids(&marketplace, BIDDER2_ACCOUNT_ID).is_empty());
+    }
+
+    #[test]
+    fn test_bids_by_account_rank() {
+        let (mut marketplace, _) = setup();
+        let bid_id = bid_on_secondary(&mut marketplace, BIDDER_ACCOUNT_ID, MIN_BID_YOCTO);
+        let bid2_id = bid_on_secondary(&mut marketplace, BIDDER2_ACCOUNT_ID, 6 * ONE_NEAR / 10);
+
+        let bids = account_bids(&marketplace, BIDDER_ACCOUNT_ID);
+        assert_eq!(bids.len(), 1);
+        assert_eq!(bids[0].bid_id, bid_id);
+        assert_eq!(bids[0].rank.0, 1);
+
+        let bids2 = account_bids(&marketplace, BIDDER2_ACCOUNT_ID);
+        assert_eq!(bids2.len(), 1);
+        assert_eq!(bids2[0].bid_id, bid2_id);
+        assert_eq!(bids2[0].rank.0, 0);
+    }
+
+    #[test]
+    fn test_bids_by_account_drops_revoked_bid() {
+        let (mut marketplace, bundle_id) = setup();
+        bid_on_all(&mut marketplace, bundle_id);
+
+        set_context(BIDDER_ACCOUNT_ID, NOW, 0);
+        marketplace.secondary_listing_revoke_bid(
+            account(NFT_CONTRACT_ID),
+            TOKEN_ID.to_string(),
+            U64(0),
+        );
+
+        let bids = account_bids(&marketplace, BIDDER_ACCOUNT_ID);
+        assert_eq!(bids.len(), 2);
+        assert!(bids
+            .iter()
+            .all(|bid| !matches!(bid.listing_id, ListingIdJson::Secondary { .. })));
+    }
+
+    #[test]
+    fn test_bids_by_account_proxy_bid_escrow() {
+        let (mut marketplace, _) = setup();
+        set_context(BIDDER_ACCOUNT_ID, NOW, 2 * MIN_BID_YOCTO);
+        marketplace.primary_listing_place_bid(
+            account(NFT_CONTRACT_ID),
+            U64(0),
+            U128(MIN_BID_YOCTO),
+            Some(U128(2 * MIN_BID_YOCTO)),
+        );
+
+        let bids = account_bids(&marketplace, BIDDER_ACCOUNT_ID);
+        assert_eq!(bids.len(), 1);
+        assert_eq!(bids[0].amount_yocto.0, MIN_BID_YOCTO);
+        assert_eq!(bids[0].max_amount_yocto.0, 2 * MIN_BID_YOCTO);
+    }
+
+    #[test]
+    fn test_bids_by_account_skips_stale_entries() {
+        let (mut marketplace, bundle_id) = setup();
+        bid_on_all(&mut marketplace, bundle_id);
+
+        // the index still points at a listing that's no longer stored
+        marketplace
+            .secondary_listings_by_id
+            .remove(&SecondaryListingId {
+                nft_contract_id: account(NFT_CONTRACT_ID),
+                token_id: TOKEN_ID.to_string(),
+            });
+
+        let bids = account_bids(&marketplace, BIDDER_ACCOUNT_ID);
+        assert_eq!(bids.len(), 2);
+        assert!(bids
+            .iter()
+            .all(|bid| !matches!(bid.listing_id, ListingIdJson::Secondary { .. })));
+    }
+
+    #[test]
+    fn test_bids_by_account_pagination() {
+        let (mut marketplace, bundle_id) = setup();
+        bid_on_all(&mut marketplace, bundle_id);
+
+        let page = marketplace.bids_by_account(account(BIDDER_ACCOUNT_ID), Some(U128(1)), Some(1));
+        assert_eq!(page.len(), 1);
+        assert!(matches!(
+            page[0].listing_id,
+            ListingIdJson::Secondary { .. }
+        ));
+        assert!(marketplace
+            .bids_by_account(account(BIDDER_ACCOUNT_ID), Some(U128(3)), None)
+            .is_empty());
+    }
+}
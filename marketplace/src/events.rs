@@ -0,0 +1,66 @@
+use std::fmt;
+
+use crate::config::JsonMarketplaceConfig;
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::AccountId;
+
+pub const MARKETPLACE_STANDARD_NAME: &str = "eneftigo_marketplace";
+pub const MARKETPLACE_EVENTS_VERSION: &str = "1.0.0";
+
+/// Enum that represents the data type of the EventLog.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "event", content = "data")]
+#[serde(rename_all = "snake_case")]
+#[serde(crate = "near_sdk::serde")]
+#[non_exhaustive]
+pub enum EventLogVariant {
+    ConfigUpdate(ConfigUpdateLog),
+}
+
+/// Interface to capture data about an event, see NEP-297
+///
+/// Arguments:
+/// * `standard`: name of standard e.g. eneftigo_marketplace
+/// * `version`: e.g. 1.0.0
+/// * `event`: associate event data
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct EventLog {
+    pub standard: String,
+    pub version: String,
+
+    // `flatten` to not have "event": {<EventLogVariant>} in the JSON, just have the contents of {<EventLogVariant>}.
+    #[serde(flatten)]
+    pub event: EventLogVariant,
+}
+
+impl EventLog {
+    pub(crate) fn new(event: EventLogVariant) -> Self {
+        Self {
+            standard: MARKETPLACE_STANDARD_NAME.to_string(),
+            version: MARKETPLACE_EVENTS_VERSION.to_string(),
+            event,
+        }
+    }
+}
+
+impl fmt::Display for EventLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_fmt(format_args!(
+            "EVENT_JSON:{}",
+            &near_sdk::serde_json::to_string(self).map_err(|_| fmt::Error)?
+        ))
+    }
+}
+
+/// An event log to capture a change of the marketplace config
+///
+/// Arguments
+/// * `updated_by`: "owner.near"
+/// * `config`: the config in effect from now on
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ConfigUpdateLog {
+    pub updated_by: AccountId,
+    pub config: JsonMarketplaceConfig,
+}
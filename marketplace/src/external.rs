@@ -3,7 +3,7 @@ use crate::*;
 use near_sdk::json_types::{U64};
 
 #[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
-#[derive(Clone)]
+#[derive(Clone, PartialEq)]
 #[serde(crate = "near_sdk::serde")]
 pub struct NftMetadata {
     pub title: Option<String>, // ex. "Arch Nemesis: Mail Carrier" or "Parcel #5055"
@@ -21,12 +21,24 @@ pub struct NftMetadata {
 }
 
 #[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
-#[derive(Clone)]
+#[derive(Clone, PartialEq)]
 #[serde(crate = "near_sdk::serde")]
 pub struct NftMutableMetadata {
     pub aux_audio_url: Option<String>,
 }
 
+// as returned by nft_token
+#[derive(Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct JsonNft {
+    pub token_id: NftId,
+    pub owner_id: AccountId,
+    pub collection_id: NftCollectionId,
+    pub metadata: NftMetadata,
+    pub mutable_metadata: NftMutableMetadata,
+    pub approved_account_ids: HashMap<AccountId, u64>,
+}
+
 impl NftMetadata {
     pub(crate) fn new(title: &str, media: &str) -> NftMetadata {
         NftMetadata { 
@@ -55,7 +67,7 @@ trait NFTContract {
         max_supply: U64
     ) -> (U64,U64);
     fn freeze_collection(&mut self, collection_id: U64);
-    fn delete_colleciton(&mut self, collection_id: U64);
+    fn delete_collection(&mut self, collection_id: U64);
 
     fn mint(
         &mut self,
@@ -71,4 +83,13 @@ trait NFTContract {
         approval_id: Option<u64>,
         memo: Option<String>,
     );
+
+    fn nft_token(&self, token_id: NftId) -> Option<JsonNft>;
+
+    fn nft_is_approved(
+        &self,
+        token_id: NftId,
+        approved_account_id: AccountId,
+        approval_id: Option<u64>,
+    ) -> bool;
 }
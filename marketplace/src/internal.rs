@@ -20,4 +20,27 @@ impl MarketplaceContract {
     pub(crate) fn fees_account_id(&self) -> AccountId {
         AccountId::new_unchecked(format!("fees.{}", env::current_account_id()))
     }
+
+    // settles storage change caused by an account's call with that account's storage deposit
+    // panics if the deposit does not cover the added storage
+    pub(crate) fn internal_charge_account_storage(
+        &mut self,
+        account_id: &AccountId,
+        storage_before: u64,
+        storage_after: u64,
+    ) {
+        let current_deposit = self.storage_deposits.get(account_id).unwrap_or(0);
+        let updated_deposit = if storage_after > storage_before {
+            let storage_cost = (storage_after - storage_before) as Balance * env::storage_byte_cost();
+            assert!(
+                current_deposit >= storage_cost,
+                "Your storage deposit is too low. Must be {} yN to process transaction. Please increase your deposit.",
+                storage_cost
+            );
+            current_deposit - storage_cost
+        } else {
+            current_deposit + (storage_before - storage_after) as Balance * env::storage_byte_cost()
+        };
+        self.storage_deposits.insert(account_id, &updated_deposit);
+    }
 }
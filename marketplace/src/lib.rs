@@ -2,24 +2,47 @@ use near_sdk::{
     env, ext_contract, near_bindgen, AccountId, Balance, Gas, PanicOnDefault,
     Promise, CryptoHash, BorshStorageKey,
     json_types::{Base64VecU8},
-    collections::{LookupMap, UnorderedMap, UnorderedSet},
+    collections::{LookupMap, TreeMap, UnorderedMap, UnorderedSet, Vector},
     serde::{Deserialize, Serialize},
     borsh::{self, BorshDeserialize, BorshSerialize},
 };
 use listing::{
     primary::lib::{PrimaryListingId, PrimaryListing},
+    primary::round::PrimaryCollection,
+    primary::draft::PrimaryDraft,
     secondary::lib::{SecondaryListingId, SecondaryListing},
+    query::ListingIndex,
+    id::ListingId,
+    bid::BidId,
+    bundle::lib::{BundleId, BundleListing},
+    sale::{MarketStats, Sale},
 };
+use offer::{
+    OfferId,
+    collection::{CollectionOffer, CollectionOfferKey},
+    token::TokenOffer,
+    swap::SwapOffer,
+};
+use config::MarketplaceConfig;
 use std::{
     collections::{HashMap},
 };
 
 mod listing;
+mod offer;
+mod checkout;
 mod internal;
 mod enumeration;
 mod external;
 mod constants;
 mod deposit;
+mod config;
+mod events;
+mod delivery;
+mod migration;
+
+#[cfg(test)]
+mod test_utils;
 
 // mod error;
 
@@ -36,6 +59,32 @@ pub struct MarketplaceContract {
     pub secondary_listings_by_id: UnorderedMap<SecondaryListingId, SecondaryListing>,
     pub secondary_listings_by_seller_id: LookupMap<AccountId, UnorderedSet<SecondaryListingId>>,
     pub storage_deposits: LookupMap<AccountId,Balance>,
+    pub next_listing_seq: u64,                          // shared by primary and secondary listings
+    pub primary_listing_index: ListingIndex<PrimaryListingId>,
+    pub secondary_listing_index: ListingIndex<SecondaryListingId>,
+    pub bids_by_account: LookupMap<AccountId, UnorderedSet<(ListingId, BidId)>>,
+    pub sales: Vector<Sale>,
+    pub market_stats_by_contract: LookupMap<AccountId, MarketStats>,
+    pub market_stats_by_collection: LookupMap<(AccountId, NftCollectionId), MarketStats>,
+    pub floor_by_contract: TreeMap<(AccountId, u128, u64), ListingId>,      // (nft_contract_id, price, seq)
+    pub floor_by_collection: TreeMap<(AccountId, NftCollectionId, u128, u64), ListingId>,
+    pub next_offer_id: OfferId,
+    pub collection_offers_by_id: LookupMap<OfferId, CollectionOffer>,
+    pub collection_offer_book: TreeMap<CollectionOfferKey, OfferId>,
+    pub collection_offers_by_account: LookupMap<AccountId, UnorderedSet<OfferId>>,
+    pub token_offers_by_id: LookupMap<OfferId, TokenOffer>,
+    pub token_offers_by_token: LookupMap<(AccountId, NftId), UnorderedSet<OfferId>>,
+    pub token_offers_by_account: LookupMap<AccountId, UnorderedSet<OfferId>>,
+    pub next_bundle_id: BundleId,
+    pub bundle_listings_by_id: LookupMap<BundleId, BundleListing>,
+    pub bundle_id_by_token: LookupMap<(AccountId, NftId), BundleId>,
+    pub swap_offers_by_id: LookupMap<OfferId, SwapOffer>,
+    pub swap_offers_by_account: LookupMap<AccountId, UnorderedSet<OfferId>>,   // proposer and counterparty
+    pub config: MarketplaceConfig,
+    pub primary_collections: LookupMap<(AccountId, NftCollectionId), PrimaryCollection>,
+    pub primary_drafts_by_id: LookupMap<PrimaryListingId, PrimaryDraft>,
+    pub primary_drafts_by_seller_id: LookupMap<AccountId, UnorderedSet<PrimaryListingId>>,
+    pub undelivered_tokens: LookupMap<(AccountId, NftId), AccountId>,     // token -> receiver
 }
 
 /// Helper structure to for keys of the persistent collections.
@@ -48,6 +97,38 @@ pub enum MarketplaceStorageKey {
     SecondaryListingsBySellerId,
     SecondaryListingsBySellerIdInner { account_id_hash: CryptoHash },
     StorageDeposits,
+    PrimaryListingsBySeq,
+    PrimaryListingsByPrice,
+    PrimaryListingsByEnd,
+    SecondaryListingsBySeq,
+    SecondaryListingsByPrice,
+    SecondaryListingsByEnd,
+    BidsByAccount,
+    BidsByAccountInner { account_id_hash: CryptoHash },
+    Sales,
+    MarketStatsByContract,
+    MarketStatsByCollection,
+    FloorByContract,
+    FloorByCollection,
+    CollectionOffersById,
+    CollectionOfferBook,
+    CollectionOffersByAccount,
+    CollectionOffersByAccountInner { account_id_hash: CryptoHash },
+    TokenOffersById,
+    TokenOffersByToken,
+    TokenOffersByTokenInner { token_id_hash: CryptoHash },
+    TokenOffersByAccount,
+    TokenOffersByAccountInner { account_id_hash: CryptoHash },
+    BundleListingsById,
+    BundleIdByToken,
+    SwapOffersById,
+    SwapOffersByAccount,
+    SwapOffersByAccountInner { account_id_hash: CryptoHash },
+    PrimaryCollections,
+    PrimaryDraftsById,
+    PrimaryDraftsBySellerId,
+    PrimaryDraftsBySellerIdInner { account_id_hash: CryptoHash },
+    UndeliveredTokens,
 }
 
 #[near_bindgen]
@@ -66,6 +147,40 @@ impl MarketplaceContract {
             secondary_listings_by_id: UnorderedMap::new(MarketplaceStorageKey::SecondaryListingsById),
             secondary_listings_by_seller_id: LookupMap::new(MarketplaceStorageKey::SecondaryListingsBySellerId),
             storage_deposits: LookupMap::new(MarketplaceStorageKey::StorageDeposits),
+            next_listing_seq: 0,
+            primary_listing_index: ListingIndex::new(
+                MarketplaceStorageKey::PrimaryListingsBySeq,
+                MarketplaceStorageKey::PrimaryListingsByPrice,
+                MarketplaceStorageKey::PrimaryListingsByEnd,
+            ),
+            secondary_listing_index: ListingIndex::new(
+                MarketplaceStorageKey::SecondaryListingsBySeq,
+                MarketplaceStorageKey::SecondaryListingsByPrice,
+                MarketplaceStorageKey::SecondaryListingsByEnd,
+            ),
+            bids_by_account: LookupMap::new(MarketplaceStorageKey::BidsByAccount),
+            sales: Vector::new(MarketplaceStorageKey::Sales),
+            market_stats_by_contract: LookupMap::new(MarketplaceStorageKey::MarketStatsByContract),
+            market_stats_by_collection: LookupMap::new(MarketplaceStorageKey::MarketStatsByCollection),
+            floor_by_contract: TreeMap::new(MarketplaceStorageKey::FloorByContract),
+            floor_by_collection: TreeMap::new(MarketplaceStorageKey::FloorByCollection),
+            next_offer_id: 0,
+            collection_offers_by_id: LookupMap::new(MarketplaceStorageKey::CollectionOffersById),
+            collection_offer_book: TreeMap::new(MarketplaceStorageKey::CollectionOfferBook),
+            collection_offers_by_account: LookupMap::new(MarketplaceStorageKey::CollectionOffersByAccount),
+            token_offers_by_id: LookupMap::new(MarketplaceStorageKey::TokenOffersById),
+            token_offers_by_token: LookupMap::new(MarketplaceStorageKey::TokenOffersByToken),
+            token_offers_by_account: LookupMap::new(MarketplaceStorageKey::TokenOffersByAccount),
+            next_bundle_id: 0,
+            bundle_listings_by_id: LookupMap::new(MarketplaceStorageKey::BundleListingsById),
+            bundle_id_by_token: LookupMap::new(MarketplaceStorageKey::BundleIdByToken),
+            swap_offers_by_id: LookupMap::new(MarketplaceStorageKey::SwapOffersById),
+            swap_offers_by_account: LookupMap::new(MarketplaceStorageKey::SwapOffersByAccount),
+            config: MarketplaceConfig::default(),
+            primary_collections: LookupMap::new(MarketplaceStorageKey::PrimaryCollections),
+            primary_drafts_by_id: LookupMap::new(MarketplaceStorageKey::PrimaryDraftsById),
+            primary_drafts_by_seller_id: LookupMap::new(MarketplaceStorageKey::PrimaryDraftsBySellerId),
+            undelivered_tokens: LookupMap::new(MarketplaceStorageKey::UndeliveredTokens),
         }
     }
 
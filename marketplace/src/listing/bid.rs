@@ -1,34 +1,25 @@
-use crate::*;
+use crate::{internal::hash_account_id, listing::id::ListingId, *};
 
-use std::cmp::Ordering;
+use near_sdk::collections::TreeMap;
+use near_sdk::IntoStorageKey;
 use std::fmt;
 
 pub type BidId = u64;
 
+#[cfg(test)]
+#[path = "bid_tests.rs"]
+mod bid_tests;
+
+// (u128::MAX - amount_yocto, id) so that ascending key order is the bid order,
+// best bid comes first and among equal amounts the earlier one wins
+type BidKey = (u128, BidId);
+
 #[derive(BorshDeserialize, BorshSerialize, Eq)]
 pub struct Bid {
     pub id: BidId,
     pub bidder_id: AccountId,
-    pub amount_yocto: u128,
-}
-
-impl Ord for Bid {
-    // best proposal comes first
-    fn cmp(&self, other: &Self) -> Ordering {
-        if self.amount_yocto < other.amount_yocto {
-            Ordering::Greater
-        } else if self.amount_yocto == other.amount_yocto {
-            self.id.cmp(&other.id)      // earlier comes first
-        } else {
-            Ordering::Less
-        }
-    }
-}
-
-impl PartialOrd for Bid {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other))
-    }
+    pub amount_yocto: u128,     // effective amount, the only one shown publicly
+    pub max_amount_yocto: u128, // escrowed, above amount_yocto for proxy bids
 }
 
 impl PartialEq for Bid {
@@ -45,4 +36,140 @@ impl fmt::Display for Bid {
             self.id, self.bidder_id, self.amount_yocto
         )
     }
-}
\ No newline at end of file
+}
+
+impl Bid {
+    fn key(&self) -> BidKey {
+        (u128::MAX - self.amount_yocto, self.id)
+    }
+}
+
+// bids of a single listing kept in bid order
+// insertion and removal take O(log n) storage operations, best and worst bids are
+// single lookups so nothing needs to be re-sorted or rewritten when a bid comes or goes
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct BidBook {
+    bids: TreeMap<BidKey, Bid>,
+    keys_by_id: LookupMap<BidId, BidKey>,
+}
+
+impl BidBook {
+    pub fn new<S: IntoStorageKey>(bids_prefix: S, keys_by_id_prefix: S) -> Self {
+        Self {
+            bids: TreeMap::new(bids_prefix),
+            keys_by_id: LookupMap::new(keys_by_id_prefix),
+        }
+    }
+
+    pub fn len(&self) -> u64 {
+        self.bids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bids.len() == 0
+    }
+
+    pub fn insert(&mut self, bid: &Bid) {
+        let key = bid.key();
+        self.bids.insert(&key, bid);
+        self.keys_by_id.insert(&bid.id, &key);
+    }
+
+    pub fn get(&self, bid_id: BidId) -> Option<Bid> {
+        self.keys_by_id
+            .get(&bid_id)
+            .and_then(|key| self.bids.get(&key))
+    }
+
+    pub fn remove(&mut self, bid_id: BidId) -> Option<Bid> {
+        self.keys_by_id
+            .remove(&bid_id)
+            .and_then(|key| self.bids.remove(&key))
+    }
+
+    pub fn best(&self) -> Option<Bid> {
+        self.bids.min().and_then(|key| self.bids.get(&key))
+    }
+
+    pub fn worst(&self) -> Option<Bid> {
+        self.bids.max().and_then(|key| self.bids.get(&key))
+    }
+
+    pub fn pop_worst(&mut self) -> Option<Bid> {
+        self.bids.max().and_then(|key| {
+            self.keys_by_id.remove(&key.1);
+            self.bids.remove(&key)
+        })
+    }
+
+    // best bid first
+    pub fn iter<'a>(&'a self) -> impl Iterator<Item = Bid> + 'a {
+        self.bids.iter().map(|(_, bid)| bid)
+    }
+
+    // worst bid first
+    pub fn iter_rev<'a>(&'a self) -> impl Iterator<Item = Bid> + 'a {
+        self.bids.iter_rev().map(|(_, bid)| bid)
+    }
+
+    // position of the bid in bid order, 0 is the best one; walks the better bids, which is
+    // only done by views
+    pub fn rank(&self, bid: &Bid) -> Option<u64> {
+        let key = bid.key();
+        if !self.bids.contains_key(&key) {
+            return None;
+        }
+        Some(
+            self.bids
+                .iter()
+                .take_while(|(bid_key, _)| bid_key < &key)
+                .count() as u64,
+        )
+    }
+
+    pub fn clear(&mut self) {
+        for (_, bid) in self.bids.iter() {
+            self.keys_by_id.remove(&bid.id);
+        }
+        self.bids.clear();
+    }
+}
+
+impl MarketplaceContract {
+    // keeps track of open bids of every account, across all listings
+    pub(crate) fn internal_add_bid_to_account(
+        &mut self,
+        bidder_id: &AccountId,
+        listing_id: ListingId,
+        bid_id: BidId,
+    ) {
+        let mut bids = self.bids_by_account.get(bidder_id).unwrap_or_else(|| {
+            UnorderedSet::new(
+                MarketplaceStorageKey::BidsByAccountInner {
+                    account_id_hash: hash_account_id(bidder_id),
+                }
+                .try_to_vec()
+                .unwrap(),
+            )
+        });
+        bids.insert(&(listing_id, bid_id));
+        self.bids_by_account.insert(bidder_id, &bids);
+    }
+
+    // to be called whenever a bid leaves the listing (revoked, outbid, accepted or refunded)
+    pub(crate) fn internal_remove_bid_from_account(
+        &mut self,
+        bidder_id: &AccountId,
+        listing_id: ListingId,
+        bid_id: BidId,
+    ) {
+        if let Some(mut bids) = self.bids_by_account.get(bidder_id) {
+            bids.remove(&(listing_id, bid_id));
+            if bids.is_empty() {
+                self.bids_by_account.remove(bidder_id);
+            } else {
+                self.bids_by_account.insert(bidder_id, &bids);
+            }
+        }
+    }
+}
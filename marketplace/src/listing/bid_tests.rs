@@ -0,0 +1,99 @@
+#[cfg(test)]
+mod bid_tests {
+    use super::super::{Bid, BidBook};
+    use crate::{test_utils::*, *};
+
+    fn bid(id: u64, amount_yocto: Balance) -> Bid {
+        Bid {
+            id,
+            bidder_id: account(BIDDER_ACCOUNT_ID),
+            amount_yocto,
+            max_amount_yocto: amount_yocto,
+        }
+    }
+
+    // bids 0.6N, 0.8N, 0.6N and 0.7N placed in this order
+    fn book() -> BidBook {
+        set_context(BIDDER_ACCOUNT_ID, NOW, 0);
+        let mut book = BidBook::new(b"b".to_vec(), b"k".to_vec());
+        for (id, amount_yocto) in [6, 8, 6, 7].iter().enumerate() {
+            book.insert(&bid(id as u64, amount_yocto * ONE_NEAR / 10));
+        }
+        book
+    }
+
+    fn bid_ids(bids: impl Iterator<Item = Bid>) -> Vec<u64> {
+        bids.map(|bid| bid.id).collect()
+    }
+
+    /* order */
+
+    #[test]
+    fn test_bid_order() {
+        let book = book();
+
+        // among equal amounts the earlier bid comes first
+        assert_eq!(bid_ids(book.iter()), vec![1, 3, 0, 2]);
+        assert_eq!(bid_ids(book.iter_rev()), vec![2, 0, 3, 1]);
+        assert_eq!(book.best().unwrap().id, 1);
+        assert_eq!(book.worst().unwrap().id, 2);
+        assert_eq!(book.len(), 4);
+    }
+
+    #[test]
+    fn test_rank() {
+        let book = book();
+
+        let ranks: Vec<Option<u64>> = (0..4).map(|id| book.rank(&book.get(id).unwrap())).collect();
+        assert_eq!(ranks, vec![Some(2), Some(0), Some(3), Some(1)]);
+        // a bid which isn't in the book has no rank
+        assert_eq!(book.rank(&bid(4, ONE_NEAR / 2)), None);
+    }
+
+    /* removal */
+
+    #[test]
+    fn test_remove() {
+        let mut book = book();
+
+        assert_eq!(book.remove(3).unwrap().amount_yocto, 7 * ONE_NEAR / 10);
+        assert!(book.remove(3).is_none());
+        assert!(book.get(3).is_none());
+        assert_eq!(bid_ids(book.iter()), vec![1, 0, 2]);
+        assert_eq!(book.rank(&book.get(2).unwrap()), Some(2));
+    }
+
+    #[test]
+    fn test_pop_worst() {
+        let mut book = book();
+
+        assert_eq!(book.pop_worst().unwrap().id, 2);
+        assert!(book.get(2).is_none());
+        assert_eq!(bid_ids(book.iter()), vec![1, 3, 0]);
+    }
+
+    #[test]
+    fn test_clear() {
+        let mut book = book();
+
+        book.clear();
+        assert!(book.is_empty());
+        assert!(book.best().is_none());
+        assert!((0..4).all(|id| book.get(id).is_none()));
+    }
+
+    /* storage */
+
+    #[test]
+    fn test_book_size_independent_of_bid_count() {
+        set_context(BIDDER_ACCOUNT_ID, NOW, 0);
+        let empty_book = BidBook::new(b"e".to_vec(), b"f".to_vec());
+        let book = book();
+
+        // the listing only holds the tree root and lengths, the bids have entries of their own
+        assert_eq!(
+            book.try_to_vec().unwrap().len(),
+            empty_book.try_to_vec().unwrap().len()
+        );
+    }
+}
@@ -0,0 +1,205 @@
+use crate::{
+    listing::{
+        bid::Bid, bundle::lib::BundleListing, id::ListingId, status::ListingStatus,
+    },
+    *,
+};
+use near_sdk::json_types::{U128, U64};
+
+#[cfg(test)]
+#[path = "buyer_tests.rs"]
+mod buyer_tests;
+
+#[near_bindgen]
+impl MarketplaceContract {
+    // every token of the bundle goes to receiver_id (the caller if missing)
+    // on top of the price, 2yN per token must be attached for the NFT transfers, the caller
+    // gets the refunds; if any of the tokens cannot be transferred nothing is sold
+    #[payable]
+    pub fn bundle_listing_buy(
+        &mut self,
+        bundle_id: U64,
+        receiver_id: Option<AccountId>,
+        max_price_yocto: Option<U128>,
+    ) -> Promise {
+        let bundle_id = bundle_id.0;
+        let mut listing = self
+            .bundle_listings_by_id
+            .get(&bundle_id)
+            .expect("Could not find this listing");
+        listing.update_status();
+
+        let price_yocto = listing
+            .price_yocto
+            .expect("Buy Now is not possible for this listing");
+        if let Some(max_price_yocto) = max_price_yocto {
+            assert!(
+                price_yocto <= max_price_yocto.0,
+                "Price of {} exceeds the maximum price of {}",
+                price_yocto,
+                max_price_yocto.0
+            );
+        }
+
+        assert!(
+            listing.status == ListingStatus::Running,
+            "This listing is {}",
+            listing.status.as_str()
+        );
+        assert!(!listing.is_selling, "This listing is being sold");
+
+        let buyer_id = env::predecessor_account_id();
+        assert!(buyer_id != listing.seller_id, "Cannot buy from yourself");
+        let receiver_id = receiver_id.unwrap_or_else(|| buyer_id.clone());
+        assert!(
+            receiver_id != listing.seller_id,
+            "Cannot buy for the seller"
+        );
+
+        // each token is transferred twice: into custody and then to the receiver
+        let required_deposit = price_yocto + 2 * listing.tokens.len() as u128;
+        let attached_deposit = env::attached_deposit();
+        assert!(
+            attached_deposit >= required_deposit,
+            "Attached deposit of {} is insufficient to pay the price of {} and 2yN per token for NFT transfers",
+            attached_deposit,
+            price_yocto,
+        );
+
+        listing.is_selling = true;
+        self.bundle_listings_by_id.insert(&bundle_id, &listing);
+
+        self.internal_sell_bundle_listing(
+            &listing,
+            buyer_id,
+            receiver_id,
+            attached_deposit,
+            price_yocto,
+        )
+    }
+
+    // the bid amount is escrowed, its storage is paid from the bidder's storage deposit; the
+    // book holds up to bundle_listing_bids_max bids, when it's full the lowest one is refunded
+    // to make room
+    #[payable]
+    pub fn bundle_listing_place_bid(&mut self, bundle_id: U64, amount_yocto: U128) -> U64 {
+        let bundle_id = bundle_id.0;
+        let amount_yocto = amount_yocto.0;
+
+        let mut listing = self
+            .bundle_listings_by_id
+            .get(&bundle_id)
+            .expect("Could not find this listing");
+        listing.update_status();
+
+        assert!(
+            listing.status == ListingStatus::Running,
+            "This listing is {}",
+            listing.status.as_str()
+        );
+        assert!(!listing.is_selling, "This listing is being sold");
+
+        let bidder_id = env::predecessor_account_id();
+        assert!(
+            bidder_id != listing.seller_id,
+            "Cannot submit a bid to your own listing"
+        );
+
+        let acceptable_bid_yocto = acceptable_bid_yocto(&listing);
+        if let Some(price_yocto) = listing.price_yocto {
+            assert!(
+                amount_yocto < price_yocto,
+                "Bid must be lower than buy now price of {}",
+                price_yocto
+            );
+        }
+        listing.rules.assert_bid_amount(amount_yocto);
+        assert!(
+            amount_yocto >= acceptable_bid_yocto,
+            "Bid is too low. The lowest acceptable amount is {:?}",
+            acceptable_bid_yocto
+        );
+
+        let attached_deposit = env::attached_deposit();
+        assert!(
+            attached_deposit >= amount_yocto,
+            "Attached balance must be sufficient to pay the required deposit of {} yocto Near",
+            amount_yocto
+        );
+
+        // the book is full, the lowest bid makes room
+        if listing.bids.len() >= self.config.listing_limits.bundle_listing_bids_max {
+            let worst_bid = listing.bids.worst().unwrap();
+            self.internal_bundle_listing_refund_bid(&mut listing, worst_bid.id);
+        }
+
+        let new_bid = Bid {
+            id: listing.next_bid_id,
+            bidder_id: bidder_id.clone(),
+            amount_yocto,
+            max_amount_yocto: amount_yocto,
+        };
+        listing.next_bid_id += 1;
+
+        let storage_before = env::storage_usage();
+        listing.bids.insert(&new_bid);
+        self.bundle_listings_by_id.insert(&bundle_id, &listing);
+        self.internal_add_bid_to_account(&bidder_id, ListingId::Bundle(bundle_id), new_bid.id);
+        let storage_after = env::storage_usage();
+        self.internal_charge_account_storage(&bidder_id, storage_before, storage_after);
+
+        let refund = attached_deposit - amount_yocto;
+        if refund > 0 {
+            Promise::new(bidder_id).transfer(refund);
+        }
+
+        U64(new_bid.id)
+    }
+
+    // the bid is returned minus the revoke fee, the bid storage goes back to the bidder's
+    // storage deposit
+    pub fn bundle_listing_revoke_bid(&mut self, bundle_id: U64, bid_id: U64) {
+        let bundle_id = bundle_id.0;
+
+        let mut listing = self
+            .bundle_listings_by_id
+            .get(&bundle_id)
+            .expect("Could not find this listing");
+        listing.update_status();
+        assert!(
+            listing.status == ListingStatus::Running,
+            "This listing is {}",
+            listing.status.as_str()
+        );
+        assert!(!listing.is_selling, "This listing is being sold");
+
+        let storage_before = env::storage_usage();
+        let removed_bid = listing.bids.remove(bid_id.0).expect("Could not find bid");
+        assert!(
+            removed_bid.bidder_id == env::predecessor_account_id(),
+            "Not authorized to revoke this bid"
+        );
+        self.bundle_listings_by_id.insert(&bundle_id, &listing);
+        self.internal_remove_bid_from_account(
+            &removed_bid.bidder_id,
+            ListingId::Bundle(bundle_id),
+            removed_bid.id,
+        );
+        let storage_after = env::storage_usage();
+        self.internal_charge_account_storage(&removed_bid.bidder_id, storage_before, storage_after);
+
+        let fee = removed_bid.amount_yocto * self.config.listing_limits.revoke_fee_rate as u128 / 100;
+        Promise::new(removed_bid.bidder_id).transfer(removed_bid.max_amount_yocto - fee);
+        Promise::new(self.fees_account_id()).transfer(fee);
+    }
+}
+
+// new bid must beat the best one by the listing's increment
+fn acceptable_bid_yocto(listing: &BundleListing) -> u128 {
+    let min_bid_yocto = listing
+        .min_bid_yocto
+        .expect("Bids are not accepted for this listing");
+    listing.bids.best().map_or(min_bid_yocto, |best_bid| {
+        std::cmp::max(min_bid_yocto, listing.rules.next_bid_yocto(best_bid.amount_yocto))
+    })
+}
@@ -0,0 +1,221 @@
+#[cfg(test)]
+mod buyer_tests {
+    use crate::{test_utils::*, *};
+    use near_sdk::json_types::{U128, U64};
+
+    const TOKEN_IDS: [&str; 2] = ["0:1", "0:2"];
+
+    fn auction(marketplace: &mut MarketplaceContract) -> U64 {
+        U64(add_bundle_listing(
+            marketplace,
+            &TOKEN_IDS,
+            Some(10 * ONE_NEAR),
+            Some(MIN_BID_YOCTO),
+        ))
+    }
+
+    fn place_bid(
+        marketplace: &mut MarketplaceContract,
+        bundle_id: U64,
+        bidder_id: &str,
+        amount_yocto: Balance,
+    ) -> U64 {
+        set_context(bidder_id, NOW, amount_yocto);
+        marketplace.bundle_listing_place_bid(bundle_id, U128(amount_yocto))
+    }
+
+    fn bid_amounts(marketplace: &MarketplaceContract, bundle_id: U64) -> Vec<Balance> {
+        marketplace
+            .bundle_listing_bids(bundle_id, None, None)
+            .iter()
+            .map(|bid| bid.amount_yocto.0)
+            .collect()
+    }
+
+    fn storage_deposit(marketplace: &MarketplaceContract, account_id: &str) -> Balance {
+        marketplace
+            .storage_deposits
+            .get(&account(account_id))
+            .unwrap()
+    }
+
+    /* bids */
+
+    #[test]
+    fn test_place_bid_charges_bidder() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        let bundle_id = auction(&mut marketplace);
+
+        place_bid(
+            &mut marketplace,
+            bundle_id,
+            BIDDER_ACCOUNT_ID,
+            MIN_BID_YOCTO,
+        );
+
+        assert_eq!(bid_amounts(&marketplace, bundle_id), vec![MIN_BID_YOCTO]);
+        assert!(storage_deposit(&marketplace, BIDDER_ACCOUNT_ID) < STORAGE_DEPOSIT);
+        assert_eq!(
+            storage_deposit(&marketplace, SELLER_ACCOUNT_ID),
+            STORAGE_DEPOSIT
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Your storage deposit is too low"#)]
+    fn test_place_bid_without_storage_deposit() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        let bundle_id = auction(&mut marketplace);
+        marketplace
+            .storage_deposits
+            .remove(&account(BIDDER_ACCOUNT_ID));
+
+        place_bid(
+            &mut marketplace,
+            bundle_id,
+            BIDDER_ACCOUNT_ID,
+            MIN_BID_YOCTO,
+        );
+    }
+
+    #[test]
+    fn test_full_book_refunds_lowest_bid() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        marketplace.config.listing_limits.bundle_listing_bids_max = 2;
+        let bundle_id = auction(&mut marketplace);
+
+        place_bid(
+            &mut marketplace,
+            bundle_id,
+            BIDDER_ACCOUNT_ID,
+            MIN_BID_YOCTO,
+        );
+        place_bid(
+            &mut marketplace,
+            bundle_id,
+            BIDDER2_ACCOUNT_ID,
+            2 * MIN_BID_YOCTO,
+        );
+        place_bid(
+            &mut marketplace,
+            bundle_id,
+            BUYER_ACCOUNT_ID,
+            3 * MIN_BID_YOCTO,
+        );
+
+        assert_eq!(
+            bid_amounts(&marketplace, bundle_id),
+            vec![3 * MIN_BID_YOCTO, 2 * MIN_BID_YOCTO]
+        );
+        // the outbid bidder got the bid storage back
+        assert!(marketplace
+            .bids_by_account(account(BIDDER_ACCOUNT_ID), None, None)
+            .is_empty());
+        assert_eq!(
+            storage_deposit(&marketplace, BIDDER_ACCOUNT_ID),
+            STORAGE_DEPOSIT
+        );
+    }
+
+    #[test]
+    fn test_revoke_bid_returns_storage() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        let bundle_id = auction(&mut marketplace);
+        let bid_id = place_bid(
+            &mut marketplace,
+            bundle_id,
+            BIDDER_ACCOUNT_ID,
+            MIN_BID_YOCTO,
+        );
+
+        set_context(BIDDER_ACCOUNT_ID, NOW, 0);
+        marketplace.bundle_listing_revoke_bid(bundle_id, bid_id);
+
+        assert!(bid_amounts(&marketplace, bundle_id).is_empty());
+        assert_eq!(
+            storage_deposit(&marketplace, BIDDER_ACCOUNT_ID),
+            STORAGE_DEPOSIT
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Not authorized to revoke this bid"#)]
+    fn test_revoke_bid_of_other_bidder() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        let bundle_id = auction(&mut marketplace);
+        let bid_id = place_bid(
+            &mut marketplace,
+            bundle_id,
+            BIDDER_ACCOUNT_ID,
+            MIN_BID_YOCTO,
+        );
+
+        set_context(BIDDER2_ACCOUNT_ID, NOW, 0);
+        marketplace.bundle_listing_revoke_bid(bundle_id, bid_id);
+    }
+
+    /* closing */
+
+    #[test]
+    fn test_remove_refunds_bids() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        let bundle_id = auction(&mut marketplace);
+        place_bid(
+            &mut marketplace,
+            bundle_id,
+            BIDDER_ACCOUNT_ID,
+            MIN_BID_YOCTO,
+        );
+        place_bid(
+            &mut marketplace,
+            bundle_id,
+            BIDDER2_ACCOUNT_ID,
+            2 * MIN_BID_YOCTO,
+        );
+
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        marketplace.bundle_listing_remove(bundle_id);
+
+        assert!(marketplace.bundle_listing(bundle_id).is_none());
+        for token_id in TOKEN_IDS {
+            assert!(marketplace
+                .bundle_listing_for_token(account(NFT_CONTRACT_ID), token_id.to_string())
+                .is_none());
+        }
+        for bidder_id in [BIDDER_ACCOUNT_ID, BIDDER2_ACCOUNT_ID] {
+            assert!(marketplace
+                .bids_by_account(account(bidder_id), None, None)
+                .is_empty());
+            assert_eq!(storage_deposit(&marketplace, bidder_id), STORAGE_DEPOSIT);
+        }
+    }
+
+    #[test]
+    fn test_accept_bid_returns_bid_storage() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        let bundle_id = auction(&mut marketplace);
+        let bid_id = place_bid(
+            &mut marketplace,
+            bundle_id,
+            BIDDER_ACCOUNT_ID,
+            MIN_BID_YOCTO,
+        );
+
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        marketplace.bundle_listing_accept_bid(bundle_id, bid_id);
+
+        assert!(marketplace.bundle_listing(bundle_id).unwrap().is_selling);
+        assert!(bid_amounts(&marketplace, bundle_id).is_empty());
+        assert_eq!(
+            storage_deposit(&marketplace, BIDDER_ACCOUNT_ID),
+            STORAGE_DEPOSIT
+        );
+    }
+}
@@ -0,0 +1,11 @@
+// every token is transferred twice on sale (into custody and out to the buyer), the gas of a
+// single call caps the bundle size
+pub const BUNDLE_LISTING_TOKENS_MIN: usize = 2;
+pub const BUNDLE_LISTING_TOKENS_MAX: usize = 5;
+
+pub const BUNDLE_LISTING_MIN_DURATION_NANO: i64 = 3600000000000;       // 1 hour
+pub const BUNDLE_LISTING_MAX_DURATION_NANO: i64 = 3600000000000 * 24 * 14;       // 2 weeks, for bid-accepting bundles
+
+// bids a bundle listing holds at once, the lowest one is refunded when a better one comes;
+// keeps the refunds made when the bundle closes within the gas of the sale completion
+pub const BUNDLE_LISTING_BIDS_MAX: u64 = 10;
@@ -0,0 +1,113 @@
+use crate::{
+    listing::{
+        bundle::lib::{BundleListing, BundleTokenJson},
+        rules::JsonListingRules,
+        status::ListingStatus,
+    },
+    *,
+};
+
+use near_sdk::json_types::{U128, U64};
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct JsonBundleListing {
+    pub bundle_id: U64,
+    pub seller_id: AccountId,
+    pub tokens: Vec<BundleTokenJson>,
+    pub price_yocto: Option<U128>,
+    pub min_bid_yocto: Option<U128>,
+    pub start_timestamp: U64,       // nanoseconds since 1970-01-01
+    pub end_timestamp: Option<U64>, // nanoseconds since 1970-01-01
+    pub status: ListingStatus,
+    pub is_selling: bool, // tokens are being transferred to a buyer
+    pub rules: JsonListingRules,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct JsonBundleListingBid {
+    pub id: U64,
+    pub bidder_id: AccountId,
+    pub amount_yocto: U128,
+}
+
+impl BundleListing {
+    pub(crate) fn to_json(self) -> JsonBundleListing {
+        let rules = self.rules.to_json();
+        JsonBundleListing {
+            bundle_id: U64(self.id),
+            seller_id: self.seller_id,
+            tokens: self
+                .tokens
+                .iter()
+                .map(|token| BundleTokenJson {
+                    nft_contract_id: token.nft_contract_id.clone(),
+                    token_id: token.token_id.clone(),
+                    approval_id: U64(token.approval_id),
+                })
+                .collect(),
+            price_yocto: self.price_yocto.map(|p| U128(p)),
+            min_bid_yocto: self.min_bid_yocto.map(|b| U128(b)),
+            start_timestamp: U64(self.start_timestamp as u64),
+            end_timestamp: self.end_timestamp.map(|t| U64(t as u64)),
+            status: self.status,
+            is_selling: self.is_selling,
+            rules,
+        }
+    }
+}
+
+// view-only methods
+
+#[near_bindgen]
+impl MarketplaceContract {
+    pub fn bundle_listing(&self, bundle_id: U64) -> Option<JsonBundleListing> {
+        self.bundle_listings_by_id
+            .get(&bundle_id.0)
+            .map(|mut listing| {
+                listing.update_status();
+                listing.to_json()
+            })
+    }
+
+    // best bid first, results are paginated
+    pub fn bundle_listing_bids(
+        &self,
+        bundle_id: U64,
+        from_index: Option<U128>,
+        limit: Option<U64>,
+    ) -> Vec<JsonBundleListingBid> {
+        let listing = self
+            .bundle_listings_by_id
+            .get(&bundle_id.0)
+            .expect("Could not find bundle listing");
+
+        // where to start pagination - if we have a from_index, we'll use that - otherwise start from 0 index
+        let start = from_index.map_or(0, |i| i.0) as usize;
+        let count = limit.map_or(10, |l| l.0) as usize;
+
+        listing
+            .bids
+            .iter()
+            .skip(start) //skip to the index we specified in the start variable
+            .take(count) // return "limit" elements or 0 if missing
+            .map(|bid| JsonBundleListingBid {
+                id: U64(bid.id),
+                bidder_id: bid.bidder_id,
+                amount_yocto: U128(bid.amount_yocto),
+            })
+            .collect()
+    }
+
+    // the bundle the token is part of, if any
+    pub fn bundle_listing_for_token(
+        &self,
+        nft_contract_id: AccountId,
+        token_id: NftId,
+    ) -> Option<U64> {
+        self.bundle_id_by_token
+            .get(&(nft_contract_id, token_id))
+            .map(|bundle_id| U64(bundle_id))
+    }
+}
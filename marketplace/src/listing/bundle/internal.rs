@@ -0,0 +1,77 @@
+use crate::{
+    listing::{
+        bid::{Bid, BidId},
+        bundle::lib::{BundleId, BundleListing},
+        id::ListingId,
+    },
+    *,
+};
+
+pub(crate) fn hash_bundle_id(bundle_id: BundleId) -> CryptoHash {
+    let hashed_string = format!("bundle.{}", bundle_id);
+    let mut hash = CryptoHash::default();
+    hash.copy_from_slice(&env::sha256(hashed_string.as_bytes()));
+    hash
+}
+
+impl MarketplaceContract {
+    // removes the bundle and releases its tokens, standing bids are refunded (there are at most
+    // bundle_listing_bids_max of them) and the freed storage goes back to the bidders' and the
+    // seller's deposits
+    pub(crate) fn internal_close_bundle_listing(&mut self, bundle_id: BundleId) -> BundleListing {
+        if let Some(mut listing) = self.bundle_listings_by_id.get(&bundle_id) {
+            let bid_ids: Vec<BidId> = listing.bids.iter().map(|bid| bid.id).collect();
+            for bid_id in bid_ids {
+                self.internal_bundle_listing_refund_bid(&mut listing, bid_id);
+            }
+        }
+
+        let storage_before = env::storage_usage();
+        let removed_listing = self
+            .bundle_listings_by_id
+            .remove(&bundle_id)
+            .expect("Could not remove bundle: Could not find bundle");
+        for token in removed_listing.tokens.iter() {
+            self.bundle_id_by_token
+                .remove(&(token.nft_contract_id.clone(), token.token_id.clone()));
+        }
+        let storage_after = env::storage_usage();
+        self.internal_charge_account_storage(
+            &removed_listing.seller_id,
+            storage_before,
+            storage_after,
+        );
+        removed_listing
+    }
+
+    // takes the bid off the book and returns its escrow, the bid storage goes back to the
+    // bidder's deposit; the listing is stored here as the bid book state is part of it, later
+    // changes are up to the caller
+    pub(crate) fn internal_bundle_listing_refund_bid(
+        &mut self,
+        listing: &mut BundleListing,
+        bid_id: BidId,
+    ) -> Option<Bid> {
+        let storage_before = env::storage_usage();
+        let bid = listing.bids.remove(bid_id)?;
+        self.internal_remove_bid_from_account(
+            &bid.bidder_id,
+            ListingId::Bundle(listing.id),
+            bid.id,
+        );
+        self.bundle_listings_by_id.insert(&listing.id, listing);
+        let storage_after = env::storage_usage();
+        self.internal_charge_account_storage(&bid.bidder_id, storage_before, storage_after);
+        Promise::new(bid.bidder_id.clone()).transfer(bid.max_amount_yocto);
+        Some(bid)
+    }
+
+    // removes a bundle the marketplace can no longer sell (an approval is gone)
+    pub(crate) fn internal_delist_bundle_listing(&mut self, bundle_id: BundleId) {
+        self.internal_close_bundle_listing(bundle_id);
+        env::log_str(&format!(
+            "Bundle listing {} removed, the marketplace can no longer transfer all of its tokens",
+            bundle_id
+        ));
+    }
+}
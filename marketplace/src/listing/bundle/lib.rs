@@ -0,0 +1,82 @@
+use crate::*;
+use super::super::{
+    bid::{BidBook},
+    status::{ListingStatus},
+    rules::ListingRules,
+};
+use near_sdk::json_types::U64;
+
+pub type BundleId = u64;
+
+#[derive(BorshStorageKey, BorshSerialize)]
+pub enum BundleListingStorageKey {
+    Bids {
+        bundle_id_hash: CryptoHash,
+    },
+    BidKeys {
+        bundle_id_hash: CryptoHash,
+    },
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+#[derive(Serialize,Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[derive(Clone)]
+pub struct BundleToken {
+    pub nft_contract_id: AccountId,
+    pub token_id: NftId,
+    pub approval_id: u64,
+    pub collection_id: Option<NftCollectionId>, // known once the NFT contract confirms the token
+}
+
+#[derive(Serialize,Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct BundleTokenJson {
+    pub nft_contract_id: AccountId,
+    pub token_id: NftId,
+    pub approval_id: U64,
+}
+
+// several tokens, possibly of different NFT contracts, sold together for one price or in one
+// auction; every token must be approved to the marketplace
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct BundleListing {
+    pub id: BundleId,
+    pub seller_id: AccountId,
+    pub tokens: Vec<BundleToken>,
+    pub price_yocto: Option<u128>,
+    pub min_bid_yocto: Option<u128>,            // if None then no bids will be accepted
+    pub start_timestamp: i64,                   // nanoseconds since 1970-01-01
+    pub end_timestamp: Option<i64>,             // nanoseconds since 1970-01-01
+    pub status: ListingStatus,
+    pub bids: BidBook,
+    pub next_bid_id: u64,
+    pub is_selling: bool,                       // tokens are being transferred to a buyer
+    pub rules: ListingRules,                    // price and bid steps, fixed when the listing is added
+}
+
+impl BundleListing {
+    pub(crate) fn update_status(&mut self) {
+        let block_timestamp = env::block_timestamp() as i64;
+
+        if self.status == ListingStatus::Ended || self.status == ListingStatus::Pending {
+            return;
+        }
+
+        if let Some(end_timestamp) = self.end_timestamp {
+            if block_timestamp >= end_timestamp {
+                self.status = ListingStatus::Ended;
+                return;
+            }
+        }
+
+        if self.status == ListingStatus::Running {
+            return;
+        }
+
+        if block_timestamp >= self.start_timestamp {
+            self.status = ListingStatus::Running;
+            return;
+        }
+    }
+}
@@ -0,0 +1,9 @@
+pub mod lib;
+mod seller;
+mod buyer;
+mod sale;
+mod validate;
+pub mod enumeration;
+
+pub(crate) mod internal;
+pub mod config;
@@ -0,0 +1,199 @@
+use crate::{
+    constants::NO_DEPOSIT,
+    delivery::{NFT_DELIVERY_COMPLETION_GAS, NFT_DELIVERY_GAS},
+    external::nft_contract,
+    listing::{
+        bundle::lib::{BundleId, BundleListing},
+        id::ListingId,
+        sale::Sale,
+    },
+    *,
+};
+use near_sdk::json_types::{U128, U64};
+use near_sdk::PromiseResult;
+
+const NFT_TRANSFER_GAS: Gas = Gas(15_000_000_000_000); // TODO: measure
+const BUNDLE_CUSTODY_COMPLETION_GAS: Gas = Gas(10_000_000_000_000); // TODO: measure, excluding transfers
+
+#[cfg(test)]
+#[path = "sale_tests.rs"]
+mod sale_tests;
+
+impl MarketplaceContract {
+    // tokens cannot be taken back from the receiver, so the sale goes in two steps: all tokens
+    // are first moved to the marketplace using the seller's approvals, and only if every one of
+    // them made it they are passed on to the receiver; otherwise they go back to the seller
+    // escrow is what the payer deposited, 2yN per token are taken from it for the transfers
+    pub(crate) fn internal_sell_bundle_listing(
+        &mut self,
+        listing: &BundleListing,
+        payer_id: AccountId,
+        receiver_id: AccountId,
+        escrow_yocto: u128,
+        price_yocto: u128,
+    ) -> Promise {
+        let mut transfers = listing.tokens.iter().map(|token| {
+            nft_contract::nft_transfer(
+                env::current_account_id(),
+                token.token_id.clone(),
+                Some(token.approval_id),
+                None,
+                token.nft_contract_id.clone(),
+                1,
+                NFT_TRANSFER_GAS,
+            )
+        });
+        let first_transfer = transfers.next().unwrap();
+        let completion_gas = BUNDLE_CUSTODY_COMPLETION_GAS.0
+            + (NFT_DELIVERY_GAS.0 + NFT_DELIVERY_COMPLETION_GAS.0) * listing.tokens.len() as u64;
+        transfers
+            .fold(first_transfer, |all, transfer| all.and(transfer))
+            .then(ext_self_nft::bundle_listing_custody_completion(
+                U64(listing.id),
+                payer_id,
+                receiver_id,
+                U128(escrow_yocto),
+                U128(price_yocto),
+                env::current_account_id(),
+                NO_DEPOSIT,
+                Gas(completion_gas),
+            ))
+    }
+}
+
+#[ext_contract(ext_self_nft)]
+trait BundleListingSaleCallback {
+    fn bundle_listing_custody_completion(
+        &mut self,
+        bundle_id: U64,
+        payer_id: AccountId,
+        receiver_id: AccountId,
+        escrow_yocto: U128,
+        price_yocto: U128,
+    ) -> bool;
+}
+
+trait BundleListingSaleCallback {
+    fn bundle_listing_custody_completion(
+        &mut self,
+        bundle_id: U64,
+        payer_id: AccountId,
+        receiver_id: AccountId,
+        escrow_yocto: U128,
+        price_yocto: U128,
+    ) -> bool;
+}
+
+#[near_bindgen]
+impl BundleListingSaleCallback for MarketplaceContract {
+    // we don't panic here, it'd revert the refunds and leave the tokens with the marketplace
+    #[private]
+    fn bundle_listing_custody_completion(
+        &mut self,
+        bundle_id: U64,
+        payer_id: AccountId,
+        receiver_id: AccountId,
+        escrow_yocto: U128,
+        price_yocto: U128,
+    ) -> bool {
+        let bundle_id: BundleId = bundle_id.0;
+        let escrow_yocto = escrow_yocto.0;
+        let price_yocto = price_yocto.0;
+
+        // the bundle cannot be removed while it's being sold
+        let listing = self
+            .bundle_listings_by_id
+            .get(&bundle_id)
+            .expect("Could not find this listing");
+        assert_eq!(
+            env::promise_results_count(),
+            listing.tokens.len() as u64,
+            "Unexpected number of data receipts"
+        );
+        let is_in_custody: Vec<bool> = (0..listing.tokens.len())
+            .map(|index| {
+                matches!(
+                    env::promise_result(index as u64),
+                    PromiseResult::Successful(_)
+                )
+            })
+            .collect();
+
+        // the tokens that reached the marketplace lost their approvals, their own listings are
+        // dead; the bundle is being sold so it's left alone here
+        for (token, _) in listing
+            .tokens
+            .iter()
+            .zip(is_in_custody.iter())
+            .filter(|(_, &is_ok)| is_ok)
+        {
+            self.internal_delist_token(&token.nft_contract_id, &token.token_id, &listing.seller_id);
+        }
+
+        if is_in_custody.iter().all(|&is_ok| is_ok) {
+            for token in listing.tokens.iter() {
+                self.internal_deliver_token(&token.nft_contract_id, &token.token_id, &receiver_id);
+            }
+
+            // 1yN per token was spent on each of the two transfers
+            let available_yocto = escrow_yocto - 2 * listing.tokens.len() as u128;
+            let payout_yocto = std::cmp::min(price_yocto, available_yocto);
+            Promise::new(listing.seller_id.clone()).transfer(payout_yocto);
+            let refund_yocto = available_yocto - payout_yocto;
+            if refund_yocto > 0 {
+                Promise::new(payer_id).transfer(refund_yocto);
+            }
+
+            let listing = self.internal_close_bundle_listing(bundle_id);
+
+            // the price is split evenly between the tokens, the last one takes the remainder
+            let token_count = listing.tokens.len() as u128;
+            let token_price_yocto = payout_yocto / token_count;
+            for (index, token) in listing.tokens.iter().enumerate() {
+                let sale_price_yocto = if index as u128 == token_count - 1 {
+                    payout_yocto - token_price_yocto * (token_count - 1)
+                } else {
+                    token_price_yocto
+                };
+                self.internal_record_sale(
+                    Sale {
+                        listing_id: Some(ListingId::Bundle(bundle_id)),
+                        token_id: token.token_id.clone(),
+                        buyer_id: receiver_id.clone(),
+                        seller_id: listing.seller_id.clone(),
+                        price_yocto: sale_price_yocto,
+                        timestamp: env::block_timestamp(),
+                    },
+                    &token.nft_contract_id,
+                    token.collection_id,
+                );
+            }
+            true
+        } else {
+            // the tokens that made it to the marketplace go back to the seller, the 1yN
+            // of the failed transfers came back to us
+            let mut returned_count: u128 = 0;
+            for (token, _) in listing
+                .tokens
+                .iter()
+                .zip(is_in_custody.iter())
+                .filter(|(_, &is_ok)| is_ok)
+            {
+                self.internal_deliver_token(
+                    &token.nft_contract_id,
+                    &token.token_id,
+                    &listing.seller_id,
+                );
+                returned_count += 1;
+            }
+            let refund_yocto = escrow_yocto - 2 * returned_count;
+            if refund_yocto > 0 {
+                Promise::new(payer_id).transfer(refund_yocto);
+            }
+
+            // at least one approval is no longer valid
+            self.internal_delist_bundle_listing(bundle_id);
+            false
+        }
+    }
+}
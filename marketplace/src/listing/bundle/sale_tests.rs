@@ -0,0 +1,52 @@
+#[cfg(test)]
+mod sale_tests {
+    use super::super::BundleListingSaleCallback;
+    use crate::{test_utils::*, *};
+    use near_sdk::json_types::{U128, U64};
+    use near_sdk::PromiseResult;
+
+    const TOKEN_IDS: [&str; 2] = ["0:1", "0:2"];
+
+    fn market_stats(
+        marketplace: &MarketplaceContract,
+        collection_id: Option<u64>,
+    ) -> (u64, Balance) {
+        let stats = marketplace.market_stats(account(NFT_CONTRACT_ID), collection_id.map(U64));
+        (stats.sales_count.0, stats.volume_yocto.0)
+    }
+
+    /* custody */
+
+    #[test]
+    fn test_custody_completion_records_collection_sales() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        let bundle_id = add_bundle_listing(&mut marketplace, &TOKEN_IDS, Some(PRICE_YOCTO), None);
+        let mut listing = marketplace.bundle_listings_by_id.get(&bundle_id).unwrap();
+        listing.is_selling = true;
+        listing.tokens[1].collection_id = Some(1);
+        marketplace
+            .bundle_listings_by_id
+            .insert(&bundle_id, &listing);
+
+        set_callback_context(
+            NOW,
+            vec![
+                PromiseResult::Successful(vec![]),
+                PromiseResult::Successful(vec![]),
+            ],
+        );
+        assert!(marketplace.bundle_listing_custody_completion(
+            U64(bundle_id),
+            account(BUYER_ACCOUNT_ID),
+            account(BUYER_ACCOUNT_ID),
+            U128(PRICE_YOCTO + 4),
+            U128(PRICE_YOCTO),
+        ));
+
+        // the price is split between the tokens
+        assert_eq!(market_stats(&marketplace, None), (2, PRICE_YOCTO));
+        assert_eq!(market_stats(&marketplace, Some(0)), (1, PRICE_YOCTO / 2));
+        assert_eq!(market_stats(&marketplace, Some(1)), (1, PRICE_YOCTO / 2));
+    }
+}
@@ -0,0 +1,307 @@
+use crate::{
+    constants::NO_DEPOSIT,
+    external::{nft_contract, JsonNft},
+    listing::{
+        bid::BidBook,
+        date::DateInput,
+        bundle::{
+            config::*,
+            internal::hash_bundle_id,
+            lib::{BundleListing, BundleListingStorageKey, BundleToken, BundleTokenJson},
+        },
+        id::ListingId,
+        rules::JsonListingRules,
+        status::ListingStatus,
+    },
+    *,
+};
+use near_sdk::json_types::{U128, U64};
+use near_sdk::PromiseResult;
+
+const NFT_TOKEN_GAS: Gas = Gas(5_000_000_000_000); // TODO: measure
+const BUNDLE_LISTING_ADD_COMPLETION_GAS: Gas = Gas(15_000_000_000_000); // TODO: measure
+
+#[cfg(test)]
+#[path = "seller_tests.rs"]
+mod seller_tests;
+
+#[near_bindgen]
+impl MarketplaceContract {
+    // the tokens must be approved to the marketplace beforehand, the approval ids are passed in;
+    // the bundle is stored as pending and only goes live once nft_token confirms the owner and
+    // the approval of every token; returns the bundle id
+    pub fn bundle_listing_add(
+        &mut self,
+        tokens: Vec<BundleTokenJson>,
+        price_yocto: Option<U128>,
+        min_bid_yocto: Option<U128>, // if None, only buy now is allowed
+        start_date: Option<DateInput>, // if missing, it'll start accepting bids when this transaction is mined
+        end_date: Option<DateInput>,   // a duration counts from the start date
+        rules: Option<JsonListingRules>, // if missing, the marketplace defaults apply
+    ) -> Promise {
+        let price_yocto = price_yocto.map(|p| p.0);
+        let min_bid_yocto = min_bid_yocto.map(|b| b.0);
+        let seller_id = env::predecessor_account_id();
+
+        assert!(
+            tokens.len() >= BUNDLE_LISTING_TOKENS_MIN && tokens.len() <= BUNDLE_LISTING_TOKENS_MAX,
+            "Bundle must have between {} and {} tokens",
+            BUNDLE_LISTING_TOKENS_MIN,
+            BUNDLE_LISTING_TOKENS_MAX
+        );
+        let tokens: Vec<BundleToken> = tokens
+            .into_iter()
+            .map(|token| BundleToken {
+                nft_contract_id: token.nft_contract_id,
+                token_id: token.token_id,
+                approval_id: token.approval_id.0,
+                collection_id: None,
+            })
+            .collect();
+        for (index, token) in tokens.iter().enumerate() {
+            assert!(
+                !tokens[..index]
+                    .iter()
+                    .any(|other| other.nft_contract_id == token.nft_contract_id
+                        && other.token_id == token.token_id),
+                "Token {} is in the bundle more than once",
+                token.token_id
+            );
+            assert!(
+                self.bundle_id_by_token
+                    .get(&(token.nft_contract_id.clone(), token.token_id.clone()))
+                    .is_none(),
+                "Token {} is already in a bundle",
+                token.token_id
+            );
+        }
+
+        assert!(
+            price_yocto.is_some() || min_bid_yocto.is_some(),
+            "Either buy now price or min bid must be set"
+        );
+        let rules = self.config.listing_rules(rules);
+        if let Some(price_yocto) = price_yocto {
+            rules.assert_price(price_yocto);
+        }
+        if let Some(min_bid_yocto) = min_bid_yocto {
+            rules.assert_min_bid(min_bid_yocto);
+            if let Some(price_yocto) = price_yocto {
+                assert!(
+                    min_bid_yocto < price_yocto,
+                    "Min bid must be lower than buy now price"
+                );
+            }
+        }
+
+        // same rules as for secondary listings: bid-accepting bundles must end, and not too late
+        let current_block_timestamp = env::block_timestamp() as i64;
+        let start_timestamp = if let Some(start_date) = start_date {
+            let start_timestamp = start_date.to_timestamp(current_block_timestamp);
+            assert!(
+                start_timestamp >= current_block_timestamp,
+                "Start date into the past"
+            );
+            start_timestamp
+        } else {
+            current_block_timestamp
+        };
+        let end_timestamp: Option<i64> =
+            end_date.map(|end_date| end_date.to_timestamp(start_timestamp));
+        if let Some(end_timestamp) = end_timestamp {
+            let duration = end_timestamp - start_timestamp;
+            assert!(
+                duration >= BUNDLE_LISTING_MIN_DURATION_NANO,
+                "Listing duration too short"
+            );
+            if min_bid_yocto.is_some() {
+                assert!(
+                    duration <= BUNDLE_LISTING_MAX_DURATION_NANO,
+                    "Listing duration too long"
+                );
+            }
+        } else {
+            assert!(
+                min_bid_yocto.is_none(),
+                "End date must be set for bid-accepting listing"
+            );
+        }
+
+        let bundle_id = self.next_bundle_id;
+        self.next_bundle_id += 1;
+        let bundle_id_hash = hash_bundle_id(bundle_id);
+        let listing = BundleListing {
+            id: bundle_id,
+            seller_id: seller_id.clone(),
+            tokens,
+            price_yocto,
+            min_bid_yocto,
+            start_timestamp,
+            end_timestamp,
+            status: ListingStatus::Pending,
+            bids: BidBook::new(
+                BundleListingStorageKey::Bids { bundle_id_hash }
+                    .try_to_vec()
+                    .unwrap(),
+                BundleListingStorageKey::BidKeys { bundle_id_hash }
+                    .try_to_vec()
+                    .unwrap(),
+            ),
+            next_bid_id: 0,
+            is_selling: false,
+            rules,
+        };
+
+        let storage_before = env::storage_usage();
+        self.bundle_listings_by_id.insert(&bundle_id, &listing);
+        for token in listing.tokens.iter() {
+            self.bundle_id_by_token.insert(
+                &(token.nft_contract_id.clone(), token.token_id.clone()),
+                &bundle_id,
+            );
+        }
+        let storage_after = env::storage_usage();
+        self.internal_charge_account_storage(&seller_id, storage_before, storage_after);
+
+        let mut lookups = listing.tokens.iter().map(|token| {
+            nft_contract::nft_token(
+                token.token_id.clone(),
+                token.nft_contract_id.clone(),
+                NO_DEPOSIT,
+                NFT_TOKEN_GAS,
+            )
+        });
+        let first_lookup = lookups.next().unwrap();
+        lookups
+            .fold(first_lookup, |all, lookup| all.and(lookup))
+            .then(ext_self_nft::bundle_listing_add_completion(
+                U64(bundle_id),
+                env::current_account_id(),
+                NO_DEPOSIT,
+                BUNDLE_LISTING_ADD_COMPLETION_GAS,
+            ))
+    }
+
+    // the bid leaves the book right away, it's refunded if any of the transfers fails
+    pub fn bundle_listing_accept_bid(&mut self, bundle_id: U64, bid_id: U64) -> Promise {
+        let bundle_id = bundle_id.0;
+        let mut listing = self
+            .bundle_listings_by_id
+            .get(&bundle_id)
+            .expect("Could not find this listing");
+        listing.update_status();
+
+        assert!(
+            env::predecessor_account_id() == listing.seller_id,
+            "Only the seller can accept bids"
+        );
+        assert!(
+            listing.status == ListingStatus::Running || listing.status == ListingStatus::Ended,
+            "This listing is {}",
+            listing.status.as_str()
+        );
+        assert!(!listing.is_selling, "This listing is being sold");
+
+        // bid storage was covered by the bidder
+        let storage_before = env::storage_usage();
+        let bid = listing.bids.remove(bid_id.0).expect("Could not find bid");
+        listing.is_selling = true;
+        self.bundle_listings_by_id.insert(&bundle_id, &listing);
+        self.internal_remove_bid_from_account(&bid.bidder_id, ListingId::Bundle(bundle_id), bid.id);
+        let storage_after = env::storage_usage();
+        self.internal_charge_account_storage(&bid.bidder_id, storage_before, storage_after);
+
+        // the yoctoNear attached to the transfers are taken from the bid
+        self.internal_sell_bundle_listing(
+            &listing,
+            bid.bidder_id.clone(),
+            bid.bidder_id,
+            bid.max_amount_yocto,
+            bid.amount_yocto,
+        )
+    }
+
+    // standing bids are refunded
+    pub fn bundle_listing_remove(&mut self, bundle_id: U64) {
+        let listing = self
+            .bundle_listings_by_id
+            .get(&bundle_id.0)
+            .expect("Could not find this listing");
+        assert!(
+            env::predecessor_account_id() == listing.seller_id,
+            "Only the seller can remove a listing"
+        );
+        assert!(
+            listing.status != ListingStatus::Pending,
+            "This listing is {}",
+            listing.status.as_str()
+        );
+        assert!(!listing.is_selling, "This listing is being sold");
+
+        self.internal_close_bundle_listing(bundle_id.0);
+    }
+}
+
+#[ext_contract(ext_self_nft)]
+trait BundleListingSellerCallback {
+    fn bundle_listing_add_completion(&mut self, bundle_id: U64) -> bool;
+}
+
+trait BundleListingSellerCallback {
+    fn bundle_listing_add_completion(&mut self, bundle_id: U64) -> bool;
+}
+
+#[near_bindgen]
+impl BundleListingSellerCallback for MarketplaceContract {
+    // activates the pending bundle if the seller owns every token and the approvals match,
+    // removes it otherwise; we don't panic on mismatch, the removal must not be reverted
+    #[private]
+    fn bundle_listing_add_completion(&mut self, bundle_id: U64) -> bool {
+        let bundle_id = bundle_id.0;
+        // may have been removed already if an approval got revoked meanwhile
+        let mut listing = match self.bundle_listings_by_id.get(&bundle_id) {
+            Some(listing) => listing,
+            None => return false,
+        };
+
+        assert_eq!(
+            env::promise_results_count(),
+            listing.tokens.len() as u64,
+            "Unexpected number of data receipts"
+        );
+        let nfts: Vec<Option<JsonNft>> = (0..listing.tokens.len())
+            .map(|index| match env::promise_result(index as u64) {
+                PromiseResult::Successful(val) => {
+                    near_sdk::serde_json::from_slice::<Option<JsonNft>>(&val).unwrap_or(None)
+                }
+                _ => None,
+            })
+            .collect();
+        let is_verified = listing.tokens.iter().zip(nfts.iter()).all(|(token, nft)| {
+            nft.as_ref().map_or(false, |nft| {
+                nft.owner_id == listing.seller_id
+                    && nft.approved_account_ids.get(&env::current_account_id())
+                        == Some(&token.approval_id)
+            })
+        });
+
+        if is_verified {
+            // store what the NFT contract says, the collections go into the sale stats
+            for (token, nft) in listing.tokens.iter_mut().zip(nfts.into_iter()) {
+                token.collection_id = nft.map(|nft| nft.collection_id);
+            }
+            listing.status = ListingStatus::Unstarted;
+            listing.update_status();
+
+            let storage_before = env::storage_usage();
+            self.bundle_listings_by_id.insert(&bundle_id, &listing);
+            let storage_after = env::storage_usage();
+            self.internal_charge_account_storage(&listing.seller_id, storage_before, storage_after);
+            true
+        } else {
+            env::log_str("Token owner or approval mismatch, bundle listing removed");
+            self.internal_close_bundle_listing(bundle_id);
+            false
+        }
+    }
+}
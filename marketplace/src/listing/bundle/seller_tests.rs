@@ -0,0 +1,95 @@
+#[cfg(test)]
+mod seller_tests {
+    use super::super::BundleListingSellerCallback;
+    use crate::{listing::status::ListingStatus, test_utils::*, *};
+    use near_sdk::json_types::U64;
+    use near_sdk::serde_json::{self, json};
+    use near_sdk::PromiseResult;
+
+    const TOKEN_IDS: [&str; 2] = ["0:1", "3:1"];
+
+    // a bundle waiting for the NFT contract to confirm its tokens
+    fn setup() -> (MarketplaceContract, U64) {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        let bundle_id = add_bundle_listing(&mut marketplace, &TOKEN_IDS, Some(10 * ONE_NEAR), None);
+        let mut listing = marketplace.bundle_listings_by_id.get(&bundle_id).unwrap();
+        listing.status = ListingStatus::Pending;
+        for token in listing.tokens.iter_mut() {
+            token.collection_id = None;
+        }
+        marketplace
+            .bundle_listings_by_id
+            .insert(&bundle_id, &listing);
+        (marketplace, U64(bundle_id))
+    }
+
+    fn token_result(token_id: &str, owner_id: &str, collection_id: u64) -> PromiseResult {
+        PromiseResult::Successful(
+            serde_json::to_vec(&json!({
+                "token_id": token_id,
+                "owner_id": owner_id,
+                "collection_id": collection_id,
+                "metadata": {"title": "Collection 0"},
+                "mutable_metadata": {},
+                "approved_account_ids": {MARKETPLACE_ACCOUNT_ID: 0}
+            }))
+            .unwrap(),
+        )
+    }
+
+    fn collection_ids(marketplace: &MarketplaceContract, bundle_id: U64) -> Vec<Option<u64>> {
+        marketplace
+            .bundle_listings_by_id
+            .get(&bundle_id.0)
+            .unwrap()
+            .tokens
+            .iter()
+            .map(|token| token.collection_id)
+            .collect()
+    }
+
+    /* verification */
+
+    #[test]
+    fn test_add_completion_stores_collections() {
+        let (mut marketplace, bundle_id) = setup();
+
+        set_callback_context(
+            NOW,
+            vec![
+                token_result(TOKEN_IDS[0], SELLER_ACCOUNT_ID, 0),
+                token_result(TOKEN_IDS[1], SELLER_ACCOUNT_ID, 3),
+            ],
+        );
+        assert!(marketplace.bundle_listing_add_completion(bundle_id));
+
+        assert_eq!(
+            collection_ids(&marketplace, bundle_id),
+            vec![Some(0), Some(3)]
+        );
+        assert!(
+            marketplace
+                .storage_deposits
+                .get(&account(SELLER_ACCOUNT_ID))
+                .unwrap()
+                < STORAGE_DEPOSIT
+        );
+    }
+
+    #[test]
+    fn test_add_completion_owner_mismatch() {
+        let (mut marketplace, bundle_id) = setup();
+
+        set_callback_context(
+            NOW,
+            vec![
+                token_result(TOKEN_IDS[0], SELLER_ACCOUNT_ID, 0),
+                token_result(TOKEN_IDS[1], BUYER_ACCOUNT_ID, 3),
+            ],
+        );
+        assert!(!marketplace.bundle_listing_add_completion(bundle_id));
+
+        assert!(marketplace.bundle_listing(bundle_id).is_none());
+    }
+}
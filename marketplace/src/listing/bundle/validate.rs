@@ -0,0 +1,118 @@
+use crate::{
+    constants::NO_DEPOSIT,
+    external::{nft_contract, JsonNft},
+    listing::{bundle::lib::BundleId, status::ListingStatus},
+    *,
+};
+use near_sdk::json_types::U64;
+use near_sdk::PromiseResult;
+
+const NFT_TOKEN_GAS: Gas = Gas(5_000_000_000_000); // a view, the token and its metadata are read
+const BUNDLE_LISTING_VALIDATE_COMPLETION_GAS: Gas = Gas(20_000_000_000_000); // TODO: measure, grows with the number of bids
+
+#[cfg(test)]
+#[path = "validate_tests.rs"]
+mod validate_tests;
+
+// the NFT contracts of a bundle don't have to notify the marketplace when an approval is revoked
+// or a token moves, so anyone can ask for a bundle to be re-checked; bundles with a token that
+// left the seller or lost the approval are removed and their bidders refunded
+#[near_bindgen]
+impl MarketplaceContract {
+    pub fn bundle_listing_validate(&mut self, bundle_id: U64) -> Promise {
+        let listing = self
+            .bundle_listings_by_id
+            .get(&bundle_id.0)
+            .expect("Could not find this listing");
+        assert!(
+            listing.status != ListingStatus::Pending,
+            "This listing is {}",
+            listing.status.as_str()
+        );
+        // the tokens are on their way to the buyer, they'd look moved
+        assert!(!listing.is_selling, "This listing is being sold");
+
+        // with too little gas the lookups could fail and make a live bundle look dead
+        let required_gas = NFT_TOKEN_GAS.0 * listing.tokens.len() as u64
+            + BUNDLE_LISTING_VALIDATE_COMPLETION_GAS.0;
+        assert!(
+            env::prepaid_gas().0 >= required_gas,
+            "Attach at least {} gas",
+            required_gas
+        );
+
+        let mut lookups = listing.tokens.iter().map(|token| {
+            nft_contract::nft_token(
+                token.token_id.clone(),
+                token.nft_contract_id.clone(),
+                NO_DEPOSIT,
+                NFT_TOKEN_GAS,
+            )
+        });
+        let first_lookup = lookups.next().unwrap();
+        lookups
+            .fold(first_lookup, |all, lookup| all.and(lookup))
+            .then(ext_self_nft::bundle_listing_validate_completion(
+                bundle_id,
+                env::current_account_id(),
+                NO_DEPOSIT,
+                BUNDLE_LISTING_VALIDATE_COMPLETION_GAS,
+            ))
+    }
+}
+
+#[ext_contract(ext_self_nft)]
+trait BundleListingValidateCallback {
+    fn bundle_listing_validate_completion(&mut self, bundle_id: U64) -> bool;
+}
+
+trait BundleListingValidateCallback {
+    fn bundle_listing_validate_completion(&mut self, bundle_id: U64) -> bool;
+}
+
+#[near_bindgen]
+impl BundleListingValidateCallback for MarketplaceContract {
+    // returns true if the bundle is still live
+    #[private]
+    fn bundle_listing_validate_completion(&mut self, bundle_id: U64) -> bool {
+        let bundle_id: BundleId = bundle_id.0;
+        // may have been removed in the meantime
+        let listing = match self.bundle_listings_by_id.get(&bundle_id) {
+            Some(listing) => listing,
+            None => return false,
+        };
+        // a sale started meanwhile, its completion settles the bundle
+        if listing.is_selling {
+            return true;
+        }
+
+        assert_eq!(
+            env::promise_results_count(),
+            listing.tokens.len() as u64,
+            "Unexpected number of data receipts"
+        );
+        let mut is_live = true;
+        for (index, token) in listing.tokens.iter().enumerate() {
+            let nft = match env::promise_result(index as u64) {
+                PromiseResult::Successful(val) => {
+                    near_sdk::serde_json::from_slice::<Option<JsonNft>>(&val)
+                        .expect("NFT nft_token returned unexpected value")
+                }
+                _ => {
+                    env::log_str("Could not look up the tokens, listing left unchanged");
+                    return true;
+                }
+            };
+            is_live &= nft.map_or(false, |nft| {
+                nft.owner_id == listing.seller_id
+                    && nft.approved_account_ids.get(&env::current_account_id())
+                        == Some(&token.approval_id)
+            });
+        }
+
+        if !is_live {
+            self.internal_delist_bundle_listing(bundle_id);
+        }
+        is_live
+    }
+}
@@ -0,0 +1,177 @@
+#[cfg(test)]
+mod validate_tests {
+    use super::super::BundleListingValidateCallback;
+    use crate::{test_utils::*, *};
+    use near_sdk::json_types::{U128, U64};
+    use near_sdk::serde_json::{self, json};
+    use near_sdk::PromiseResult;
+
+    const TOKEN_IDS: [&str; 2] = ["0:1", "0:2"];
+
+    fn setup() -> (MarketplaceContract, U64) {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        let bundle_id = U64(add_bundle_listing(
+            &mut marketplace,
+            &TOKEN_IDS,
+            Some(10 * ONE_NEAR),
+            Some(MIN_BID_YOCTO),
+        ));
+        set_context(BIDDER_ACCOUNT_ID, NOW, MIN_BID_YOCTO);
+        marketplace.bundle_listing_place_bid(bundle_id, U128(MIN_BID_YOCTO));
+        (marketplace, bundle_id)
+    }
+
+    fn token_result(token_id: &str, owner_id: Option<&str>, approval_id: u64) -> PromiseResult {
+        let token = owner_id.map(|owner_id| {
+            json!({
+                "token_id": token_id,
+                "owner_id": owner_id,
+                "collection_id": 0,
+                "metadata": {"title": "Collection 0"},
+                "mutable_metadata": {},
+                "approved_account_ids": {MARKETPLACE_ACCOUNT_ID: approval_id}
+            })
+        });
+        PromiseResult::Successful(serde_json::to_vec(&token).unwrap())
+    }
+
+    fn complete(
+        marketplace: &mut MarketplaceContract,
+        bundle_id: U64,
+        promise_results: Vec<PromiseResult>,
+    ) -> bool {
+        set_callback_context(NOW, promise_results);
+        marketplace.bundle_listing_validate_completion(bundle_id)
+    }
+
+    fn assert_delisted(marketplace: &MarketplaceContract, bundle_id: U64) {
+        assert!(marketplace.bundle_listing(bundle_id).is_none());
+        for token_id in TOKEN_IDS {
+            assert!(marketplace
+                .bundle_listing_for_token(account(NFT_CONTRACT_ID), token_id.to_string())
+                .is_none());
+        }
+        // the bidder was refunded
+        assert!(marketplace
+            .bids_by_account(account(BIDDER_ACCOUNT_ID), None, None)
+            .is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = r#"This listing is being sold"#)]
+    fn test_validate_while_selling() {
+        let (mut marketplace, bundle_id) = setup();
+        let mut listing = marketplace.bundle_listings_by_id.get(&bundle_id.0).unwrap();
+        listing.is_selling = true;
+        marketplace
+            .bundle_listings_by_id
+            .insert(&bundle_id.0, &listing);
+
+        set_context(BUYER_ACCOUNT_ID, NOW, 0);
+        marketplace.bundle_listing_validate(bundle_id);
+    }
+
+    #[test]
+    fn test_validate_completion_live() {
+        let (mut marketplace, bundle_id) = setup();
+
+        assert!(complete(
+            &mut marketplace,
+            bundle_id,
+            vec![
+                token_result(TOKEN_IDS[0], Some(SELLER_ACCOUNT_ID), 0),
+                token_result(TOKEN_IDS[1], Some(SELLER_ACCOUNT_ID), 0),
+            ]
+        ));
+
+        assert!(marketplace.bundle_listing(bundle_id).is_some());
+    }
+
+    #[test]
+    fn test_validate_completion_revoked() {
+        let (mut marketplace, bundle_id) = setup();
+
+        // the approval of the second token was replaced
+        assert!(!complete(
+            &mut marketplace,
+            bundle_id,
+            vec![
+                token_result(TOKEN_IDS[0], Some(SELLER_ACCOUNT_ID), 0),
+                token_result(TOKEN_IDS[1], Some(SELLER_ACCOUNT_ID), 1),
+            ]
+        ));
+
+        assert_delisted(&marketplace, bundle_id);
+    }
+
+    #[test]
+    fn test_validate_completion_moved() {
+        let (mut marketplace, bundle_id) = setup();
+
+        assert!(!complete(
+            &mut marketplace,
+            bundle_id,
+            vec![
+                token_result(TOKEN_IDS[0], Some(BUYER_ACCOUNT_ID), 0),
+                token_result(TOKEN_IDS[1], Some(SELLER_ACCOUNT_ID), 0),
+            ]
+        ));
+
+        assert_delisted(&marketplace, bundle_id);
+    }
+
+    #[test]
+    fn test_validate_completion_burnt() {
+        let (mut marketplace, bundle_id) = setup();
+
+        assert!(!complete(
+            &mut marketplace,
+            bundle_id,
+            vec![
+                token_result(TOKEN_IDS[0], Some(SELLER_ACCOUNT_ID), 0),
+                token_result(TOKEN_IDS[1], None, 0),
+            ]
+        ));
+
+        assert_delisted(&marketplace, bundle_id);
+    }
+
+    #[test]
+    fn test_validate_completion_failed_lookup() {
+        let (mut marketplace, bundle_id) = setup();
+
+        assert!(complete(
+            &mut marketplace,
+            bundle_id,
+            vec![
+                token_result(TOKEN_IDS[0], Some(SELLER_ACCOUNT_ID), 0),
+                PromiseResult::Failed,
+            ]
+        ));
+
+        assert!(marketplace.bundle_listing(bundle_id).is_some());
+    }
+
+    #[test]
+    fn test_validate_completion_sold_meanwhile() {
+        let (mut marketplace, bundle_id) = setup();
+        let mut listing = marketplace.bundle_listings_by_id.get(&bundle_id.0).unwrap();
+        listing.is_selling = true;
+        marketplace
+            .bundle_listings_by_id
+            .insert(&bundle_id.0, &listing);
+
+        // the tokens are on their way, the sale completion settles the bundle
+        assert!(complete(
+            &mut marketplace,
+            bundle_id,
+            vec![
+                token_result(TOKEN_IDS[0], Some(MARKETPLACE_ACCOUNT_ID), 0),
+                token_result(TOKEN_IDS[1], Some(SELLER_ACCOUNT_ID), 0),
+            ]
+        ));
+
+        assert!(marketplace.bundle_listing(bundle_id).is_some());
+    }
+}
@@ -1,5 +1,7 @@
-pub const MAX_LISTING_TITLE_LEN: usize = 128;
+// default, the limit in effect is kept in MarketplaceConfig
+pub const MAX_LISTING_TITLE_LEN: u32 = 128;
 
+// defaults of the listing rules (see MarketplaceConfig), offers are always held to these
 pub const MIN_PRICE_YOCTO: u128 = 100_000_000_000_000_000_000_000;  // 0.1 Near
 pub const PRICE_STEP_YOCTO: u128 = 100_000_000_000_000_000_000_000; // 0.1 Near
 
@@ -10,7 +12,8 @@ pub const BID_STEP_YOCTO: u128 = 100_000_000_000_000_000_000_000;   // 0.1 Near
 // the full price of the proposal must be deposited in order to be 
 // accepted and will be either paid to the seller or returned;
 // also, we allow proposers to revoke their proposal at the cost of
-// the penalty which is set (in percentage) by this constant
-pub const PROPOSAL_REVOKE_FEE_RATE: u128 = 10;     // percent
+// the penalty which is set (in percentage) by this constant, the default of
+// the rate kept in MarketplaceConfig
+pub const PROPOSAL_REVOKE_FEE_RATE: u8 = 10;     // percent
 
 
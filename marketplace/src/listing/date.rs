@@ -0,0 +1,41 @@
+use crate::*;
+use chrono::DateTime;
+use near_sdk::json_types::U64;
+
+// listing start and end dates, f.ex. {"rfc3339":"2022-01-22T11:20:55+08:00"},
+// {"unix_ms":"1642821655000"} or {"duration_ms":"3600000"}
+// a duration counts from the listing start for end dates and from now for start dates
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(rename_all = "snake_case")]
+pub enum DateInput {
+    Rfc3339(String),
+    UnixMs(U64),
+    UnixNs(U64),
+    DurationMs(U64),
+}
+
+impl DateInput {
+    // nanoseconds since 1970-01-01
+    pub(crate) fn to_timestamp(&self, relative_to_timestamp: i64) -> i64 {
+        match self {
+            DateInput::Rfc3339(date_str) => DateTime::parse_from_rfc3339(date_str)
+                .expect(
+                    "Wrong date format. Must be ISO8601/RFC3339 (f.ex. 2022-01-22T11:20:55+08:00)",
+                )
+                .timestamp_nanos(),
+            DateInput::UnixMs(timestamp_ms) => to_nanos(timestamp_ms.0, 1_000_000),
+            DateInput::UnixNs(timestamp_ns) => to_nanos(timestamp_ns.0, 1),
+            DateInput::DurationMs(duration_ms) => relative_to_timestamp
+                .checked_add(to_nanos(duration_ms.0, 1_000_000))
+                .expect("Date out of range"),
+        }
+    }
+}
+
+fn to_nanos(value: u64, nanos_per_unit: u64) -> i64 {
+    value
+        .checked_mul(nanos_per_unit)
+        .and_then(|nanos| i64::try_from(nanos).ok())
+        .expect("Date out of range")
+}
@@ -0,0 +1,45 @@
+use crate::{listing::bundle::lib::BundleId, *};
+use near_sdk::json_types::U64;
+
+// identifies a listing of any type, used where listings of different types are mixed
+#[derive(BorshDeserialize, BorshSerialize, Clone)]
+pub enum ListingId {
+    Primary(PrimaryListingId),
+    Secondary(SecondaryListingId),
+    Bundle(BundleId),
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ListingIdJson {
+    Primary {
+        nft_contract_id: AccountId,
+        collection_id: U64,
+    },
+    Secondary {
+        nft_contract_id: AccountId,
+        token_id: NftId,
+    },
+    Bundle {
+        bundle_id: U64,
+    },
+}
+
+impl ListingId {
+    pub(crate) fn to_json(&self) -> ListingIdJson {
+        match self {
+            ListingId::Primary(listing_id) => ListingIdJson::Primary {
+                nft_contract_id: listing_id.nft_contract_id.clone(),
+                collection_id: U64(listing_id.collection_id),
+            },
+            ListingId::Secondary(listing_id) => ListingIdJson::Secondary {
+                nft_contract_id: listing_id.nft_contract_id.clone(),
+                token_id: listing_id.token_id.clone(),
+            },
+            ListingId::Bundle(bundle_id) => ListingIdJson::Bundle {
+                bundle_id: U64(*bundle_id),
+            },
+        }
+    }
+}
@@ -1,6 +1,14 @@
-mod status;
+pub mod status;
 pub mod bid;
-mod constants;
+pub mod id;
+pub mod constants;
+pub mod date;
+pub mod rules;
+pub mod terms;
+pub mod receipt;
+pub mod query;
+pub mod sale;
 
 pub mod primary;
-pub mod secondary;
\ No newline at end of file
+pub mod secondary;
+pub mod bundle;
@@ -0,0 +1,433 @@
+#[cfg(test)]
+mod bid_tests {
+    use crate::{
+        listing::primary::{
+            enumeration::JsonPrimaryListingBid, lib::PrimaryListingIdJson,
+            seller::PrimaryListingSellerCallback,
+        },
+        test_utils::*,
+        *,
+    };
+    use near_sdk::json_types::{U128, U64};
+    use near_sdk::{PromiseOrValue, PromiseResult};
+
+    const STEP_YOCTO: Balance = ONE_NEAR / 10;
+
+    fn auction(marketplace: &mut MarketplaceContract, supply_total: u64) -> PrimaryListingId {
+        add_primary_listing(
+            marketplace,
+            0,
+            supply_total,
+            Some(2 * ONE_NEAR),
+            Some(MIN_BID_YOCTO),
+            None,
+        )
+    }
+
+    fn place_bid(
+        marketplace: &mut MarketplaceContract,
+        bidder_id: &str,
+        amount_yocto: Balance,
+        max_amount_yocto: Balance,
+    ) -> U64 {
+        set_context(bidder_id, NOW, max_amount_yocto);
+        marketplace.primary_listing_place_bid(
+            account(NFT_CONTRACT_ID),
+            U64(0),
+            U128(amount_yocto),
+            Some(U128(max_amount_yocto)),
+        )
+    }
+
+    // (bidder, effective amount) in bid order
+    fn bids(
+        marketplace: &MarketplaceContract,
+        listing_id: &PrimaryListingId,
+    ) -> Vec<(AccountId, Balance)> {
+        marketplace
+            .primary_listings_by_id
+            .get(listing_id)
+            .unwrap()
+            .bids
+            .iter()
+            .map(|bid| (bid.bidder_id, bid.amount_yocto))
+            .collect()
+    }
+
+    fn accept_bids(marketplace: &mut MarketplaceContract, count: u64) {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        marketplace.primary_listing_accept_bids(account(NFT_CONTRACT_ID), U64(0), U64(count));
+    }
+
+    fn complete_accept_bids(
+        marketplace: &mut MarketplaceContract,
+        bids: Vec<(&str, Balance)>,
+        results: Vec<PromiseResult>,
+    ) -> Vec<u128> {
+        set_callback_context(NOW, results);
+        marketplace
+            .primary_listing_accept_bids_mint_completion(
+                account(SELLER_ACCOUNT_ID),
+                PrimaryListingIdJson {
+                    nft_contract_id: account(NFT_CONTRACT_ID),
+                    collection_id: U64(0),
+                },
+                U64(0),
+                bids.into_iter()
+                    .enumerate()
+                    .map(|(id, (bidder_id, amount_yocto))| JsonPrimaryListingBid {
+                        id: U64(id as u64),
+                        bidder_id: account(bidder_id),
+                        amount_yocto: U128(amount_yocto),
+                    })
+                    .collect(),
+            )
+            .iter()
+            .map(|receipt| receipt.price_yocto.0)
+            .collect()
+    }
+
+    /* proxy bids and re-bids */
+
+    #[test]
+    fn test_proxy_bid_raised_when_outbid() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        let listing_id = auction(&mut marketplace, 1);
+
+        place_bid(&mut marketplace, BIDDER_ACCOUNT_ID, MIN_BID_YOCTO, ONE_NEAR);
+        place_bid(
+            &mut marketplace,
+            BIDDER2_ACCOUNT_ID,
+            MIN_BID_YOCTO + STEP_YOCTO,
+            MIN_BID_YOCTO + STEP_YOCTO,
+        );
+
+        // the proxy bid stays one step above the bid it pushed out
+        assert_eq!(
+            bids(&marketplace, &listing_id),
+            vec![(account(BIDDER_ACCOUNT_ID), MIN_BID_YOCTO + 2 * STEP_YOCTO)]
+        );
+        assert!(marketplace
+            .bids_by_account(account(BIDDER2_ACCOUNT_ID), None, None)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_rebid_above_proxy_maximum() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        let listing_id = auction(&mut marketplace, 1);
+        place_bid(&mut marketplace, BIDDER_ACCOUNT_ID, MIN_BID_YOCTO, ONE_NEAR);
+        place_bid(
+            &mut marketplace,
+            BIDDER2_ACCOUNT_ID,
+            MIN_BID_YOCTO + STEP_YOCTO,
+            MIN_BID_YOCTO + STEP_YOCTO,
+        );
+
+        // the outbid bidder comes back with a higher maximum
+        place_bid(
+            &mut marketplace,
+            BIDDER2_ACCOUNT_ID,
+            MIN_BID_YOCTO + 3 * STEP_YOCTO,
+            3 * ONE_NEAR / 2,
+        );
+
+        assert_eq!(
+            bids(&marketplace, &listing_id),
+            vec![(account(BIDDER2_ACCOUNT_ID), ONE_NEAR + STEP_YOCTO)]
+        );
+        assert!(marketplace
+            .bids_by_account(account(BIDDER_ACCOUNT_ID), None, None)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_rebid_equal_maximum_loses() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        let listing_id = auction(&mut marketplace, 1);
+        place_bid(&mut marketplace, BIDDER_ACCOUNT_ID, MIN_BID_YOCTO, ONE_NEAR);
+
+        // among equal maximums the earlier bid wins
+        place_bid(
+            &mut marketplace,
+            BIDDER2_ACCOUNT_ID,
+            MIN_BID_YOCTO + STEP_YOCTO,
+            ONE_NEAR,
+        );
+
+        assert_eq!(
+            bids(&marketplace, &listing_id),
+            vec![(account(BIDDER_ACCOUNT_ID), ONE_NEAR)]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Maximum bid amount must be an integer multiple of")]
+    fn test_proxy_maximum_not_multiple_of_step() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        auction(&mut marketplace, 1);
+
+        place_bid(
+            &mut marketplace,
+            BIDDER_ACCOUNT_ID,
+            MIN_BID_YOCTO,
+            ONE_NEAR + STEP_YOCTO / 2,
+        );
+    }
+
+    /* accept bids */
+
+    #[test]
+    fn test_accept_bids_takes_best_bids() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        let listing_id = auction(&mut marketplace, 2);
+        place_bid(
+            &mut marketplace,
+            BIDDER_ACCOUNT_ID,
+            MIN_BID_YOCTO,
+            MIN_BID_YOCTO,
+        );
+        place_bid(
+            &mut marketplace,
+            BIDDER2_ACCOUNT_ID,
+            ONE_NEAR,
+            3 * ONE_NEAR / 2,
+        );
+
+        accept_bids(&mut marketplace, 1);
+
+        let listing = marketplace.primary_listings_by_id.get(&listing_id).unwrap();
+        assert_eq!(listing.supply_pending, 1);
+        assert_eq!(
+            bids(&marketplace, &listing_id),
+            vec![(account(BIDDER_ACCOUNT_ID), MIN_BID_YOCTO)]
+        );
+        assert!(marketplace
+            .bids_by_account(account(BIDDER2_ACCOUNT_ID), None, None)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_accept_bids_completion() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        let listing_id = auction(&mut marketplace, 2);
+        place_bid(
+            &mut marketplace,
+            BIDDER_ACCOUNT_ID,
+            MIN_BID_YOCTO,
+            MIN_BID_YOCTO,
+        );
+        place_bid(&mut marketplace, BIDDER2_ACCOUNT_ID, ONE_NEAR, ONE_NEAR);
+        accept_bids(&mut marketplace, 2);
+
+        let prices = complete_accept_bids(
+            &mut marketplace,
+            vec![
+                (BIDDER2_ACCOUNT_ID, ONE_NEAR),
+                (BIDDER_ACCOUNT_ID, MIN_BID_YOCTO),
+            ],
+            vec![mint_result("0:1", 1000), PromiseResult::Failed],
+        );
+
+        // the failed mint is refunded and its supply released
+        assert_eq!(prices, vec![ONE_NEAR, 0]);
+        let listing = marketplace.primary_listings_by_id.get(&listing_id).unwrap();
+        assert_eq!(listing.supply_left, 1);
+        assert_eq!(listing.supply_pending, 0);
+        assert!(listing.bids.is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the seller can accept bids")]
+    fn test_accept_bids_by_bidder() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        auction(&mut marketplace, 1);
+        place_bid(
+            &mut marketplace,
+            BIDDER_ACCOUNT_ID,
+            MIN_BID_YOCTO,
+            MIN_BID_YOCTO,
+        );
+
+        set_context(BIDDER_ACCOUNT_ID, NOW, 0);
+        marketplace.primary_listing_accept_bids(account(NFT_CONTRACT_ID), U64(0), U64(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "There's not enough bids (1)")]
+    fn test_accept_too_many_bids() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        auction(&mut marketplace, 2);
+        place_bid(
+            &mut marketplace,
+            BIDDER_ACCOUNT_ID,
+            MIN_BID_YOCTO,
+            MIN_BID_YOCTO,
+        );
+
+        accept_bids(&mut marketplace, 2);
+    }
+
+    #[test]
+    fn test_accept_bids_skips_bidder_at_limit() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        let listing_id = add_primary_listing(
+            &mut marketplace,
+            0,
+            3,
+            Some(2 * ONE_NEAR),
+            Some(MIN_BID_YOCTO),
+            Some(1),
+        );
+        place_bid(&mut marketplace, BIDDER_ACCOUNT_ID, ONE_NEAR, ONE_NEAR);
+        place_bid(
+            &mut marketplace,
+            BIDDER_ACCOUNT_ID,
+            ONE_NEAR - STEP_YOCTO,
+            ONE_NEAR - STEP_YOCTO,
+        );
+        place_bid(
+            &mut marketplace,
+            BIDDER2_ACCOUNT_ID,
+            MIN_BID_YOCTO,
+            MIN_BID_YOCTO,
+        );
+
+        accept_bids(&mut marketplace, 2);
+
+        // the second bid of the bidder is refunded, the next best one takes its place
+        let listing = marketplace.primary_listings_by_id.get(&listing_id).unwrap();
+        assert_eq!(listing.supply_pending, 2);
+        assert!(listing.bids.is_empty());
+        assert_eq!(
+            listing
+                .purchases_by_account
+                .get(&account(BIDDER_ACCOUNT_ID)),
+            Some(1)
+        );
+        assert_eq!(
+            listing
+                .purchases_by_account
+                .get(&account(BIDDER2_ACCOUNT_ID)),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn test_accept_bids_skips_bidder_without_deposit() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        let listing_id = auction(&mut marketplace, 2);
+        place_bid(
+            &mut marketplace,
+            BIDDER_ACCOUNT_ID,
+            MIN_BID_YOCTO,
+            MIN_BID_YOCTO,
+        );
+        place_bid(&mut marketplace, BIDDER2_ACCOUNT_ID, ONE_NEAR, ONE_NEAR);
+        // the deposit went into other purchases after bidding
+        marketplace
+            .storage_deposits
+            .insert(&account(BIDDER2_ACCOUNT_ID), &0);
+
+        accept_bids(&mut marketplace, 1);
+
+        let listing = marketplace.primary_listings_by_id.get(&listing_id).unwrap();
+        assert_eq!(listing.supply_pending, 1);
+        assert!(listing.bids.is_empty());
+        assert!(marketplace
+            .bids_by_account(account(BIDDER2_ACCOUNT_ID), None, None)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_accept_bids_all_skipped() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        let listing_id = auction(&mut marketplace, 1);
+        place_bid(
+            &mut marketplace,
+            BIDDER_ACCOUNT_ID,
+            MIN_BID_YOCTO,
+            MIN_BID_YOCTO,
+        );
+        marketplace
+            .storage_deposits
+            .insert(&account(BIDDER_ACCOUNT_ID), &0);
+
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let receipts =
+            marketplace.primary_listing_accept_bids(account(NFT_CONTRACT_ID), U64(0), U64(1));
+
+        // nothing to mint, the supply stays available
+        assert!(matches!(receipts, PromiseOrValue::Value(receipts) if receipts.is_empty()));
+        let listing = marketplace.primary_listings_by_id.get(&listing_id).unwrap();
+        assert_eq!(listing.supply_pending, 0);
+        assert!(listing.bids.is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "Your storage deposit is too low")]
+    fn test_place_bid_without_deposit() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        auction(&mut marketplace, 1);
+        marketplace
+            .storage_deposits
+            .insert(&account(BIDDER_ACCOUNT_ID), &0);
+
+        place_bid(
+            &mut marketplace,
+            BIDDER_ACCOUNT_ID,
+            MIN_BID_YOCTO,
+            MIN_BID_YOCTO,
+        );
+    }
+
+    #[test]
+    fn test_record_mints_short_deposit() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        auction(&mut marketplace, 1);
+        place_bid(&mut marketplace, BIDDER_ACCOUNT_ID, ONE_NEAR, ONE_NEAR);
+        accept_bids(&mut marketplace, 1);
+        marketplace
+            .storage_deposits
+            .insert(&account(BIDDER_ACCOUNT_ID), &ONE_NEAR);
+
+        // the deposit covers what it can, the rest comes out of the seller's proceeds
+        let (storage_cost, storage_shortfall) = marketplace.internal_primary_listing_record_mints(
+            &PrimaryListingId {
+                nft_contract_id: account(NFT_CONTRACT_ID),
+                collection_id: 0,
+            },
+            0,
+            &account(SELLER_ACCOUNT_ID),
+            &account(BIDDER_ACCOUNT_ID),
+            &account(BIDDER_ACCOUNT_ID),
+            ONE_NEAR,
+            1,
+            &["0:1".to_string()],
+            (ONE_NEAR / env::storage_byte_cost()) as u64 + 1000,
+            None,
+        );
+
+        assert_eq!(storage_cost, ONE_NEAR);
+        assert_eq!(storage_shortfall, 1000 * env::storage_byte_cost());
+        assert_eq!(
+            marketplace
+                .storage_deposits
+                .get(&account(BIDDER_ACCOUNT_ID)),
+            Some(0)
+        );
+    }
+}
@@ -2,20 +2,22 @@ use crate::{
     constants::*,
     external::nft_contract,
     listing::{
-        constants::*,
         primary::{config::*, lib::PrimaryListingIdJson},
         bid::Bid,
+        id::ListingId,
+        receipt::PurchaseReceipt,
         status::ListingStatus,
     },
     *,
 };
 use near_sdk::{
-    json_types::{U128, U64},
+    json_types::{Base64VecU8, U128, U64},
     PromiseResult,
 };
 
-const NFT_MINT_GAS: Gas = Gas(15_000_000_000_000); // TODO: measure
-const NFT_MINT_COMPLETION_GAS: Gas = Gas(5_000_000_000_000); // TODO: measure
+pub(crate) const NFT_MINT_GAS: Gas = Gas(15_000_000_000_000); // TODO: measure
+const NFT_MINT_COMPLETION_BASE_GAS: Gas = Gas(5_000_000_000_000); // TODO: measure
+const NFT_MINT_COMPLETION_ITEM_GAS: Gas = Gas(3_000_000_000_000); // per minted token, TODO: measure
 
 // const NFT_MINT_WORST_CASE_STORAGE: u64 = 830;                       // actual, measured
 
@@ -23,17 +25,45 @@ const NFT_MINT_COMPLETION_GAS: Gas = Gas(5_000_000_000_000); // TODO: measure
 #[path = "buyer_tests.rs"]
 mod buyer_tests;
 
+#[cfg(test)]
+#[path = "purchase_tests.rs"]
+mod purchase_tests;
+
+#[cfg(test)]
+#[path = "bid_tests.rs"]
+mod bid_tests;
+
 pub type NftId = String;
 
 #[near_bindgen]
 impl MarketplaceContract {
     // purchase at buy now price, provided there's supply
+    // max_price_yocto and version protect the buyer against the listing being modified
+    // after it was presented to them; the call fails (and the deposit is returned) if
+    // the price went up or the listing version does not match
+    // during a presale phase the phase price and limits apply; merkle_proof is required
+    // only if the phase allowlist is a merkle root
+    // quantity tokens (1 if missing) are minted in parallel, max_price_yocto is per token
+    // tokens are minted to receiver_id (the caller if missing); the caller pays for them and for
+    // their storage, gets the refunds and is the one the allowlists and purchase limits apply to
     #[payable]
     pub fn primary_listing_buy(
         &mut self,
         nft_contract_id: AccountId,
         collection_id: U64,
+        quantity: Option<U64>,
+        receiver_id: Option<AccountId>,
+        max_price_yocto: Option<U128>,
+        version: Option<U64>,
+        merkle_proof: Option<Vec<Base64VecU8>>,
     ) -> Promise {
+        let quantity = quantity.map(|q| q.0).unwrap_or(1);
+        assert!(
+            quantity > 0 && quantity <= PRIMARY_LISTING_BUY_QUANTITY_MAX,
+            "Quantity must be between 1 and {}",
+            PRIMARY_LISTING_BUY_QUANTITY_MAX
+        );
+
         let listing_id = PrimaryListingId {
             nft_contract_id,
             collection_id: collection_id.0,
@@ -47,42 +77,103 @@ impl MarketplaceContract {
         listing.update_status();
         self.primary_listings_by_id.insert(&listing_id, &listing);
 
-        // make sure buy now is possible
-        let price_yocto = listing
-            .price_yocto
-            .expect("Buy Now is not possible for this listing");
-
-        assert!(
-            listing.status == ListingStatus::Running,
-            "This listing is {}",
-            listing.status.as_str()
-        );
-
         let buyer_id = env::predecessor_account_id();
         assert!(buyer_id != listing.seller_id, "Cannot buy from yourself");
+        let receiver_id = receiver_id.unwrap_or_else(|| buyer_id.clone());
 
-        // ensure there's supply left
+        // presale phase, if active, sets the price and decides who can buy
+        let phase_index = listing.current_phase_index();
+        let price_yocto = if let Some(phase_index) = phase_index {
+            let phase = listing.phases.get(phase_index).unwrap();
+            assert!(
+                phase.allowlist.contains(&buyer_id, &merkle_proof),
+                "You are not allowed to buy during this presale phase"
+            );
+            if let Some(max_per_account) = phase.max_per_account {
+                let purchased = phase.purchases_by_account.get(&buyer_id).unwrap_or(0);
+                assert!(
+                    purchased + quantity <= max_per_account,
+                    "Presale limit is {} per account, you have already bought {}",
+                    max_per_account,
+                    purchased
+                );
+            }
+            phase.price_yocto
+        } else {
+            assert!(
+                listing.status == ListingStatus::Running,
+                "This listing is {}",
+                listing.status.as_str()
+            );
+
+            // make sure buy now is possible
+            listing
+                .price_yocto
+                .expect("Buy Now is not possible for this listing")
+        };
+
+        // make sure the listing is what the buyer expects it to be
+        if let Some(max_price_yocto) = max_price_yocto {
+            assert!(
+                price_yocto <= max_price_yocto.0,
+                "Price of {} exceeds the maximum price of {}",
+                price_yocto,
+                max_price_yocto.0
+            );
+        }
+        if let Some(version) = version {
+            assert!(
+                listing.version == version.0,
+                "Listing has been modified. Current version is {}",
+                listing.version
+            );
+        }
+
+        // ensure there's supply left, supply reserved by purchases in flight doesn't count
+        let supply_available = listing.supply_available();
         assert!(
-            listing.supply_left > 0,
+            supply_available > 0,
             "You are late. All NFTs have been sold."
         );
+        assert!(
+            supply_available >= quantity,
+            "Only {} NFTs left",
+            supply_available
+        );
+
+        // enforce seller-set limit per account
+        if let Some(max_per_account) = listing.max_per_account {
+            let purchased = listing.purchases_by_account.get(&buyer_id).unwrap_or(0);
+            assert!(
+                purchased + quantity <= max_per_account,
+                "Limit is {} per account, you have already bought {}",
+                max_per_account,
+                purchased
+            );
+        }
 
         // ensure the attached balance is sufficient to pay the price
+        let total_price_yocto = price_yocto * quantity as u128;
         let attached_deposit = env::attached_deposit();
         assert!(
-            attached_deposit >= price_yocto,
+            attached_deposit >= total_price_yocto,
             "Attached deposit of {} is insufficient to pay the price of {}",
             attached_deposit,
-            price_yocto
+            total_price_yocto
         );
 
+        // the mint callback releases whatever doesn't get minted
+        self.internal_primary_listing_reserve(&mut listing, &buyer_id, quantity, phase_index);
+        self.primary_listings_by_id.insert(&listing_id, &listing);
+
         let storage_byte_cost = env::storage_byte_cost();
         let current_deposit: Balance = self.storage_deposits.get(&buyer_id).unwrap_or(0);
-        let nft_worst_case_storage_cost = NFT_MINT_STORAGE_MAX as Balance * storage_byte_cost;
+        let nft_worst_case_storage_cost = self.config.storage_limits.nft_mint_storage_max as Balance * storage_byte_cost;
+        let total_worst_case_storage_cost = nft_worst_case_storage_cost * quantity as u128;
         assert!(
-            current_deposit >= nft_worst_case_storage_cost,
+            current_deposit >= total_worst_case_storage_cost,
             "Your storage deposit is too low. Must be {} yN to process transaction. Please increase your deposit.",
-            nft_worst_case_storage_cost
+            total_worst_case_storage_cost
         );
 
         let listing_id_json = PrimaryListingIdJson {
@@ -90,27 +181,48 @@ impl MarketplaceContract {
             collection_id: U64(listing_id.collection_id),
         };
 
-        nft_contract::mint(
+        // mints are independent of each other, the completion reconciles partial failures
+        let mut mints = nft_contract::mint(
             U64(listing_id.collection_id),
-            buyer_id,
+            receiver_id.clone(),
             None, // perpetual royalties
             listing_id.nft_contract_id.clone(),
             nft_worst_case_storage_cost,
             NFT_MINT_GAS,
-        )
-        .then(ext_self_nft::primary_listing_buy_now_mint_completion(
+        );
+        for _ in 1..quantity {
+            mints = mints.and(nft_contract::mint(
+                U64(listing_id.collection_id),
+                receiver_id.clone(),
+                None, // perpetual royalties
+                listing_id.nft_contract_id.clone(),
+                nft_worst_case_storage_cost,
+                NFT_MINT_GAS,
+            ));
+        }
+
+        // the completion records every mint and refunds the failed ones, so it grows with quantity
+        let completion_gas =
+            Gas(NFT_MINT_COMPLETION_BASE_GAS.0 + NFT_MINT_COMPLETION_ITEM_GAS.0 * quantity);
+        mints.then(ext_self_nft::primary_listing_buy_now_mint_completion(
+            buyer_id,
+            receiver_id,
             listing.seller_id.clone(),
             attached_deposit,
             price_yocto,
+            U64(quantity),
             listing_id_json,
+            U64(listing.round),
+            phase_index.map(|i| U64(i)),
             env::current_account_id(), // we are invoking this function on the current contract
             NO_DEPOSIT,                // don't attach any deposit
-            NFT_MINT_COMPLETION_GAS,   // GAS attached to the completion call
+            completion_gas,            // GAS attached to the completion call
         ))
     }
 
     // place bid
-    // we only expect the deposit equal to the bid amount
+    // we only expect the deposit equal to the bid amount, or to max_amount_yocto for a proxy bid
+    // which then gets raised automatically whenever it's outbid, up to that maximum
     // the extra storage gets paid either by the marketplace (currently)
     // or the seller (TODO, probably safer but we need to block extra amount when
     // the listing is added)
@@ -121,11 +233,13 @@ impl MarketplaceContract {
         nft_contract_id: AccountId,
         collection_id: U64,
         amount_yocto: U128,
+        max_amount_yocto: Option<U128>,
     ) -> U64 {
         // TODO: check prepaid gas, terminate early if insufficient
 
         let collection_id = collection_id.0;
         let amount_yocto = amount_yocto.0;
+        let max_amount_yocto = max_amount_yocto.map_or(amount_yocto, |m| m.0);
 
         let listing_id = PrimaryListingId {
             nft_contract_id,
@@ -172,14 +286,28 @@ impl MarketplaceContract {
             );
         }
 
-        // bid must be multiple of PRICE_STEP_YOCTO
+        // bid must be multiple of the listing's bid step
+        listing.rules.assert_bid_amount(amount_yocto);
+
+        // proxy bid maximum follows the same rules as the amount
+        assert!(
+            max_amount_yocto >= amount_yocto,
+            "Maximum bid amount cannot be lower than the bid amount"
+        );
         assert!(
-            amount_yocto % BID_STEP_YOCTO == 0,
-            "Bid amount must be an integer multple of {} yocto Near",
-            BID_STEP_YOCTO
+            max_amount_yocto % listing.rules.bid_step_yocto == 0,
+            "Maximum bid amount must be an integer multiple of {} yocto Near",
+            listing.rules.bid_step_yocto
         );
+        if let Some(price_yocto) = listing.price_yocto {
+            assert!(
+                max_amount_yocto < price_yocto,
+                "Maximum bid amount must be lower than buy now price of {}",
+                price_yocto
+            );
+        }
 
-        // get bids vector (was sorted on write) and check if bid is acceptable
+        // check if bid is acceptable
         let acceptable_bid_yocto = listing.acceptable_bid_yocto();
         assert!(
             amount_yocto >= acceptable_bid_yocto,
@@ -190,9 +318,20 @@ impl MarketplaceContract {
         // ensure the attached balance is sufficient to pay deposit
         let attached_deposit = env::attached_deposit();
         assert!(
-            attached_deposit >= amount_yocto,
+            attached_deposit >= max_amount_yocto,
             "Attached balance must be sufficient to pay the required deposit of {} yocto Near",
-            amount_yocto
+            max_amount_yocto
+        );
+
+        // the bidder pays for the mint storage once the bid is accepted; a bidder whose deposit
+        // falls short by then is skipped and refunded
+        let nft_worst_case_storage_cost =
+            self.config.storage_limits.nft_mint_storage_max as Balance * env::storage_byte_cost();
+        let current_deposit: Balance = self.storage_deposits.get(&bidder_id).unwrap_or(0);
+        assert!(
+            current_deposit >= nft_worst_case_storage_cost,
+            "Your storage deposit is too low. Must be {} yN to process transaction. Please increase your deposit.",
+            nft_worst_case_storage_cost
         );
 
         // create and add the new bid
@@ -200,24 +339,28 @@ impl MarketplaceContract {
             id: listing.next_bid_id,
             bidder_id: bidder_id.clone(),
             amount_yocto: amount_yocto,
+            max_amount_yocto,
         };
         listing.next_bid_id += 1;
 
         let storage_byte_cost = env::storage_byte_cost();
         let storage_usage_before = env::storage_usage();
 
-        // push to acceptable bids vector, storage is covered by seller reserve
-        listing.bids.push(&new_bid);
+        // add to acceptable bids, storage is covered by seller reserve
+        listing.bids.insert(&new_bid);
 
-        // sort acceptable bids
-        listing.sort_bids();
+        self.internal_add_bid_to_account(
+            &bidder_id,
+            ListingId::Primary(listing_id.clone()),
+            new_bid.id,
+        );
 
         // TODO: deduct storage from seller deposit?
         // check if attached deposit is sufficient and compute proposer refund (if any)
         // let storage_usage_after = env::storage_usage();
         // let storage_usage_added = storage_usage_after - storage_usage_before;
         // let storage_cost_added = storage_usage_added as Balance * storage_byte_cost;
-        let required_deposit = amount_yocto;// + storage_cost_added;
+        let required_deposit = max_amount_yocto;// + storage_cost_added;
         assert!(
             attached_deposit >= required_deposit,
             "Insufficient storage deposit. Please attach at least {}",
@@ -268,7 +411,7 @@ impl MarketplaceContract {
             // price must be multiple of PRICE_STEP_YOCTO
             assert!(
                 price_yocto % PRICE_STEP_YOCTO == 0,
-                "Price must be an integer multple of {} yocto Near",
+                "Price must be an integer multiple of {} yocto Near",
                 PRICE_STEP_YOCTO
             );
 
@@ -406,32 +549,34 @@ impl MarketplaceContract {
 
         let storage_before = env::storage_usage();
 
-        let index = listing
-            .bids
-            .iter()
-            .position(|bid| bid.id == bid_id)
-            .expect("Could not find bid");
-        let removed_bid = listing.bids.swap_remove(index as u64);
+        let removed_bid = listing.bids.remove(bid_id).expect("Could not find bid");
         assert!(
             removed_bid.bidder_id == env::predecessor_account_id(),
             "Not authorized to revoke this bid"
         );
-        listing.sort_bids();
 
         let storage_after = env::storage_usage();
         let storage_freed = storage_before - storage_after;
         let storage_refund = storage_freed as Balance * env::storage_byte_cost();
 
+        self.internal_remove_bid_from_account(
+            &removed_bid.bidder_id,
+            ListingId::Primary(listing_id.clone()),
+            removed_bid.id,
+        );
+
         // store
         self.primary_listings_by_id.insert(&listing_id, &listing);
 
-        // return deposit minus penalty
-        let fee = removed_bid.amount_yocto * PROPOSAL_REVOKE_FEE_RATE / 100;
+        // return deposit minus penalty, the penalty is taken from the effective amount only
+        let fee = removed_bid.amount_yocto * self.config.listing_limits.revoke_fee_rate as u128 / 100;
         Promise::new(env::predecessor_account_id())
-            .transfer(removed_bid.amount_yocto + storage_refund - fee);
+            .transfer(removed_bid.max_amount_yocto + storage_refund - fee);
 
         // transfer penalty to Eneftigo profit account
-        Promise::new(self.fees_account_id()).transfer(fee);
+        if fee > 0 {
+            Promise::new(self.fees_account_id()).transfer(fee);
+        }
     }
 }
 
@@ -445,21 +590,31 @@ impl MarketplaceContract {
 trait PrimaryListingBuyerCallback {
     fn primary_listing_buy_now_mint_completion(
         &mut self,
+        buyer_id: AccountId,
+        receiver_id: AccountId,
         seller_id: AccountId,
         attached_deposit: Balance,
         price: Balance,
+        quantity: U64,
         listing_id: PrimaryListingIdJson,
-    ) -> (NftId, Balance);
+        round: U64,
+        phase_index: Option<U64>,
+    ) -> PurchaseReceipt;
 }
 
-trait PrimaryListingBuyerCallback {
+pub(crate) trait PrimaryListingBuyerCallback {
     fn primary_listing_buy_now_mint_completion(
         &mut self,
+        buyer_id: AccountId,
+        receiver_id: AccountId,
         seller_id: AccountId,
         attached_deposit: Balance,
         price: Balance,
+        quantity: U64,
         listing_id: PrimaryListingIdJson,
-    ) -> (NftId, Balance);
+        round: U64,
+        phase_index: Option<U64>,
+    ) -> PurchaseReceipt;
 }
 
 #[near_bindgen]
@@ -467,65 +622,73 @@ impl PrimaryListingBuyerCallback for MarketplaceContract {
     #[private]
     fn primary_listing_buy_now_mint_completion(
         &mut self,
+        buyer_id: AccountId,
+        receiver_id: AccountId,
         seller_id: AccountId,
         attached_deposit: Balance,
         price: Balance,
+        quantity: U64,
         listing_id: PrimaryListingIdJson,
-    ) -> (NftId, Balance) {
+        round: U64,
+        phase_index: Option<U64>,
+    ) -> PurchaseReceipt {
         let listing_id = PrimaryListingId {
             nft_contract_id: listing_id.nft_contract_id,
             collection_id: listing_id.collection_id.0,
         };
 
         // Here the attached_deposit is the deposit attach buy buyer to the marketplace call (like buy_now)
-        // The price is the amount due to be transferred to the seller's account if minting succeeds
+        // The price is the per-token amount due to be transferred to the seller's account for every
+        // successful mint; price of failed mints is returned to the buyer together with excess deposit
         // Pruning the bids will return deposit provided by respective proposers
-        assert_eq!(env::promise_results_count(), 1, "Too many data receipts");
-        let mint_result = env::promise_result(0);
-        match mint_result {
-            PromiseResult::NotReady | PromiseResult::Failed => {
-                if attached_deposit > 0 {
-                    Promise::new(env::signer_account_id()).transfer(attached_deposit);
+        let mut token_ids: Vec<NftId> = Vec::new();
+        let mut mint_storage_bytes: u64 = 0;
+        for result_index in 0..env::promise_results_count() {
+            // payments are scheduled below, an unexpected value counts as a failed mint
+            if let PromiseResult::Successful(val) = env::promise_result(result_index) {
+                if let Ok((token_id, token_storage_bytes)) =
+                    near_sdk::serde_json::from_slice::<(NftId, U64)>(&val)
+                {
+                    token_ids.push(token_id);
+                    mint_storage_bytes += token_storage_bytes.0;
                 }
-                panic!("NFT mint failed");
             }
-            PromiseResult::Successful(val) => {
-                let buyer_id = env::signer_account_id();
-                // here the NFT was minted and transferred so we pay the seller before we can panic
-                // so that at least this part of the transaction is ok
-                Promise::new(seller_id).transfer(price);
-                // update listing supply, changing supply_left won't affect the storage so we don't
-                // need to update seller's storage deposit
-                let mut listing = self
-                    .primary_listings_by_id
-                    .get(&listing_id)
-                    .expect("Could not find NFT listing");
-                listing.supply_left -= 1;
-                self.primary_listing_remove_supply_exceeding_bids_and_refund_bidders(
-                    &mut listing,
-                );
-                self.primary_listings_by_id.insert(&listing_id, &listing);
-                // get the token ID and NFT storage and update buyer storage deposit
-                let (token_id, mint_storage_bytes) =
-                    near_sdk::serde_json::from_slice::<(NftId, U64)>(&val)
-                        .expect("NFT mint returned unexpected value.");
-                let mint_storage_cost = mint_storage_bytes.0 as Balance * env::storage_byte_cost();
-                let current_deposit = self
-                    .storage_deposits
-                    .get(&buyer_id)
-                    .expect("Could not find buyer's storage deposit record");
-                // this should never happen. when it does to be totally correct we should revert the minting
-                // and seller payment but it's water under the bridge now. to avoid it we pessimistically
-                // compute the storage cost at the beginning of the primary_listing_buy contract call
-                let updated_deposit = if current_deposit >= mint_storage_cost {
-                    current_deposit - mint_storage_cost
-                } else {
-                    0 // should never happen, TODO: log a warning to review deposit logic?
-                };
-                self.storage_deposits.insert(&buyer_id, &updated_deposit);
+        }
+        let minted_count = token_ids.len() as u64;
+
+        // releases the reservation of the failed mints too
+        let (storage_cost, storage_shortfall) = self.internal_primary_listing_record_mints(
+            &listing_id,
+            round.0,
+            &seller_id,
+            &buyer_id,
+            &receiver_id,
+            price,
+            quantity.0,
+            &token_ids,
+            mint_storage_bytes,
+            phase_index.map(|i| i.0),
+        );
 
-                (token_id, updated_deposit)
-            }
+        // here the NFTs were minted and transferred so we pay the seller and return the rest
+        let price_paid = price * minted_count as u128;
+        let seller_proceeds = price_paid.saturating_sub(storage_shortfall);
+        if seller_proceeds > 0 {
+            Promise::new(seller_id.clone()).transfer(seller_proceeds);
+        }
+        let refund = attached_deposit - price_paid;
+        if refund > 0 {
+            Promise::new(buyer_id.clone()).transfer(refund);
+        }
+
+        PurchaseReceipt {
+            nft_contract_id: listing_id.nft_contract_id,
+            receiver_id,
+            token_ids,
+            price_yocto: U128(price_paid),
+            fee_yocto: U128(0),
+            storage_cost_yocto: U128(storage_cost),
+            refund_yocto: U128(refund),
         }
     }
 }
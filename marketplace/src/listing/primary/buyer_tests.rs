@@ -51,7 +51,7 @@ mod seller_tests {
         test_place_proposals(&mut fpo);
         test_add_fpo(&mut marketplace, &fpo);
 
-        marketplace.primary_listing_buy(nft_contract_id, U64(collection_id));
+        marketplace.primary_listing_buy(nft_contract_id, U64(collection_id), None, None, None, None, None);
     }
 
     #[test]
@@ -73,7 +73,7 @@ mod seller_tests {
         test_place_proposals(&mut fpo);
         test_add_fpo(&mut marketplace, &fpo);
 
-        marketplace.primary_listing_buy(nft_contract_id, U64(collection_id));
+        marketplace.primary_listing_buy(nft_contract_id, U64(collection_id), None, None, None, None, None);
     }
 
     #[test]
@@ -96,7 +96,7 @@ mod seller_tests {
             AccountId::new_unchecked(NONEXISTENT_NFT_CONTRACT_ID.to_string());
         let collection_id: NftCollectionId = 0;
 
-        marketplace.primary_listing_buy(nonexistent_nft_contract_id, U64(collection_id));
+        marketplace.primary_listing_buy(nonexistent_nft_contract_id, U64(collection_id), None, None, None, None, None);
     }
 
     #[test]
@@ -120,7 +120,73 @@ mod seller_tests {
         let nft_contract_id = AccountId::new_unchecked(NFT_CONTRACT_ID.to_string());
         let collection_id: NftCollectionId = 0;
 
-        marketplace.primary_listing_buy(nft_contract_id, U64(collection_id));
+        marketplace.primary_listing_buy(nft_contract_id, U64(collection_id), None, None, None, None, None);
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Price of 1000 exceeds the maximum price of 999"#)]
+    fn test_buy_now_price_above_max() {
+        let context = test_get_context(
+            BIDDER_ACCOUNT_ID,
+            Utc.ymd(1975, 6, 1).and_hms(00, 00, 00),
+            1000,
+            0,
+        );
+        testing_env!(context);
+
+        let mut marketplace = test_marketplace();
+        let mut fpo = test_fpo(true);
+        test_place_proposals(&mut fpo);
+        test_add_fpo(&mut marketplace, &fpo);
+
+        let nft_contract_id = AccountId::new_unchecked(NFT_CONTRACT_ID.to_string());
+        let collection_id: NftCollectionId = 0;
+
+        marketplace.primary_listing_buy(nft_contract_id, U64(collection_id), None, None, Some(U128(999)), None, None);
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Listing has been modified. Current version is 0"#)]
+    fn test_buy_now_version_mismatch() {
+        let context = test_get_context(
+            BIDDER_ACCOUNT_ID,
+            Utc.ymd(1975, 6, 1).and_hms(00, 00, 00),
+            1000,
+            0,
+        );
+        testing_env!(context);
+
+        let mut marketplace = test_marketplace();
+        let mut fpo = test_fpo(true);
+        test_place_proposals(&mut fpo);
+        test_add_fpo(&mut marketplace, &fpo);
+
+        let nft_contract_id = AccountId::new_unchecked(NFT_CONTRACT_ID.to_string());
+        let collection_id: NftCollectionId = 0;
+
+        marketplace.primary_listing_buy(nft_contract_id, U64(collection_id), None, None, None, Some(U64(1)), None);
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Only 3 NFTs left"#)]
+    fn test_buy_now_quantity_exceeds_supply() {
+        let context = test_get_context(
+            BIDDER_ACCOUNT_ID,
+            Utc.ymd(1975, 6, 1).and_hms(00, 00, 00),
+            4000,
+            0,
+        );
+        testing_env!(context);
+
+        let mut marketplace = test_marketplace();
+        let mut fpo = test_fpo(true);
+        test_place_proposals(&mut fpo);
+        test_add_fpo(&mut marketplace, &fpo);
+
+        let nft_contract_id = AccountId::new_unchecked(NFT_CONTRACT_ID.to_string());
+        let collection_id: NftCollectionId = 0;
+
+        marketplace.primary_listing_buy(nft_contract_id, U64(collection_id), Some(U64(4)), None, None, None, None);
     }
 
     #[test]
@@ -145,7 +211,7 @@ mod seller_tests {
             collection_id,
         };
 
-        marketplace.primary_listing_buy(nft_contract_id.clone(), U64(collection_id));
+        marketplace.primary_listing_buy(nft_contract_id.clone(), U64(collection_id), None, None, None, None, None);
         let fpo = marketplace
             .primary_listings_by_id
             .get(&offering_id)
@@ -160,7 +226,7 @@ mod seller_tests {
             .collect();
         assert!(proposals == vec![3, 2], "Proposals state incorrect");
 
-        marketplace.primary_listing_buy(nft_contract_id.clone(), U64(collection_id));
+        marketplace.primary_listing_buy(nft_contract_id.clone(), U64(collection_id), None, None, None, None, None);
         let fpo = marketplace
             .primary_listings_by_id
             .get(&offering_id)
@@ -175,7 +241,7 @@ mod seller_tests {
             .collect();
         assert!(proposals == vec![2], "Proposals state incorrect");
 
-        marketplace.primary_listing_buy(nft_contract_id.clone(), U64(collection_id));
+        marketplace.primary_listing_buy(nft_contract_id.clone(), U64(collection_id), None, None, None, None, None);
         let fpo = marketplace
             .primary_listings_by_id
             .get(&offering_id)
@@ -211,19 +277,19 @@ mod seller_tests {
             collection_id,
         };
 
-        marketplace.primary_listing_buy(nft_contract_id.clone(), U64(collection_id));
+        marketplace.primary_listing_buy(nft_contract_id.clone(), U64(collection_id), None, None, None, None, None);
         let fpo = marketplace.primary_listings_by_id.get(&offering_id).unwrap();
         assert!(fpo.supply_left == 2, "supply_left incorrect");
 
-        marketplace.primary_listing_buy(nft_contract_id.clone(), U64(collection_id));
+        marketplace.primary_listing_buy(nft_contract_id.clone(), U64(collection_id), None, None, None, None, None);
         let fpo = marketplace.primary_listings_by_id.get(&offering_id).unwrap();
         assert!(fpo.supply_left == 1, "supply_left incorrect");
 
-        marketplace.primary_listing_buy(nft_contract_id.clone(), U64(collection_id));
+        marketplace.primary_listing_buy(nft_contract_id.clone(), U64(collection_id), None, None, None, None, None);
         let fpo = marketplace.primary_listings_by_id.get(&offering_id).unwrap();
         assert!(fpo.supply_left == 0, "supply_left incorrect");
 
-        marketplace.primary_listing_buy(nft_contract_id.clone(), U64(collection_id));
+        marketplace.primary_listing_buy(nft_contract_id.clone(), U64(collection_id), None, None, None, None, None);
     }
 
     /* primary_listing_place_proposal */
@@ -461,7 +527,7 @@ mod seller_tests {
             nft_contract_id: nft_contract_id.clone(),
             collection_id,
         };
-        marketplace.primary_listing_buy(nft_contract_id.clone(), U64(collection_id));
+        marketplace.primary_listing_buy(nft_contract_id.clone(), U64(collection_id), None, None, None, None, None);
         let fpo = marketplace
             .primary_listings_by_id
             .get(&offering_id)
@@ -503,7 +569,7 @@ mod seller_tests {
             collection_id,
         };
 
-        marketplace.primary_listing_buy(nft_contract_id.clone(), U64(collection_id));
+        marketplace.primary_listing_buy(nft_contract_id.clone(), U64(collection_id), None, None, None, None, None);
         let fpo = marketplace
             .primary_listings_by_id
             .get(&offering_id)
@@ -1,5 +1,9 @@
+// defaults of the limits kept in MarketplaceConfig, the owner can change them at runtime
 pub const TOTAL_SUPPLY_MAX: u64 = 100;
 
+// max number of tokens minted in a single buy, bound by the gas limit (mints run in parallel)
+pub const PRIMARY_LISTING_BUY_QUANTITY_MAX: u64 = 10;
+
 // these define the allowed offering lifetime
 // maximum duration is only applicable to proposal-accepting offering
 // the rationale here is to avoid keeping proposers escrows for too long
@@ -10,6 +14,7 @@ pub const NFT_MAKE_COLLECTION_STORAGE_MAX: u64 = 4376;              // TODO: mea
 pub const NFT_MINT_STORAGE_MAX: u64 = 3317;                         // worst case storage
 
 pub const PRIMARY_LISTING_ADD_STORAGE_MAX: u64 = 3021;              // worst case storage TODO
+pub const PURCHASE_RECORD_STORAGE_MAX: u64 = 500;                  // worst case storage of an account's purchase count, 64 character account id
 
 // there are situations where we reserve the right to keep some Near as
 // our immediate profit, such as when a proposer revokes their proposal
@@ -0,0 +1,626 @@
+use crate::{
+    constants::*,
+    external::{nft_contract, NftMetadata, NftMutableMetadata},
+    internal::hash_account_id,
+    listing::{
+        bid::BidBook,
+        date::DateInput,
+        primary::{
+            internal::hash_primary_listing_id,
+            lib::PrimaryListingStorageKey,
+            round::PrimaryCollection,
+            seller::{NFT_MAKE_COLLECTION_COMPLETION_GAS, NFT_MAKE_COLLECTION_GAS},
+        },
+        rules::{JsonListingRules, ListingRules},
+        status::ListingStatus,
+    },
+    *,
+};
+use near_sdk::{
+    collections::Vector,
+    json_types::{U128, U64},
+    AccountId, PromiseResult,
+};
+use url::Url;
+
+const NFT_DELETE_COLLECTION_GAS: Gas = Gas(5_000_000_000_000);
+const NFT_DELETE_COLLECTION_COMPLETION_GAS: Gas = Gas(10_000_000_000_000); // removes the draft and its seller index entry
+
+#[cfg(test)]
+#[path = "draft_tests.rs"]
+mod draft_tests;
+
+// primary listing staged by the seller; its NFT collection is already made (so the media and
+// metadata have been validated by the NFT contract) but the sale opens only once it's published
+// the dates are given on publishing so that relative ones count from the moment the sale opens
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct PrimaryDraft {
+    pub id: PrimaryListingId,
+    pub seller_id: AccountId,
+    pub nft_metadata: NftMetadata,
+    pub nft_mutable_metadata: NftMutableMetadata,
+    pub collection_supply: u64,
+    pub supply_total: u64,
+    pub price_yocto: Option<u128>,
+    pub min_bid_yocto: Option<u128>,
+    pub max_per_account: Option<u64>,
+    pub rules: ListingRules,
+    pub nft_storage: u64, // paid by the seller when the collection was made, refunded if deleted
+    pub is_deleting: bool, // the NFT collection is being deleted
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct JsonPrimaryDraft {
+    pub nft_contract_id: AccountId,
+    pub collection_id: U64,
+    pub seller_id: AccountId,
+    pub nft_metadata: NftMetadata,
+    pub nft_mutable_metadata: NftMutableMetadata,
+    pub collection_supply: U64,
+    pub supply_total: U64,
+    pub price_yocto: Option<U128>,
+    pub min_bid_yocto: Option<U128>,
+    pub max_per_account: Option<U64>,
+    pub rules: JsonListingRules,
+    pub is_deleting: bool,
+}
+
+#[near_bindgen]
+impl MarketplaceContract {
+    // makes the NFT collection and stores the listing terms without opening the sale
+    // returns the collection id, the draft is identified the same way as the listing it becomes
+    pub fn primary_draft_add(
+        &mut self,
+        title: String,
+        image_url: String,
+        aux_audio_url: Option<String>,
+        supply_total: U64,
+        price_yocto: Option<U128>,
+        min_bid_yocto: Option<U128>,
+        max_per_account: Option<U64>, // if missing, there's no limit on how many NFTs one account can buy
+        rules: Option<JsonListingRules>, // if missing, the marketplace defaults apply
+        collection_supply: Option<U64>, // if missing, it equals supply_total; the rest can be sold in later rounds
+    ) -> Promise {
+        let price_yocto = price_yocto.map(|p| p.0);
+        let min_bid_yocto = min_bid_yocto.map(|b| b.0);
+
+        let seller_id = env::predecessor_account_id();
+
+        // Is deposit sufficient to cover the storage in the worst-case scenario?
+        let storage_byte_cost = env::storage_byte_cost();
+        let current_deposit: Balance = self.storage_deposits.get(&seller_id).unwrap_or(0);
+        let marketplace_worst_case_storage_cost =
+            self.config.storage_limits.primary_listing_add_storage_max as Balance
+                * storage_byte_cost;
+        let nft_worst_case_storage_cost = self.config.storage_limits.nft_make_collection_storage_max
+            as Balance
+            * storage_byte_cost;
+        let worst_case_storage_cost =
+            marketplace_worst_case_storage_cost + nft_worst_case_storage_cost;
+        assert!(
+            current_deposit >= worst_case_storage_cost,
+            "Your storage deposit is too low. Must be {} yN to process transaction. Please increase your deposit.",
+            worst_case_storage_cost
+        );
+
+        // Is listing length ok?
+        let title_len_max = self.config.listing_limits.title_len_max;
+        assert!(
+            title.len() <= title_len_max as usize,
+            "Title length cannot exceed {} characters",
+            title_len_max
+        );
+
+        // Is URL valid?
+        assert!(Url::parse(&image_url).is_ok(), "NFT media URL is invalid");
+
+        let collection_supply = collection_supply.unwrap_or(supply_total);
+        self.internal_primary_draft_assert_supply(supply_total.0, collection_supply.0);
+
+        if let Some(max_per_account) = max_per_account {
+            assert!(max_per_account.0 > 0, "Per-account limit must be positive");
+        }
+
+        // the rules are validated against the marketplace bounds, defaults apply if missing
+        let rules = self.config.listing_rules(rules);
+        if let Some(price_yocto) = price_yocto {
+            rules.assert_price(price_yocto);
+        }
+        if let Some(min_bid_yocto) = min_bid_yocto {
+            rules.assert_min_bid(min_bid_yocto);
+        }
+
+        let nft_contract_id = self.internal_nft_shared_contract_id();
+        let nft_metadata = NftMetadata::new(&title, &image_url);
+        let nft_mutable_metadata = NftMutableMetadata { aux_audio_url };
+
+        nft_contract::make_collection(
+            nft_metadata.clone(),
+            nft_mutable_metadata.clone(),
+            collection_supply,
+            nft_contract_id.clone(),
+            nft_worst_case_storage_cost,
+            NFT_MAKE_COLLECTION_GAS,
+        )
+        .then(
+            ext_self_draft::primary_draft_add_make_collection_completion(
+                seller_id,
+                nft_contract_id,
+                nft_metadata,
+                nft_mutable_metadata,
+                collection_supply,
+                supply_total,
+                price_yocto.map(|p| U128(p)),
+                min_bid_yocto.map(|b| U128(b)),
+                max_per_account,
+                rules.to_json(),
+                env::current_account_id(),
+                NO_DEPOSIT,
+                NFT_MAKE_COLLECTION_COMPLETION_GAS,
+            ),
+        )
+    }
+
+    // changes the terms of a draft; only the fields that are passed in are changed
+    // the title and media can't be changed as the collection has been made already, to change
+    // them the draft must be deleted and added again
+    pub fn primary_draft_update(
+        &mut self,
+        nft_contract_id: AccountId,
+        collection_id: U64,
+        supply_total: Option<U64>,
+        price_yocto: Option<U128>,
+        min_bid_yocto: Option<U128>,
+        max_per_account: Option<U64>,
+        nft_mutable_metadata: Option<NftMutableMetadata>,
+    ) {
+        let draft_id = PrimaryListingId {
+            nft_contract_id,
+            collection_id: collection_id.0,
+        };
+        let mut draft = self
+            .primary_drafts_by_id
+            .get(&draft_id)
+            .expect("Could not find this draft");
+        let seller_id = env::predecessor_account_id();
+        assert!(
+            seller_id == draft.seller_id,
+            "Only the seller can update the draft"
+        );
+        assert!(!draft.is_deleting, "This draft is being deleted");
+
+        if let Some(supply_total) = supply_total {
+            self.internal_primary_draft_assert_supply(supply_total.0, draft.collection_supply);
+            draft.supply_total = supply_total.0;
+        }
+
+        if let Some(price_yocto) = price_yocto {
+            draft.rules.assert_price(price_yocto.0);
+            draft.price_yocto = Some(price_yocto.0);
+        }
+
+        if let Some(min_bid_yocto) = min_bid_yocto {
+            draft.rules.assert_min_bid(min_bid_yocto.0);
+            draft.min_bid_yocto = Some(min_bid_yocto.0);
+        }
+
+        if let Some(max_per_account) = max_per_account {
+            assert!(max_per_account.0 > 0, "Per-account limit must be positive");
+            draft.max_per_account = Some(max_per_account.0);
+        }
+
+        if let Some(nft_mutable_metadata) = nft_mutable_metadata {
+            draft.nft_mutable_metadata = nft_mutable_metadata;
+        }
+
+        // mutable metadata may have changed size, settle the difference with seller's deposit
+        let storage_before = env::storage_usage();
+        self.primary_drafts_by_id.insert(&draft_id, &draft);
+        let storage_after = env::storage_usage();
+        self.internal_charge_account_storage(&seller_id, storage_before, storage_after);
+    }
+
+    // deletes the NFT collection and then removes the draft, all the storage is refunded to the
+    // seller; the draft stays (and can't be updated or published) until the NFT contract confirms
+    // the deletion, if it fails the draft can be used again
+    // returns the updated storage deposit of the seller
+    pub fn primary_draft_delete(
+        &mut self,
+        nft_contract_id: AccountId,
+        collection_id: U64,
+    ) -> Promise {
+        let draft_id = PrimaryListingId {
+            nft_contract_id,
+            collection_id: collection_id.0,
+        };
+        let mut draft = self
+            .primary_drafts_by_id
+            .get(&draft_id)
+            .expect("Could not find this draft");
+        let seller_id = env::predecessor_account_id();
+        assert!(
+            seller_id == draft.seller_id,
+            "Only the seller can delete the draft"
+        );
+        assert!(!draft.is_deleting, "This draft is being deleted");
+
+        draft.is_deleting = true;
+        self.primary_drafts_by_id.insert(&draft_id, &draft);
+
+        nft_contract::delete_collection(
+            collection_id,
+            draft_id.nft_contract_id.clone(),
+            NO_DEPOSIT,
+            NFT_DELETE_COLLECTION_GAS,
+        )
+        .then(ext_self_draft::primary_draft_delete_collection_completion(
+            draft_id.nft_contract_id,
+            collection_id,
+            env::current_account_id(),
+            NO_DEPOSIT,
+            NFT_DELETE_COLLECTION_COMPLETION_GAS,
+        ))
+    }
+
+    // opens the sale; the draft becomes the first round of its collection
+    pub fn primary_draft_publish(
+        &mut self,
+        nft_contract_id: AccountId,
+        collection_id: U64,
+        start_date: Option<DateInput>, // if missing, it'll start accepting bids when this transaction is mined
+        end_date: Option<DateInput>,   // a duration counts from the start date
+    ) {
+        let draft_id = PrimaryListingId {
+            nft_contract_id,
+            collection_id: collection_id.0,
+        };
+        let draft = self
+            .primary_drafts_by_id
+            .get(&draft_id)
+            .expect("Could not find this draft");
+        let seller_id = env::predecessor_account_id();
+        assert!(
+            seller_id == draft.seller_id,
+            "Only the seller can publish the draft"
+        );
+        assert!(!draft.is_deleting, "This draft is being deleted");
+
+        let (start_timestamp, end_timestamp) = self.internal_primary_listing_timestamps(
+            draft.min_bid_yocto.is_some(),
+            start_date,
+            end_date,
+        );
+
+        let collection = PrimaryCollection {
+            seller_id: seller_id.clone(),
+            nft_metadata: draft.nft_metadata.clone(),
+            nft_mutable_metadata: draft.nft_mutable_metadata.clone(),
+            max_supply: draft.collection_supply,
+            supply_minted: 0,
+            supply_pending: 0,
+            next_round: 1,
+        };
+        let listing_id_hash = hash_primary_listing_id(&draft_id, 0);
+        let mut listing = PrimaryListing {
+            id: draft_id.clone(),
+            seller_id: seller_id.clone(),
+            nft_metadata: draft.nft_metadata,
+            nft_mutable_metadata: draft.nft_mutable_metadata,
+            supply_total: draft.supply_total,
+            price_yocto: draft.price_yocto,
+            min_bid_yocto: draft.min_bid_yocto,
+            start_timestamp,
+            end_timestamp,
+            status: ListingStatus::Unstarted,
+            supply_left: draft.supply_total,
+            supply_pending: 0,
+            bids: BidBook::new(
+                PrimaryListingStorageKey::Bids { listing_id_hash }
+                    .try_to_vec()
+                    .unwrap(),
+                PrimaryListingStorageKey::BidKeys { listing_id_hash }
+                    .try_to_vec()
+                    .unwrap(),
+            ),
+            next_bid_id: 0,
+            version: 0,
+            phases: Vector::new(
+                PrimaryListingStorageKey::Phases { listing_id_hash }
+                    .try_to_vec()
+                    .unwrap(),
+            ),
+            max_per_account: draft.max_per_account,
+            purchases_by_account: UnorderedMap::new(
+                PrimaryListingStorageKey::Purchases { listing_id_hash }
+                    .try_to_vec()
+                    .unwrap(),
+            ),
+            seq: 0, // assigned by internal_add_primary_listing
+            rules: draft.rules,
+            round: 0,
+        };
+
+        let storage_before = env::storage_usage();
+        self.internal_remove_primary_draft(&draft_id);
+        self.internal_add_primary_listing(&mut listing);
+        self.primary_collections.insert(
+            &(draft_id.nft_contract_id.clone(), draft_id.collection_id),
+            &collection,
+        );
+        let storage_after = env::storage_usage();
+        self.internal_charge_account_storage(&seller_id, storage_before, storage_after);
+    }
+
+    pub fn primary_drafts_by_seller(
+        &self,
+        seller_account_id: AccountId,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<JsonPrimaryDraft> {
+        let draft_ids = self.primary_drafts_by_seller_id.get(&seller_account_id);
+        if draft_ids.is_none() {
+            return vec![];
+        }
+        let draft_ids = draft_ids.unwrap();
+
+        let start = u128::from(from_index.unwrap_or(U128(0))) as usize;
+        let count = limit.unwrap_or(10) as usize;
+
+        draft_ids
+            .iter()
+            .skip(start)
+            .take(count)
+            .map(|draft_id| {
+                self.primary_drafts_by_id
+                    .get(&draft_id)
+                    .expect("Draft record does not exist")
+                    .to_json()
+            })
+            .collect()
+    }
+}
+
+impl PrimaryDraft {
+    pub(crate) fn to_json(self) -> JsonPrimaryDraft {
+        JsonPrimaryDraft {
+            nft_contract_id: self.id.nft_contract_id,
+            collection_id: U64(self.id.collection_id),
+            seller_id: self.seller_id,
+            nft_metadata: self.nft_metadata,
+            nft_mutable_metadata: self.nft_mutable_metadata,
+            collection_supply: U64(self.collection_supply),
+            supply_total: U64(self.supply_total),
+            price_yocto: self.price_yocto.map(|p| U128(p)),
+            min_bid_yocto: self.min_bid_yocto.map(|b| U128(b)),
+            max_per_account: self.max_per_account.map(|m| U64(m)),
+            rules: self.rules.to_json(),
+            is_deleting: self.is_deleting,
+        }
+    }
+}
+
+impl MarketplaceContract {
+    fn internal_primary_draft_assert_supply(&self, supply_total: u64, collection_supply: u64) {
+        let total_supply_max = self.config.listing_limits.total_supply_max;
+        assert!(
+            supply_total > 0 && supply_total <= total_supply_max,
+            "Max NFT supply must be between 1 and {}.",
+            total_supply_max
+        );
+        assert!(
+            collection_supply >= supply_total && collection_supply <= total_supply_max,
+            "Collection supply must be between {} and {}.",
+            supply_total,
+            total_supply_max
+        );
+    }
+
+    // doesn't check if already there!
+    fn internal_add_primary_draft(&mut self, draft: &PrimaryDraft) {
+        self.primary_drafts_by_id.insert(&draft.id, draft);
+        let mut drafts_by_this_seller = self
+            .primary_drafts_by_seller_id
+            .get(&draft.seller_id)
+            .unwrap_or_else(|| {
+                UnorderedSet::new(
+                    MarketplaceStorageKey::PrimaryDraftsBySellerIdInner {
+                        account_id_hash: hash_account_id(&draft.seller_id),
+                    }
+                    .try_to_vec()
+                    .unwrap(),
+                )
+            });
+        drafts_by_this_seller.insert(&draft.id);
+        self.primary_drafts_by_seller_id
+            .insert(&draft.seller_id, &drafts_by_this_seller);
+    }
+
+    fn internal_remove_primary_draft(&mut self, draft_id: &PrimaryListingId) -> PrimaryDraft {
+        let removed_draft = self
+            .primary_drafts_by_id
+            .remove(draft_id)
+            .expect("Could not remove draft: Could not find draft");
+        let seller_id = &removed_draft.seller_id;
+
+        let mut drafts_by_this_seller = self
+            .primary_drafts_by_seller_id
+            .get(seller_id)
+            .expect("Could not remove draft: Could not find drafts for this seller");
+        let did_remove = drafts_by_this_seller.remove(draft_id);
+        assert!(
+            did_remove,
+            "Could not remove draft: Draft not on seller's list"
+        );
+
+        if drafts_by_this_seller.is_empty() {
+            self.primary_drafts_by_seller_id
+                .remove(seller_id)
+                .expect("Could not remove draft: Could not remove the now-empty seller list");
+        } else {
+            self.primary_drafts_by_seller_id
+                .insert(seller_id, &drafts_by_this_seller);
+        }
+
+        removed_draft
+    }
+}
+
+#[ext_contract(ext_self_draft)]
+trait PrimaryDraftCallback {
+    fn primary_draft_add_make_collection_completion(
+        &mut self,
+        seller_id: AccountId,
+        nft_account_id: AccountId,
+        nft_metadata: NftMetadata,
+        nft_mutable_metadata: NftMutableMetadata,
+        collection_supply: U64,
+        supply_total: U64,
+        price_yocto: Option<U128>,
+        min_bid_yocto: Option<U128>,
+        max_per_account: Option<U64>,
+        rules: JsonListingRules,
+    ) -> (U64, Balance);
+
+    fn primary_draft_delete_collection_completion(
+        &mut self,
+        nft_contract_id: AccountId,
+        collection_id: U64,
+    ) -> Balance;
+}
+
+trait PrimaryDraftCallback {
+    fn primary_draft_add_make_collection_completion(
+        &mut self,
+        seller_id: AccountId,
+        nft_account_id: AccountId,
+        nft_metadata: NftMetadata,
+        nft_mutable_metadata: NftMutableMetadata,
+        collection_supply: U64,
+        supply_total: U64,
+        price_yocto: Option<U128>,
+        min_bid_yocto: Option<U128>,
+        max_per_account: Option<U64>,
+        rules: JsonListingRules,
+    ) -> (U64, Balance);
+
+    fn primary_draft_delete_collection_completion(
+        &mut self,
+        nft_contract_id: AccountId,
+        collection_id: U64,
+    ) -> Balance;
+}
+
+#[near_bindgen]
+impl PrimaryDraftCallback for MarketplaceContract {
+    #[private]
+    fn primary_draft_add_make_collection_completion(
+        &mut self,
+        seller_id: AccountId,
+        nft_account_id: AccountId,
+        nft_metadata: NftMetadata,
+        nft_mutable_metadata: NftMutableMetadata,
+        collection_supply: U64,
+        supply_total: U64,
+        price_yocto: Option<U128>,
+        min_bid_yocto: Option<U128>,
+        max_per_account: Option<U64>,
+        rules: JsonListingRules,
+    ) -> (U64, Balance) {
+        assert_eq!(env::promise_results_count(), 1, "Too many data receipts");
+        match env::promise_result(0) {
+            PromiseResult::NotReady => {
+                unreachable!("NFT contract unreachable")
+            }
+            PromiseResult::Failed => {
+                panic!("NFT make_collection failed")
+            }
+            PromiseResult::Successful(val) => {
+                let (collection_id, nft_storage) =
+                    near_sdk::serde_json::from_slice::<(U64, U64)>(&val)
+                        .expect("NFT make_collection returned unexpected value");
+                let draft = PrimaryDraft {
+                    id: PrimaryListingId {
+                        nft_contract_id: nft_account_id,
+                        collection_id: collection_id.0,
+                    },
+                    seller_id: seller_id.clone(),
+                    nft_metadata,
+                    nft_mutable_metadata,
+                    collection_supply: collection_supply.0,
+                    supply_total: supply_total.0,
+                    price_yocto: price_yocto.map(|p| p.0),
+                    min_bid_yocto: min_bid_yocto.map(|b| b.0),
+                    max_per_account: max_per_account.map(|m| m.0),
+                    rules: ListingRules::from_json(&rules),
+                    nft_storage: nft_storage.0,
+                    is_deleting: false,
+                };
+
+                let marketplace_storage_before = env::storage_usage();
+
+                self.internal_add_primary_draft(&draft);
+
+                let storage_byte_cost = env::storage_byte_cost();
+                let marketplace_storage = env::storage_usage() - marketplace_storage_before;
+                let marketplace_storage_cost = marketplace_storage as Balance * storage_byte_cost;
+                let nft_storage_cost = nft_storage.0 as Balance * storage_byte_cost;
+                let total_storage_cost = marketplace_storage_cost + nft_storage_cost;
+                let current_deposit = self
+                    .storage_deposits
+                    .get(&seller_id)
+                    .expect("Could not find seller storage deposit record");
+                let updated_deposit = if current_deposit >= total_storage_cost {
+                    current_deposit - total_storage_cost
+                } else {
+                    0 // should never happen; TODO: log warning to review storage deposit logic
+                };
+                self.storage_deposits.insert(&seller_id, &updated_deposit);
+
+                (collection_id, updated_deposit)
+            }
+        }
+    }
+
+    // the NFT contract refunds the freed storage to the marketplace, it's passed on to the seller
+    // together with the storage of the draft; we don't panic on failure, the draft must be
+    // released
+    #[private]
+    fn primary_draft_delete_collection_completion(
+        &mut self,
+        nft_contract_id: AccountId,
+        collection_id: U64,
+    ) -> Balance {
+        let draft_id = PrimaryListingId {
+            nft_contract_id,
+            collection_id: collection_id.0,
+        };
+        let mut draft = self
+            .primary_drafts_by_id
+            .get(&draft_id)
+            .expect("Could not find this draft");
+        let seller_id = draft.seller_id.clone();
+
+        assert_eq!(env::promise_results_count(), 1, "Too many data receipts");
+        match env::promise_result(0) {
+            PromiseResult::Successful(_) => {
+                let storage_before = env::storage_usage();
+                self.internal_remove_primary_draft(&draft_id);
+                let storage_after = env::storage_usage();
+                self.internal_charge_account_storage(&seller_id, storage_before, storage_after);
+
+                let refunded_deposit = draft.nft_storage as Balance * env::storage_byte_cost();
+                let current_deposit = self.storage_deposits.get(&seller_id).unwrap_or(0);
+                let updated_deposit = current_deposit + refunded_deposit;
+                self.storage_deposits.insert(&seller_id, &updated_deposit);
+                updated_deposit
+            }
+            _ => {
+                env::log_str("NFT delete_collection failed, the draft can be used again");
+                draft.is_deleting = false;
+                self.primary_drafts_by_id.insert(&draft_id, &draft);
+                self.storage_deposits.get(&seller_id).unwrap_or(0)
+            }
+        }
+    }
+}
@@ -0,0 +1,155 @@
+#[cfg(test)]
+mod draft_tests {
+    use super::super::{PrimaryDraft, PrimaryDraftCallback};
+    use crate::{external::NftMutableMetadata, test_utils::*, *};
+    use near_sdk::json_types::{U128, U64};
+    use near_sdk::PromiseResult;
+
+    const COLLECTION_ID: u64 = 7;
+    const NFT_STORAGE: u64 = 1000;
+
+    fn setup() -> (MarketplaceContract, PrimaryListingId) {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        let draft = PrimaryDraft {
+            id: PrimaryListingId {
+                nft_contract_id: account(NFT_CONTRACT_ID),
+                collection_id: COLLECTION_ID,
+            },
+            seller_id: account(SELLER_ACCOUNT_ID),
+            nft_metadata: nft_metadata(COLLECTION_ID),
+            nft_mutable_metadata: NftMutableMetadata {
+                aux_audio_url: None,
+            },
+            collection_supply: 10,
+            supply_total: 10,
+            price_yocto: Some(PRICE_YOCTO),
+            min_bid_yocto: None,
+            max_per_account: None,
+            rules: marketplace.config.listing_rules_default.clone(),
+            nft_storage: NFT_STORAGE,
+            is_deleting: false,
+        };
+        marketplace.internal_add_primary_draft(&draft);
+        (marketplace, draft.id)
+    }
+
+    fn delete(marketplace: &mut MarketplaceContract) {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        marketplace.primary_draft_delete(account(NFT_CONTRACT_ID), U64(COLLECTION_ID));
+    }
+
+    fn complete_delete(marketplace: &mut MarketplaceContract, result: PromiseResult) -> Balance {
+        set_callback_context(NOW, vec![result]);
+        marketplace.primary_draft_delete_collection_completion(
+            account(NFT_CONTRACT_ID),
+            U64(COLLECTION_ID),
+        )
+    }
+
+    fn is_deleting(marketplace: &MarketplaceContract, draft_id: &PrimaryListingId) -> bool {
+        marketplace
+            .primary_drafts_by_id
+            .get(draft_id)
+            .unwrap()
+            .is_deleting
+    }
+
+    /* delete */
+
+    #[test]
+    fn test_delete_waits_for_nft_contract() {
+        let (mut marketplace, draft_id) = setup();
+
+        delete(&mut marketplace);
+
+        assert!(is_deleting(&marketplace, &draft_id));
+        assert!(
+            marketplace.primary_drafts_by_seller(account(SELLER_ACCOUNT_ID), None, None)[0]
+                .is_deleting
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Only the seller can delete the draft"#)]
+    fn test_delete_by_other_account() {
+        let (mut marketplace, _) = setup();
+
+        set_context(BUYER_ACCOUNT_ID, NOW, 0);
+        marketplace.primary_draft_delete(account(NFT_CONTRACT_ID), U64(COLLECTION_ID));
+    }
+
+    #[test]
+    #[should_panic(expected = r#"This draft is being deleted"#)]
+    fn test_delete_twice() {
+        let (mut marketplace, _) = setup();
+
+        delete(&mut marketplace);
+        delete(&mut marketplace);
+    }
+
+    #[test]
+    #[should_panic(expected = r#"This draft is being deleted"#)]
+    fn test_update_while_deleting() {
+        let (mut marketplace, _) = setup();
+        delete(&mut marketplace);
+
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        marketplace.primary_draft_update(
+            account(NFT_CONTRACT_ID),
+            U64(COLLECTION_ID),
+            None,
+            Some(U128(2 * PRICE_YOCTO)),
+            None,
+            None,
+            None,
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = r#"This draft is being deleted"#)]
+    fn test_publish_while_deleting() {
+        let (mut marketplace, _) = setup();
+        delete(&mut marketplace);
+
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        marketplace.primary_draft_publish(account(NFT_CONTRACT_ID), U64(COLLECTION_ID), None, None);
+    }
+
+    #[test]
+    fn test_delete_completion_refunds_storage() {
+        let (mut marketplace, draft_id) = setup();
+        delete(&mut marketplace);
+
+        let updated_deposit = complete_delete(&mut marketplace, PromiseResult::Successful(vec![]));
+
+        assert!(marketplace.primary_drafts_by_id.get(&draft_id).is_none());
+        assert!(marketplace
+            .primary_drafts_by_seller(account(SELLER_ACCOUNT_ID), None, None)
+            .is_empty());
+        // the collection storage and the storage of the draft itself
+        let nft_storage_cost = NFT_STORAGE as Balance * env::storage_byte_cost();
+        assert!(updated_deposit > STORAGE_DEPOSIT + nft_storage_cost);
+        assert_eq!(
+            marketplace
+                .storage_deposits
+                .get(&account(SELLER_ACCOUNT_ID)),
+            Some(updated_deposit)
+        );
+    }
+
+    #[test]
+    fn test_delete_completion_failed_keeps_draft() {
+        let (mut marketplace, draft_id) = setup();
+        delete(&mut marketplace);
+
+        let updated_deposit = complete_delete(&mut marketplace, PromiseResult::Failed);
+
+        assert!(!is_deleting(&marketplace, &draft_id));
+        assert_eq!(updated_deposit, STORAGE_DEPOSIT);
+
+        // the draft can be deleted again
+        delete(&mut marketplace);
+        assert!(is_deleting(&marketplace, &draft_id));
+    }
+}
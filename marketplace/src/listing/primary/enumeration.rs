@@ -1,4 +1,13 @@
-use crate::{external::{NftMetadata, NftMutableMetadata}, *, listing::status::ListingStatus};
+use crate::{
+    external::{NftMetadata, NftMutableMetadata},
+    *,
+    listing::{
+        primary::phase::JsonPresalePhase,
+        query::{ListingFilter, ListingPage, ListingSort, LISTING_QUERY_LIMIT_DEFAULT, LISTING_QUERY_SCAN_MAX},
+        rules::JsonListingRules,
+        status::ListingStatus,
+    },
+};
 
 use near_sdk::json_types::{U128, U64};
 
@@ -14,9 +23,15 @@ pub struct JsonPrimaryListing {
     pub acceptable_bid_yocto: Option<U128>,
     pub nft_metadata: NftMetadata,
     pub nft_mutable_metadata: NftMutableMetadata,
-    pub end_timestamp: Option<i64>, // nanoseconds since 1970-01-01
+    pub start_timestamp: U64,       // nanoseconds since 1970-01-01
+    pub end_timestamp: Option<U64>, // nanoseconds since 1970-01-01
     pub supply_left: U64,
     pub status: ListingStatus,
+    pub version: U64,
+    pub max_per_account: Option<U64>,
+    pub current_phase: Option<JsonPresalePhase>, // presale phase active at the time of the call
+    pub rules: JsonListingRules,
+    pub round: U64,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -74,27 +89,7 @@ impl MarketplaceContract {
             .iter()
             .skip(start) //skip to the index we specified in the start variable
             .take(count) // return "limit" elements or 0 if missing
-            .map(|listing| {
-                let acceptable_bid_yocto: Option<u128> = if listing.min_bid_yocto.is_some() {
-                    Some(listing.acceptable_bid_yocto())
-                } else {
-                    None
-                };
-                JsonPrimaryListing {
-                    nft_contract_id: listing.id.nft_contract_id,
-                    collection_id: U64(listing.id.collection_id),
-                    seller_id: listing.seller_id,
-                    supply_total: U64(listing.supply_total),
-                    price_yocto: listing.price_yocto.map(|p| U128(p)),
-                    min_bid_yocto: listing.min_bid_yocto.map(|b| U128(b)),
-                    acceptable_bid_yocto: acceptable_bid_yocto.map(|b| U128(b)),
-                    nft_metadata: listing.nft_metadata,
-                    nft_mutable_metadata: listing.nft_mutable_metadata,
-                    end_timestamp: listing.end_timestamp,
-                    supply_left: U64(listing.supply_left),
-                    status: listing.status,
-                }
-            })
+            .map(|listing| listing.to_json())
             .collect()
     }
 
@@ -120,33 +115,69 @@ impl MarketplaceContract {
             .skip(start) //skip to the index we specified in the start variable
             .take(count) // return "limit" elements or 0 if missing
             .map(|listing_id| {
-                let listing = self
+                self
                     .primary_listings_by_id
                     .get(&listing_id)
-                    .expect("Listing record does not exist");
-                let acceptable_bid_yocto: Option<u128> = if listing.min_bid_yocto.is_some() {
-                    Some(listing.acceptable_bid_yocto())
-                } else {
-                    None
-                };
-                JsonPrimaryListing {
-                    nft_contract_id: listing.id.nft_contract_id,
-                    collection_id: U64(listing.id.collection_id),
-                    seller_id: listing.seller_id,
-                    supply_total: U64(listing.supply_total),
-                    price_yocto: listing.price_yocto.map(|p| U128(p)),
-                    min_bid_yocto: listing.min_bid_yocto.map(|b| U128(b)),
-                    acceptable_bid_yocto: acceptable_bid_yocto.map(|b| U128(b)),
-                    nft_metadata: listing.nft_metadata,
-                    nft_mutable_metadata: listing.nft_mutable_metadata,
-                    end_timestamp: listing.end_timestamp,
-                    supply_left: U64(listing.supply_left),
-                    status: listing.status,
-                }
+                    .expect("Listing record does not exist")
+                    .to_json()
             })
             .collect()
     }
 
+    // filtered and sorted listings, paginated with a cursor that's stable across additions and removals
+    // pass next_cursor of the returned page to get the next one; a page may hold fewer than limit
+    // listings (even none) if the scan cap was hit, only next_cursor == None means there's no more
+    pub fn primary_listings_query(
+        &self,
+        filter: Option<ListingFilter>,
+        sort: Option<ListingSort>,
+        cursor: Option<String>,
+        limit: Option<u64>,
+    ) -> ListingPage<JsonPrimaryListing> {
+        let filter = filter.unwrap_or_default();
+        let sort = sort.unwrap_or(ListingSort::CreatedAsc);
+        let limit = limit.unwrap_or(LISTING_QUERY_LIMIT_DEFAULT) as usize;
+
+        let mut listings = Vec::new();
+        let mut last_cursor: Option<String> = None;
+        let mut scanned = 0;
+        let mut index_iter = self.primary_listing_index.scan(&sort, cursor);
+        while listings.len() < limit && scanned < LISTING_QUERY_SCAN_MAX {
+            match index_iter.next() {
+                Some((cursor, listing_id)) => {
+                    scanned += 1;
+                    last_cursor = Some(cursor);
+                    let mut listing = self
+                        .primary_listings_by_id
+                        .get(&listing_id)
+                        .expect("Listing record does not exist");
+                    listing.update_status();
+                    if filter.matches(
+                        &listing.status,
+                        &listing.seller_id,
+                        &listing.id.nft_contract_id,
+                        listing.price_yocto,
+                        listing.min_bid_yocto,
+                        listing.end_timestamp,
+                    ) {
+                        listings.push(listing.to_json());
+                    }
+                }
+                None => {
+                    return ListingPage {
+                        listings,
+                        next_cursor: None,
+                    }
+                }
+            }
+        }
+
+        ListingPage {
+            listings,
+            next_cursor: last_cursor,
+        }
+    }
+
     // get PrimaryListing by nft_contract_id
     pub fn primary_listing(
         &self,
@@ -157,29 +188,10 @@ impl MarketplaceContract {
             nft_contract_id: nft_contract_id.clone(),
             collection_id: collection_id.0,
         };
-        let listing = self
-            .primary_listings_by_id
+        self.primary_listings_by_id
             .get(&listing_id)
-            .expect("Could not find primary listing");
-        let acceptable_bid_yocto: Option<u128> = if listing.min_bid_yocto.is_some() {
-            Some(listing.acceptable_bid_yocto())
-        } else {
-            None
-        };
-        JsonPrimaryListing {
-            nft_contract_id: nft_contract_id,
-            collection_id: collection_id,
-            seller_id: listing.seller_id,
-            supply_total: U64(listing.supply_total),
-            price_yocto: listing.price_yocto.map(|p| U128(p)),
-            min_bid_yocto: listing.min_bid_yocto.map(|p| U128(p)),
-            acceptable_bid_yocto: acceptable_bid_yocto.map(|b| U128(b)),
-            nft_metadata: listing.nft_metadata,
-            nft_mutable_metadata: listing.nft_mutable_metadata,
-            end_timestamp: listing.end_timestamp,
-            supply_left: U64(listing.supply_left),
-            status: listing.status,
-        }
+            .expect("Could not find primary listing")
+            .to_json()
     }
 
     // get bid by nft_contract_id and BidId
@@ -287,8 +299,42 @@ impl MarketplaceContract {
 }
 
 impl PrimaryListing {
+    pub(crate) fn to_json(mut self) -> JsonPrimaryListing {
+        // stored status may be stale, it only gets updated on calls
+        self.update_status();
+        let current_phase = self
+            .current_phase_index()
+            .map(|index| self.phases.get(index).unwrap().to_json(index));
+        let acceptable_bid_yocto: Option<u128> = if self.min_bid_yocto.is_some() {
+            Some(self.acceptable_bid_yocto())
+        } else {
+            None
+        };
+        let rules = self.rules.to_json();
+        JsonPrimaryListing {
+            nft_contract_id: self.id.nft_contract_id,
+            collection_id: U64(self.id.collection_id),
+            seller_id: self.seller_id,
+            supply_total: U64(self.supply_total),
+            price_yocto: self.price_yocto.map(|p| U128(p)),
+            min_bid_yocto: self.min_bid_yocto.map(|b| U128(b)),
+            acceptable_bid_yocto: acceptable_bid_yocto.map(|b| U128(b)),
+            nft_metadata: self.nft_metadata,
+            nft_mutable_metadata: self.nft_mutable_metadata,
+            start_timestamp: U64(self.start_timestamp as u64),
+            end_timestamp: self.end_timestamp.map(|t| U64(t as u64)),
+            supply_left: U64(self.supply_left),
+            status: self.status,
+            version: U64(self.version),
+            max_per_account: self.max_per_account.map(|m| U64(m)),
+            current_phase,
+            rules,
+            round: U64(self.round),
+        }
+    }
+
     pub(crate) fn bid(&self, bid_id: &u64) -> Option<JsonPrimaryListingBid> {
-        if let Some(bid) = self.bids.iter().find(|bid| bid.id == *bid_id) {
+        if let Some(bid) = self.bids.get(*bid_id) {
             Some(JsonPrimaryListingBid {
                 id: U64(bid.id),
                 bidder_id: bid.bidder_id,
@@ -1,14 +1,20 @@
 use crate::{
     internal::hash_account_id,
-    listing::{constants::*, status::ListingStatus},
+    listing::{bid::Bid, date::DateInput, id::ListingId, primary::phase::PresaleAllowlist, sale::Sale, status::ListingStatus},
     *,
 };
 
-pub(crate) fn hash_primary_listing_id(listing_id: &PrimaryListingId) -> CryptoHash {
-    let hashed_string = format!(
-        "{}.{}",
-        listing_id.nft_contract_id, listing_id.collection_id
-    );
+// each sale round of a collection gets its own storage prefixes, the first one keeps the
+// prefixes listings had before rounds were introduced
+pub(crate) fn hash_primary_listing_id(listing_id: &PrimaryListingId, round: u64) -> CryptoHash {
+    let hashed_string = if round == 0 {
+        format!("{}.{}", listing_id.nft_contract_id, listing_id.collection_id)
+    } else {
+        format!(
+            "{}.{}.{}",
+            listing_id.nft_contract_id, listing_id.collection_id, round
+        )
+    };
     let mut hash = CryptoHash::default();
     hash.copy_from_slice(&env::sha256(hashed_string.as_bytes()));
     hash
@@ -49,11 +55,21 @@ impl PrimaryListing {
         }
     }
 
-    pub(crate) fn sort_bids(&mut self) {
-        let mut bids_vec_sorted = self.bids.to_vec();
-        bids_vec_sorted.sort();
-        self.bids.clear();
-        self.bids.extend(bids_vec_sorted);
+    // index of the presale phase that is currently active, if any
+    // phases only apply before the public sale starts
+    pub(crate) fn current_phase_index(&self) -> Option<u64> {
+        if self.status != ListingStatus::Unstarted {
+            return None;
+        }
+        let block_timestamp = env::block_timestamp() as i64;
+        (0..self.phases.len())
+            .rev()
+            .find(|index| self.phases.get(*index).unwrap().start_timestamp <= block_timestamp)
+    }
+
+    // supply that's neither sold nor reserved by purchases in flight
+    pub(crate) fn supply_available(&self) -> u64 {
+        self.supply_left.saturating_sub(self.supply_pending)
     }
 
     pub(crate) fn acceptable_bid_yocto(&self) -> u128 {
@@ -63,30 +79,128 @@ impl PrimaryListing {
         return if unmatched_supply_exists {
             min_bid_yocto
         } else {
-            let worst_acceptable_bid = self.bids.get(num_bids - 1).unwrap();
-            worst_acceptable_bid.amount_yocto + BID_STEP_YOCTO
+            let worst_acceptable_bid = self.bids.worst().unwrap();
+            self.rules.next_bid_yocto(worst_acceptable_bid.amount_yocto)
         };
     }
 }
 
 impl MarketplaceContract {
+    // start and end of a new primary listing or sale round
+    pub(crate) fn internal_primary_listing_timestamps(
+        &self,
+        is_accepting_bids: bool,
+        start_date: Option<DateInput>,
+        end_date: Option<DateInput>,
+    ) -> (i64, Option<i64>) {
+        // the logic here is:
+        // - if start timestamp is missing, current block timestamp is used
+        // - for bid-accepting listings the end date must be set and the duration cannot exceed
+        //   max allowed (this is to prevent keeping bid deposits indefinitely)
+        // - for buy-now-only listings, there's no upper limit on duration
+
+        let current_block_timestamp = env::block_timestamp() as i64;
+
+        let start_timestamp = if let Some(start_date) = start_date {
+            let start_timestamp = start_date.to_timestamp(current_block_timestamp);
+            assert!(
+                start_timestamp >= current_block_timestamp,
+                "Start date into the past"
+            );
+            start_timestamp
+        } else {
+            current_block_timestamp
+        };
+
+        let end_timestamp: Option<i64> = if let Some(end_date) = end_date {
+            let end_timestamp = end_date.to_timestamp(start_timestamp);
+            assert!(
+                end_timestamp >= current_block_timestamp,
+                "End date into the past"
+            );
+            Some(end_timestamp)
+        } else {
+            None
+        };
+
+        if let Some(end_timestamp) = end_timestamp {
+            // end timestamp set
+            let duration = end_timestamp - start_timestamp;
+            assert!(
+                duration >= self.config.listing_limits.primary_listing_min_duration_nano,
+                "Listing duration too short"
+            );
+            if is_accepting_bids {
+                assert!(
+                    duration <= self.config.listing_limits.primary_listing_max_duration_nano,
+                    "Listing duration too long"
+                );
+            }
+        } else {
+            assert!(
+                !is_accepting_bids,
+                "End date must be set for bid-accepting listing"
+            );
+        }
+
+        (start_timestamp, end_timestamp)
+    }
+
     // doesn't check if already there!
-    pub(crate) fn internal_add_primary_listing(&mut self, listing: &PrimaryListing) {
+    // assigns the listing its sequence number
+    pub(crate) fn internal_add_primary_listing(&mut self, listing: &mut PrimaryListing) {
+        listing.seq = self.next_listing_seq;
+        self.next_listing_seq += 1;
         self.primary_listings_by_id.insert(&listing.id, &listing);
         self.internal_add_primary_listing_to_seller(&listing.seller_id, &listing.id);
+        self.primary_listing_index.insert(
+            listing.seq,
+            listing.price_yocto,
+            listing.end_timestamp,
+            &listing.id,
+        );
+        self.internal_add_listing_to_floor(
+            &ListingId::Primary(listing.id.clone()),
+            &listing.id.nft_contract_id,
+            Some(listing.id.collection_id),
+            listing.price_yocto,
+            listing.seq,
+        );
     }
 
     // removes all FPO-related records from Marketplace without initiating any NEAR transfers
+    // the purchase records are paid for by the buyers, clear them first with
+    // internal_primary_listing_clear_purchases so that the freed storage isn't credited to the seller
     pub(crate) fn internal_remove_primary_listing(
         &mut self,
         listing_id: &PrimaryListingId,
     ) -> PrimaryListing {
-        let removed_listing = self
+        let mut removed_listing = self
             .primary_listings_by_id
             .remove(listing_id)
             .expect("Could not remove listing: Could not find listing");
         let seller_id = &removed_listing.seller_id;
 
+        self.primary_listing_index.remove(
+            removed_listing.seq,
+            removed_listing.price_yocto,
+            removed_listing.end_timestamp,
+        );
+        self.internal_remove_listing_from_floor(
+            &removed_listing.id.nft_contract_id,
+            Some(removed_listing.id.collection_id),
+            removed_listing.price_yocto,
+            removed_listing.seq,
+        );
+
+        // drop on-chain presale allowlists
+        for mut phase in removed_listing.phases.iter() {
+            if let PresaleAllowlist::Accounts(account_ids) = &mut phase.allowlist {
+                account_ids.clear();
+            }
+        }
+        removed_listing.phases.clear();
+
         let mut listings_by_this_seller = self
             .primary_listings_by_seller_id
             .get(seller_id)
@@ -140,23 +254,61 @@ impl MarketplaceContract {
     }
 
     // this won't insert updated listing back into contract, caller must do it (if needed)
+    // proxy bids would keep outbidding each other one step at a time until all but one reach
+    // their maximum, so the outcome is settled at once: the bid with the lowest maximum drops
+    // out and the bids it would have pushed out are raised one step above that maximum; once
+    // there's no supply left there's nothing to compete for and the worst bids are dropped
     pub(crate) fn primary_listing_remove_supply_exceeding_bids_and_refund_bidders(
         &mut self,
         listing: &mut PrimaryListing,
     ) {
         // TODO: this won't work with SecondaryListing reference! will lead to inconsistent state
-        if listing.supply_left >= listing.bids.len() {
-            return;
-        }
-        let num_outbid_bids = listing.bids.len() - listing.supply_left;
-        for _ in 0..num_outbid_bids {
+        while listing.bids.len() > listing.supply_left {
             let storage_before = env::storage_usage();
-            let removed_bid = listing
-                .bids
-                .pop()
-                .expect("Could not remove a bid");
+            let removed_bid = if listing.supply_left == 0 {
+                listing.bids.pop_worst().expect("Could not remove a bid")
+            } else {
+                // among equal maximums the later bid drops out, as with equal amounts
+                let dropped_bid = listing
+                    .bids
+                    .iter()
+                    .min_by(|a, b| {
+                        a.max_amount_yocto
+                            .cmp(&b.max_amount_yocto)
+                            .then(b.id.cmp(&a.id))
+                    })
+                    .unwrap();
+                let removed_bid = listing
+                    .bids
+                    .remove(dropped_bid.id)
+                    .expect("Could not remove a bid");
+                let raised_amount_yocto = listing.rules.next_bid_yocto(removed_bid.max_amount_yocto);
+                let raised_bids: Vec<Bid> = listing
+                    .bids
+                    .iter_rev()
+                    .take_while(|bid| bid.amount_yocto < raised_amount_yocto)
+                    .filter(|bid| bid.max_amount_yocto > bid.amount_yocto)
+                    .collect();
+                // bids must stay lower than buy now price, it may have been lowered meanwhile
+                let amount_cap_yocto = listing
+                    .price_yocto
+                    .map_or(u128::MAX, |price_yocto| {
+                        price_yocto - listing.rules.bid_step_yocto
+                    });
+                for bid in raised_bids {
+                    listing.bids.remove(bid.id);
+                    listing.bids.insert(&Bid {
+                        amount_yocto: raised_amount_yocto
+                            .min(bid.max_amount_yocto)
+                            .min(amount_cap_yocto),
+                        ..bid
+                    });
+                }
+                removed_bid
+            };
             let bidder_id = removed_bid.bidder_id;
-            Promise::new(bidder_id.clone()).transfer(removed_bid.amount_yocto);
+            // unused escrow of a proxy bid included
+            Promise::new(bidder_id.clone()).transfer(removed_bid.max_amount_yocto);
             let storage_after = env::storage_usage();
             let freed_storage = storage_before - storage_after; // this was covered by bidder
             let freed_storage_cost = freed_storage as Balance * env::storage_byte_cost();
@@ -166,6 +318,178 @@ impl MarketplaceContract {
             } else {
                 // this should never happen! TODO: we may want to log some message if it does
             }
+            self.internal_remove_bid_from_account(
+                &bidder_id,
+                ListingId::Primary(listing.id.clone()),
+                removed_bid.id,
+            );
         }
     }
+
+    // reserves supply and the buyer's purchase counts (of the listing and of the presale phase)
+    // before the mints are started so that concurrent purchases can't overshoot the limits;
+    // the purchase records are paid for by the buyer; the caller must store the listing
+    pub(crate) fn internal_primary_listing_reserve(
+        &mut self,
+        listing: &mut PrimaryListing,
+        buyer_id: &AccountId,
+        quantity: u64,
+        phase_index: Option<u64>,
+    ) {
+        listing.supply_pending += quantity;
+        let collection_key = (listing.id.nft_contract_id.clone(), listing.id.collection_id);
+        if let Some(mut collection) = self.primary_collections.get(&collection_key) {
+            collection.supply_pending += quantity;
+            self.primary_collections.insert(&collection_key, &collection);
+        }
+
+        let storage_before = env::storage_usage();
+        if listing.max_per_account.is_some() {
+            let purchased = listing.purchases_by_account.get(buyer_id).unwrap_or(0);
+            listing
+                .purchases_by_account
+                .insert(buyer_id, &(purchased + quantity));
+        }
+        if let Some(phase_index) = phase_index {
+            let mut phase = listing.phases.get(phase_index).unwrap();
+            if phase.max_per_account.is_some() {
+                let purchased = phase.purchases_by_account.get(buyer_id).unwrap_or(0);
+                phase
+                    .purchases_by_account
+                    .insert(buyer_id, &(purchased + quantity));
+                listing.phases.replace(phase_index, &phase);
+            }
+        }
+        let storage_after = env::storage_usage();
+        self.internal_charge_account_storage(buyer_id, storage_before, storage_after);
+    }
+
+    // removes the purchase records of the listing and of its presale phases, refunding their
+    // storage to the buyers who paid for it; the caller must store the listing
+    pub(crate) fn internal_primary_listing_clear_purchases(&mut self, listing: &mut PrimaryListing) {
+        self.internal_clear_purchase_records(&mut listing.purchases_by_account);
+        for phase_index in 0..listing.phases.len() {
+            let mut phase = listing.phases.get(phase_index).unwrap();
+            self.internal_clear_purchase_records(&mut phase.purchases_by_account);
+            listing.phases.replace(phase_index, &phase);
+        }
+    }
+
+    fn internal_clear_purchase_records(
+        &mut self,
+        purchases_by_account: &mut UnorderedMap<AccountId, u64>,
+    ) {
+        let buyer_ids: Vec<AccountId> = purchases_by_account.keys().collect();
+        for buyer_id in buyer_ids.iter() {
+            let storage_before = env::storage_usage();
+            purchases_by_account.remove(buyer_id);
+            let storage_after = env::storage_usage();
+            self.internal_charge_account_storage(buyer_id, storage_before, storage_after);
+        }
+    }
+
+    // bookkeeping once the mints of a purchase have completed: supply, purchase limits, buyer's
+    // storage deposit and the sales ledger; paying the seller is up to the caller
+    // quantity is what the purchase reserved, whatever didn't get minted is released
+    // it's called from the mint callbacks which schedule the payments, so it must not panic,
+    // the listing may have been concluded meanwhile and the next round of the collection, which
+    // has the same listing id, added; the listing is only updated if it's still the purchase round
+    // returns the storage cost deducted from the buyer's storage deposit and the part of the
+    // mint storage the deposit didn't cover, which the caller takes out of the seller's proceeds
+    pub(crate) fn internal_primary_listing_record_mints(
+        &mut self,
+        listing_id: &PrimaryListingId,
+        round: u64,
+        seller_id: &AccountId,
+        buyer_id: &AccountId,
+        receiver_id: &AccountId,
+        price_yocto: Balance,
+        quantity: u64,
+        token_ids: &[NftId],
+        mint_storage_bytes: u64,
+        phase_index: Option<u64>,
+    ) -> (Balance, Balance) {
+        let minted_count = token_ids.len() as u64;
+        let unminted_count = quantity.saturating_sub(minted_count);
+
+        // update listing supply, changing supply_left won't affect the storage so we don't
+        // need to update seller's storage deposit; purchase records were paid for when reserved
+        if let Some(mut listing) = self
+            .primary_listings_by_id
+            .get(listing_id)
+            .filter(|listing| listing.round == round)
+        {
+            listing.supply_pending = listing.supply_pending.saturating_sub(quantity);
+            listing.supply_left = listing.supply_left.saturating_sub(minted_count);
+            // the reserved purchase count goes down, the record stays
+            if unminted_count > 0 && listing.max_per_account.is_some() {
+                let purchased = listing.purchases_by_account.get(buyer_id).unwrap_or(0);
+                listing
+                    .purchases_by_account
+                    .insert(buyer_id, &purchased.saturating_sub(unminted_count));
+            }
+            if minted_count > 0 {
+                self.primary_listing_remove_supply_exceeding_bids_and_refund_bidders(&mut listing);
+                // sold out listing no longer counts towards the floor
+                if listing.supply_left == 0 {
+                    self.internal_remove_listing_from_floor(
+                        &listing_id.nft_contract_id,
+                        Some(listing_id.collection_id),
+                        listing.price_yocto,
+                        listing.seq,
+                    );
+                }
+            }
+            // same for the presale phase the purchase was made in
+            if unminted_count > 0 {
+                if let Some(phase_index) = phase_index {
+                    if let Some(mut phase) = listing.phases.get(phase_index) {
+                        if phase.max_per_account.is_some() {
+                            let purchased = phase.purchases_by_account.get(buyer_id).unwrap_or(0);
+                            phase
+                                .purchases_by_account
+                                .insert(buyer_id, &purchased.saturating_sub(unminted_count));
+                            listing.phases.replace(phase_index, &phase);
+                        }
+                    }
+                }
+            }
+            self.primary_listings_by_id.insert(listing_id, &listing);
+        }
+
+        // the collection limits the supply of later rounds; listings made before the rounds
+        // were introduced have no collection record
+        let collection_key = (listing_id.nft_contract_id.clone(), listing_id.collection_id);
+        if let Some(mut collection) = self.primary_collections.get(&collection_key) {
+            collection.supply_minted += minted_count;
+            collection.supply_pending = collection.supply_pending.saturating_sub(quantity);
+            self.primary_collections.insert(&collection_key, &collection);
+        }
+
+        // update buyer storage deposit; the worst case is checked before minting so it should
+        // cover the mints, if it doesn't the seller pays the rest rather than the marketplace
+        let mint_storage_cost = mint_storage_bytes as Balance * env::storage_byte_cost();
+        let current_deposit = self.storage_deposits.get(buyer_id).unwrap_or(0);
+        let storage_cost = current_deposit.min(mint_storage_cost);
+        self.storage_deposits
+            .insert(buyer_id, &(current_deposit - storage_cost));
+
+        // sales ledger storage is on the marketplace
+        for token_id in token_ids.iter() {
+            self.internal_record_sale(
+                Sale {
+                    listing_id: Some(ListingId::Primary(listing_id.clone())),
+                    token_id: token_id.clone(),
+                    buyer_id: receiver_id.clone(),
+                    seller_id: seller_id.clone(),
+                    price_yocto,
+                    timestamp: env::block_timestamp(),
+                },
+                &listing_id.nft_contract_id,
+                Some(listing_id.collection_id),
+            );
+        }
+
+        (storage_cost, mint_storage_cost - storage_cost)
+    }
 }
@@ -1,8 +1,10 @@
 use crate::*;
 use crate::external::{NftMetadata, NftMutableMetadata};
 use super::super::{
-    bid::{Bid},
+    bid::{BidBook},
     status::{ListingStatus},
+    primary::phase::PresalePhase,
+    rules::ListingRules,
 };
 use std::fmt;
 use near_sdk::collections::Vector;
@@ -13,6 +15,23 @@ pub enum PrimaryListingStorageKey {
     Bids {
         listing_id_hash: CryptoHash,
     },
+    BidKeys {
+        listing_id_hash: CryptoHash,
+    },
+    Phases {
+        listing_id_hash: CryptoHash,
+    },
+    Purchases {
+        listing_id_hash: CryptoHash,
+    },
+    PhaseAllowlist {
+        listing_id_hash: CryptoHash,
+        phase_index: u64,
+    },
+    PhasePurchases {
+        listing_id_hash: CryptoHash,
+        phase_index: u64,
+    },
 }
 
 #[derive(BorshDeserialize, BorshSerialize)]
@@ -55,8 +74,16 @@ pub struct PrimaryListing {
     pub end_timestamp: Option<i64>,             // nanoseconds since 1970-01-01
     pub status: ListingStatus,                  // will be updated when any buyer transaction is mined
     pub supply_left: u64,
-    pub bids: Vector<Bid>,
+    pub supply_pending: u64,                    // reserved by purchases whose mints are in flight
+    pub bids: BidBook,
     pub next_bid_id: u64,
+    pub version: u64,                           // bumped on every seller update
+    pub phases: Vector<PresalePhase>,           // presale phases preceding start_timestamp, ordered by start
+    pub max_per_account: Option<u64>,           // if None, one account can buy the whole supply
+    pub purchases_by_account: UnorderedMap<AccountId, u64>, // only tracked if max_per_account is set
+    pub seq: u64,                               // creation order, assigned when the listing is added
+    pub rules: ListingRules,                    // price and bid steps, fixed when the listing is added
+    pub round: u64,                             // sale round of the collection, 0 for the listing which made it
 }
 
 impl fmt::Display for PrimaryListing {
@@ -2,6 +2,9 @@ pub mod lib;
 pub mod seller;
 pub mod buyer;
 pub mod enumeration;
+pub mod phase;
+pub mod round;
+pub mod draft;
 
-mod internal;
-mod config;
\ No newline at end of file
+pub(crate) mod internal;
+pub mod config;
\ No newline at end of file
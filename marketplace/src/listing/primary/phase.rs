@@ -0,0 +1,86 @@
+use crate::*;
+use near_sdk::json_types::{U128, U64};
+
+// who is allowed to buy during a presale phase
+#[derive(BorshDeserialize, BorshSerialize)]
+pub enum PresaleAllowlist {
+    Public,
+    Accounts(UnorderedSet<AccountId>),
+    MerkleRoot(CryptoHash), // leaves are sha256(account_id), pairs are hashed in sorted order
+}
+
+impl PresaleAllowlist {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PresaleAllowlist::Public => "Public",
+            PresaleAllowlist::Accounts(_) => "Accounts",
+            PresaleAllowlist::MerkleRoot(_) => "MerkleRoot",
+        }
+    }
+
+    pub(crate) fn contains(&self, account_id: &AccountId, merkle_proof: &Option<Vec<Base64VecU8>>) -> bool {
+        match self {
+            PresaleAllowlist::Public => true,
+            PresaleAllowlist::Accounts(account_ids) => account_ids.contains(account_id),
+            PresaleAllowlist::MerkleRoot(root) => {
+                let proof = merkle_proof
+                    .as_ref()
+                    .expect("Merkle proof is required for this phase");
+                verify_merkle_proof(root, account_id, proof)
+            }
+        }
+    }
+}
+
+// a phase runs from its start until the next phase starts or the public sale begins
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct PresalePhase {
+    pub start_timestamp: i64,                   // nanoseconds since 1970-01-01
+    pub price_yocto: u128,
+    pub max_per_account: Option<u64>,           // if None, there's no per-account limit
+    pub allowlist: PresaleAllowlist,
+    pub purchases_by_account: UnorderedMap<AccountId, u64>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct JsonPresalePhase {
+    pub index: U64,
+    pub start_timestamp: U64, // nanoseconds since 1970-01-01
+    pub price_yocto: U128,
+    pub max_per_account: Option<U64>,
+    pub allowlist: String,
+}
+
+impl PresalePhase {
+    pub(crate) fn to_json(&self, index: u64) -> JsonPresalePhase {
+        JsonPresalePhase {
+            index: U64(index),
+            start_timestamp: U64(self.start_timestamp as u64),
+            price_yocto: U128(self.price_yocto),
+            max_per_account: self.max_per_account.map(|m| U64(m)),
+            allowlist: self.allowlist.as_str().to_string(),
+        }
+    }
+}
+
+fn verify_merkle_proof(root: &CryptoHash, account_id: &AccountId, proof: &Vec<Base64VecU8>) -> bool {
+    let mut hash = env::sha256(account_id.as_bytes());
+    for sibling in proof {
+        let sibling = &sibling.0;
+        let mut pair = Vec::with_capacity(hash.len() + sibling.len());
+        if hash.as_slice() <= sibling.as_slice() {
+            pair.extend_from_slice(&hash);
+            pair.extend_from_slice(sibling);
+        } else {
+            pair.extend_from_slice(sibling);
+            pair.extend_from_slice(&hash);
+        }
+        hash = env::sha256(&pair);
+    }
+    hash.as_slice() == root
+}
+
+#[cfg(test)]
+#[path = "phase_tests.rs"]
+mod phase_tests;
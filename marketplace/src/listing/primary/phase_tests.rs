@@ -0,0 +1,307 @@
+#[cfg(test)]
+mod phase_tests {
+    use super::super::{verify_merkle_proof, PresaleAllowlist};
+    use crate::{
+        listing::{
+            date::DateInput,
+            primary::{internal::hash_primary_listing_id, lib::PrimaryListingStorageKey},
+        },
+        test_utils::*,
+        *,
+    };
+    use near_sdk::json_types::{Base64VecU8, U128, U64};
+
+    const PHASE_PRICE_YOCTO: Balance = PRICE_YOCTO / 2;
+
+    fn hash_pair(a: &[u8], b: &[u8]) -> Vec<u8> {
+        let mut pair = Vec::new();
+        if a <= b {
+            pair.extend_from_slice(a);
+            pair.extend_from_slice(b);
+        } else {
+            pair.extend_from_slice(b);
+            pair.extend_from_slice(a);
+        }
+        env::sha256(&pair)
+    }
+
+    fn root_of(hash: &[u8]) -> CryptoHash {
+        let mut root = CryptoHash::default();
+        root.copy_from_slice(hash);
+        root
+    }
+
+    // listing starting in 10 hours with a presale phase that starts in 2 hours
+    fn add_listing_with_phase(
+        marketplace: &mut MarketplaceContract,
+        max_per_account: Option<u64>,
+        allowlist_account_ids: Option<Vec<AccountId>>,
+    ) -> PrimaryListingId {
+        let listing_id = add_primary_listing(marketplace, 0, 10, Some(PRICE_YOCTO), None, None);
+        let mut listing = marketplace.primary_listings_by_id.get(&listing_id).unwrap();
+        listing.start_timestamp = (10 * HOUR_NANO) as i64;
+        marketplace
+            .primary_listings_by_id
+            .insert(&listing_id, &listing);
+
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        marketplace.primary_listing_add_phase(
+            account(NFT_CONTRACT_ID),
+            U64(0),
+            DateInput::UnixNs(U64(2 * HOUR_NANO)),
+            U128(PHASE_PRICE_YOCTO),
+            max_per_account.map(|m| U64(m)),
+            allowlist_account_ids,
+            None,
+        );
+        listing_id
+    }
+
+    fn buy_in_phase(marketplace: &mut MarketplaceContract, buyer_id: &str, quantity: u64) {
+        set_context(
+            buyer_id,
+            3 * HOUR_NANO,
+            PHASE_PRICE_YOCTO * quantity as u128,
+        );
+        marketplace.primary_listing_buy(
+            account(NFT_CONTRACT_ID),
+            U64(0),
+            Some(U64(quantity)),
+            None,
+            Some(U128(PHASE_PRICE_YOCTO)),
+            None,
+            None,
+        );
+    }
+
+    /* allowlists */
+
+    #[test]
+    fn test_public_allowlist_contains_everyone() {
+        set_context(BUYER_ACCOUNT_ID, NOW, 0);
+        assert!(PresaleAllowlist::Public.contains(&account(BUYER_ACCOUNT_ID), &None));
+    }
+
+    #[test]
+    fn test_merkle_proof() {
+        set_context(BUYER_ACCOUNT_ID, NOW, 0);
+        let leaves: Vec<Vec<u8>> = [BUYER_ACCOUNT_ID, BUYER2_ACCOUNT_ID, BIDDER_ACCOUNT_ID]
+            .iter()
+            .map(|account_id| env::sha256(account_id.as_bytes()))
+            .collect();
+        // three leaves, the odd one is paired with the hash of the other two
+        let branch = hash_pair(&leaves[0], &leaves[1]);
+        let root = root_of(&hash_pair(&branch, &leaves[2]));
+
+        let buyer_proof = vec![
+            Base64VecU8(leaves[1].clone()),
+            Base64VecU8(leaves[2].clone()),
+        ];
+        let buyer2_proof = vec![
+            Base64VecU8(leaves[0].clone()),
+            Base64VecU8(leaves[2].clone()),
+        ];
+        let bidder_proof = vec![Base64VecU8(branch.clone())];
+        assert!(verify_merkle_proof(
+            &root,
+            &account(BUYER_ACCOUNT_ID),
+            &buyer_proof
+        ));
+        assert!(verify_merkle_proof(
+            &root,
+            &account(BUYER2_ACCOUNT_ID),
+            &buyer2_proof
+        ));
+        assert!(verify_merkle_proof(
+            &root,
+            &account(BIDDER_ACCOUNT_ID),
+            &bidder_proof
+        ));
+
+        // someone else's proof or an account outside of the tree
+        assert!(!verify_merkle_proof(
+            &root,
+            &account(BUYER_ACCOUNT_ID),
+            &buyer2_proof
+        ));
+        assert!(!verify_merkle_proof(
+            &root,
+            &account(BIDDER2_ACCOUNT_ID),
+            &buyer_proof
+        ));
+        assert!(!verify_merkle_proof(
+            &root,
+            &account(BUYER_ACCOUNT_ID),
+            &vec![]
+        ));
+
+        let allowlist = PresaleAllowlist::MerkleRoot(root);
+        assert!(allowlist.contains(&account(BUYER_ACCOUNT_ID), &Some(buyer_proof)));
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Merkle proof is required for this phase"#)]
+    fn test_merkle_allowlist_requires_proof() {
+        set_context(BUYER_ACCOUNT_ID, NOW, 0);
+        let root = root_of(&env::sha256(BUYER_ACCOUNT_ID.as_bytes()));
+        PresaleAllowlist::MerkleRoot(root).contains(&account(BUYER_ACCOUNT_ID), &None);
+    }
+
+    #[test]
+    fn test_account_allowlist_allows_listed_accounts() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        let listing_id = add_listing_with_phase(
+            &mut marketplace,
+            None,
+            Some(vec![account(BUYER_ACCOUNT_ID)]),
+        );
+
+        buy_in_phase(&mut marketplace, BUYER_ACCOUNT_ID, 1);
+
+        let listing = marketplace.primary_listings_by_id.get(&listing_id).unwrap();
+        assert_eq!(listing.supply_pending, 1);
+    }
+
+    #[test]
+    #[should_panic(expected = r#"You are not allowed to buy during this presale phase"#)]
+    fn test_account_allowlist_rejects_other_accounts() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        add_listing_with_phase(
+            &mut marketplace,
+            None,
+            Some(vec![account(BUYER_ACCOUNT_ID)]),
+        );
+
+        buy_in_phase(&mut marketplace, BUYER2_ACCOUNT_ID, 1);
+    }
+
+    #[test]
+    fn test_extend_phase_allowlist() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        add_listing_with_phase(
+            &mut marketplace,
+            None,
+            Some(vec![account(BUYER_ACCOUNT_ID)]),
+        );
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        marketplace.primary_listing_extend_phase_allowlist(
+            account(NFT_CONTRACT_ID),
+            U64(0),
+            U64(0),
+            vec![account(BUYER2_ACCOUNT_ID)],
+        );
+
+        buy_in_phase(&mut marketplace, BUYER2_ACCOUNT_ID, 1);
+    }
+
+    /* phases */
+
+    #[test]
+    #[should_panic(
+        expected = r#"Price of 1000000000000000000000000 exceeds the maximum price of 500000000000000000000000"#
+    )]
+    fn test_phase_price_ends_with_phase() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        add_listing_with_phase(&mut marketplace, None, None);
+
+        // the public sale has started, the phase price no longer applies
+        set_context(BUYER_ACCOUNT_ID, 11 * HOUR_NANO, PRICE_YOCTO);
+        marketplace.primary_listing_buy(
+            account(NFT_CONTRACT_ID),
+            U64(0),
+            None,
+            None,
+            Some(U128(PHASE_PRICE_YOCTO)),
+            None,
+            None,
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Presale limit is 2 per account, you have already bought 2"#)]
+    fn test_phase_limit_counts_purchases_in_flight() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        add_listing_with_phase(&mut marketplace, Some(2), None);
+
+        // the mints of the first purchase are still in flight
+        buy_in_phase(&mut marketplace, BUYER_ACCOUNT_ID, 2);
+        buy_in_phase(&mut marketplace, BUYER_ACCOUNT_ID, 1);
+    }
+
+    #[test]
+    fn test_phase_limit_is_released_by_failed_mints() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        let listing_id = add_listing_with_phase(&mut marketplace, Some(2), None);
+
+        buy_in_phase(&mut marketplace, BUYER_ACCOUNT_ID, 2);
+        set_callback_context(
+            3 * HOUR_NANO,
+            vec![mint_result("0:1", 800), near_sdk::PromiseResult::Failed],
+        );
+        marketplace.internal_primary_listing_record_mints(
+            &listing_id,
+            0,
+            &account(SELLER_ACCOUNT_ID),
+            &account(BUYER_ACCOUNT_ID),
+            &account(BUYER_ACCOUNT_ID),
+            PHASE_PRICE_YOCTO,
+            2,
+            &["0:1".to_string()],
+            800,
+            Some(0),
+        );
+
+        let listing = marketplace.primary_listings_by_id.get(&listing_id).unwrap();
+        let phase = listing.phases.get(0).unwrap();
+        assert_eq!(
+            phase.purchases_by_account.get(&account(BUYER_ACCOUNT_ID)),
+            Some(1)
+        );
+        buy_in_phase(&mut marketplace, BUYER_ACCOUNT_ID, 1);
+    }
+
+    #[test]
+    fn test_conclude_clears_phases_and_purchases() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        let listing_id = add_listing_with_phase(&mut marketplace, Some(2), None);
+        buy_in_phase(&mut marketplace, BUYER_ACCOUNT_ID, 2);
+        let buyer_deposit = marketplace
+            .storage_deposits
+            .get(&account(BUYER_ACCOUNT_ID))
+            .unwrap();
+
+        set_context(SELLER_ACCOUNT_ID, 3 * HOUR_NANO, 0);
+        marketplace.primary_listing_conclude(account(NFT_CONTRACT_ID), 0);
+
+        // the buyer gets the storage of the purchase record back
+        assert!(
+            marketplace
+                .storage_deposits
+                .get(&account(BUYER_ACCOUNT_ID))
+                .unwrap()
+                > buyer_deposit
+        );
+        let listing_id_hash = hash_primary_listing_id(&listing_id, 0);
+        let mut phase_key = PrimaryListingStorageKey::Phases { listing_id_hash }
+            .try_to_vec()
+            .unwrap();
+        phase_key.extend_from_slice(&0u64.to_le_bytes());
+        assert!(!env::storage_has_key(&phase_key));
+        let phase_purchases: UnorderedMap<AccountId, u64> = UnorderedMap::new(
+            PrimaryListingStorageKey::PhasePurchases {
+                listing_id_hash,
+                phase_index: 0,
+            }
+            .try_to_vec()
+            .unwrap(),
+        );
+        assert!(phase_purchases.get(&account(BUYER_ACCOUNT_ID)).is_none());
+    }
+}
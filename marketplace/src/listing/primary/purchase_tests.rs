@@ -0,0 +1,243 @@
+#[cfg(test)]
+mod purchase_tests {
+    use super::super::PrimaryListingBuyerCallback;
+    use crate::{
+        listing::{primary::lib::PrimaryListingIdJson, receipt::PurchaseReceipt},
+        test_utils::*,
+        *,
+    };
+    use near_sdk::json_types::U64;
+    use near_sdk::PromiseResult;
+
+    fn listing_id_json() -> PrimaryListingIdJson {
+        PrimaryListingIdJson {
+            nft_contract_id: account(NFT_CONTRACT_ID),
+            collection_id: U64(0),
+        }
+    }
+
+    fn buy(marketplace: &mut MarketplaceContract, buyer_id: &str, quantity: u64) {
+        set_context(buyer_id, NOW, PRICE_YOCTO * quantity as u128);
+        marketplace.primary_listing_buy(
+            account(NFT_CONTRACT_ID),
+            U64(0),
+            Some(U64(quantity)),
+            None,
+            None,
+            None,
+            None,
+        );
+    }
+
+    fn complete(
+        marketplace: &mut MarketplaceContract,
+        buyer_id: &str,
+        quantity: u64,
+        promise_results: Vec<PromiseResult>,
+    ) -> PurchaseReceipt {
+        set_callback_context(NOW, promise_results);
+        marketplace.primary_listing_buy_now_mint_completion(
+            account(buyer_id),
+            account(buyer_id),
+            account(SELLER_ACCOUNT_ID),
+            PRICE_YOCTO * quantity as u128,
+            PRICE_YOCTO,
+            U64(quantity),
+            listing_id_json(),
+            U64(0),
+            None,
+        )
+    }
+
+    #[test]
+    fn test_buy_reserves_supply() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        let listing_id = add_primary_listing(&mut marketplace, 0, 3, Some(PRICE_YOCTO), None, None);
+
+        buy(&mut marketplace, BUYER_ACCOUNT_ID, 2);
+
+        let listing = marketplace.primary_listings_by_id.get(&listing_id).unwrap();
+        assert_eq!(listing.supply_left, 3);
+        assert_eq!(listing.supply_pending, 2);
+        assert_eq!(listing.supply_available(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Only 1 NFTs left"#)]
+    fn test_concurrent_buys_cannot_overshoot_supply() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        add_primary_listing(&mut marketplace, 0, 3, Some(PRICE_YOCTO), None, None);
+
+        // the mints of the first purchase are still in flight
+        buy(&mut marketplace, BUYER_ACCOUNT_ID, 2);
+        buy(&mut marketplace, BUYER2_ACCOUNT_ID, 2);
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Limit is 2 per account, you have already bought 2"#)]
+    fn test_concurrent_buys_cannot_overshoot_max_per_account() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        add_primary_listing(&mut marketplace, 0, 10, Some(PRICE_YOCTO), None, Some(2));
+
+        buy(&mut marketplace, BUYER_ACCOUNT_ID, 2);
+        buy(&mut marketplace, BUYER_ACCOUNT_ID, 1);
+    }
+
+    #[test]
+    fn test_max_per_account_is_per_buyer() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        let listing_id =
+            add_primary_listing(&mut marketplace, 0, 10, Some(PRICE_YOCTO), None, Some(2));
+
+        buy(&mut marketplace, BUYER_ACCOUNT_ID, 2);
+        buy(&mut marketplace, BUYER2_ACCOUNT_ID, 2);
+
+        let listing = marketplace.primary_listings_by_id.get(&listing_id).unwrap();
+        assert_eq!(listing.supply_pending, 4);
+        assert_eq!(
+            listing.purchases_by_account.get(&account(BUYER_ACCOUNT_ID)),
+            Some(2)
+        );
+        assert_eq!(
+            listing
+                .purchases_by_account
+                .get(&account(BUYER2_ACCOUNT_ID)),
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn test_mint_completion_records_mints() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        let listing_id =
+            add_primary_listing(&mut marketplace, 0, 3, Some(PRICE_YOCTO), None, Some(2));
+
+        buy(&mut marketplace, BUYER_ACCOUNT_ID, 2);
+        let receipt = complete(
+            &mut marketplace,
+            BUYER_ACCOUNT_ID,
+            2,
+            vec![mint_result("0:1", 800), mint_result("0:2", 800)],
+        );
+
+        assert_eq!(
+            receipt.token_ids,
+            vec!["0:1".to_string(), "0:2".to_string()]
+        );
+        assert_eq!(receipt.price_yocto.0, 2 * PRICE_YOCTO);
+        assert_eq!(receipt.refund_yocto.0, 0);
+        let listing = marketplace.primary_listings_by_id.get(&listing_id).unwrap();
+        assert_eq!(listing.supply_left, 1);
+        assert_eq!(listing.supply_pending, 0);
+        assert_eq!(
+            listing.purchases_by_account.get(&account(BUYER_ACCOUNT_ID)),
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn test_mint_completion_releases_failed_mints() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        let listing_id =
+            add_primary_listing(&mut marketplace, 0, 3, Some(PRICE_YOCTO), None, Some(2));
+
+        buy(&mut marketplace, BUYER_ACCOUNT_ID, 2);
+        let receipt = complete(
+            &mut marketplace,
+            BUYER_ACCOUNT_ID,
+            2,
+            vec![mint_result("0:1", 800), PromiseResult::Failed],
+        );
+
+        assert_eq!(receipt.token_ids, vec!["0:1".to_string()]);
+        assert_eq!(receipt.price_yocto.0, PRICE_YOCTO);
+        assert_eq!(receipt.refund_yocto.0, PRICE_YOCTO);
+        let listing = marketplace.primary_listings_by_id.get(&listing_id).unwrap();
+        assert_eq!(listing.supply_left, 2);
+        assert_eq!(listing.supply_pending, 0);
+        assert_eq!(
+            listing.purchases_by_account.get(&account(BUYER_ACCOUNT_ID)),
+            Some(1)
+        );
+
+        // the released purchase can be made again
+        buy(&mut marketplace, BUYER_ACCOUNT_ID, 1);
+    }
+
+    #[test]
+    fn test_mint_completion_tolerates_unexpected_mint_value() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        let listing_id = add_primary_listing(&mut marketplace, 0, 3, Some(PRICE_YOCTO), None, None);
+
+        buy(&mut marketplace, BUYER_ACCOUNT_ID, 1);
+        let receipt = complete(
+            &mut marketplace,
+            BUYER_ACCOUNT_ID,
+            1,
+            vec![PromiseResult::Successful(b"\"0:1\"".to_vec())],
+        );
+
+        assert!(receipt.token_ids.is_empty());
+        assert_eq!(receipt.refund_yocto.0, PRICE_YOCTO);
+        let listing = marketplace.primary_listings_by_id.get(&listing_id).unwrap();
+        assert_eq!(listing.supply_left, 3);
+        assert_eq!(listing.supply_pending, 0);
+    }
+
+    #[test]
+    fn test_mint_completion_tolerates_removed_listing() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        let listing_id = add_primary_listing(&mut marketplace, 0, 3, Some(PRICE_YOCTO), None, None);
+
+        buy(&mut marketplace, BUYER_ACCOUNT_ID, 1);
+        marketplace.internal_remove_primary_listing(&listing_id);
+        let receipt = complete(
+            &mut marketplace,
+            BUYER_ACCOUNT_ID,
+            1,
+            vec![mint_result("0:1", 800)],
+        );
+
+        assert_eq!(receipt.token_ids, vec!["0:1".to_string()]);
+        assert_eq!(receipt.price_yocto.0, PRICE_YOCTO);
+        assert!(marketplace
+            .primary_listings_by_id
+            .get(&listing_id)
+            .is_none());
+    }
+
+    #[test]
+    fn test_mint_completion_tolerates_sold_out_listing() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        let listing_id = add_primary_listing(&mut marketplace, 0, 1, Some(PRICE_YOCTO), None, None);
+
+        buy(&mut marketplace, BUYER_ACCOUNT_ID, 1);
+        // supply drained by other means while the mint was in flight
+        let mut listing = marketplace.primary_listings_by_id.get(&listing_id).unwrap();
+        listing.supply_left = 0;
+        listing.supply_pending = 0;
+        marketplace
+            .primary_listings_by_id
+            .insert(&listing_id, &listing);
+
+        complete(
+            &mut marketplace,
+            BUYER_ACCOUNT_ID,
+            1,
+            vec![mint_result("0:1", 800)],
+        );
+
+        let listing = marketplace.primary_listings_by_id.get(&listing_id).unwrap();
+        assert_eq!(listing.supply_left, 0);
+        assert_eq!(listing.supply_pending, 0);
+    }
+}
@@ -0,0 +1,192 @@
+use crate::{
+    external::{NftMetadata, NftMutableMetadata},
+    listing::{
+        bid::BidBook,
+        date::DateInput,
+        primary::{internal::hash_primary_listing_id, lib::PrimaryListingStorageKey},
+        rules::JsonListingRules,
+        status::ListingStatus,
+    },
+    *,
+};
+use near_sdk::{
+    collections::Vector,
+    json_types::{U128, U64},
+};
+
+// NFT collection made by primary_listing_add; its supply can be sold over several rounds, each
+// being a primary listing of its own; rounds don't overlap, the previous one must be concluded
+// before the next is added
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct PrimaryCollection {
+    pub seller_id: AccountId,
+    pub nft_metadata: NftMetadata,
+    pub nft_mutable_metadata: NftMutableMetadata, // follows the updates of the listings
+    pub max_supply: u64,
+    pub supply_minted: u64,
+    pub supply_pending: u64, // reserved by purchases whose mints are in flight, whatever the round
+    pub next_round: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct JsonPrimaryCollection {
+    pub nft_contract_id: AccountId,
+    pub collection_id: U64,
+    pub seller_id: AccountId,
+    pub max_supply: U64,
+    pub supply_minted: U64,
+    pub supply_pending: U64,
+    pub rounds: U64,
+    pub has_open_round: bool, // a listing of the collection exists
+}
+
+#[near_bindgen]
+impl MarketplaceContract {
+    // opens the next sale round of a collection whose previous listing has been concluded;
+    // the round sells supply_total more tokens as long as the collection's max supply allows
+    // returns the round number, the first listing of a collection being round 0
+    pub fn primary_listing_add_round(
+        &mut self,
+        nft_contract_id: AccountId,
+        collection_id: U64,
+        supply_total: U64,
+        price_yocto: Option<U128>,
+        min_bid_yocto: Option<U128>,
+        start_date: Option<DateInput>, // if missing, it'll start accepting bids when this transaction is mined
+        end_date: Option<DateInput>,   // a duration counts from the start date
+        max_per_account: Option<U64>, // if missing, there's no limit on how many NFTs one account can buy
+        rules: Option<JsonListingRules>, // if missing, the marketplace defaults apply
+    ) -> U64 {
+        let price_yocto = price_yocto.map(|p| p.0);
+        let min_bid_yocto = min_bid_yocto.map(|b| b.0);
+        let collection_key = (nft_contract_id.clone(), collection_id.0);
+        let listing_id = PrimaryListingId {
+            nft_contract_id,
+            collection_id: collection_id.0,
+        };
+
+        let mut collection = self
+            .primary_collections
+            .get(&collection_key)
+            .expect("Could not find this collection");
+        let seller_id = env::predecessor_account_id();
+        assert!(
+            seller_id == collection.seller_id,
+            "Only the seller can add sale rounds"
+        );
+        assert!(
+            self.primary_listings_by_id.get(&listing_id).is_none(),
+            "The previous round must be concluded first"
+        );
+
+        // mints of the previous round may still be in flight
+        let supply_available = collection
+            .max_supply
+            .saturating_sub(collection.supply_minted + collection.supply_pending);
+        assert!(
+            supply_total.0 > 0 && supply_total.0 <= supply_available,
+            "Round supply must be between 1 and {}",
+            supply_available
+        );
+
+        if let Some(max_per_account) = max_per_account {
+            assert!(max_per_account.0 > 0, "Per-account limit must be positive");
+        }
+
+        // the rules are validated against the marketplace bounds, defaults apply if missing
+        let rules = self.config.listing_rules(rules);
+        if let Some(price_yocto) = price_yocto {
+            rules.assert_price(price_yocto);
+        }
+        if let Some(min_bid_yocto) = min_bid_yocto {
+            rules.assert_min_bid(min_bid_yocto);
+        }
+
+        let (start_timestamp, end_timestamp) = self.internal_primary_listing_timestamps(
+            min_bid_yocto.is_some(),
+            start_date,
+            end_date,
+        );
+
+        let round = collection.next_round;
+        let listing_id_hash = hash_primary_listing_id(&listing_id, round);
+        let mut listing = PrimaryListing {
+            id: listing_id,
+            seller_id: seller_id.clone(),
+            nft_metadata: collection.nft_metadata.clone(),
+            nft_mutable_metadata: collection.nft_mutable_metadata.clone(),
+            supply_total: supply_total.0,
+            price_yocto,
+            min_bid_yocto,
+            start_timestamp,
+            end_timestamp,
+            status: ListingStatus::Unstarted,
+            supply_left: supply_total.0,
+            supply_pending: 0,
+            bids: BidBook::new(
+                PrimaryListingStorageKey::Bids { listing_id_hash }
+                    .try_to_vec()
+                    .unwrap(),
+                PrimaryListingStorageKey::BidKeys { listing_id_hash }
+                    .try_to_vec()
+                    .unwrap(),
+            ),
+            next_bid_id: 0,
+            version: 0,
+            phases: Vector::new(
+                PrimaryListingStorageKey::Phases { listing_id_hash }
+                    .try_to_vec()
+                    .unwrap(),
+            ),
+            max_per_account: max_per_account.map(|m| m.0),
+            purchases_by_account: UnorderedMap::new(
+                PrimaryListingStorageKey::Purchases { listing_id_hash }
+                    .try_to_vec()
+                    .unwrap(),
+            ),
+            seq: 0, // assigned by internal_add_primary_listing
+            rules,
+            round,
+        };
+
+        let storage_before = env::storage_usage();
+        self.internal_add_primary_listing(&mut listing);
+        collection.next_round += 1;
+        self.primary_collections.insert(&collection_key, &collection);
+        let storage_after = env::storage_usage();
+        self.internal_charge_account_storage(&seller_id, storage_before, storage_after);
+
+        U64(round)
+    }
+
+    pub fn primary_collection(
+        &self,
+        nft_contract_id: AccountId,
+        collection_id: U64,
+    ) -> Option<JsonPrimaryCollection> {
+        let collection_key = (nft_contract_id, collection_id.0);
+        self.primary_collections
+            .get(&collection_key)
+            .map(|collection| {
+                let listing_id = PrimaryListingId {
+                    nft_contract_id: collection_key.0.clone(),
+                    collection_id: collection_id.0,
+                };
+                JsonPrimaryCollection {
+                    nft_contract_id: collection_key.0.clone(),
+                    collection_id,
+                    seller_id: collection.seller_id,
+                    max_supply: U64(collection.max_supply),
+                    supply_minted: U64(collection.supply_minted),
+                    supply_pending: U64(collection.supply_pending),
+                    rounds: U64(collection.next_round),
+                    has_open_round: self.primary_listings_by_id.get(&listing_id).is_some(),
+                }
+            })
+    }
+}
+
+#[cfg(test)]
+#[path = "round_tests.rs"]
+mod round_tests;
@@ -0,0 +1,185 @@
+#[cfg(test)]
+mod round_tests {
+    use super::super::PrimaryCollection;
+    use crate::{
+        listing::primary::{buyer::PrimaryListingBuyerCallback, lib::PrimaryListingIdJson},
+        test_utils::*,
+        *,
+    };
+    use near_sdk::json_types::{U128, U64};
+    use near_sdk::PromiseResult;
+
+    // round 0 listing of a collection whose max supply is max_supply
+    fn add_collection(
+        marketplace: &mut MarketplaceContract,
+        max_supply: u64,
+        supply_total: u64,
+    ) -> PrimaryListingId {
+        let listing_id =
+            add_primary_listing(marketplace, 0, supply_total, Some(PRICE_YOCTO), None, None);
+        let listing = marketplace.primary_listings_by_id.get(&listing_id).unwrap();
+        marketplace.primary_collections.insert(
+            &(account(NFT_CONTRACT_ID), 0),
+            &PrimaryCollection {
+                seller_id: account(SELLER_ACCOUNT_ID),
+                nft_metadata: listing.nft_metadata,
+                nft_mutable_metadata: listing.nft_mutable_metadata,
+                max_supply,
+                supply_minted: 0,
+                supply_pending: 0,
+                next_round: 1,
+            },
+        );
+        listing_id
+    }
+
+    fn buy(marketplace: &mut MarketplaceContract, quantity: u64) {
+        set_context(BUYER_ACCOUNT_ID, NOW, PRICE_YOCTO * quantity as u128);
+        marketplace.primary_listing_buy(
+            account(NFT_CONTRACT_ID),
+            U64(0),
+            Some(U64(quantity)),
+            None,
+            None,
+            None,
+            None,
+        );
+    }
+
+    fn conclude(marketplace: &mut MarketplaceContract) {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        marketplace.primary_listing_conclude(account(NFT_CONTRACT_ID), 0);
+    }
+
+    fn add_round(marketplace: &mut MarketplaceContract, supply_total: u64) -> U64 {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        marketplace.primary_listing_add_round(
+            account(NFT_CONTRACT_ID),
+            U64(0),
+            U64(supply_total),
+            Some(U128(PRICE_YOCTO)),
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+    }
+
+    fn complete_round_0(
+        marketplace: &mut MarketplaceContract,
+        quantity: u64,
+        promise_results: Vec<PromiseResult>,
+    ) {
+        set_callback_context(NOW, promise_results);
+        marketplace.primary_listing_buy_now_mint_completion(
+            account(BUYER_ACCOUNT_ID),
+            account(BUYER_ACCOUNT_ID),
+            account(SELLER_ACCOUNT_ID),
+            PRICE_YOCTO * quantity as u128,
+            PRICE_YOCTO,
+            U64(quantity),
+            PrimaryListingIdJson {
+                nft_contract_id: account(NFT_CONTRACT_ID),
+                collection_id: U64(0),
+            },
+            U64(0),
+            None,
+        );
+    }
+
+    #[test]
+    fn test_add_round() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        let listing_id = add_collection(&mut marketplace, 5, 2);
+        conclude(&mut marketplace);
+
+        assert_eq!(add_round(&mut marketplace, 5), U64(1));
+
+        let listing = marketplace.primary_listings_by_id.get(&listing_id).unwrap();
+        assert_eq!(listing.round, 1);
+        assert_eq!(listing.supply_total, 5);
+        let collection = marketplace
+            .primary_collection(account(NFT_CONTRACT_ID), U64(0))
+            .unwrap();
+        assert_eq!(collection.rounds, U64(2));
+        assert!(collection.has_open_round);
+    }
+
+    #[test]
+    #[should_panic(expected = r#"The previous round must be concluded first"#)]
+    fn test_add_round_while_previous_is_open() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        add_collection(&mut marketplace, 5, 2);
+
+        add_round(&mut marketplace, 2);
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Round supply must be between 1 and 1"#)]
+    fn test_add_round_counts_mints_in_flight() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        add_collection(&mut marketplace, 3, 3);
+
+        // the mints of round 0 are still in flight when it's concluded
+        buy(&mut marketplace, 2);
+        conclude(&mut marketplace);
+
+        add_round(&mut marketplace, 2);
+    }
+
+    #[test]
+    fn test_previous_round_mint_leaves_next_round_alone() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        let listing_id = add_collection(&mut marketplace, 5, 2);
+        buy(&mut marketplace, 2);
+        conclude(&mut marketplace);
+        add_round(&mut marketplace, 3);
+
+        complete_round_0(
+            &mut marketplace,
+            2,
+            vec![mint_result("0:1", 800), PromiseResult::Failed],
+        );
+
+        // round 1 listing shares the listing id but none of the round 0 purchase
+        let listing = marketplace.primary_listings_by_id.get(&listing_id).unwrap();
+        assert_eq!(listing.round, 1);
+        assert_eq!(listing.supply_left, 3);
+        assert_eq!(listing.supply_pending, 0);
+        // the collection counts the mint whatever the round
+        let collection = marketplace
+            .primary_collection(account(NFT_CONTRACT_ID), U64(0))
+            .unwrap();
+        assert_eq!(collection.supply_minted, U64(1));
+        assert_eq!(collection.supply_pending, U64(0));
+    }
+
+    #[test]
+    fn test_current_round_mint_updates_collection() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        add_collection(&mut marketplace, 5, 2);
+        buy(&mut marketplace, 2);
+        let collection = marketplace
+            .primary_collection(account(NFT_CONTRACT_ID), U64(0))
+            .unwrap();
+        assert_eq!(collection.supply_pending, U64(2));
+
+        complete_round_0(
+            &mut marketplace,
+            2,
+            vec![mint_result("0:1", 800), mint_result("0:2", 800)],
+        );
+
+        let collection = marketplace
+            .primary_collection(account(NFT_CONTRACT_ID), U64(0))
+            .unwrap();
+        assert_eq!(collection.supply_minted, U64(2));
+        assert_eq!(collection.supply_pending, U64(0));
+    }
+}
@@ -2,22 +2,35 @@ use crate::{
     constants::*,
     external::{nft_contract, NftMetadata, NftMutableMetadata},
     listing::{
-        constants::*,
-        primary::{config::*, internal::hash_primary_listing_id, lib::PrimaryListingStorageKey},
+        bid::{Bid, BidBook},
+        date::DateInput,
+        primary::{
+            buyer::NFT_MINT_GAS,
+            config::*,
+            enumeration::JsonPrimaryListingBid,
+            internal::hash_primary_listing_id,
+            lib::{PrimaryListingIdJson, PrimaryListingStorageKey},
+            phase::{PresaleAllowlist, PresalePhase},
+            round::PrimaryCollection,
+        },
+        id::ListingId,
+        receipt::PurchaseReceipt,
+        rules::{JsonListingRules, ListingRules},
         status::ListingStatus,
+        terms::{ListingTerms, ListingTermsUpdate},
     },
     *,
 };
-use chrono::DateTime;
 use near_sdk::{
     collections::Vector,
     json_types::{U128, U64},
-    AccountId, PromiseResult,
+    AccountId, PromiseOrValue, PromiseResult,
 };
 use url::Url;
 
-const NFT_MAKE_COLLECTION_GAS: Gas = Gas(5_000_000_000_000); // highest measured 3_920_035_683_889
-const NFT_MAKE_COLLECTION_COMPLETION_GAS: Gas = Gas(6_000_000_000_000); // highest measured 5_089_357_803_858
+pub(crate) const NFT_MAKE_COLLECTION_GAS: Gas = Gas(5_000_000_000_000); // highest measured 3_920_035_683_889
+pub(crate) const NFT_MAKE_COLLECTION_COMPLETION_GAS: Gas = Gas(6_000_000_000_000); // highest measured 5_089_357_803_858
+const PRIMARY_LISTING_ACCEPT_BID_COMPLETION_GAS: Gas = Gas(5_000_000_000_000); // per accepted bid, TODO: measure
 
 #[cfg(test)]
 #[path = "seller_tests.rs"]
@@ -33,8 +46,11 @@ impl MarketplaceContract {
         supply_total: U64,
         price_yocto: Option<U128>,
         min_bid_yocto: Option<U128>,
-        start_date: Option<String>, // if missing, it'll start accepting bids when this transaction is mined
-        end_date: Option<String>,
+        start_date: Option<DateInput>, // if missing, it'll start accepting bids when this transaction is mined
+        end_date: Option<DateInput>,   // a duration counts from the start date
+        max_per_account: Option<U64>, // if missing, there's no limit on how many NFTs one account can buy
+        rules: Option<JsonListingRules>, // if missing, the marketplace defaults apply
+        collection_supply: Option<U64>, // if missing, it equals supply_total; the rest can be sold in later rounds
     ) -> Promise {
         let price_yocto = price_yocto.map(|p| p.0);
         let min_bid_yocto = min_bid_yocto.map(|b| b.0);
@@ -45,9 +61,9 @@ impl MarketplaceContract {
         let storage_byte_cost = env::storage_byte_cost();
         let current_deposit: Balance = self.storage_deposits.get(&seller_id).unwrap_or(0);
         let marketplace_worst_case_storage_cost =
-            PRIMARY_LISTING_ADD_STORAGE_MAX as Balance * storage_byte_cost;
+            self.config.storage_limits.primary_listing_add_storage_max as Balance * storage_byte_cost;
         let nft_worst_case_storage_cost =
-            NFT_MAKE_COLLECTION_STORAGE_MAX as Balance * storage_byte_cost;
+            self.config.storage_limits.nft_make_collection_storage_max as Balance * storage_byte_cost;
         let worst_case_storage_cost =
             marketplace_worst_case_storage_cost + nft_worst_case_storage_cost;
         assert!(
@@ -57,113 +73,55 @@ impl MarketplaceContract {
         );
 
         // Is listing length ok?
+        let title_len_max = self.config.listing_limits.title_len_max;
         assert!(
-            title.len() <= MAX_LISTING_TITLE_LEN,
+            title.len() <= title_len_max as usize,
             "Title length cannot exceed {} characters",
-            MAX_LISTING_TITLE_LEN
+            title_len_max
         );
 
         // Is URL valid?
         assert!(Url::parse(&image_url).is_ok(), "NFT media URL is invalid");
 
         // Is max_supply within limit?
+        let total_supply_max = self.config.listing_limits.total_supply_max;
         assert!(
-            supply_total.0 > 0 && supply_total.0 <= TOTAL_SUPPLY_MAX,
+            supply_total.0 > 0 && supply_total.0 <= total_supply_max,
             "Max NFT supply must be between 1 and {}.",
-            TOTAL_SUPPLY_MAX
+            total_supply_max
         );
 
+        // Is collection supply within limit?
+        let collection_supply = collection_supply.unwrap_or(supply_total);
+        assert!(
+            collection_supply.0 >= supply_total.0 && collection_supply.0 <= total_supply_max,
+            "Collection supply must be between {} and {}.",
+            supply_total.0,
+            total_supply_max
+        );
+
+        if let Some(max_per_account) = max_per_account {
+            assert!(max_per_account.0 > 0, "Per-account limit must be positive");
+        }
+
+        // the rules are validated against the marketplace bounds, defaults apply if missing
+        let rules = self.config.listing_rules(rules);
+
         // Is the price ok?
         if let Some(price_yocto) = price_yocto {
-            assert!(
-                price_yocto >= MIN_PRICE_YOCTO,
-                "Price cannot be lower than {} yoctoNear",
-                MIN_PRICE_YOCTO
-            );
-
-            // Is the price multiple of marketplace price unit?
-            assert!(
-                price_yocto % PRICE_STEP_YOCTO == 0,
-                "Price must be integer multiple of {} yoctoNear",
-                PRICE_STEP_YOCTO
-            );
+            rules.assert_price(price_yocto);
         }
 
         // Is min bid ok?
         if let Some(min_bid_yocto) = min_bid_yocto {
-            assert!(
-                min_bid_yocto >= MIN_BID_YOCTO,
-                "Bid cannot be lower than {} yoctoNear",
-                MIN_BID_YOCTO
-            );
-
-            // Is the price multiple of marketplace price unit?
-            assert!(
-                min_bid_yocto % BID_STEP_YOCTO == 0,
-                "Bid must be integer multiple of {} yoctoNear",
-                BID_STEP_YOCTO
-            );
+            rules.assert_min_bid(min_bid_yocto);
         }
 
-        // the logic here is:
-        // - if start timestamp is missing, current block timestamp is used
-        // - for bid-accepting listings the end date must be set and the duration cannot exceed
-        //   max allowed (this is to prevent keeping bid deposits indefinitely)
-        // - for buy-now-only listings, there's no upper limit on duration
-
-        let is_accepting_bids = min_bid_yocto.is_some();
-        let current_block_timestamp = env::block_timestamp() as i64;
-
-        let start_timestamp = if let Some(start_date_str) = start_date {
-            let start_datetime = DateTime::parse_from_rfc3339(&start_date_str).expect(
-                "Wrong date format. Must be ISO8601/RFC3339 (f.ex. 2022-01-22T11:20:55+08:00)",
-            );
-            let start_timestamp = start_datetime.timestamp_nanos();
-            assert!(
-                start_timestamp >= current_block_timestamp,
-                "Start date into the past"
-            );
-            start_timestamp
-        } else {
-            current_block_timestamp
-        };
-
-        let end_timestamp: Option<i64> = if let Some(end_date_str) = end_date {
-            let end_datetime = DateTime::parse_from_rfc3339(&end_date_str).expect(
-                "Wrong date format. Must be ISO8601/RFC3339 (f.ex. 2022-01-22T11:20:55+08:00)",
-            );
-            let end_timestamp = end_datetime.timestamp_nanos();
-            let current_block_timestamp = env::block_timestamp() as i64;
-            assert!(
-                end_timestamp >= current_block_timestamp,
-                "End date into the past"
-            );
-            Some(end_timestamp)
-            // let end_datetime_str = (Utc.ymd(1970, 1, 1).and_hms(0, 0, 0) + Duration::nanoseconds(end_timestamp_nanos)).to_rfc3339();
-            // env::log_str(&end_datetime_str);
-        } else {
-            None
-        };
-
-        if let Some(end_timestamp) = end_timestamp {
-            // end timestamp set
-            let duration = end_timestamp - start_timestamp;
-            assert!(
-                duration >= PRIMARY_LISTING_MIN_DURATION_NANO,
-                "Listing duration too short"
-            );
-            if is_accepting_bids {
-                assert!(
-                    duration <= PRIMARY_LISTING_MAX_DURATION_NANO,
-                    "Listing duration too long"
-                );
-            }
-        } else {
-            assert!(
-                !is_accepting_bids,
-                "End date must be set for bid-accepting listing"
-            );
-        }
+        let (start_timestamp, end_timestamp) = self.internal_primary_listing_timestamps(
+            min_bid_yocto.is_some(),
+            start_date,
+            end_date,
+        );
 
         let nft_contract_id = self.internal_nft_shared_contract_id();
         let nft_metadata = NftMetadata::new(&title, &image_url);
@@ -174,13 +132,14 @@ impl MarketplaceContract {
         nft_contract::make_collection(
             nft_metadata.clone(),
             nft_mutable_metadata.clone(),
-            supply_total,
+            collection_supply,
             nft_contract_id.clone(),
             nft_worst_case_storage_cost,
             NFT_MAKE_COLLECTION_GAS,
         )
         .then(
             ext_self_nft::primary_listing_add_make_collection_completion(
+                seller_id,
                 nft_contract_id,
                 nft_metadata,
                 nft_mutable_metadata,
@@ -189,6 +148,9 @@ impl MarketplaceContract {
                 min_bid_yocto.map(|b| U128(b)),
                 start_timestamp,
                 end_timestamp,
+                max_per_account,
+                rules.to_json(),
+                collection_supply,
                 env::current_account_id(),
                 NO_DEPOSIT,
                 NFT_MAKE_COLLECTION_COMPLETION_GAS,
@@ -196,12 +158,314 @@ impl MarketplaceContract {
         )
     }
 
+    // modifies a live listing; only the fields that are passed in are changed
+    // once bids have been placed the seller can neither lower the minimum bid nor
+    // shorten the listing so that the bidders are not put at a disadvantage
+    // returns the new listing version
+    pub fn primary_listing_update(
+        &mut self,
+        nft_contract_id: AccountId,
+        collection_id: U64,
+        price_yocto: Option<U128>,
+        min_bid_yocto: Option<U128>,
+        end_date: Option<DateInput>, // a duration counts from the listing start
+        nft_mutable_metadata: Option<NftMutableMetadata>,
+        max_per_account: Option<U64>,
+    ) -> U64 {
+        let listing_id = PrimaryListingId {
+            nft_contract_id,
+            collection_id: collection_id.0,
+        };
+
+        // get the listing
+        let mut listing = self
+            .primary_listings_by_id
+            .get(&listing_id)
+            .expect("Could not find NFT listing");
+        listing.update_status();
+
+        // make sure it's the seller who's calling this
+        let seller_id = env::predecessor_account_id();
+        assert!(
+            seller_id == listing.seller_id,
+            "Only the seller can update the listing"
+        );
+
+        assert!(
+            listing.status != ListingStatus::Ended,
+            "This listing is {}",
+            listing.status.as_str()
+        );
+
+        // needed to update the listing index
+        let old_price_yocto = listing.price_yocto;
+        let old_end_timestamp = listing.end_timestamp;
+
+        let mut terms = ListingTerms {
+            price_yocto: listing.price_yocto,
+            min_bid_yocto: listing.min_bid_yocto,
+            start_timestamp: listing.start_timestamp,
+            end_timestamp: listing.end_timestamp,
+        };
+        terms.update(
+            ListingTermsUpdate {
+                price_yocto: price_yocto.map(|p| p.0),
+                min_bid_yocto: min_bid_yocto.map(|b| b.0),
+                end_date,
+            },
+            &listing.rules,
+            &listing.bids,
+            self.config.listing_limits.primary_listing_min_duration_nano,
+            self.config.listing_limits.primary_listing_max_duration_nano,
+        );
+        listing.price_yocto = terms.price_yocto;
+        listing.min_bid_yocto = terms.min_bid_yocto;
+        listing.end_timestamp = terms.end_timestamp;
+
+        if let Some(nft_mutable_metadata) = nft_mutable_metadata {
+            listing.nft_mutable_metadata = nft_mutable_metadata;
+        }
+
+        // purchases are only counted while the limit is set, so it can't be introduced once
+        // the sale has started
+        if let Some(max_per_account) = max_per_account {
+            assert!(max_per_account.0 > 0, "Per-account limit must be positive");
+            assert!(
+                listing.max_per_account.is_some()
+                    || (listing.supply_left == listing.supply_total && listing.supply_pending == 0),
+                "Cannot introduce per-account limit once NFTs have been sold"
+            );
+            listing.max_per_account = Some(max_per_account.0);
+        }
+
+        listing.version += 1;
+
+        let storage_before = env::storage_usage();
+        self.primary_listings_by_id.insert(&listing_id, &listing);
+        self.primary_listing_index
+            .remove(listing.seq, old_price_yocto, old_end_timestamp);
+        self.primary_listing_index.insert(
+            listing.seq,
+            listing.price_yocto,
+            listing.end_timestamp,
+            &listing_id,
+        );
+        self.internal_remove_listing_from_floor(
+            &listing_id.nft_contract_id,
+            Some(listing_id.collection_id),
+            old_price_yocto,
+            listing.seq,
+        );
+        self.internal_add_listing_to_floor(
+            &ListingId::Primary(listing_id.clone()),
+            &listing_id.nft_contract_id,
+            Some(listing_id.collection_id),
+            listing.price_yocto,
+            listing.seq,
+        );
+        // later rounds of the collection start from the latest metadata
+        let collection_key = (listing_id.nft_contract_id.clone(), listing_id.collection_id);
+        if let Some(mut collection) = self.primary_collections.get(&collection_key) {
+            if collection.nft_mutable_metadata != listing.nft_mutable_metadata {
+                collection.nft_mutable_metadata = listing.nft_mutable_metadata.clone();
+                self.primary_collections.insert(&collection_key, &collection);
+            }
+        }
+        let storage_after = env::storage_usage();
+
+        // mutable metadata may have changed size, settle the difference with seller's deposit
+        self.internal_charge_account_storage(&seller_id, storage_before, storage_after);
+
+        U64(listing.version)
+    }
+
+    // appends a presale phase to a listing that hasn't started yet
+    // the allowlist is either a set of accounts (can be extended later), a merkle root
+    // or, if neither is provided, everyone can buy at the phase price
+    // returns the index of the new phase
+    pub fn primary_listing_add_phase(
+        &mut self,
+        nft_contract_id: AccountId,
+        collection_id: U64,
+        start_date: DateInput, // a duration counts from now
+        price_yocto: U128,
+        max_per_account: Option<U64>,
+        allowlist_account_ids: Option<Vec<AccountId>>,
+        allowlist_merkle_root: Option<Base64VecU8>,
+    ) -> U64 {
+        let price_yocto = price_yocto.0;
+        let listing_id = PrimaryListingId {
+            nft_contract_id,
+            collection_id: collection_id.0,
+        };
+
+        let mut listing = self
+            .primary_listings_by_id
+            .get(&listing_id)
+            .expect("Could not find NFT listing");
+        listing.update_status();
+
+        let seller_id = env::predecessor_account_id();
+        assert!(
+            seller_id == listing.seller_id,
+            "Only the seller can add presale phases"
+        );
+
+        assert!(
+            listing.status == ListingStatus::Unstarted,
+            "This listing is {}",
+            listing.status.as_str()
+        );
+
+        // Is the price ok?
+        listing.rules.assert_price(price_yocto);
+
+        if let Some(max_per_account) = max_per_account {
+            assert!(max_per_account.0 > 0, "Per-account limit must be positive");
+        }
+
+        // phases are appended in chronological order and must all precede the public sale
+        let start_timestamp = start_date.to_timestamp(env::block_timestamp() as i64);
+        assert!(
+            start_timestamp >= env::block_timestamp() as i64,
+            "Start date into the past"
+        );
+        assert!(
+            start_timestamp < listing.start_timestamp,
+            "Presale phase must start before the listing start date"
+        );
+        let num_phases = listing.phases.len();
+        if num_phases > 0 {
+            let last_phase = listing.phases.get(num_phases - 1).unwrap();
+            assert!(
+                start_timestamp > last_phase.start_timestamp,
+                "Presale phase must start after the previous phase"
+            );
+        }
+
+        let listing_id_hash = hash_primary_listing_id(&listing_id, listing.round);
+        let storage_before = env::storage_usage();
+
+        let allowlist = match (allowlist_account_ids, allowlist_merkle_root) {
+            (Some(_), Some(_)) => {
+                panic!("Allowlist can either be a list of accounts or a merkle root, not both")
+            }
+            (Some(account_ids), None) => {
+                let mut allowlist = UnorderedSet::new(
+                    PrimaryListingStorageKey::PhaseAllowlist {
+                        listing_id_hash,
+                        phase_index: num_phases,
+                    }
+                    .try_to_vec()
+                    .unwrap(),
+                );
+                allowlist.extend(account_ids);
+                PresaleAllowlist::Accounts(allowlist)
+            }
+            (None, Some(merkle_root)) => {
+                let mut root = CryptoHash::default();
+                assert!(
+                    merkle_root.0.len() == root.len(),
+                    "Merkle root must be a sha256 hash"
+                );
+                root.copy_from_slice(&merkle_root.0);
+                PresaleAllowlist::MerkleRoot(root)
+            }
+            (None, None) => PresaleAllowlist::Public,
+        };
+
+        let phase = PresalePhase {
+            start_timestamp,
+            price_yocto,
+            max_per_account: max_per_account.map(|m| m.0),
+            allowlist,
+            purchases_by_account: UnorderedMap::new(
+                PrimaryListingStorageKey::PhasePurchases {
+                    listing_id_hash,
+                    phase_index: num_phases,
+                }
+                .try_to_vec()
+                .unwrap(),
+            ),
+        };
+        listing.phases.push(&phase);
+        listing.version += 1;
+        self.primary_listings_by_id.insert(&listing_id, &listing);
+
+        let storage_after = env::storage_usage();
+        self.internal_charge_account_storage(&seller_id, storage_before, storage_after);
+
+        U64(num_phases)
+    }
+
+    // adds accounts to an account-set allowlist of a presale phase
+    pub fn primary_listing_extend_phase_allowlist(
+        &mut self,
+        nft_contract_id: AccountId,
+        collection_id: U64,
+        phase_index: U64,
+        account_ids: Vec<AccountId>,
+    ) {
+        let listing_id = PrimaryListingId {
+            nft_contract_id,
+            collection_id: collection_id.0,
+        };
+
+        let mut listing = self
+            .primary_listings_by_id
+            .get(&listing_id)
+            .expect("Could not find NFT listing");
+        listing.update_status();
+
+        let seller_id = env::predecessor_account_id();
+        assert!(
+            seller_id == listing.seller_id,
+            "Only the seller can modify presale phases"
+        );
+
+        assert!(
+            listing.status == ListingStatus::Unstarted,
+            "This listing is {}",
+            listing.status.as_str()
+        );
+
+        let storage_before = env::storage_usage();
+
+        let mut phase = listing
+            .phases
+            .get(phase_index.0)
+            .expect("Could not find presale phase");
+        match &mut phase.allowlist {
+            PresaleAllowlist::Accounts(allowlist) => allowlist.extend(account_ids),
+            _ => panic!("This presale phase does not use an account allowlist"),
+        }
+        listing.phases.replace(phase_index.0, &phase);
+        listing.version += 1;
+        self.primary_listings_by_id.insert(&listing_id, &listing);
+
+        let storage_after = env::storage_usage();
+        self.internal_charge_account_storage(&seller_id, storage_before, storage_after);
+    }
+
+    // sells one NFT to each of the best accepted_bids_count bids at its effective amount;
+    // the unused escrow of proxy bids goes back right away, the mint completion pays the
+    // seller and refunds the bidders whose NFT couldn't be minted
+    // a bidder who reached the per-account limit or whose storage deposit doesn't cover the mint
+    // can't take the token, such bids are refunded and the next best ones accepted instead;
+    // if none is left there's nothing to mint and no receipts are returned
     pub fn primary_listing_accept_bids(
         &mut self,
         nft_contract_id: AccountId,
         collection_id: U64,
         accepted_bids_count: U64,
-    ) {
+    ) -> PromiseOrValue<Vec<PurchaseReceipt>> {
+        let accepted_bids_count = accepted_bids_count.0;
+        assert!(
+            accepted_bids_count > 0 && accepted_bids_count <= PRIMARY_LISTING_BUY_QUANTITY_MAX,
+            "Bid count must be between 1 and {}",
+            PRIMARY_LISTING_BUY_QUANTITY_MAX
+        );
+
         let listing_id = PrimaryListingId {
             nft_contract_id,
             collection_id: collection_id.0,
@@ -212,6 +476,7 @@ impl MarketplaceContract {
             .primary_listings_by_id
             .get(&listing_id)
             .expect("Could not find NFT listing");
+        listing.update_status();
 
         // make sure it's the seller who's calling this
         assert!(
@@ -222,59 +487,125 @@ impl MarketplaceContract {
         // make sure there's enough bids
         let num_bids = listing.bids.len();
         assert!(
-            num_bids >= accepted_bids_count.0,
+            num_bids >= accepted_bids_count,
             "There's not enough bids ({})",
             num_bids
         );
 
-        // accept best bids
-        let bids_vec = listing.bids.to_vec();
-        // let first_accepted_proposal_index = (num_proposals - accepted_proposals_count) as usize;
-
-        // let best_proposals_iter =
-        //     proposals_vec.drain(first_accepted_proposal_index..(num_proposals as usize));
-        // for proposal_being_accepted_id in best_proposals_iter {
-        // TODO:
-        // let proposal_being_accepted = listing
-        //     .proposals
-        //     .get(&proposal_being_accepted_id)
-        //     .expect("Proposal being accepted is missing, inconsistent state");
-        // let proposer_id = proposal_being_accepted.proposer_id;
-
-        // TODO:
-
-        // TODO: make more specific callback function to rollback
-        // self.primary_listing_process_purchase(
-        //     listing.clone(),
-        //     proposer_id.clone(),
-        //     proposal_being_accepted.price_yocto.clone(),
-        // );
+        // supply reserved by purchases in flight doesn't count
+        let supply_available = listing.supply_available();
+        assert!(
+            supply_available >= accepted_bids_count,
+            "Only {} NFTs left",
+            supply_available
+        );
+
+        // accepted and skipped bids leave the book
+        let storage_byte_cost = env::storage_byte_cost();
+        let nft_worst_case_storage_cost =
+            self.config.storage_limits.nft_mint_storage_max as Balance * storage_byte_cost;
+        let purchase_record_storage_cost = if listing.max_per_account.is_some() {
+            PURCHASE_RECORD_STORAGE_MAX as Balance * storage_byte_cost
+        } else {
+            0
+        };
+        let bids: Vec<Bid> = listing.bids.iter().collect();
+        let mut accepted_bids: Vec<Bid> = Vec::new();
+        for bid in bids {
+            if accepted_bids.len() as u64 == accepted_bids_count {
+                break;
+            }
+            listing.bids.remove(bid.id);
+            self.internal_remove_bid_from_account(
+                &bid.bidder_id,
+                ListingId::Primary(listing_id.clone()),
+                bid.id,
+            );
+
+            // purchase counts include the bids accepted above, their mints are charged later
+            let purchased = listing
+                .purchases_by_account
+                .get(&bid.bidder_id)
+                .unwrap_or(0);
+            let is_within_limit = listing
+                .max_per_account
+                .map_or(true, |max_per_account| purchased < max_per_account);
+            let accepted_count = accepted_bids
+                .iter()
+                .filter(|accepted_bid| accepted_bid.bidder_id == bid.bidder_id)
+                .count() as Balance;
+            let required_deposit =
+                (accepted_count + 1) * nft_worst_case_storage_cost + purchase_record_storage_cost;
+            let current_deposit = self.storage_deposits.get(&bid.bidder_id).unwrap_or(0);
+            if !is_within_limit || current_deposit < required_deposit {
+                env::log_str(&format!(
+                    "Bid {} of {} skipped and refunded, {}",
+                    bid.id,
+                    bid.bidder_id,
+                    if is_within_limit {
+                        "storage deposit too low"
+                    } else {
+                        "per account limit reached"
+                    }
+                ));
+                Promise::new(bid.bidder_id.clone()).transfer(bid.max_amount_yocto);
+                continue;
+            }
 
-        // TODO: move these to primary_listing_process_purchase resolve
-        // let _removed_proposal = listing
-        //     .proposals
-        //     .remove(&proposal_being_accepted_id)
-        //     .expect("Could not find proposal");
-
-        // let mut proposals_by_this_proposer = listing
-        //     .proposals_by_proposer
-        //     .get(&proposer_id)
-        //     .expect("Could not get proposals for proposer whose proposal is being accepted");
-        // let removed = proposals_by_this_proposer.remove(&proposal_being_accepted_id);
-        // assert!(removed, "Could not find id for proposer's proposals");
-        // if proposals_by_this_proposer.is_empty() {
-        //     listing.proposals_by_proposer.remove(&proposer_id).expect("Could not remove empty array for proposer whose proposals have all been accepted");
-        // } else {
-        //     listing.proposals_by_proposer
-        //         .insert(&proposer_id, &proposals_by_this_proposer);
-        // }
-        // }
-
-        listing.bids.clear();
-        listing.bids.extend(bids_vec);
-
-        listing.supply_left -= accepted_bids_count.0; // TODO: move to resolve, one by one
+            if bid.max_amount_yocto > bid.amount_yocto {
+                Promise::new(bid.bidder_id.clone())
+                    .transfer(bid.max_amount_yocto - bid.amount_yocto);
+            }
+
+            // the mint completion releases whatever doesn't get minted
+            self.internal_primary_listing_reserve(&mut listing, &bid.bidder_id, 1, None);
+            accepted_bids.push(bid);
+        }
         self.primary_listings_by_id.insert(&listing_id, &listing);
+
+        // mints are independent of each other, the completion settles every bid on its own
+        let mut mints: Option<Promise> = None;
+        for bid in accepted_bids.iter() {
+            let mint = nft_contract::mint(
+                U64(listing_id.collection_id),
+                bid.bidder_id.clone(),
+                None, // perpetual royalties
+                listing_id.nft_contract_id.clone(),
+                nft_worst_case_storage_cost,
+                NFT_MINT_GAS,
+            );
+            mints = Some(match mints {
+                Some(mints) => mints.and(mint),
+                None => mint,
+            });
+        }
+
+        let mints = match mints {
+            Some(mints) => mints,
+            None => return PromiseOrValue::Value(vec![]),
+        };
+        let accepted_count = accepted_bids.len() as u64;
+        PromiseOrValue::Promise(
+            mints.then(ext_self_nft::primary_listing_accept_bids_mint_completion(
+                listing.seller_id.clone(),
+                PrimaryListingIdJson {
+                    nft_contract_id: listing_id.nft_contract_id.clone(),
+                    collection_id: U64(listing_id.collection_id),
+                },
+                U64(listing.round),
+                accepted_bids
+                    .into_iter()
+                    .map(|bid| JsonPrimaryListingBid {
+                        id: U64(bid.id),
+                        bidder_id: bid.bidder_id,
+                        amount_yocto: U128(bid.amount_yocto),
+                    })
+                    .collect(),
+                env::current_account_id(),
+                NO_DEPOSIT,
+                Gas(PRIMARY_LISTING_ACCEPT_BID_COMPLETION_GAS.0 * accepted_count),
+            )),
+        )
     }
 
     // here the caller will need to cover the refund transfers gas if there's supply left
@@ -313,7 +644,8 @@ impl MarketplaceContract {
         // reset supply and refund proposers
         listing.supply_left = 0;
         self.primary_listing_remove_supply_exceeding_bids_and_refund_bidders(&mut listing);
-        // self.primary_listings_by_id.insert(&listing_id, &listing);
+        self.internal_primary_listing_clear_purchases(&mut listing);
+        self.primary_listings_by_id.insert(&listing_id, &listing);
 
         // remove listing and refund the seller
 
@@ -340,6 +672,7 @@ impl MarketplaceContract {
 trait PrimaryListingSellerCallback {
     fn primary_listing_add_make_collection_completion(
         &mut self,
+        seller_id: AccountId,
         nft_account_id: AccountId,
         nft_metadata: NftMetadata,
         nft_mutable_metadata: NftMutableMetadata,
@@ -348,12 +681,24 @@ trait PrimaryListingSellerCallback {
         min_bid_yocto: Option<U128>,
         start_timestamp: i64,
         end_timestamp: Option<i64>,
+        max_per_account: Option<U64>,
+        rules: JsonListingRules,
+        collection_supply: U64,
     ) -> (U64, Balance);
+
+    fn primary_listing_accept_bids_mint_completion(
+        &mut self,
+        seller_id: AccountId,
+        listing_id: PrimaryListingIdJson,
+        round: U64,
+        bids: Vec<JsonPrimaryListingBid>,
+    ) -> Vec<PurchaseReceipt>;
 }
 
-trait PrimaryListingSellerCallback {
+pub(crate) trait PrimaryListingSellerCallback {
     fn primary_listing_add_make_collection_completion(
         &mut self,
+        seller_id: AccountId,
         nft_account_id: AccountId,
         nft_metadata: NftMetadata,
         nft_mutable_metadata: NftMutableMetadata,
@@ -362,7 +707,18 @@ trait PrimaryListingSellerCallback {
         min_bid_yocto: Option<U128>,
         start_timestamp: i64,
         end_timestamp: Option<i64>,
+        max_per_account: Option<U64>,
+        rules: JsonListingRules,
+        collection_supply: U64,
     ) -> (U64, Balance);
+
+    fn primary_listing_accept_bids_mint_completion(
+        &mut self,
+        seller_id: AccountId,
+        listing_id: PrimaryListingIdJson,
+        round: U64,
+        bids: Vec<JsonPrimaryListingBid>,
+    ) -> Vec<PurchaseReceipt>;
 }
 
 #[near_bindgen]
@@ -370,6 +726,7 @@ impl PrimaryListingSellerCallback for MarketplaceContract {
     #[private]
     fn primary_listing_add_make_collection_completion(
         &mut self,
+        seller_id: AccountId,
         nft_account_id: AccountId,
         nft_metadata: NftMetadata,
         nft_mutable_metadata: NftMutableMetadata,
@@ -378,6 +735,9 @@ impl PrimaryListingSellerCallback for MarketplaceContract {
         min_bid_yocto: Option<U128>,
         start_timestamp: i64,
         end_timestamp: Option<i64>,
+        max_per_account: Option<U64>,
+        rules: JsonListingRules,
+        collection_supply: U64,
     ) -> (U64, Balance) {
         assert_eq!(env::promise_results_count(), 1, "Too many data receipts");
         match env::promise_result(0) {
@@ -391,13 +751,21 @@ impl PrimaryListingSellerCallback for MarketplaceContract {
                 let (collection_id, nft_storage) =
                     near_sdk::serde_json::from_slice::<(U64, U64)>(&val)
                         .expect("NFT make_collection returned unexpected value");
-                let seller_id = env::signer_account_id();
                 let listing_id = PrimaryListingId {
                     nft_contract_id: nft_account_id.clone(),
                     collection_id: collection_id.0,
                 };
-                let listing_id_hash = hash_primary_listing_id(&listing_id);
-                let listing = PrimaryListing {
+                let listing_id_hash = hash_primary_listing_id(&listing_id, 0);
+                let collection = PrimaryCollection {
+                    seller_id: seller_id.clone(),
+                    nft_metadata: nft_metadata.clone(),
+                    nft_mutable_metadata: nft_mutable_metadata.clone(),
+                    max_supply: collection_supply.0,
+                    supply_minted: 0,
+                    supply_pending: 0,
+                    next_round: 1,
+                };
+                let mut listing = PrimaryListing {
                     id: listing_id,
                     seller_id: seller_id.clone(),
                     nft_metadata,
@@ -409,17 +777,38 @@ impl PrimaryListingSellerCallback for MarketplaceContract {
                     end_timestamp,
                     status: ListingStatus::Unstarted,
                     supply_left: supply_total.0,
-                    bids: Vector::new(
+                    supply_pending: 0,
+                    bids: BidBook::new(
                         PrimaryListingStorageKey::Bids { listing_id_hash }
                             .try_to_vec()
                             .unwrap(),
+                        PrimaryListingStorageKey::BidKeys { listing_id_hash }
+                            .try_to_vec()
+                            .unwrap(),
                     ),
                     next_bid_id: 0,
+                    version: 0,
+                    phases: Vector::new(
+                        PrimaryListingStorageKey::Phases { listing_id_hash }
+                            .try_to_vec()
+                            .unwrap(),
+                    ),
+                    max_per_account: max_per_account.map(|m| m.0),
+                    purchases_by_account: UnorderedMap::new(
+                        PrimaryListingStorageKey::Purchases { listing_id_hash }
+                            .try_to_vec()
+                            .unwrap(),
+                    ),
+                    seq: 0, // assigned by internal_add_primary_listing
+                    rules: ListingRules::from_json(&rules),
+                    round: 0,
                 };
 
                 let marketplace_storage_before = env::storage_usage();
 
-                self.internal_add_primary_listing(&listing);
+                self.internal_add_primary_listing(&mut listing);
+                self.primary_collections
+                    .insert(&(nft_account_id, collection_id.0), &collection);
 
                 let storage_byte_cost = env::storage_byte_cost();
                 let marketplace_storage = env::storage_usage() - marketplace_storage_before;
@@ -441,6 +830,76 @@ impl PrimaryListingSellerCallback for MarketplaceContract {
             }
         }
     }
+
+    // every bid is settled on its own, by the result of its mint; the bidder pays the effective
+    // amount of the bid, the rest of the escrow was refunded when the bids were accepted
+    #[private]
+    fn primary_listing_accept_bids_mint_completion(
+        &mut self,
+        seller_id: AccountId,
+        listing_id: PrimaryListingIdJson,
+        round: U64,
+        bids: Vec<JsonPrimaryListingBid>,
+    ) -> Vec<PurchaseReceipt> {
+        let listing_id = PrimaryListingId {
+            nft_contract_id: listing_id.nft_contract_id,
+            collection_id: listing_id.collection_id.0,
+        };
+
+        let mut receipts: Vec<PurchaseReceipt> = Vec::new();
+        for (result_index, bid) in bids.into_iter().enumerate() {
+            let mut token_ids: Vec<NftId> = Vec::new();
+            let mut mint_storage_bytes: u64 = 0;
+            // payments are scheduled below, an unexpected value counts as a failed mint
+            if let PromiseResult::Successful(val) = env::promise_result(result_index as u64) {
+                if let Ok((token_id, token_storage_bytes)) =
+                    near_sdk::serde_json::from_slice::<(NftId, U64)>(&val)
+                {
+                    token_ids.push(token_id);
+                    mint_storage_bytes = token_storage_bytes.0;
+                }
+            }
+
+            // releases the reservation of a failed mint too
+            let (storage_cost, storage_shortfall) = self.internal_primary_listing_record_mints(
+                &listing_id,
+                round.0,
+                &seller_id,
+                &bid.bidder_id,
+                &bid.bidder_id,
+                bid.amount_yocto.0,
+                1,
+                &token_ids,
+                mint_storage_bytes,
+                None,
+            );
+
+            let price_paid = if token_ids.is_empty() {
+                0
+            } else {
+                bid.amount_yocto.0
+            };
+            let seller_proceeds = price_paid.saturating_sub(storage_shortfall);
+            if seller_proceeds > 0 {
+                Promise::new(seller_id.clone()).transfer(seller_proceeds);
+            }
+            let refund = bid.amount_yocto.0 - price_paid;
+            if refund > 0 {
+                Promise::new(bid.bidder_id.clone()).transfer(refund);
+            }
+
+            receipts.push(PurchaseReceipt {
+                nft_contract_id: listing_id.nft_contract_id.clone(),
+                receiver_id: bid.bidder_id,
+                token_ids,
+                price_yocto: U128(price_paid),
+                fee_yocto: U128(0),
+                storage_cost_yocto: U128(storage_cost),
+                refund_yocto: U128(refund),
+            });
+        }
+        receipts
+    }
 }
 
 // 701 + 64*2 + 128 + 2048 + 8 + 8 =
@@ -0,0 +1,203 @@
+use crate::{listing::status::ListingStatus, *};
+use near_sdk::collections::TreeMap;
+use near_sdk::json_types::{U128, U64};
+use near_sdk::IntoStorageKey;
+
+#[cfg(test)]
+#[path = "query_tests.rs"]
+mod query_tests;
+
+// max number of index entries visited by a single query call, keeps view calls within gas limit
+// if it's reached before the page is full, the returned cursor lets the client continue
+pub const LISTING_QUERY_SCAN_MAX: usize = 500;
+pub const LISTING_QUERY_LIMIT_DEFAULT: u64 = 10;
+
+// secondary indexes of a listing type, values are listing IDs
+// - by_seq holds all listings in creation order
+// - by_price holds buy-now listings only
+// - by_end holds time-limited listings only
+// seq is unique so it's used to break ties
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct ListingIndex<T> {
+    by_seq: TreeMap<u64, T>,
+    by_price: TreeMap<(u128, u64), T>,
+    by_end: TreeMap<(i64, u64), T>,
+}
+
+impl<T: BorshSerialize + BorshDeserialize> ListingIndex<T> {
+    pub fn new<S: IntoStorageKey>(by_seq_prefix: S, by_price_prefix: S, by_end_prefix: S) -> Self {
+        Self {
+            by_seq: TreeMap::new(by_seq_prefix),
+            by_price: TreeMap::new(by_price_prefix),
+            by_end: TreeMap::new(by_end_prefix),
+        }
+    }
+
+    pub(crate) fn insert(&mut self, seq: u64, price_yocto: Option<u128>, end_timestamp: Option<i64>, listing_id: &T) {
+        self.by_seq.insert(&seq, listing_id);
+        if let Some(price_yocto) = price_yocto {
+            self.by_price.insert(&(price_yocto, seq), listing_id);
+        }
+        if let Some(end_timestamp) = end_timestamp {
+            self.by_end.insert(&(end_timestamp, seq), listing_id);
+        }
+    }
+
+    pub(crate) fn remove(&mut self, seq: u64, price_yocto: Option<u128>, end_timestamp: Option<i64>) {
+        self.by_seq.remove(&seq);
+        if let Some(price_yocto) = price_yocto {
+            self.by_price.remove(&(price_yocto, seq));
+        }
+        if let Some(end_timestamp) = end_timestamp {
+            self.by_end.remove(&(end_timestamp, seq));
+        }
+    }
+
+    // walks the index for the given sort order, starting right after the cursor
+    // yields (cursor, listing_id) pairs
+    pub(crate) fn scan<'a>(
+        &'a self,
+        sort: &ListingSort,
+        cursor: Option<String>,
+    ) -> Box<dyn Iterator<Item = (String, T)> + 'a> {
+        match sort {
+            ListingSort::CreatedAsc | ListingSort::CreatedDesc => {
+                let iter: Box<dyn Iterator<Item = (u64, T)> + 'a> =
+                    match (sort, cursor.map(|c| parse_seq_cursor(&c))) {
+                        (ListingSort::CreatedAsc, None) => Box::new(self.by_seq.iter()),
+                        (ListingSort::CreatedAsc, Some(key)) => Box::new(self.by_seq.iter_from(key)),
+                        (_, None) => Box::new(self.by_seq.iter_rev()),
+                        (_, Some(key)) => Box::new(self.by_seq.iter_rev_from(key)),
+                    };
+                Box::new(iter.map(|(seq, listing_id)| (seq.to_string(), listing_id)))
+            }
+            ListingSort::PriceAsc | ListingSort::PriceDesc => {
+                let iter: Box<dyn Iterator<Item = ((u128, u64), T)> + 'a> =
+                    match (sort, cursor.map(|c| parse_pair_cursor::<u128>(&c))) {
+                        (ListingSort::PriceAsc, None) => Box::new(self.by_price.iter()),
+                        (ListingSort::PriceAsc, Some(key)) => Box::new(self.by_price.iter_from(key)),
+                        (_, None) => Box::new(self.by_price.iter_rev()),
+                        (_, Some(key)) => Box::new(self.by_price.iter_rev_from(key)),
+                    };
+                Box::new(iter.map(|((price, seq), listing_id)| (format!("{}:{}", price, seq), listing_id)))
+            }
+            ListingSort::EndAsc | ListingSort::EndDesc => {
+                let iter: Box<dyn Iterator<Item = ((i64, u64), T)> + 'a> =
+                    match (sort, cursor.map(|c| parse_pair_cursor::<i64>(&c))) {
+                        (ListingSort::EndAsc, None) => Box::new(self.by_end.iter()),
+                        (ListingSort::EndAsc, Some(key)) => Box::new(self.by_end.iter_from(key)),
+                        (_, None) => Box::new(self.by_end.iter_rev()),
+                        (_, Some(key)) => Box::new(self.by_end.iter_rev_from(key)),
+                    };
+                Box::new(iter.map(|((end, seq), listing_id)| (format!("{}:{}", end, seq), listing_id)))
+            }
+        }
+    }
+}
+
+fn parse_seq_cursor(cursor: &str) -> u64 {
+    cursor.parse().expect("Invalid cursor")
+}
+
+fn parse_pair_cursor<K: std::str::FromStr>(cursor: &str) -> (K, u64) {
+    let (key, seq) = cursor.split_once(':').expect("Invalid cursor");
+    (
+        key.parse().ok().expect("Invalid cursor"),
+        seq.parse().expect("Invalid cursor"),
+    )
+}
+
+// sorting by price only returns buy-now listings, sorting by end time only returns
+// time-limited listings
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub enum ListingSort {
+    CreatedAsc,
+    CreatedDesc,
+    PriceAsc,
+    PriceDesc,
+    EndAsc,
+    EndDesc,
+}
+
+// all conditions must be met; the price range only matches buy-now listings
+#[derive(Serialize, Deserialize, Default)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ListingFilter {
+    pub status: Option<ListingStatus>,
+    pub min_price_yocto: Option<U128>,
+    pub max_price_yocto: Option<U128>,
+    pub seller_id: Option<AccountId>,
+    pub nft_contract_id: Option<AccountId>,
+    pub is_buy_now: Option<bool>,
+    pub is_accepting_bids: Option<bool>,
+    pub ends_before: Option<U64>, // nanoseconds since 1970-01-01
+}
+
+impl ListingFilter {
+    pub(crate) fn matches(
+        &self,
+        status: &ListingStatus,
+        seller_id: &AccountId,
+        nft_contract_id: &AccountId,
+        price_yocto: Option<u128>,
+        min_bid_yocto: Option<u128>,
+        end_timestamp: Option<i64>,
+    ) -> bool {
+        // pending listings are not for sale until the NFT contract confirms the token
+        if status == &ListingStatus::Pending {
+            return false;
+        }
+        if let Some(wanted_status) = &self.status {
+            if wanted_status != status {
+                return false;
+            }
+        }
+        if let Some(wanted_seller_id) = &self.seller_id {
+            if wanted_seller_id != seller_id {
+                return false;
+            }
+        }
+        if let Some(wanted_nft_contract_id) = &self.nft_contract_id {
+            if wanted_nft_contract_id != nft_contract_id {
+                return false;
+            }
+        }
+        if self.min_price_yocto.is_some() || self.max_price_yocto.is_some() {
+            match price_yocto {
+                None => return false,
+                Some(price_yocto) => {
+                    if self.min_price_yocto.map_or(false, |min| price_yocto < min.0)
+                        || self.max_price_yocto.map_or(false, |max| price_yocto > max.0)
+                    {
+                        return false;
+                    }
+                }
+            }
+        }
+        if let Some(is_buy_now) = self.is_buy_now {
+            if is_buy_now != price_yocto.is_some() {
+                return false;
+            }
+        }
+        if let Some(is_accepting_bids) = self.is_accepting_bids {
+            if is_accepting_bids != min_bid_yocto.is_some() {
+                return false;
+            }
+        }
+        if let Some(ends_before) = self.ends_before {
+            match end_timestamp {
+                Some(end_timestamp) if end_timestamp < ends_before.0 as i64 => {}
+                _ => return false,
+            }
+        }
+        true
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ListingPage<T> {
+    pub listings: Vec<T>,
+    pub next_cursor: Option<String>, // None when there's nothing more to scan
+}
@@ -0,0 +1,399 @@
+#[cfg(test)]
+mod query_tests {
+    use super::super::{ListingFilter, ListingSort, LISTING_QUERY_SCAN_MAX};
+    use crate::{listing::status::ListingStatus, test_utils::*, *};
+    use near_sdk::json_types::{U128, U64};
+    use near_sdk::serde_json::{self, json};
+
+    // 0:1 buy now at 1N
+    // 0:2 buy now at 3N, auction ending after 24h
+    // 0:3 auction ending after 12h
+    // 0:4 buy now at 2N, of another seller
+    // 0:5 buy now at 5N, pending
+    fn setup() -> MarketplaceContract {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        add_secondary_listing(&mut marketplace, "0:1", Some(ONE_NEAR), None, false);
+        add_secondary_listing(
+            &mut marketplace,
+            "0:2",
+            Some(3 * ONE_NEAR),
+            Some(MIN_BID_YOCTO),
+            false,
+        );
+        let listing_id =
+            add_secondary_listing(&mut marketplace, "0:3", None, Some(MIN_BID_YOCTO), false);
+        set_end_timestamp(&mut marketplace, &listing_id, (12 * HOUR_NANO) as i64);
+        let listing_id =
+            add_secondary_listing(&mut marketplace, "0:4", Some(2 * ONE_NEAR), None, false);
+        update_listing(&mut marketplace, &listing_id, |listing| {
+            listing.seller_id = account(BUYER2_ACCOUNT_ID);
+        });
+        let listing_id =
+            add_secondary_listing(&mut marketplace, "0:5", Some(5 * ONE_NEAR), None, false);
+        update_listing(&mut marketplace, &listing_id, |listing| {
+            listing.status = ListingStatus::Pending;
+        });
+        marketplace
+    }
+
+    fn update_listing(
+        marketplace: &mut MarketplaceContract,
+        listing_id: &SecondaryListingId,
+        update: impl FnOnce(&mut SecondaryListing),
+    ) {
+        let mut listing = marketplace
+            .secondary_listings_by_id
+            .get(listing_id)
+            .unwrap();
+        update(&mut listing);
+        marketplace
+            .secondary_listings_by_id
+            .insert(listing_id, &listing);
+    }
+
+    // the index is keyed by the end time, so it's updated along with the listing
+    fn set_end_timestamp(
+        marketplace: &mut MarketplaceContract,
+        listing_id: &SecondaryListingId,
+        end_timestamp: i64,
+    ) {
+        let listing = marketplace
+            .secondary_listings_by_id
+            .get(listing_id)
+            .unwrap();
+        marketplace.secondary_listing_index.remove(
+            listing.seq,
+            listing.price_yocto,
+            listing.end_timestamp,
+        );
+        marketplace.secondary_listing_index.insert(
+            listing.seq,
+            listing.price_yocto,
+            Some(end_timestamp),
+            listing_id,
+        );
+        update_listing(marketplace, listing_id, |listing| {
+            listing.end_timestamp = Some(end_timestamp);
+        });
+    }
+
+    // token ids of the page and its cursor
+    fn query(
+        marketplace: &MarketplaceContract,
+        filter: ListingFilter,
+        sort: Option<ListingSort>,
+        cursor: Option<String>,
+        limit: Option<u64>,
+    ) -> (Vec<String>, Option<String>) {
+        let page = marketplace.secondary_listings_query(Some(filter), sort, cursor, limit);
+        (
+            page.listings
+                .iter()
+                .map(|listing| listing.token_id.clone())
+                .collect(),
+            page.next_cursor,
+        )
+    }
+
+    fn token_ids(
+        marketplace: &MarketplaceContract,
+        filter: ListingFilter,
+        sort: Option<ListingSort>,
+    ) -> Vec<String> {
+        query(marketplace, filter, sort, None, Some(100)).0
+    }
+
+    /* filters */
+
+    #[test]
+    fn test_query_without_filter() {
+        let marketplace = setup();
+
+        // pending listings are never returned
+        let (token_ids, next_cursor) =
+            query(&marketplace, ListingFilter::default(), None, None, None);
+        assert_eq!(token_ids, vec!["0:1", "0:2", "0:3", "0:4"]);
+        assert!(next_cursor.is_none());
+    }
+
+    #[test]
+    fn test_query_filter_price_range() {
+        let marketplace = setup();
+
+        let filter = ListingFilter {
+            min_price_yocto: Some(U128(3 * ONE_NEAR / 2)),
+            max_price_yocto: Some(U128(3 * ONE_NEAR)),
+            ..Default::default()
+        };
+        assert_eq!(token_ids(&marketplace, filter, None), vec!["0:2", "0:4"]);
+    }
+
+    #[test]
+    fn test_query_filter_seller_and_contract() {
+        let marketplace = setup();
+
+        let filter = ListingFilter {
+            seller_id: Some(account(BUYER2_ACCOUNT_ID)),
+            ..Default::default()
+        };
+        assert_eq!(token_ids(&marketplace, filter, None), vec!["0:4"]);
+
+        let filter = ListingFilter {
+            nft_contract_id: Some(account(BUYER_ACCOUNT_ID)),
+            ..Default::default()
+        };
+        assert!(token_ids(&marketplace, filter, None).is_empty());
+    }
+
+    #[test]
+    fn test_query_filter_sale_kind() {
+        let marketplace = setup();
+
+        let filter = ListingFilter {
+            is_buy_now: Some(false),
+            ..Default::default()
+        };
+        assert_eq!(token_ids(&marketplace, filter, None), vec!["0:3"]);
+
+        let filter = ListingFilter {
+            is_accepting_bids: Some(true),
+            ..Default::default()
+        };
+        assert_eq!(token_ids(&marketplace, filter, None), vec!["0:2", "0:3"]);
+    }
+
+    #[test]
+    fn test_query_filter_ends_before() {
+        let marketplace = setup();
+
+        // U64 comes as a string in JSON
+        let filter: ListingFilter = serde_json::from_value(json!({
+            "ends_before": (20 * HOUR_NANO).to_string()
+        }))
+        .unwrap();
+        assert_eq!(filter.ends_before, Some(U64(20 * HOUR_NANO)));
+        assert_eq!(token_ids(&marketplace, filter, None), vec!["0:3"]);
+    }
+
+    #[test]
+    fn test_query_filter_status() {
+        let marketplace = setup();
+
+        let filter = ListingFilter {
+            status: Some(ListingStatus::Ended),
+            ..Default::default()
+        };
+        assert!(token_ids(&marketplace, filter, None).is_empty());
+
+        // the status is brought up to date before filtering
+        set_context(SELLER_ACCOUNT_ID, 13 * HOUR_NANO, 0);
+        let filter = ListingFilter {
+            status: Some(ListingStatus::Ended),
+            ..Default::default()
+        };
+        assert_eq!(token_ids(&marketplace, filter, None), vec!["0:3"]);
+        let filter = ListingFilter {
+            status: Some(ListingStatus::Running),
+            ..Default::default()
+        };
+        assert_eq!(
+            token_ids(&marketplace, filter, None),
+            vec!["0:1", "0:2", "0:4"]
+        );
+    }
+
+    /* sorting */
+
+    #[test]
+    fn test_query_sort_by_price() {
+        let marketplace = setup();
+
+        // listings without a buy now price are left out
+        assert_eq!(
+            token_ids(
+                &marketplace,
+                ListingFilter::default(),
+                Some(ListingSort::PriceAsc)
+            ),
+            vec!["0:1", "0:4", "0:2"]
+        );
+        assert_eq!(
+            token_ids(
+                &marketplace,
+                ListingFilter::default(),
+                Some(ListingSort::PriceDesc)
+            ),
+            vec!["0:2", "0:4", "0:1"]
+        );
+    }
+
+    #[test]
+    fn test_query_sort_by_end_and_creation() {
+        let marketplace = setup();
+
+        // listings without an end date are left out
+        assert_eq!(
+            token_ids(
+                &marketplace,
+                ListingFilter::default(),
+                Some(ListingSort::EndAsc)
+            ),
+            vec!["0:3", "0:2"]
+        );
+        assert_eq!(
+            token_ids(
+                &marketplace,
+                ListingFilter::default(),
+                Some(ListingSort::EndDesc)
+            ),
+            vec!["0:2", "0:3"]
+        );
+        assert_eq!(
+            token_ids(
+                &marketplace,
+                ListingFilter::default(),
+                Some(ListingSort::CreatedDesc)
+            ),
+            vec!["0:4", "0:3", "0:2", "0:1"]
+        );
+    }
+
+    #[test]
+    fn test_primary_query_sort_by_price() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        add_primary_listing(&mut marketplace, 0, 10, Some(2 * ONE_NEAR), None, None);
+        add_primary_listing(&mut marketplace, 1, 10, Some(ONE_NEAR), None, None);
+        add_primary_listing(&mut marketplace, 2, 10, None, Some(MIN_BID_YOCTO), None);
+
+        let page =
+            marketplace.primary_listings_query(None, Some(ListingSort::PriceAsc), None, None);
+        let collection_ids: Vec<u64> = page
+            .listings
+            .iter()
+            .map(|listing| listing.collection_id.0)
+            .collect();
+        assert_eq!(collection_ids, vec![1, 0]);
+        assert!(page.next_cursor.is_none());
+    }
+
+    /* cursors */
+
+    #[test]
+    fn test_query_pages() {
+        let marketplace = setup();
+
+        let (token_ids, cursor) =
+            query(&marketplace, ListingFilter::default(), None, None, Some(2));
+        assert_eq!(token_ids, vec!["0:1", "0:2"]);
+        let (token_ids, cursor) = query(
+            &marketplace,
+            ListingFilter::default(),
+            None,
+            cursor,
+            Some(2),
+        );
+        assert_eq!(token_ids, vec!["0:3", "0:4"]);
+        // a full page always comes with a cursor, the next one may turn out empty
+        assert!(cursor.is_some());
+        let (token_ids, cursor) = query(
+            &marketplace,
+            ListingFilter::default(),
+            None,
+            cursor,
+            Some(2),
+        );
+        assert!(token_ids.is_empty());
+        assert!(cursor.is_none());
+    }
+
+    #[test]
+    fn test_query_price_pages() {
+        let marketplace = setup();
+        let sort = || Some(ListingSort::PriceDesc);
+
+        let (token_ids, cursor) = query(
+            &marketplace,
+            ListingFilter::default(),
+            sort(),
+            None,
+            Some(2),
+        );
+        // the pending listing at 5N was scanned and skipped
+        assert_eq!(token_ids, vec!["0:2", "0:4"]);
+        let (token_ids, cursor) = query(
+            &marketplace,
+            ListingFilter::default(),
+            sort(),
+            cursor,
+            Some(2),
+        );
+        assert_eq!(token_ids, vec!["0:1"]);
+        assert!(cursor.is_none());
+    }
+
+    #[test]
+    fn test_query_cursor_survives_removal() {
+        let mut marketplace = setup();
+
+        let (_, cursor) = query(&marketplace, ListingFilter::default(), None, None, Some(2));
+        // the listing the cursor points at goes away
+        for token_id in ["0:2", "0:3"] {
+            marketplace.internal_remove_secondary_listing(&SecondaryListingId {
+                nft_contract_id: account(NFT_CONTRACT_ID),
+                token_id: token_id.to_string(),
+            });
+        }
+
+        let (token_ids, _) = query(
+            &marketplace,
+            ListingFilter::default(),
+            None,
+            cursor,
+            Some(2),
+        );
+        assert_eq!(token_ids, vec!["0:4"]);
+    }
+
+    #[test]
+    fn test_query_stops_at_scan_limit() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        for index in 0..LISTING_QUERY_SCAN_MAX {
+            add_secondary_listing(
+                &mut marketplace,
+                &format!("0:{}", index),
+                Some(ONE_NEAR),
+                None,
+                false,
+            );
+        }
+        add_secondary_listing(&mut marketplace, "1:1", Some(2 * ONE_NEAR), None, false);
+        let filter = || ListingFilter {
+            min_price_yocto: Some(U128(2 * ONE_NEAR)),
+            ..Default::default()
+        };
+
+        // nothing matched within the scan limit, the cursor lets the client go on
+        let (token_ids, cursor) = query(&marketplace, filter(), None, None, None);
+        assert!(token_ids.is_empty());
+        assert!(cursor.is_some());
+        let (token_ids, cursor) = query(&marketplace, filter(), None, cursor, None);
+        assert_eq!(token_ids, vec!["1:1"]);
+        assert!(cursor.is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Invalid cursor"#)]
+    fn test_query_invalid_cursor() {
+        let marketplace = setup();
+        query(
+            &marketplace,
+            ListingFilter::default(),
+            Some(ListingSort::PriceAsc),
+            Some("1000".to_string()),
+            None,
+        );
+    }
+}
@@ -0,0 +1,15 @@
+use crate::*;
+use near_sdk::json_types::U128;
+
+// returned by the buy calls once the NFT has been delivered to the buyer
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PurchaseReceipt {
+    pub nft_contract_id: AccountId,
+    pub receiver_id: AccountId,     // holds the tokens now, may differ from the payer
+    pub token_ids: Vec<NftId>,      // may be fewer than requested if some mints failed
+    pub price_yocto: U128,          // total paid to the seller
+    pub fee_yocto: U128,            // kept by the marketplace
+    pub storage_cost_yocto: U128,   // deducted from buyer's storage deposit
+    pub refund_yocto: U128,         // excess attached deposit returned to the buyer
+}
@@ -0,0 +1,93 @@
+use crate::*;
+use near_sdk::json_types::U128;
+
+#[cfg(test)]
+#[path = "rules_tests.rs"]
+mod rules_tests;
+
+// price and bid rules of a single listing, chosen by the seller within the bounds kept in
+// the marketplace config
+#[derive(BorshDeserialize, BorshSerialize, Clone)]
+pub struct ListingRules {
+    pub min_price_yocto: u128,
+    pub price_step_yocto: u128, // prices must be its integer multiples
+    pub min_bid_yocto: u128,
+    pub bid_step_yocto: u128,   // bids must be its integer multiples
+    pub bid_increment_rate: u8, // percent of the bid being outbid, 0 if the step alone applies
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct JsonListingRules {
+    pub min_price_yocto: U128,
+    pub price_step_yocto: U128,
+    pub min_bid_yocto: U128,
+    pub bid_step_yocto: U128,
+    pub bid_increment_rate: u8,
+}
+
+impl ListingRules {
+    pub(crate) fn from_json(rules: &JsonListingRules) -> Self {
+        Self {
+            min_price_yocto: rules.min_price_yocto.0,
+            price_step_yocto: rules.price_step_yocto.0,
+            min_bid_yocto: rules.min_bid_yocto.0,
+            bid_step_yocto: rules.bid_step_yocto.0,
+            bid_increment_rate: rules.bid_increment_rate,
+        }
+    }
+
+    pub(crate) fn to_json(&self) -> JsonListingRules {
+        JsonListingRules {
+            min_price_yocto: U128(self.min_price_yocto),
+            price_step_yocto: U128(self.price_step_yocto),
+            min_bid_yocto: U128(self.min_bid_yocto),
+            bid_step_yocto: U128(self.bid_step_yocto),
+            bid_increment_rate: self.bid_increment_rate,
+        }
+    }
+
+    pub(crate) fn assert_price(&self, price_yocto: u128) {
+        assert!(
+            price_yocto >= self.min_price_yocto,
+            "Price cannot be lower than {} yoctoNear",
+            self.min_price_yocto
+        );
+        assert!(
+            price_yocto % self.price_step_yocto == 0,
+            "Price must be integer multiple of {} yoctoNear",
+            self.price_step_yocto
+        );
+    }
+
+    pub(crate) fn assert_min_bid(&self, min_bid_yocto: u128) {
+        assert!(
+            min_bid_yocto >= self.min_bid_yocto,
+            "Bid cannot be lower than {} yoctoNear",
+            self.min_bid_yocto
+        );
+        assert!(
+            min_bid_yocto % self.bid_step_yocto == 0,
+            "Bid must be integer multiple of {} yoctoNear",
+            self.bid_step_yocto
+        );
+    }
+
+    pub(crate) fn assert_bid_amount(&self, amount_yocto: u128) {
+        assert!(
+            amount_yocto % self.bid_step_yocto == 0,
+            "Bid amount must be an integer multiple of {} yocto Near",
+            self.bid_step_yocto
+        );
+    }
+
+    // lowest bid that outbids the given amount: one step, or the percentage rounded up to
+    // whole steps if that's more
+    pub(crate) fn next_bid_yocto(&self, amount_yocto: u128) -> u128 {
+        let rate_increment_yocto = amount_yocto * self.bid_increment_rate as u128 / 100;
+        let rate_increment_yocto = (rate_increment_yocto + self.bid_step_yocto - 1)
+            / self.bid_step_yocto
+            * self.bid_step_yocto;
+        amount_yocto + std::cmp::max(self.bid_step_yocto, rate_increment_yocto)
+    }
+}
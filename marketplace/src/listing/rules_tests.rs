@@ -0,0 +1,68 @@
+#[cfg(test)]
+mod rules_tests {
+    use super::super::ListingRules;
+    use crate::test_utils::*;
+
+    const STEP_YOCTO: u128 = ONE_NEAR / 10;
+
+    fn rules(bid_increment_rate: u8) -> ListingRules {
+        ListingRules {
+            min_price_yocto: STEP_YOCTO,
+            price_step_yocto: STEP_YOCTO,
+            min_bid_yocto: STEP_YOCTO,
+            bid_step_yocto: STEP_YOCTO,
+            bid_increment_rate,
+        }
+    }
+
+    /* next_bid_yocto */
+
+    #[test]
+    fn test_next_bid_step_only() {
+        assert_eq!(rules(0).next_bid_yocto(ONE_NEAR), ONE_NEAR + STEP_YOCTO);
+    }
+
+    #[test]
+    fn test_next_bid_rate_below_step() {
+        // 5% of 1 Near is less than the step, the step applies
+        assert_eq!(rules(5).next_bid_yocto(ONE_NEAR), ONE_NEAR + STEP_YOCTO);
+    }
+
+    #[test]
+    fn test_next_bid_rate_above_step() {
+        // 10% of 5 Near
+        assert_eq!(
+            rules(10).next_bid_yocto(5 * ONE_NEAR),
+            5 * ONE_NEAR + 5 * STEP_YOCTO
+        );
+    }
+
+    #[test]
+    fn test_next_bid_rate_rounded_up_to_step() {
+        // 5% of 3 Near is 0.15 Near, rounded up to 0.2 Near
+        assert_eq!(
+            rules(5).next_bid_yocto(3 * ONE_NEAR),
+            3 * ONE_NEAR + 2 * STEP_YOCTO
+        );
+    }
+
+    /* amount checks */
+
+    #[test]
+    #[should_panic(expected = "Price cannot be lower than")]
+    fn test_price_too_low() {
+        rules(0).assert_price(STEP_YOCTO / 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "Bid must be integer multiple of")]
+    fn test_min_bid_not_multiple_of_step() {
+        rules(0).assert_min_bid(STEP_YOCTO + STEP_YOCTO / 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "Bid amount must be an integer multiple of")]
+    fn test_bid_amount_not_multiple_of_step() {
+        rules(0).assert_bid_amount(STEP_YOCTO + STEP_YOCTO / 2);
+    }
+}
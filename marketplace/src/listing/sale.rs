@@ -0,0 +1,240 @@
+use crate::{
+    listing::{
+        id::{ListingId, ListingIdJson},
+        query::LISTING_QUERY_SCAN_MAX,
+        status::ListingStatus,
+    },
+    *,
+};
+use near_sdk::json_types::{U128, U64};
+
+#[cfg(test)]
+#[path = "sale_tests.rs"]
+mod sale_tests;
+
+// one entry per token sold, kept forever
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct Sale {
+    pub listing_id: Option<ListingId>, // None for sales through offers
+    pub token_id: NftId,
+    pub buyer_id: AccountId, // receiver of the token
+    pub seller_id: AccountId,
+    pub price_yocto: u128,
+    pub timestamp: u64, // nanoseconds since 1970-01-01
+}
+
+// aggregates updated on every sale
+#[derive(BorshDeserialize, BorshSerialize, Default)]
+pub struct MarketStats {
+    pub volume_yocto: u128,
+    pub sales_count: u64,
+    pub last_sale_index: Option<u64>, // index into the sales ledger
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct JsonSale {
+    pub index: U64,
+    pub listing_id: Option<ListingIdJson>,
+    pub token_id: NftId,
+    pub buyer_id: AccountId,
+    pub seller_id: AccountId,
+    pub price_yocto: U128,
+    pub timestamp: U64,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct JsonMarketStats {
+    pub volume_yocto: U128,
+    pub sales_count: U64,
+    pub last_sale: Option<JsonSale>,
+    pub floor_price_yocto: Option<U128>, // lowest buy-now price among running listings
+}
+
+impl Sale {
+    fn to_json(&self, index: u64) -> JsonSale {
+        JsonSale {
+            index: U64(index),
+            listing_id: self.listing_id.as_ref().map(|listing_id| listing_id.to_json()),
+            token_id: self.token_id.clone(),
+            buyer_id: self.buyer_id.clone(),
+            seller_id: self.seller_id.clone(),
+            price_yocto: U128(self.price_yocto),
+            timestamp: U64(self.timestamp),
+        }
+    }
+}
+
+impl MarketplaceContract {
+    // appends the sale to the ledger and updates contract-wide and per-collection stats
+    // collection_id is None for tokens the NFT contract never confirmed
+    pub(crate) fn internal_record_sale(
+        &mut self,
+        sale: Sale,
+        nft_contract_id: &AccountId,
+        collection_id: Option<NftCollectionId>,
+    ) {
+        let sale_index = self.sales.len();
+        let price_yocto = sale.price_yocto;
+        self.sales.push(&sale);
+
+        let mut contract_stats = self
+            .market_stats_by_contract
+            .get(nft_contract_id)
+            .unwrap_or_default();
+        contract_stats.volume_yocto += price_yocto;
+        contract_stats.sales_count += 1;
+        contract_stats.last_sale_index = Some(sale_index);
+        self.market_stats_by_contract
+            .insert(nft_contract_id, &contract_stats);
+
+        if let Some(collection_id) = collection_id {
+            let stats_key = (nft_contract_id.clone(), collection_id);
+            let mut collection_stats = self
+                .market_stats_by_collection
+                .get(&stats_key)
+                .unwrap_or_default();
+            collection_stats.volume_yocto += price_yocto;
+            collection_stats.sales_count += 1;
+            collection_stats.last_sale_index = Some(sale_index);
+            self.market_stats_by_collection
+                .insert(&stats_key, &collection_stats);
+        }
+    }
+
+    // buy-now listings are tracked by price so that the floor is a single lookup
+    pub(crate) fn internal_add_listing_to_floor(
+        &mut self,
+        listing_id: &ListingId,
+        nft_contract_id: &AccountId,
+        collection_id: Option<NftCollectionId>,
+        price_yocto: Option<u128>,
+        seq: u64,
+    ) {
+        if let Some(price_yocto) = price_yocto {
+            self.floor_by_contract
+                .insert(&(nft_contract_id.clone(), price_yocto, seq), listing_id);
+            if let Some(collection_id) = collection_id {
+                self.floor_by_collection.insert(
+                    &(nft_contract_id.clone(), collection_id, price_yocto, seq),
+                    listing_id,
+                );
+            }
+        }
+    }
+
+    pub(crate) fn internal_remove_listing_from_floor(
+        &mut self,
+        nft_contract_id: &AccountId,
+        collection_id: Option<NftCollectionId>,
+        price_yocto: Option<u128>,
+        seq: u64,
+    ) {
+        if let Some(price_yocto) = price_yocto {
+            self.floor_by_contract
+                .remove(&(nft_contract_id.clone(), price_yocto, seq));
+            if let Some(collection_id) = collection_id {
+                self.floor_by_collection
+                    .remove(&(nft_contract_id.clone(), collection_id, price_yocto, seq));
+            }
+        }
+    }
+
+    // floor entries of the NFT contract, or of one of its collections, cheapest first
+    // key price 0 cannot exist, the config keeps the minimum listing price positive
+    pub(crate) fn internal_floor(
+        &self,
+        nft_contract_id: &AccountId,
+        collection_id: Option<NftCollectionId>,
+    ) -> Box<dyn Iterator<Item = (u128, ListingId)> + '_> {
+        let contract_id = nft_contract_id.clone();
+        if let Some(collection_id) = collection_id {
+            Box::new(
+                self.floor_by_collection
+                    .iter_from((nft_contract_id.clone(), collection_id, 0, 0))
+                    .take_while(move |(key, _)| key.0 == contract_id && key.1 == collection_id)
+                    .map(|(key, listing_id)| (key.2, listing_id)),
+            )
+        } else {
+            Box::new(
+                self.floor_by_contract
+                    .iter_from((nft_contract_id.clone(), 0, 0))
+                    .take_while(move |(key, _)| key.0 == contract_id)
+                    .map(|(key, listing_id)| (key.1, listing_id)),
+            )
+        }
+    }
+
+    // listings stay on the floor until they are removed, the status changes with time so it's
+    // checked on read; only running listings which can still be bought are active
+    pub(crate) fn internal_is_floor_listing_active(&self, listing_id: &ListingId) -> bool {
+        match listing_id {
+            ListingId::Primary(listing_id) => {
+                let mut listing = self.primary_listings_by_id.get(listing_id).unwrap();
+                listing.update_status();
+                listing.status == ListingStatus::Running && listing.supply_available() > 0
+            }
+            ListingId::Secondary(listing_id) => {
+                let mut listing = self.secondary_listings_by_id.get(listing_id).unwrap();
+                listing.update_status();
+                listing.status == ListingStatus::Running
+            }
+            ListingId::Bundle(bundle_id) => {
+                let mut listing = self.bundle_listings_by_id.get(bundle_id).unwrap();
+                listing.update_status();
+                listing.status == ListingStatus::Running && !listing.is_selling
+            }
+        }
+    }
+}
+
+// view-only methods
+
+#[near_bindgen]
+impl MarketplaceContract {
+    // stats of all sales on the NFT contract, or of a single collection if collection_id is given
+    pub fn market_stats(
+        &self,
+        nft_contract_id: AccountId,
+        collection_id: Option<U64>,
+    ) -> JsonMarketStats {
+        let collection_id = collection_id.map(|c| c.0);
+        let stats = if let Some(collection_id) = collection_id {
+            self.market_stats_by_collection
+                .get(&(nft_contract_id.clone(), collection_id))
+                .unwrap_or_default()
+        } else {
+            self.market_stats_by_contract
+                .get(&nft_contract_id)
+                .unwrap_or_default()
+        };
+        // the cheapest active listing, a floor full of stale entries gives up after the scan limit
+        let floor_price_yocto = self
+            .internal_floor(&nft_contract_id, collection_id)
+            .take(LISTING_QUERY_SCAN_MAX)
+            .find(|(_, listing_id)| self.internal_is_floor_listing_active(listing_id))
+            .map(|(price_yocto, _)| price_yocto);
+
+        JsonMarketStats {
+            volume_yocto: U128(stats.volume_yocto),
+            sales_count: U64(stats.sales_count),
+            last_sale: stats
+                .last_sale_index
+                .map(|index| self.sales.get(index).unwrap().to_json(index)),
+            floor_price_yocto: floor_price_yocto.map(|p| U128(p)),
+        }
+    }
+
+    // sales ledger, oldest first, results are paginated
+    pub fn sales(&self, from_index: Option<U128>, limit: Option<u64>) -> Vec<JsonSale> {
+        //where to start pagination - if we have a from_index, we'll use that - otherwise start from 0 index
+        let start = u128::from(from_index.unwrap_or(U128(0))) as u64;
+        let count = limit.unwrap_or(10);
+        let end = std::cmp::min(start.saturating_add(count), self.sales.len());
+
+        (start..end)
+            .map(|index| self.sales.get(index).unwrap().to_json(index))
+            .collect()
+    }
+}
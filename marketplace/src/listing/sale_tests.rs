@@ -0,0 +1,116 @@
+#[cfg(test)]
+mod sale_tests {
+    use crate::{listing::query::LISTING_QUERY_SCAN_MAX, test_utils::*, *};
+    use near_sdk::json_types::U64;
+
+    fn floor_price(
+        marketplace: &MarketplaceContract,
+        collection_id: Option<u64>,
+    ) -> Option<Balance> {
+        marketplace
+            .market_stats(account(NFT_CONTRACT_ID), collection_id.map(U64))
+            .floor_price_yocto
+            .map(|price_yocto| price_yocto.0)
+    }
+
+    fn update_secondary_listing(
+        marketplace: &mut MarketplaceContract,
+        listing_id: &SecondaryListingId,
+        update: impl FnOnce(&mut SecondaryListing),
+    ) {
+        let mut listing = marketplace
+            .secondary_listings_by_id
+            .get(listing_id)
+            .unwrap();
+        update(&mut listing);
+        marketplace
+            .secondary_listings_by_id
+            .insert(listing_id, &listing);
+    }
+
+    /* floor */
+
+    #[test]
+    fn test_floor_is_cheapest_running_listing() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        add_secondary_listing(&mut marketplace, "0:1", Some(3 * ONE_NEAR), None, false);
+        add_secondary_listing(&mut marketplace, "0:2", Some(2 * ONE_NEAR), None, false);
+
+        assert_eq!(floor_price(&marketplace, None), Some(2 * ONE_NEAR));
+        assert_eq!(floor_price(&marketplace, Some(0)), Some(2 * ONE_NEAR));
+        assert_eq!(floor_price(&marketplace, Some(1)), None);
+    }
+
+    #[test]
+    fn test_floor_skips_unstarted_listing() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        let listing_id =
+            add_secondary_listing(&mut marketplace, "0:1", Some(ONE_NEAR), None, false);
+        add_secondary_listing(&mut marketplace, "0:2", Some(2 * ONE_NEAR), None, false);
+        update_secondary_listing(&mut marketplace, &listing_id, |listing| {
+            listing.start_timestamp = (2 * HOUR_NANO) as i64;
+        });
+
+        assert_eq!(floor_price(&marketplace, None), Some(2 * ONE_NEAR));
+        assert_eq!(floor_price(&marketplace, Some(0)), Some(2 * ONE_NEAR));
+
+        // the listing joins the floor once it starts
+        set_context(SELLER_ACCOUNT_ID, 2 * HOUR_NANO, 0);
+        assert_eq!(floor_price(&marketplace, None), Some(ONE_NEAR));
+    }
+
+    #[test]
+    fn test_floor_skips_ended_listing() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        let listing_id =
+            add_secondary_listing(&mut marketplace, "0:1", Some(ONE_NEAR), None, false);
+        add_secondary_listing(&mut marketplace, "0:2", Some(2 * ONE_NEAR), None, false);
+        update_secondary_listing(&mut marketplace, &listing_id, |listing| {
+            listing.end_timestamp = Some(NOW as i64);
+        });
+
+        assert_eq!(floor_price(&marketplace, None), Some(2 * ONE_NEAR));
+    }
+
+    #[test]
+    fn test_floor_skips_reserved_primary_listing() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        let listing_id = add_primary_listing(&mut marketplace, 0, 1, Some(ONE_NEAR), None, None);
+        add_secondary_listing(&mut marketplace, "0:1", Some(2 * ONE_NEAR), None, false);
+        assert_eq!(floor_price(&marketplace, Some(0)), Some(ONE_NEAR));
+
+        // the last token is being minted for a buyer
+        let mut listing = marketplace.primary_listings_by_id.get(&listing_id).unwrap();
+        listing.supply_pending = 1;
+        marketplace
+            .primary_listings_by_id
+            .insert(&listing_id, &listing);
+
+        assert_eq!(floor_price(&marketplace, Some(0)), Some(2 * ONE_NEAR));
+    }
+
+    #[test]
+    fn test_floor_gives_up_after_scan_limit() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        for index in 0..LISTING_QUERY_SCAN_MAX {
+            let listing_id = add_secondary_listing(
+                &mut marketplace,
+                &format!("0:{}", index),
+                Some(ONE_NEAR),
+                None,
+                false,
+            );
+            update_secondary_listing(&mut marketplace, &listing_id, |listing| {
+                listing.end_timestamp = Some(NOW as i64);
+            });
+        }
+        add_secondary_listing(&mut marketplace, "1:1", Some(2 * ONE_NEAR), None, false);
+
+        assert_eq!(floor_price(&marketplace, None), None);
+    }
+}
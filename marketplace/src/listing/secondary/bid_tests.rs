@@ -0,0 +1,217 @@
+#[cfg(test)]
+mod bid_tests {
+    use crate::{test_utils::*, *};
+    use near_sdk::json_types::{U128, U64};
+
+    const TOKEN_ID: &str = "0:1";
+
+    fn place_bid(
+        marketplace: &mut MarketplaceContract,
+        bidder_id: &str,
+        amount_yocto: Balance,
+    ) -> U64 {
+        set_context(bidder_id, NOW, amount_yocto);
+        marketplace.secondary_listing_place_bid(
+            account(NFT_CONTRACT_ID),
+            TOKEN_ID.to_string(),
+            U128(amount_yocto),
+        )
+    }
+
+    fn bid_amounts(marketplace: &MarketplaceContract) -> Vec<Balance> {
+        marketplace
+            .secondary_listing_bids(account(NFT_CONTRACT_ID), TOKEN_ID.to_string(), None, None)
+            .iter()
+            .map(|bid| bid.amount_yocto.0)
+            .collect()
+    }
+
+    fn auction(marketplace: &mut MarketplaceContract) -> SecondaryListingId {
+        add_secondary_listing(
+            marketplace,
+            TOKEN_ID,
+            Some(10 * ONE_NEAR),
+            Some(MIN_BID_YOCTO),
+            false,
+        )
+    }
+
+    #[test]
+    fn test_place_bid() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        auction(&mut marketplace);
+
+        let bid_id = place_bid(&mut marketplace, BIDDER_ACCOUNT_ID, MIN_BID_YOCTO);
+
+        assert_eq!(bid_id, U64(0));
+        assert_eq!(bid_amounts(&marketplace), vec![MIN_BID_YOCTO]);
+        // the bidder pays for the bid storage
+        assert!(
+            marketplace
+                .storage_deposits
+                .get(&account(BIDDER_ACCOUNT_ID))
+                .unwrap()
+                < STORAGE_DEPOSIT
+        );
+        let account_bids = marketplace.bids_by_account(account(BIDDER_ACCOUNT_ID), None, None);
+        assert_eq!(account_bids.len(), 1);
+        assert_eq!(account_bids[0].bid_id, U64(0));
+        assert_eq!(account_bids[0].rank, U64(0));
+    }
+
+    #[test]
+    #[should_panic(
+        expected = r#"Bid is too low. The lowest acceptable amount is 600000000000000000000000"#
+    )]
+    fn test_place_bid_must_outbid_best_bid() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        auction(&mut marketplace);
+
+        place_bid(&mut marketplace, BIDDER_ACCOUNT_ID, MIN_BID_YOCTO);
+        place_bid(&mut marketplace, BIDDER2_ACCOUNT_ID, MIN_BID_YOCTO);
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Bids are not accepted for this listing"#)]
+    fn test_place_bid_on_buy_now_listing() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        add_secondary_listing(&mut marketplace, TOKEN_ID, Some(PRICE_YOCTO), None, false);
+
+        place_bid(&mut marketplace, BIDDER_ACCOUNT_ID, MIN_BID_YOCTO);
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Cannot submit a bid to your own listing"#)]
+    fn test_place_bid_on_own_listing() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        auction(&mut marketplace);
+
+        place_bid(&mut marketplace, SELLER_ACCOUNT_ID, MIN_BID_YOCTO);
+    }
+
+    #[test]
+    fn test_full_book_refunds_lowest_bid() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        marketplace.config.listing_limits.secondary_listing_bids_max = 2;
+        auction(&mut marketplace);
+
+        place_bid(&mut marketplace, BIDDER_ACCOUNT_ID, MIN_BID_YOCTO);
+        place_bid(&mut marketplace, BIDDER2_ACCOUNT_ID, 2 * MIN_BID_YOCTO);
+        place_bid(&mut marketplace, BUYER_ACCOUNT_ID, 3 * MIN_BID_YOCTO);
+
+        assert_eq!(
+            bid_amounts(&marketplace),
+            vec![3 * MIN_BID_YOCTO, 2 * MIN_BID_YOCTO]
+        );
+        // the outbid bidder got the bid storage back
+        assert!(marketplace
+            .bids_by_account(account(BIDDER_ACCOUNT_ID), None, None)
+            .is_empty());
+        assert_eq!(
+            marketplace
+                .storage_deposits
+                .get(&account(BIDDER_ACCOUNT_ID)),
+            Some(STORAGE_DEPOSIT)
+        );
+    }
+
+    #[test]
+    fn test_revoke_bid() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        auction(&mut marketplace);
+        let bid_id = place_bid(&mut marketplace, BIDDER_ACCOUNT_ID, MIN_BID_YOCTO);
+
+        set_context(BIDDER_ACCOUNT_ID, NOW, 0);
+        marketplace.secondary_listing_revoke_bid(
+            account(NFT_CONTRACT_ID),
+            TOKEN_ID.to_string(),
+            bid_id,
+        );
+
+        assert!(bid_amounts(&marketplace).is_empty());
+        assert!(marketplace
+            .bids_by_account(account(BIDDER_ACCOUNT_ID), None, None)
+            .is_empty());
+        assert_eq!(
+            marketplace
+                .storage_deposits
+                .get(&account(BIDDER_ACCOUNT_ID)),
+            Some(STORAGE_DEPOSIT)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Not authorized to revoke this bid"#)]
+    fn test_revoke_someone_elses_bid() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        auction(&mut marketplace);
+        let bid_id = place_bid(&mut marketplace, BIDDER_ACCOUNT_ID, MIN_BID_YOCTO);
+
+        set_context(BIDDER2_ACCOUNT_ID, NOW, 0);
+        marketplace.secondary_listing_revoke_bid(
+            account(NFT_CONTRACT_ID),
+            TOKEN_ID.to_string(),
+            bid_id,
+        );
+    }
+
+    #[test]
+    fn test_accept_bid() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        let listing_id = auction(&mut marketplace);
+        place_bid(&mut marketplace, BIDDER_ACCOUNT_ID, MIN_BID_YOCTO);
+        let bid_id = place_bid(&mut marketplace, BIDDER2_ACCOUNT_ID, 2 * MIN_BID_YOCTO);
+
+        set_context(NFT_CONTRACT_ID, NOW, 0);
+        marketplace.internal_secondary_listing_accept_bid(
+            &account(SELLER_ACCOUNT_ID),
+            listing_id,
+            1,
+            bid_id.0,
+        );
+
+        assert_eq!(bid_amounts(&marketplace), vec![MIN_BID_YOCTO]);
+        assert!(marketplace
+            .bids_by_account(account(BIDDER2_ACCOUNT_ID), None, None)
+            .is_empty());
+        assert_eq!(
+            marketplace
+                .storage_deposits
+                .get(&account(BIDDER2_ACCOUNT_ID)),
+            Some(STORAGE_DEPOSIT)
+        );
+    }
+
+    #[test]
+    fn test_close_listing_refunds_bids() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        let listing_id = auction(&mut marketplace);
+        place_bid(&mut marketplace, BIDDER_ACCOUNT_ID, MIN_BID_YOCTO);
+        place_bid(&mut marketplace, BIDDER2_ACCOUNT_ID, 2 * MIN_BID_YOCTO);
+
+        marketplace.internal_close_secondary_listing(&listing_id);
+
+        assert!(marketplace
+            .secondary_listings_by_id
+            .get(&listing_id)
+            .is_none());
+        for bidder_id in [BIDDER_ACCOUNT_ID, BIDDER2_ACCOUNT_ID] {
+            assert!(marketplace
+                .bids_by_account(account(bidder_id), None, None)
+                .is_empty());
+            assert_eq!(
+                marketplace.storage_deposits.get(&account(bidder_id)),
+                Some(STORAGE_DEPOSIT)
+            );
+        }
+    }
+}
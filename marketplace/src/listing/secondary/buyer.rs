@@ -4,14 +4,17 @@ use crate::{
     listing::{
         // constants::*, 
         // primary::lib::PrimaryListingIdJson, 
-        // bid::Bid, 
+        bid::Bid,
+        id::ListingId,
+        receipt::PurchaseReceipt,
+        sale::Sale,
         status::ListingStatus,
     },
     *,
 };
 use near_sdk::{
     // env::attached_deposit,
-    json_types::{U128},
+    json_types::{U128, U64},
     PromiseResult,
 };
 
@@ -22,15 +25,26 @@ const NFT_TRANSFER_COMPLETION_GAS: Gas = Gas(5_000_000_000_000); // TODO: measur
 #[path = "buyer_tests.rs"]
 mod buyer_tests;
 
+#[cfg(test)]
+#[path = "bid_tests.rs"]
+mod bid_tests;
+
 pub type NftId = String;
 
 #[near_bindgen]
 impl MarketplaceContract {
+    // max_price_yocto and version protect the buyer against the listing being modified
+    // after it was presented to them; the call fails (and the deposit is returned) if
+    // the price went up or the listing version does not match
+    // the token is transferred to receiver_id (the caller if missing), the caller pays and gets the refunds
     #[payable]
     pub fn secondary_listing_buy(
         &mut self,
         nft_contract_id: AccountId,
         token_id: String,
+        receiver_id: Option<AccountId>,
+        max_price_yocto: Option<U128>,
+        version: Option<U64>,
     ) -> Promise {
         let listing_id = SecondaryListingId {
             nft_contract_id: nft_contract_id.clone(),
@@ -49,6 +63,23 @@ impl MarketplaceContract {
             .price_yocto
             .expect("Buy Now is not possible for this listing");
 
+        // make sure the listing is what the buyer expects it to be
+        if let Some(max_price_yocto) = max_price_yocto {
+            assert!(
+                price_yocto <= max_price_yocto.0,
+                "Price of {} exceeds the maximum price of {}",
+                price_yocto,
+                max_price_yocto.0
+            );
+        }
+        if let Some(version) = version {
+            assert!(
+                listing.version == version.0,
+                "Listing has been modified. Current version is {}",
+                listing.version
+            );
+        }
+
         assert!(
             listing.status == ListingStatus::Running,
             "This listing is {}",
@@ -68,16 +99,27 @@ impl MarketplaceContract {
             required_deposit,
         );
 
+        let receiver_id = receiver_id.unwrap_or_else(|| buyer_id.clone());
+        assert!(receiver_id != listing.seller_id, "Cannot buy for the seller");
+
+        // the marketplace owns the token in custody, no approval involved
+        let approval_id = if listing.is_in_custody {
+            None
+        } else {
+            Some(listing.approval_id)
+        };
         nft_contract::nft_transfer(
-            buyer_id,
+            receiver_id.clone(),
             token_id.clone(),
-            Some(listing.approval_id),
+            approval_id,
             None,
             nft_contract_id.clone(),
             1,
             NFT_TRANSFER_GAS,
         )
         .then(ext_self_nft::nft_transfer_completion(
+            buyer_id,
+            receiver_id,
             nft_contract_id,
             token_id,
             listing.seller_id.clone(),
@@ -88,29 +130,177 @@ impl MarketplaceContract {
             NFT_TRANSFER_COMPLETION_GAS, // GAS attached to the completion call
         ))
     }
+
+    // the bid is escrowed until the seller accepts it, it's outbid or revoked or the listing
+    // closes; it must outbid the best bid and the deposit must cover it, the bid storage is
+    // paid from the bidder's storage deposit; the book holds up to secondary_listing_bids_max
+    // bids, when it's full the lowest one is refunded to make room
+    #[payable]
+    pub fn secondary_listing_place_bid(
+        &mut self,
+        nft_contract_id: AccountId,
+        token_id: String,
+        amount_yocto: U128,
+    ) -> U64 {
+        let amount_yocto = amount_yocto.0;
+        let listing_id = SecondaryListingId {
+            nft_contract_id,
+            token_id,
+        };
+
+        let mut listing = self
+            .secondary_listings_by_id
+            .get(&listing_id)
+            .expect("Could not find NFT listing");
+        listing.update_status();
+
+        assert!(
+            listing.status == ListingStatus::Running,
+            "This listing is {}",
+            listing.status.as_str()
+        );
+
+        let bidder_id = env::predecessor_account_id();
+        assert!(
+            bidder_id != listing.seller_id,
+            "Cannot submit a bid to your own listing"
+        );
+
+        // bid must be lower than buy now, if the latter is set
+        if let Some(price_yocto) = listing.price_yocto {
+            assert!(
+                amount_yocto < price_yocto,
+                "Bid must be lower than buy now price of {}",
+                price_yocto
+            );
+        }
+
+        // bid must be multiple of the listing's bid step
+        listing.rules.assert_bid_amount(amount_yocto);
+
+        // check if bid is acceptable, this also makes sure bids are accepted
+        let acceptable_bid_yocto = listing.acceptable_bid_yocto();
+        assert!(
+            amount_yocto >= acceptable_bid_yocto,
+            "Bid is too low. The lowest acceptable amount is {:?}",
+            acceptable_bid_yocto
+        );
+
+        // ensure the attached balance is sufficient to pay deposit
+        let attached_deposit = env::attached_deposit();
+        assert!(
+            attached_deposit >= amount_yocto,
+            "Attached balance must be sufficient to pay the required deposit of {} yocto Near",
+            amount_yocto
+        );
+
+        // the book is full, the lowest bid makes room
+        if listing.bids.len() >= self.config.listing_limits.secondary_listing_bids_max {
+            let worst_bid = listing.bids.worst().unwrap();
+            self.internal_secondary_listing_refund_bid(&mut listing, worst_bid.id);
+        }
+
+        let new_bid = Bid {
+            id: listing.next_bid_id,
+            bidder_id: bidder_id.clone(),
+            amount_yocto,
+            max_amount_yocto: amount_yocto,
+        };
+        listing.next_bid_id += 1;
+
+        let storage_before = env::storage_usage();
+        listing.bids.insert(&new_bid);
+        self.internal_add_bid_to_account(
+            &bidder_id,
+            ListingId::Secondary(listing_id.clone()),
+            new_bid.id,
+        );
+        self.secondary_listings_by_id.insert(&listing_id, &listing);
+        let storage_after = env::storage_usage();
+        self.internal_charge_account_storage(&bidder_id, storage_before, storage_after);
+
+        let refund = attached_deposit - amount_yocto;
+        if refund > 0 {
+            Promise::new(bidder_id).transfer(refund);
+        }
+
+        U64(new_bid.id)
+    }
+
+    // the escrow is returned minus the revoke fee, the bid storage goes back to the bidder's
+    // storage deposit
+    pub fn secondary_listing_revoke_bid(
+        &mut self,
+        nft_contract_id: AccountId,
+        token_id: String,
+        bid_id: U64,
+    ) {
+        let listing_id = SecondaryListingId {
+            nft_contract_id,
+            token_id,
+        };
+
+        let mut listing = self
+            .secondary_listings_by_id
+            .get(&listing_id)
+            .expect("Could not find NFT listing");
+        listing.update_status();
+        assert!(
+            listing.status == ListingStatus::Running,
+            "This listing is {}",
+            listing.status.as_str()
+        );
+
+        let bid = listing.bids.get(bid_id.0).expect("Could not find bid");
+        assert!(
+            bid.bidder_id == env::predecessor_account_id(),
+            "Not authorized to revoke this bid"
+        );
+
+        let storage_before = env::storage_usage();
+        listing.bids.remove(bid.id);
+        self.internal_remove_bid_from_account(
+            &bid.bidder_id,
+            ListingId::Secondary(listing_id.clone()),
+            bid.id,
+        );
+        self.secondary_listings_by_id.insert(&listing_id, &listing);
+        let storage_after = env::storage_usage();
+        self.internal_charge_account_storage(&bid.bidder_id, storage_before, storage_after);
+
+        let fee = bid.amount_yocto * self.config.listing_limits.revoke_fee_rate as u128 / 100;
+        Promise::new(bid.bidder_id).transfer(bid.max_amount_yocto - fee);
+        if fee > 0 {
+            Promise::new(self.fees_account_id()).transfer(fee);
+        }
+    }
 }
 
 #[ext_contract(ext_self_nft)]
 trait SecondaryListingBuyerCallback {
     fn nft_transfer_completion(
         &mut self,
+        buyer_id: AccountId,
+        receiver_id: AccountId,
         nft_contract_id: AccountId,
         token_id: String,
         seller_id: AccountId,
         attached_deposit: U128,
         price_yocto: U128,
-    );
+    ) -> PurchaseReceipt;
 }
 
 trait SecondaryListingBuyerCallback {
     fn nft_transfer_completion(
         &mut self,
+        buyer_id: AccountId,
+        receiver_id: AccountId,
         nft_contract_id: AccountId,
         token_id: String,
         seller_id: AccountId,
         attached_deposit: U128,
         price_yocto: U128,
-    );
+    ) -> PurchaseReceipt;
 }
 
 #[near_bindgen]
@@ -118,18 +308,20 @@ impl SecondaryListingBuyerCallback for MarketplaceContract {
     #[private]
     fn nft_transfer_completion(
         &mut self,
+        buyer_id: AccountId,
+        receiver_id: AccountId,
         nft_contract_id: AccountId,
         token_id: String,
         seller_id: AccountId,
         attached_deposit: U128,
         price_yocto: U128,
-    ) {
+    ) -> PurchaseReceipt {
         let attached_deposit = attached_deposit.0;
         let price_yocto = price_yocto.0;
 
         let listing_id = SecondaryListingId {
-            nft_contract_id,
-            token_id,
+            nft_contract_id: nft_contract_id.clone(),
+            token_id: token_id.clone(),
         };
 
         // Here the attached_deposit is the deposit attach buy buyer to the marketplace call (like buy_now)
@@ -139,43 +331,63 @@ impl SecondaryListingBuyerCallback for MarketplaceContract {
         let nft_transfer_result = env::promise_result(0);
         match nft_transfer_result {
             PromiseResult::NotReady | PromiseResult::Failed => {
+                // the 1yN attached to nft_transfer came back to us so everything is returned
+                // we don't panic here, it'd revert the refund
                 if attached_deposit > 0 {
-                    Promise::new(env::signer_account_id()).transfer(attached_deposit);
+                    Promise::new(buyer_id).transfer(attached_deposit);
+                }
+                PurchaseReceipt {
+                    nft_contract_id,
+                    receiver_id,
+                    token_ids: vec![],
+                    price_yocto: U128(0),
+                    fee_yocto: U128(0),
+                    storage_cost_yocto: U128(0),
+                    refund_yocto: U128(attached_deposit),
                 }
-                panic!("nft_transfer failed");
             }
-            PromiseResult::Successful(val) => {
-                let buyer_id = env::signer_account_id();
-
+            PromiseResult::Successful(_) => {
                 // transfer price to the seller
                 Promise::new(seller_id.clone()).transfer(price_yocto);
 
-                // remove listing and return storage deposit to seller
-                let storage_before = env::storage_usage();
-                let removed_listing = self
-                    .secondary_listings_by_id
-                    .remove(&listing_id)
-                    .expect("Could not remove listing: Could not find listing");
-                let storage_after = env::storage_usage();
-                let storage_byte_cost: Balance = env::storage_byte_cost();
-                let deposit_refund =
-                    (storage_before - storage_after) as Balance * storage_byte_cost;
-                if deposit_refund > 0 {
-                    let current_deposit = self
-                        .storage_deposits
-                        .get(&seller_id)
-                        .expect("Could not find seller's storage deposit record");
-                    let updated_deposit = current_deposit + deposit_refund;
-                    self.storage_deposits.insert(&seller_id, &updated_deposit);
-                }
+                // the listing may have been delisted (validate, revoke hook) while the transfer was
+                // in flight; if it's still there its bids are refunded and its storage goes back
+                // to the seller, a listing made since by someone else is left alone
+                let collection_id = match self.secondary_listings_by_id.get(&listing_id) {
+                    Some(listing) if listing.seller_id == seller_id => {
+                        self.internal_close_secondary_listing(&listing_id).collection_id
+                    }
+                    _ => None,
+                };
 
-                // TODO: refund proposals (if any) and remove listing
-                // self.secondary_listing_remove_supply_exceeding_proposals_and_refund_proposers(&mut removed_listing);
+                self.internal_record_sale(
+                    Sale {
+                        listing_id: Some(ListingId::Secondary(listing_id.clone())),
+                        token_id: token_id.clone(),
+                        buyer_id: receiver_id.clone(),
+                        seller_id: seller_id.clone(),
+                        price_yocto,
+                        timestamp: env::block_timestamp(),
+                    },
+                    &nft_contract_id,
+                    collection_id,
+                );
 
                 // return excess attached deposit
                 let required_deposit = price_yocto + 1; // 1yN required by nft_transfer
-                if attached_deposit > required_deposit {
-                    Promise::new(buyer_id).transfer(attached_deposit - required_deposit);
+                let refund = attached_deposit.saturating_sub(required_deposit);
+                if refund > 0 {
+                    Promise::new(buyer_id).transfer(refund);
+                }
+
+                PurchaseReceipt {
+                    nft_contract_id,
+                    receiver_id,
+                    token_ids: vec![token_id],
+                    price_yocto: U128(price_yocto),
+                    fee_yocto: U128(0),
+                    storage_cost_yocto: U128(0),
+                    refund_yocto: U128(refund),
                 }
             }
         }
@@ -0,0 +1,202 @@
+#[cfg(test)]
+mod buyer_tests {
+    use super::super::SecondaryListingBuyerCallback;
+    use crate::{listing::receipt::PurchaseReceipt, test_utils::*, *};
+    use near_sdk::json_types::{U128, U64};
+    use near_sdk::PromiseResult;
+
+    const TOKEN_ID: &str = "0:1";
+
+    // buy now listing with a standing bid
+    fn setup() -> (MarketplaceContract, SecondaryListingId) {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        let listing_id = add_secondary_listing(
+            &mut marketplace,
+            TOKEN_ID,
+            Some(PRICE_YOCTO),
+            Some(MIN_BID_YOCTO),
+            false,
+        );
+        set_context(BIDDER_ACCOUNT_ID, NOW, MIN_BID_YOCTO);
+        marketplace.secondary_listing_place_bid(
+            account(NFT_CONTRACT_ID),
+            TOKEN_ID.to_string(),
+            U128(MIN_BID_YOCTO),
+        );
+        (marketplace, listing_id)
+    }
+
+    fn buy(
+        marketplace: &mut MarketplaceContract,
+        receiver_id: Option<&str>,
+        max_price_yocto: Option<Balance>,
+        version: Option<u64>,
+    ) {
+        set_context(BUYER_ACCOUNT_ID, NOW, PRICE_YOCTO + 1);
+        marketplace.secondary_listing_buy(
+            account(NFT_CONTRACT_ID),
+            TOKEN_ID.to_string(),
+            receiver_id.map(account),
+            max_price_yocto.map(U128),
+            version.map(U64),
+        );
+    }
+
+    fn complete(
+        marketplace: &mut MarketplaceContract,
+        promise_result: PromiseResult,
+    ) -> PurchaseReceipt {
+        complete_for(marketplace, BUYER_ACCOUNT_ID, promise_result)
+    }
+
+    fn complete_for(
+        marketplace: &mut MarketplaceContract,
+        receiver_id: &str,
+        promise_result: PromiseResult,
+    ) -> PurchaseReceipt {
+        set_callback_context(NOW, vec![promise_result]);
+        marketplace.nft_transfer_completion(
+            account(BUYER_ACCOUNT_ID),
+            account(receiver_id),
+            account(NFT_CONTRACT_ID),
+            TOKEN_ID.to_string(),
+            account(SELLER_ACCOUNT_ID),
+            U128(PRICE_YOCTO + 1),
+            U128(PRICE_YOCTO),
+        )
+    }
+
+    /* buying */
+
+    #[test]
+    fn test_buy_within_max_price_and_version() {
+        let (mut marketplace, _) = setup();
+        buy(&mut marketplace, None, Some(PRICE_YOCTO), Some(0));
+    }
+
+    #[test]
+    #[should_panic(expected = r#"exceeds the maximum price of"#)]
+    fn test_buy_above_max_price() {
+        let (mut marketplace, _) = setup();
+        buy(&mut marketplace, None, Some(PRICE_YOCTO - 1), None);
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Listing has been modified. Current version is 1"#)]
+    fn test_buy_modified_listing() {
+        let (mut marketplace, _) = setup();
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let version = marketplace.secondary_listing_update(
+            account(NFT_CONTRACT_ID),
+            TOKEN_ID.to_string(),
+            Some(U128(2 * ONE_NEAR)),
+            None,
+            None,
+            None,
+        );
+        assert_eq!(version.0, 1);
+
+        // the buyer was presented with the listing before the update
+        buy(&mut marketplace, None, None, Some(0));
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Cannot buy for the seller"#)]
+    fn test_buy_for_seller() {
+        let (mut marketplace, _) = setup();
+        buy(&mut marketplace, Some(SELLER_ACCOUNT_ID), None, None);
+    }
+
+    /* nft transfer completion */
+
+    #[test]
+    fn test_nft_transfer_completion() {
+        let (mut marketplace, listing_id) = setup();
+
+        let receipt = complete(&mut marketplace, PromiseResult::Successful(vec![]));
+
+        assert_eq!(receipt.token_ids, vec![TOKEN_ID.to_string()]);
+        assert_eq!(receipt.price_yocto.0, PRICE_YOCTO);
+        assert_eq!(receipt.refund_yocto.0, 0);
+        // the listing is closed and the bid refunded
+        assert!(marketplace
+            .secondary_listings_by_id
+            .get(&listing_id)
+            .is_none());
+        assert!(marketplace
+            .bids_by_account(account(BIDDER_ACCOUNT_ID), None, None)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_nft_transfer_completion_failed_transfer() {
+        let (mut marketplace, listing_id) = setup();
+
+        let receipt = complete(&mut marketplace, PromiseResult::Failed);
+
+        assert!(receipt.token_ids.is_empty());
+        assert_eq!(receipt.refund_yocto.0, PRICE_YOCTO + 1);
+        let listing = marketplace
+            .secondary_listings_by_id
+            .get(&listing_id)
+            .unwrap();
+        assert_eq!(listing.bids.len(), 1);
+    }
+
+    #[test]
+    fn test_nft_transfer_completion_tolerates_delisted_listing() {
+        let (mut marketplace, listing_id) = setup();
+        // validated away or revoked while the transfer was in flight
+        marketplace.internal_delist_secondary_listing(&listing_id);
+
+        let receipt = complete(&mut marketplace, PromiseResult::Successful(vec![]));
+
+        assert_eq!(receipt.token_ids, vec![TOKEN_ID.to_string()]);
+        assert_eq!(receipt.price_yocto.0, PRICE_YOCTO);
+        assert!(marketplace
+            .secondary_listings_by_id
+            .get(&listing_id)
+            .is_none());
+    }
+
+    #[test]
+    fn test_nft_transfer_completion_leaves_new_listing_alone() {
+        let (mut marketplace, listing_id) = setup();
+        // delisted and listed again by someone else while the transfer was in flight
+        let mut listing = marketplace
+            .secondary_listings_by_id
+            .get(&listing_id)
+            .unwrap();
+        listing.seller_id = account(BUYER2_ACCOUNT_ID);
+        marketplace
+            .secondary_listings_by_id
+            .insert(&listing_id, &listing);
+
+        complete(&mut marketplace, PromiseResult::Successful(vec![]));
+
+        let listing = marketplace
+            .secondary_listings_by_id
+            .get(&listing_id)
+            .unwrap();
+        assert_eq!(listing.seller_id, account(BUYER2_ACCOUNT_ID));
+        assert_eq!(listing.bids.len(), 1);
+    }
+
+    #[test]
+    fn test_nft_transfer_completion_for_receiver() {
+        let (mut marketplace, _) = setup();
+
+        let receipt = complete_for(
+            &mut marketplace,
+            BUYER2_ACCOUNT_ID,
+            PromiseResult::Successful(vec![]),
+        );
+
+        // the token went to the receiver, the buyer paid for it
+        assert_eq!(receipt.receiver_id, account(BUYER2_ACCOUNT_ID));
+        let sales = marketplace.sales(None, None);
+        assert_eq!(sales.len(), 1);
+        assert_eq!(sales[0].buyer_id, account(BUYER2_ACCOUNT_ID));
+    }
+}
@@ -1,5 +1,10 @@
+// defaults of the limits kept in MarketplaceConfig, the owner can change them at runtime
 pub const SECONDARY_LISTING_ADD_STORAGE_MAX: u64 = 3021;            // worst case storage TODO
 
+// bids a secondary listing holds at once, the lowest one is refunded when a better one comes;
+// keeps the refunds made when the listing closes within the gas limit
+pub const SECONDARY_LISTING_BIDS_MAX: u64 = 10;
+
 // these define the allowed offering lifetime
 // maximum duration is only applicable to proposal-accepting offering
 // the rationale here is to avoid keeping proposers escrows for too long
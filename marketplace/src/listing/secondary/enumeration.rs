@@ -1,4 +1,12 @@
-use crate::{external::{NftMetadata, NftMutableMetadata}, *, listing::status::ListingStatus};
+use crate::{
+    external::{NftMetadata, NftMutableMetadata},
+    *,
+    listing::{
+        query::{ListingFilter, ListingPage, ListingSort, LISTING_QUERY_LIMIT_DEFAULT, LISTING_QUERY_SCAN_MAX},
+        rules::JsonListingRules,
+        status::ListingStatus,
+    },
+};
 
 use near_sdk::json_types::{U128, U64};
 
@@ -8,14 +16,18 @@ pub struct JsonSecondaryListing {
     pub nft_contract_id: AccountId,
     pub token_id: String,
     pub approval_id: U64,
+    pub is_in_custody: bool, // the marketplace holds the token
+    pub collection_id: Option<U64>, // None until the listing is verified
     pub seller_id: AccountId,
     pub price_yocto: Option<U128>,
     pub min_bid_yocto: Option<U128>,
     pub nft_metadata: NftMetadata,
     pub nft_mutable_metadata: NftMutableMetadata,
-    pub start_timestamp: i64,
-    pub end_timestamp: Option<i64>, // nanoseconds since 1970-01-01
+    pub start_timestamp: U64,       // nanoseconds since 1970-01-01
+    pub end_timestamp: Option<U64>, // nanoseconds since 1970-01-01
     pub status: ListingStatus,
+    pub version: U64,
+    pub rules: JsonListingRules,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -68,24 +80,13 @@ impl MarketplaceContract {
         let start = u128::from(from_index.unwrap_or(U128(0))) as usize;
         let count = limit.unwrap_or(10) as usize;
 
-        //iterate through the listings
+        //iterate through the listings, pending ones are not for sale yet
         listings
             .iter()
+            .filter(|listing| listing.status != ListingStatus::Pending)
             .skip(start) //skip to the index we specified in the start variable
             .take(count) // return "limit" elements or 0 if missing
-            .map(|listing| JsonSecondaryListing {
-                nft_contract_id: listing.id.nft_contract_id,
-                token_id: listing.id.token_id,
-                approval_id: U64(listing.approval_id),
-                seller_id: listing.seller_id,
-                price_yocto: listing.price_yocto.map(|p| U128(p)),
-                min_bid_yocto: listing.min_bid_yocto.map(|b| U128(b)),
-                nft_metadata: listing.nft_metadata,
-                nft_mutable_metadata: listing.nft_mutable_metadata,
-                start_timestamp: listing.start_timestamp,
-                end_timestamp: listing.end_timestamp,
-                status: listing.status,
-            })
+            .map(|listing| listing.to_json())
             .collect()
     }
 
@@ -107,23 +108,10 @@ impl MarketplaceContract {
                 .skip(start) //skip to the index we specified in the start variable
                 .take(count) // return "limit" elements or 0 if missing
                 .map(|listing_id| {
-                    let listing = self
-                        .secondary_listings_by_id
+                    self.secondary_listings_by_id
                         .get(&listing_id)
-                        .expect("Could not find listing");
-                    JsonSecondaryListing {
-                        nft_contract_id: listing.id.nft_contract_id,
-                        token_id: listing.id.token_id,
-                        approval_id: U64(listing.approval_id),
-                        seller_id: listing.seller_id,
-                        price_yocto: listing.price_yocto.map(|p| U128(p)),
-                        min_bid_yocto: listing.min_bid_yocto.map(|b| U128(b)),
-                        nft_metadata: listing.nft_metadata,
-                        nft_mutable_metadata: listing.nft_mutable_metadata,
-                        start_timestamp: listing.start_timestamp,
-                        end_timestamp: listing.end_timestamp,
-                        status: listing.status,
-                    }
+                        .expect("Could not find listing")
+                        .to_json()
                 })
                 .collect()
         } else {
@@ -131,6 +119,60 @@ impl MarketplaceContract {
         }
     }
 
+    // filtered and sorted listings, paginated with a cursor that's stable across additions and removals
+    // pass next_cursor of the returned page to get the next one; a page may hold fewer than limit
+    // listings (even none) if the scan cap was hit, only next_cursor == None means there's no more
+    pub fn secondary_listings_query(
+        &self,
+        filter: Option<ListingFilter>,
+        sort: Option<ListingSort>,
+        cursor: Option<String>,
+        limit: Option<u64>,
+    ) -> ListingPage<JsonSecondaryListing> {
+        let filter = filter.unwrap_or_default();
+        let sort = sort.unwrap_or(ListingSort::CreatedAsc);
+        let limit = limit.unwrap_or(LISTING_QUERY_LIMIT_DEFAULT) as usize;
+
+        let mut listings = Vec::new();
+        let mut last_cursor: Option<String> = None;
+        let mut scanned = 0;
+        let mut index_iter = self.secondary_listing_index.scan(&sort, cursor);
+        while listings.len() < limit && scanned < LISTING_QUERY_SCAN_MAX {
+            match index_iter.next() {
+                Some((cursor, listing_id)) => {
+                    scanned += 1;
+                    last_cursor = Some(cursor);
+                    let mut listing = self
+                        .secondary_listings_by_id
+                        .get(&listing_id)
+                        .expect("Listing record does not exist");
+                    listing.update_status();
+                    if filter.matches(
+                        &listing.status,
+                        &listing.seller_id,
+                        &listing.id.nft_contract_id,
+                        listing.price_yocto,
+                        listing.min_bid_yocto,
+                        listing.end_timestamp,
+                    ) {
+                        listings.push(listing.to_json());
+                    }
+                }
+                None => {
+                    return ListingPage {
+                        listings,
+                        next_cursor: None,
+                    }
+                }
+            }
+        }
+
+        ListingPage {
+            listings,
+            next_cursor: last_cursor,
+        }
+    }
+
     // get PrimaryListing by nft_contract_id
     pub fn is_listed(&self, nft_contract_id: AccountId, token_id: String) -> bool {
         let listing_id = SecondaryListingId {
@@ -149,24 +191,10 @@ impl MarketplaceContract {
             nft_contract_id: nft_contract_id.clone(),
             token_id: token_id.clone(),
         };
-        let listing = self
-            .secondary_listings_by_id
+        self.secondary_listings_by_id
             .get(&listing_id)
-            .expect("Could not find secondary listing");
-
-        JsonSecondaryListing {
-            nft_contract_id: nft_contract_id,
-            token_id,
-            approval_id: U64(listing.approval_id),
-            seller_id: listing.seller_id,
-            price_yocto: listing.price_yocto.map(|p| U128(p)),
-            min_bid_yocto: listing.min_bid_yocto.map(|b| U128(b)),
-            nft_metadata: listing.nft_metadata,
-            nft_mutable_metadata: listing.nft_mutable_metadata,
-            start_timestamp: listing.start_timestamp,
-            end_timestamp: listing.end_timestamp,
-            status: listing.status,
-        }
+            .expect("Could not find secondary listing")
+            .to_json()
     }
 
     // get bid by nft_contract_id and BidlId
@@ -273,11 +301,29 @@ impl MarketplaceContract {
 }
 
 impl SecondaryListing {
+    pub(crate) fn to_json(self) -> JsonSecondaryListing {
+        let rules = self.rules.to_json();
+        JsonSecondaryListing {
+            nft_contract_id: self.id.nft_contract_id,
+            token_id: self.id.token_id,
+            approval_id: U64(self.approval_id),
+            is_in_custody: self.is_in_custody,
+            collection_id: self.collection_id.map(|c| U64(c)),
+            seller_id: self.seller_id,
+            price_yocto: self.price_yocto.map(|p| U128(p)),
+            min_bid_yocto: self.min_bid_yocto.map(|b| U128(b)),
+            nft_metadata: self.nft_metadata,
+            nft_mutable_metadata: self.nft_mutable_metadata,
+            start_timestamp: U64(self.start_timestamp as u64),
+            end_timestamp: self.end_timestamp.map(|t| U64(t as u64)),
+            status: self.status,
+            version: U64(self.version),
+            rules,
+        }
+    }
+
     pub(crate) fn bid(&self, bid_id: &u64) -> Option<JsonSecondaryListingBid> {
-        if let Some(bid) = self
-            .bids
-            .iter()
-            .find(|bid| bid.id == *bid_id)
+        if let Some(bid) = self.bids.get(*bid_id)
         {
             Some(JsonSecondaryListingBid {
                 id: U64(bid.id),
@@ -1,7 +1,7 @@
 use crate::{
     *,
     internal::{hash_account_id},
-    listing::{status::ListingStatus, secondary::lib::SecondaryListingId},
+    listing::{bid::{Bid, BidId}, id::ListingId, status::ListingStatus, secondary::lib::SecondaryListingId},
 };
 
 pub(crate) fn hash_secondary_listing_id(listing_id: &SecondaryListingId) -> CryptoHash {
@@ -13,9 +13,28 @@ pub(crate) fn hash_secondary_listing_id(listing_id: &SecondaryListingId) -> Cryp
 
 impl MarketplaceContract {
     // doesn't check if already there!
-    pub(crate) fn internal_add_secondary_listing(&mut self, listing: &SecondaryListing) {
+    // assigns the listing its sequence number
+    pub(crate) fn internal_add_secondary_listing(&mut self, listing: &mut SecondaryListing) {
+        listing.seq = self.next_listing_seq;
+        self.next_listing_seq += 1;
         self.secondary_listings_by_id.insert(&listing.id, &listing);
         self.internal_add_secondary_listing_to_seller(&listing.seller_id, &listing.id);
+        self.secondary_listing_index.insert(
+            listing.seq,
+            listing.price_yocto,
+            listing.end_timestamp,
+            &listing.id,
+        );
+        // pending listings cannot be bought yet, they join the floor once verified
+        if listing.status != ListingStatus::Pending {
+            self.internal_add_listing_to_floor(
+                &ListingId::Secondary(listing.id.clone()),
+                &listing.id.nft_contract_id,
+                listing.collection_id,
+                listing.price_yocto,
+                listing.seq,
+            );
+        }
     }
 
     // removes all FPO-related records from Marketplace without initiating any NEAR transfers
@@ -29,6 +48,18 @@ impl MarketplaceContract {
             .expect("Could not remove listing: Could not find listing");
         let seller_id = &removed_listing.seller_id;
 
+        self.secondary_listing_index.remove(
+            removed_listing.seq,
+            removed_listing.price_yocto,
+            removed_listing.end_timestamp,
+        );
+        self.internal_remove_listing_from_floor(
+            &removed_listing.id.nft_contract_id,
+            removed_listing.collection_id,
+            removed_listing.price_yocto,
+            removed_listing.seq,
+        );
+
         let mut listings_by_this_seller = self
             .secondary_listings_by_seller_id
             .get(seller_id)
@@ -51,6 +82,94 @@ impl MarketplaceContract {
         removed_listing
     }
 
+    // removes a listing the marketplace can no longer sell (approval revoked or token moved)
+    pub(crate) fn internal_delist_secondary_listing(&mut self, listing_id: &SecondaryListingId) {
+        self.internal_close_secondary_listing(listing_id);
+        env::log_str(&format!(
+            "Secondary listing {}.{} removed, the marketplace can no longer transfer the token",
+            listing_id.nft_contract_id, listing_id.token_id
+        ));
+    }
+
+    // the owner's approvals for the token are gone, by a revoke or a transfer; its listing and
+    // the bundle holding it can no longer be sold. Our own transfers (offers, swaps, bundle
+    // sales) reset the approvals without the NFT contract telling us, so they call this too
+    pub(crate) fn internal_delist_token(
+        &mut self,
+        nft_contract_id: &AccountId,
+        token_id: &NftId,
+        owner_id: &AccountId,
+    ) {
+        if let Some(bundle_id) = self
+            .bundle_id_by_token
+            .get(&(nft_contract_id.clone(), token_id.clone()))
+        {
+            // a bundle being sold settles on its own
+            let bundle = self.bundle_listings_by_id.get(&bundle_id).unwrap();
+            if &bundle.seller_id == owner_id && !bundle.is_selling {
+                self.internal_delist_bundle_listing(bundle_id);
+            }
+        }
+
+        let listing_id = SecondaryListingId {
+            nft_contract_id: nft_contract_id.clone(),
+            token_id: token_id.clone(),
+        };
+        if let Some(listing) = self.secondary_listings_by_id.get(&listing_id) {
+            // the approval doesn't matter for tokens the marketplace holds
+            if &listing.seller_id == owner_id && !listing.is_in_custody {
+                self.internal_delist_secondary_listing(&listing_id);
+            }
+        }
+    }
+
+    // removes the listing, standing bids are refunded (there are at most
+    // secondary_listing_bids_max of them) and the freed storage goes back to the bidders' and
+    // the seller's deposits
+    pub(crate) fn internal_close_secondary_listing(
+        &mut self,
+        listing_id: &SecondaryListingId,
+    ) -> SecondaryListing {
+        if let Some(mut listing) = self.secondary_listings_by_id.get(listing_id) {
+            let bid_ids: Vec<BidId> = listing.bids.iter().map(|bid| bid.id).collect();
+            for bid_id in bid_ids {
+                self.internal_secondary_listing_refund_bid(&mut listing, bid_id);
+            }
+        }
+
+        let storage_before = env::storage_usage();
+        let removed_listing = self.internal_remove_secondary_listing(listing_id);
+        let storage_after = env::storage_usage();
+        self.internal_charge_account_storage(
+            &removed_listing.seller_id,
+            storage_before,
+            storage_after,
+        );
+        removed_listing
+    }
+
+    // takes the bid off the book and returns its escrow, the bid storage goes back to the
+    // bidder's deposit; the listing is stored here as the bid book state is part of it, later
+    // changes are up to the caller
+    pub(crate) fn internal_secondary_listing_refund_bid(
+        &mut self,
+        listing: &mut SecondaryListing,
+        bid_id: BidId,
+    ) -> Option<Bid> {
+        let storage_before = env::storage_usage();
+        let bid = listing.bids.remove(bid_id)?;
+        self.internal_remove_bid_from_account(
+            &bid.bidder_id,
+            ListingId::Secondary(listing.id.clone()),
+            bid.id,
+        );
+        self.secondary_listings_by_id.insert(&listing.id, listing);
+        let storage_after = env::storage_usage();
+        self.internal_charge_account_storage(&bid.bidder_id, storage_before, storage_after);
+        Promise::new(bid.bidder_id.clone()).transfer(bid.max_amount_yocto);
+        Some(bid)
+    }
+
     // add seconday listing to the set of fpos an seller offered
     // doesn't check if already there
     pub(crate) fn internal_add_secondary_listing_to_seller(
@@ -83,10 +202,20 @@ impl MarketplaceContract {
 }
 
 impl SecondaryListing {
+    // a bid must outbid the best one, the first one must be at least the minimum bid
+    pub(crate) fn acceptable_bid_yocto(&self) -> u128 {
+        let min_bid_yocto = self
+            .min_bid_yocto
+            .expect("Bids are not accepted for this listing");
+        self.bids.best().map_or(min_bid_yocto, |best_bid| {
+            self.rules.next_bid_yocto(best_bid.amount_yocto)
+        })
+    }
+
     pub(crate) fn update_status(&mut self) {
         let block_timestamp = env::block_timestamp() as i64;
 
-        if self.status == ListingStatus::Ended {
+        if self.status == ListingStatus::Ended || self.status == ListingStatus::Pending {
             return;
         }
 
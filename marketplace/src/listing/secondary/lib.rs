@@ -1,20 +1,22 @@
 use crate::*;
 use external::{NftMetadata, NftMutableMetadata};
 use super::super::{
-    bid::{Bid},
+    bid::{BidBook},
     status::{ListingStatus},
+    rules::ListingRules,
 };
 use std::{
     fmt,
 };
 
-use near_sdk::collections::Vector;
-
 #[derive(BorshStorageKey, BorshSerialize)]
 pub enum SecondaryListingStorageKey {
     Bids {
         listing_id_hash: CryptoHash,
     },
+    BidKeys {
+        listing_id_hash: CryptoHash,
+    },
 }
 
 #[derive(BorshDeserialize, BorshSerialize)]
@@ -38,7 +40,9 @@ pub struct SecondaryListingIdJson {
 pub struct SecondaryListing {
     pub id: SecondaryListingId,
     pub seller_id: AccountId,
-    pub approval_id: u64,
+    pub approval_id: u64,                       // ignored for listings in custody
+    pub is_in_custody: bool,                    // marketplace holds the token (nft_transfer_call) instead of an approval
+    pub collection_id: Option<NftCollectionId>, // known once the NFT contract confirms the token
     pub nft_metadata: NftMetadata,
     pub nft_mutable_metadata: NftMutableMetadata,
     pub price_yocto: Option<u128>,
@@ -46,8 +50,11 @@ pub struct SecondaryListing {
     pub start_timestamp: i64,                   // nanoseconds since 1970-01-01
     pub end_timestamp: Option<i64>,             // nanoseconds since 1970-01-01
     pub status: ListingStatus, // will be updated when any buyer transaction is mined
-    pub bids: Vector<Bid>,
+    pub bids: BidBook,
     pub next_bid_id: u64,
+    pub version: u64,                           // bumped on every seller update
+    pub seq: u64,                               // creation order, assigned when the listing is added
+    pub rules: ListingRules,                    // price and bid steps, fixed when the listing is added
 }
 
 impl fmt::Display for SecondaryListing {
@@ -4,6 +4,7 @@ mod buyer;
 pub mod enumeration;
 
 mod nft_callback;
+mod validate;
 // mod resolve;
-mod internal;
-mod config;
\ No newline at end of file
+pub(crate) mod internal;
+pub mod config;
\ No newline at end of file
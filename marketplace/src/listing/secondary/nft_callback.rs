@@ -1,23 +1,129 @@
 use crate::{
     external::{NftMetadata, NftMutableMetadata},
+    listing::{date::DateInput, rules::JsonListingRules},
     *,
 };
 
 use near_sdk::{
-    json_types::{U128},
+    json_types::{U128, U64},
     serde::{Deserialize},
+    serde_json::{json, Value},
+    PromiseOrValue,
 };
 
+#[cfg(test)]
+#[path = "nft_callback_tests.rs"]
+mod nft_callback_tests;
+
+// current version of the approval message format, see schema/nft_approval_msg.schema.json
+pub const NFT_APPROVAL_MSG_VERSION: u32 = 2;
+// oldest version still accepted
+pub const NFT_APPROVAL_MSG_VERSION_MIN: u32 = 1;
+
+// msg passed to nft_approve, f.ex.
+// {"version":2,"action":"add_buy_now_listing","token_metadata":{..},"token_mutable_metadata":{..},"price_yocto":"1000000000000000000000000"}
+// nft_transfer_call takes the same msg but only the add_* actions
+// the version is checked by parse_nft_approval_msg before the msg is decoded
+#[derive(Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+struct NftApprovalMsg {
+    #[serde(flatten)]
+    action: NftApprovalAction,
+}
+
 #[derive(Deserialize)]
 #[serde(crate = "near_sdk::serde")]
-struct SecondaryListingNftApprovalMsg {
-    pub action: String,
-    pub token_metadata: NftMetadata,
-    pub token_mutable_metadata: NftMutableMetadata,
-    pub price_yocto: Option<U128>,
-    pub min_bid_yocto: Option<U128>,       // if None then no proposals will be accepted
-    pub start_date: Option<String>,        // nanoseconds since 1970-01-01
-    pub end_date: Option<String>,          // nanoseconds since 1970-01-01
+#[serde(tag = "action", rename_all = "snake_case")]
+enum NftApprovalAction {
+    AddBuyNowListing {
+        token_metadata: NftMetadata,
+        token_mutable_metadata: NftMutableMetadata,
+        price_yocto: U128,
+        start_date: Option<DateInput>, // if missing the listing starts right away
+        end_date: Option<DateInput>,   // if missing the listing never ends
+        rules: Option<JsonListingRules>, // if missing, the marketplace defaults apply
+    },
+    AddAuction {
+        token_metadata: NftMetadata,
+        token_mutable_metadata: NftMutableMetadata,
+        min_bid_yocto: U128,
+        price_yocto: Option<U128>, // optional buy now price
+        start_date: Option<DateInput>,
+        end_date: DateInput,
+        rules: Option<JsonListingRules>,
+    },
+    // the new approval replaces the one the listing was created with
+    UpdateListing {
+        price_yocto: Option<U128>,
+        min_bid_yocto: Option<U128>,
+        end_date: Option<DateInput>,
+        token_mutable_metadata: Option<NftMutableMetadata>,
+    },
+    AcceptOffer {
+        offer: OfferRef,
+    },
+    // answers an offer on the token with a higher price, the bidder can take it without the
+    // owner being involved again
+    CounterOffer {
+        offer_id: U64,
+        price_yocto: U128,
+    },
+}
+
+// identifies the offer being accepted
+#[derive(Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum OfferRef {
+    // standing bid on the secondary listing of the approved token
+    ListingBid { bid_id: U64 },
+    // escrowed offer for any token of the contract or of the token's collection
+    Collection { offer_id: U64 },
+    // escrowed offer for the approved token
+    Token { offer_id: U64 },
+}
+
+// kind names the message in the errors, "approval" or "transfer"
+// older versions are upgraded before decoding, version 1 took the dates as RFC3339 strings
+fn parse_nft_approval_msg(msg: &str, kind: &str) -> NftApprovalMsg {
+    let mut msg: Value = near_sdk::serde_json::from_str(msg).unwrap_or_else(|err| {
+        env::panic_str(&format!("Could not decode {} message: {}", kind, err))
+    });
+    let version = msg["version"].as_u64().unwrap_or_else(|| {
+        env::panic_str(&format!(
+            "Could not decode {} message: missing or invalid version",
+            kind
+        ))
+    });
+    assert!(
+        version >= NFT_APPROVAL_MSG_VERSION_MIN as u64 && version <= NFT_APPROVAL_MSG_VERSION as u64,
+        "Unsupported {} message version {}, expected {}",
+        kind,
+        version,
+        NFT_APPROVAL_MSG_VERSION
+    );
+
+    if version == 1 {
+        for field in ["start_date", "end_date"] {
+            if let Some(date_str) = msg[field].as_str() {
+                msg[field] = json!({ "rfc3339": date_str });
+            }
+        }
+    }
+
+    near_sdk::serde_json::from_value(msg).unwrap_or_else(|err| {
+        env::panic_str(&format!("Could not decode {} message: {}", kind, err))
+    })
+}
+
+// view-only methods
+
+#[near_bindgen]
+impl MarketplaceContract {
+    // JSON schema of the nft_approve msg, for wallets and frontends building approvals
+    pub fn nft_approval_msg_schema(&self) -> String {
+        include_str!("../../../schema/nft_approval_msg.schema.json").to_string()
+    }
 }
 
 trait NonFungibleTokenApprovalsReceiver {
@@ -28,6 +134,18 @@ trait NonFungibleTokenApprovalsReceiver {
         approval_id: u64,
         msg: String,
     );
+
+    fn nft_on_revoke(&mut self, token_id: NftId, owner_id: AccountId);
+}
+
+trait NonFungibleTokenReceiver {
+    fn nft_on_transfer(
+        &mut self,
+        sender_id: AccountId,
+        previous_owner_id: AccountId,
+        token_id: NftId,
+        msg: String,
+    ) -> PromiseOrValue<bool>;
 }
 
 
@@ -52,22 +170,230 @@ impl NonFungibleTokenApprovalsReceiver for MarketplaceContract {
         // make sure the owner ID is the signer.
         assert_eq!(owner_id, signer_id, "owner_id should be signer_id");
 
-        let msg: SecondaryListingNftApprovalMsg =
-            near_sdk::serde_json::from_str(&msg).expect("Could not decode approval message");
+        // unknown actions fail here; our nft contract then revokes the approval, other nft
+        // contracts may keep it, but we hold no listing that would use it
+        let msg = parse_nft_approval_msg(&msg, "approval");
 
-        if msg.action == "add_listing" {
-            self.secondary_listing_add(
-                owner_id,
+        // a listing made earlier through an approval is replaced, its bidders get refunded; one
+        // in custody can't be, the token is ours until the listing is concluded
+        if let NftApprovalAction::AddBuyNowListing { .. } | NftApprovalAction::AddAuction { .. } =
+            msg.action
+        {
+            let listing_id = SecondaryListingId {
+                nft_contract_id: nft_contract_id.clone(),
+                token_id: token_id.clone(),
+            };
+            if let Some(listing) = self.secondary_listings_by_id.get(&listing_id) {
+                assert!(!listing.is_in_custody, "This token is already listed in custody");
+                self.internal_close_secondary_listing(&listing_id);
+            }
+        }
+
+        match msg.action {
+            NftApprovalAction::AddBuyNowListing {
+                token_metadata,
+                token_mutable_metadata,
+                price_yocto,
+                start_date,
+                end_date,
+                rules,
+            } => {
+                self.secondary_listing_add(
+                    owner_id,
+                    nft_contract_id,
+                    approval_id,
+                    false,
+                    token_id,
+                    token_metadata,
+                    token_mutable_metadata,
+                    Some(price_yocto),
+                    None,
+                    start_date,
+                    end_date,
+                    rules,
+                );
+            }
+            NftApprovalAction::AddAuction {
+                token_metadata,
+                token_mutable_metadata,
+                min_bid_yocto,
+                price_yocto,
+                start_date,
+                end_date,
+                rules,
+            } => {
+                self.secondary_listing_add(
+                    owner_id,
+                    nft_contract_id,
+                    approval_id,
+                    false,
+                    token_id,
+                    token_metadata,
+                    token_mutable_metadata,
+                    price_yocto,
+                    Some(min_bid_yocto),
+                    start_date,
+                    Some(end_date),
+                    rules,
+                );
+            }
+            NftApprovalAction::UpdateListing {
+                price_yocto,
+                min_bid_yocto,
+                end_date,
+                token_mutable_metadata,
+            } => {
+                self.internal_update_secondary_listing(
+                    &owner_id,
+                    SecondaryListingId {
+                        nft_contract_id,
+                        token_id,
+                    },
+                    Some(approval_id),
+                    price_yocto,
+                    min_bid_yocto,
+                    end_date,
+                    token_mutable_metadata,
+                );
+            }
+            NftApprovalAction::AcceptOffer { offer } => match offer {
+                OfferRef::ListingBid { bid_id } => {
+                    self.internal_secondary_listing_accept_bid(
+                        &owner_id,
+                        SecondaryListingId {
+                            nft_contract_id,
+                            token_id,
+                        },
+                        Some(approval_id),
+                        bid_id.0,
+                    );
+                }
+                OfferRef::Collection { offer_id } => {
+                    self.internal_accept_collection_offer(
+                        &owner_id,
+                        nft_contract_id,
+                        token_id,
+                        approval_id,
+                        offer_id.0,
+                    );
+                }
+                OfferRef::Token { offer_id } => {
+                    self.internal_accept_token_offer(
+                        &owner_id,
+                        nft_contract_id,
+                        token_id,
+                        approval_id,
+                        offer_id.0,
+                    );
+                }
+            },
+            NftApprovalAction::CounterOffer {
+                offer_id,
+                price_yocto,
+            } => self.internal_counter_token_offer(
+                &owner_id,
                 nft_contract_id,
-                approval_id,
                 token_id,
-                msg.token_metadata,
-                msg.token_mutable_metadata,
-                msg.price_yocto,
-                msg.min_bid_yocto,
-                msg.start_date,
-                msg.end_date
-            );
+                approval_id,
+                offer_id.0,
+                price_yocto.0,
+            ),
+        }
+    }
+
+    // called by NFT contracts which notify the marketplace when its approval is gone, either
+    // revoked by the owner or reset by a transfer; other NFT contracts rely on
+    // secondary_listing_validate instead
+    fn nft_on_revoke(&mut self, token_id: NftId, owner_id: AccountId) {
+        // the NFT contract is the authority on its own tokens
+        let nft_contract_id = env::predecessor_account_id();
+        self.internal_delist_token(&nft_contract_id, &token_id, &owner_id);
+    }
+}
+
+// custody mode: the owner transfers the token to the marketplace with nft_transfer_call and the
+// listing is created while we hold it, so the seller cannot move it while bids are open
+#[near_bindgen]
+impl NonFungibleTokenReceiver for MarketplaceContract {
+    // returns true if the NFT contract should give the token back to previous_owner_id; any
+    // panic here has the same effect
+    fn nft_on_transfer(
+        &mut self,
+        sender_id: AccountId,
+        previous_owner_id: AccountId,
+        token_id: NftId,
+        msg: String,
+    ) -> PromiseOrValue<bool> {
+        let nft_contract_id = env::predecessor_account_id();
+        let signer_id = env::signer_account_id();
+        //make sure that the signer isn't the predecessor - make sure it's a cross-contract call
+        assert_ne!(
+            nft_contract_id, signer_id,
+            "nft_on_transfer should only be called via cross-contract call"
+        );
+        assert_eq!(
+            sender_id, previous_owner_id,
+            "Only the owner can put the token in custody"
+        );
+
+        let msg = parse_nft_approval_msg(&msg, "transfer");
+
+        // a listing made earlier through an approval is replaced, its bidders get refunded
+        let listing_id = SecondaryListingId {
+            nft_contract_id: nft_contract_id.clone(),
+            token_id: token_id.clone(),
+        };
+        if self.secondary_listings_by_id.get(&listing_id).is_some() {
+            self.internal_close_secondary_listing(&listing_id);
         }
+
+        // the approval id is meaningless for tokens in custody
+        let promise = match msg.action {
+            NftApprovalAction::AddBuyNowListing {
+                token_metadata,
+                token_mutable_metadata,
+                price_yocto,
+                start_date,
+                end_date,
+                rules,
+            } => self.secondary_listing_add(
+                previous_owner_id,
+                nft_contract_id,
+                0,
+                true,
+                token_id,
+                token_metadata,
+                token_mutable_metadata,
+                Some(price_yocto),
+                None,
+                start_date,
+                end_date,
+                rules,
+            ),
+            NftApprovalAction::AddAuction {
+                token_metadata,
+                token_mutable_metadata,
+                min_bid_yocto,
+                price_yocto,
+                start_date,
+                end_date,
+                rules,
+            } => self.secondary_listing_add(
+                previous_owner_id,
+                nft_contract_id,
+                0,
+                true,
+                token_id,
+                token_metadata,
+                token_mutable_metadata,
+                price_yocto,
+                Some(min_bid_yocto),
+                start_date,
+                Some(end_date),
+                rules,
+            ),
+            _ => env::panic_str("Only listings can be added with nft_transfer_call"),
+        };
+        PromiseOrValue::Promise(promise)
     }
 }
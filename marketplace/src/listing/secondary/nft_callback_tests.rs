@@ -0,0 +1,432 @@
+#[cfg(test)]
+mod nft_callback_tests {
+    use super::super::{
+        parse_nft_approval_msg, NftApprovalAction, NonFungibleTokenApprovalsReceiver, OfferRef,
+        NFT_APPROVAL_MSG_VERSION,
+    };
+    use crate::{
+        listing::{date::DateInput, query::ListingSort},
+        test_utils::*,
+        *,
+    };
+    use near_sdk::json_types::U128;
+    use near_sdk::serde_json::{self, json, Value};
+    use near_sdk::testing_env;
+
+    fn token_metadata() -> Value {
+        json!({"title": "Collection 0", "media": "https://eneftigo.com/media/0.png"})
+    }
+
+    fn parse(msg: Value) -> NftApprovalAction {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        parse_nft_approval_msg(&msg.to_string(), "approval").action
+    }
+
+    /* parser */
+
+    #[test]
+    fn test_parse_add_buy_now_listing() {
+        let action = parse(json!({
+            "version": NFT_APPROVAL_MSG_VERSION,
+            "action": "add_buy_now_listing",
+            "token_metadata": token_metadata(),
+            "token_mutable_metadata": {},
+            "price_yocto": "1000000000000000000000000",
+            "end_date": {"duration_ms": "3600000"}
+        }));
+        assert!(matches!(
+            action,
+            NftApprovalAction::AddBuyNowListing { price_yocto, start_date: None, end_date: Some(_), rules: None, .. }
+                if price_yocto.0 == ONE_NEAR
+        ));
+    }
+
+    #[test]
+    fn test_parse_add_auction() {
+        let action = parse(json!({
+            "version": NFT_APPROVAL_MSG_VERSION,
+            "action": "add_auction",
+            "token_metadata": token_metadata(),
+            "token_mutable_metadata": {"aux_audio_url": null},
+            "min_bid_yocto": "500000000000000000000000",
+            "start_date": {"unix_ms": "1642821655000"},
+            "end_date": {"rfc3339": "2022-01-22T11:20:55+08:00"}
+        }));
+        assert!(matches!(
+            action,
+            NftApprovalAction::AddAuction { min_bid_yocto, price_yocto: None, start_date: Some(_), .. }
+                if min_bid_yocto.0 == ONE_NEAR / 2
+        ));
+    }
+
+    #[test]
+    fn test_parse_update_listing() {
+        let action = parse(json!({
+            "version": NFT_APPROVAL_MSG_VERSION,
+            "action": "update_listing",
+            "price_yocto": "2000000000000000000000000"
+        }));
+        assert!(matches!(
+            action,
+            NftApprovalAction::UpdateListing { price_yocto: Some(price_yocto), min_bid_yocto: None, end_date: None, token_mutable_metadata: None }
+                if price_yocto.0 == 2 * ONE_NEAR
+        ));
+    }
+
+    #[test]
+    fn test_parse_accept_offer() {
+        let action = parse(json!({
+            "version": NFT_APPROVAL_MSG_VERSION,
+            "action": "accept_offer",
+            "offer": {"type": "listing_bid", "bid_id": "3"}
+        }));
+        assert!(matches!(
+            action,
+            NftApprovalAction::AcceptOffer { offer: OfferRef::ListingBid { bid_id } } if bid_id.0 == 3
+        ));
+
+        let action = parse(json!({
+            "version": NFT_APPROVAL_MSG_VERSION,
+            "action": "accept_offer",
+            "offer": {"type": "collection", "offer_id": "4"}
+        }));
+        assert!(matches!(
+            action,
+            NftApprovalAction::AcceptOffer { offer: OfferRef::Collection { offer_id } } if offer_id.0 == 4
+        ));
+
+        let action = parse(json!({
+            "version": NFT_APPROVAL_MSG_VERSION,
+            "action": "accept_offer",
+            "offer": {"type": "token", "offer_id": "5"}
+        }));
+        assert!(matches!(
+            action,
+            NftApprovalAction::AcceptOffer { offer: OfferRef::Token { offer_id } } if offer_id.0 == 5
+        ));
+    }
+
+    #[test]
+    fn test_parse_counter_offer() {
+        let action = parse(json!({
+            "version": NFT_APPROVAL_MSG_VERSION,
+            "action": "counter_offer",
+            "offer_id": "7",
+            "price_yocto": "3000000000000000000000000"
+        }));
+        assert!(matches!(
+            action,
+            NftApprovalAction::CounterOffer { offer_id, price_yocto }
+                if offer_id.0 == 7 && price_yocto.0 == 3 * ONE_NEAR
+        ));
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Could not decode approval message"#)]
+    fn test_parse_unknown_action() {
+        parse(json!({
+            "version": NFT_APPROVAL_MSG_VERSION,
+            "action": "add_raffle"
+        }));
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Could not decode approval message"#)]
+    fn test_parse_missing_field() {
+        parse(json!({
+            "version": NFT_APPROVAL_MSG_VERSION,
+            "action": "add_auction",
+            "token_metadata": token_metadata(),
+            "token_mutable_metadata": {},
+            "min_bid_yocto": "500000000000000000000000"
+        }));
+    }
+
+    /* version check */
+
+    #[test]
+    #[should_panic(expected = r#"Unsupported approval message version 3, expected 2"#)]
+    fn test_parse_newer_version() {
+        parse(json!({
+            "version": 3,
+            "action": "update_listing"
+        }));
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Unsupported transfer message version 0, expected 2"#)]
+    fn test_parse_transfer_msg_version() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        parse_nft_approval_msg(
+            &json!({"version": 0, "action": "update_listing"}).to_string(),
+            "transfer",
+        );
+    }
+
+    #[test]
+    fn test_parse_v1_string_dates() {
+        let action = parse(json!({
+            "version": 1,
+            "action": "add_auction",
+            "token_metadata": token_metadata(),
+            "token_mutable_metadata": {},
+            "min_bid_yocto": "500000000000000000000000",
+            "start_date": "2022-01-21T11:20:55+08:00",
+            "end_date": "2022-01-22T11:20:55+08:00"
+        }));
+        assert!(matches!(
+            action,
+            NftApprovalAction::AddAuction {
+                start_date: Some(DateInput::Rfc3339(start_date)),
+                end_date: DateInput::Rfc3339(end_date),
+                ..
+            } if start_date == "2022-01-21T11:20:55+08:00" && end_date == "2022-01-22T11:20:55+08:00"
+        ));
+
+        let action = parse(json!({
+            "version": 1,
+            "action": "update_listing",
+            "end_date": "2022-01-22T11:20:55+08:00"
+        }));
+        assert!(matches!(
+            action,
+            NftApprovalAction::UpdateListing {
+                end_date: Some(DateInput::Rfc3339(_)),
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Could not decode approval message"#)]
+    fn test_parse_missing_version() {
+        parse(json!({"action": "update_listing"}));
+    }
+
+    /* schema */
+
+    fn schema() -> Value {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        serde_json::from_str(&marketplace().nft_approval_msg_schema()).unwrap()
+    }
+
+    fn consts(variants: &Value, tag: &str) -> Vec<String> {
+        variants
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|variant| {
+                variant["properties"][tag]["const"]
+                    .as_str()
+                    .unwrap()
+                    .to_string()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_schema_version() {
+        assert_eq!(
+            schema()["properties"]["version"]["const"],
+            json!(NFT_APPROVAL_MSG_VERSION)
+        );
+    }
+
+    #[test]
+    fn test_schema_actions() {
+        let schema = schema();
+        assert_eq!(
+            consts(&schema["oneOf"], "action"),
+            vec![
+                "add_buy_now_listing",
+                "add_auction",
+                "update_listing",
+                "accept_offer",
+                "counter_offer"
+            ]
+        );
+        assert_eq!(
+            consts(&schema["definitions"]["offer"]["oneOf"], "type"),
+            vec!["listing_bid", "collection", "token"]
+        );
+    }
+
+    // every action of the schema, built from its required properties, is accepted
+    #[test]
+    fn test_schema_actions_parse() {
+        let schema = schema();
+        let samples = json!({
+            "token_metadata": token_metadata(),
+            "token_mutable_metadata": {},
+            "price_yocto": "1000000000000000000000000",
+            "min_bid_yocto": "500000000000000000000000",
+            "end_date": {"duration_ms": "3600000"},
+            "offer": {"type": "token", "offer_id": "1"},
+            "offer_id": "1"
+        });
+        for variant in schema["oneOf"].as_array().unwrap() {
+            let mut msg = json!({ "version": NFT_APPROVAL_MSG_VERSION });
+            for property in variant["required"].as_array().unwrap() {
+                let property = property.as_str().unwrap();
+                msg[property] = if property == "action" {
+                    variant["properties"]["action"]["const"].clone()
+                } else {
+                    samples[property].clone()
+                };
+            }
+            parse(msg);
+        }
+    }
+
+    /* revoke hook */
+
+    const TOKEN_ID: &str = "0:1";
+
+    fn revoke_setup(is_in_custody: bool) -> (MarketplaceContract, SecondaryListingId) {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        let listing_id = add_secondary_listing(
+            &mut marketplace,
+            TOKEN_ID,
+            Some(PRICE_YOCTO),
+            Some(MIN_BID_YOCTO),
+            is_in_custody,
+        );
+        set_context(BIDDER_ACCOUNT_ID, NOW, MIN_BID_YOCTO);
+        marketplace.secondary_listing_place_bid(
+            account(NFT_CONTRACT_ID),
+            TOKEN_ID.to_string(),
+            U128(MIN_BID_YOCTO),
+        );
+        (marketplace, listing_id)
+    }
+
+    #[test]
+    fn test_nft_on_revoke_delists() {
+        let (mut marketplace, listing_id) = revoke_setup(false);
+
+        set_context(NFT_CONTRACT_ID, NOW, 0);
+        marketplace.nft_on_revoke(TOKEN_ID.to_string(), account(SELLER_ACCOUNT_ID));
+
+        assert!(marketplace
+            .secondary_listings_by_id
+            .get(&listing_id)
+            .is_none());
+        assert!(marketplace
+            .bids_by_account(account(BIDDER_ACCOUNT_ID), None, None)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_nft_on_revoke_ignores_other_owner() {
+        let (mut marketplace, listing_id) = revoke_setup(false);
+
+        // a stale notification about an earlier owner
+        set_context(NFT_CONTRACT_ID, NOW, 0);
+        marketplace.nft_on_revoke(TOKEN_ID.to_string(), account(BUYER_ACCOUNT_ID));
+
+        assert!(marketplace
+            .secondary_listings_by_id
+            .get(&listing_id)
+            .is_some());
+    }
+
+    #[test]
+    fn test_nft_on_revoke_ignores_custody_listing() {
+        let (mut marketplace, listing_id) = revoke_setup(true);
+
+        set_context(NFT_CONTRACT_ID, NOW, 0);
+        marketplace.nft_on_revoke(TOKEN_ID.to_string(), account(SELLER_ACCOUNT_ID));
+
+        assert!(marketplace
+            .secondary_listings_by_id
+            .get(&listing_id)
+            .is_some());
+    }
+
+    #[test]
+    fn test_nft_on_revoke_ignores_other_contract() {
+        let (mut marketplace, listing_id) = revoke_setup(false);
+
+        // only the NFT contract of the token can delist it
+        set_context(BUYER_ACCOUNT_ID, NOW, 0);
+        marketplace.nft_on_revoke(TOKEN_ID.to_string(), account(SELLER_ACCOUNT_ID));
+
+        assert!(marketplace
+            .secondary_listings_by_id
+            .get(&listing_id)
+            .is_some());
+    }
+
+    /* re-listing */
+
+    // the NFT contract passes the approval on, the owner signed it
+    fn approve(marketplace: &mut MarketplaceContract, msg: Value) {
+        let mut context = get_context(NFT_CONTRACT_ID, NOW, 0);
+        context.signer_account_id = account(SELLER_ACCOUNT_ID);
+        testing_env!(context);
+        marketplace.nft_on_approve(
+            TOKEN_ID.to_string(),
+            account(SELLER_ACCOUNT_ID),
+            1,
+            msg.to_string(),
+        );
+    }
+
+    fn buy_now_msg(price_yocto: Balance) -> Value {
+        json!({
+            "version": NFT_APPROVAL_MSG_VERSION,
+            "action": "add_buy_now_listing",
+            "token_metadata": token_metadata(),
+            "token_mutable_metadata": {},
+            "price_yocto": U128(price_yocto)
+        })
+    }
+
+    fn index_len(marketplace: &MarketplaceContract, sort: ListingSort) -> usize {
+        marketplace
+            .secondary_listing_index
+            .scan(&sort, None)
+            .count()
+    }
+
+    #[test]
+    fn test_nft_on_approve_replaces_listing() {
+        let (mut marketplace, listing_id) = revoke_setup(false);
+
+        approve(&mut marketplace, buy_now_msg(2 * PRICE_YOCTO));
+
+        // the new listing waits for the token lookup, the auction is gone with its bid
+        let listing = marketplace
+            .secondary_listings_by_id
+            .get(&listing_id)
+            .unwrap();
+        assert_eq!(listing.price_yocto, Some(2 * PRICE_YOCTO));
+        assert_eq!(listing.approval_id, 1);
+        assert!(listing.bids.is_empty());
+        assert!(marketplace
+            .bids_by_account(account(BIDDER_ACCOUNT_ID), None, None)
+            .is_empty());
+
+        // no index entries of the auction are left behind
+        assert_eq!(index_len(&marketplace, ListingSort::CreatedAsc), 1);
+        assert_eq!(index_len(&marketplace, ListingSort::PriceAsc), 1);
+        assert_eq!(index_len(&marketplace, ListingSort::EndAsc), 0);
+        assert!(marketplace.floor_by_contract.iter().next().is_none());
+        assert!(marketplace.floor_by_collection.iter().next().is_none());
+        assert_eq!(
+            marketplace
+                .secondary_listings_by_seller(account(SELLER_ACCOUNT_ID), None, None)
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = r#"This token is already listed in custody"#)]
+    fn test_nft_on_approve_custody_listing() {
+        let (mut marketplace, _) = revoke_setup(true);
+
+        approve(&mut marketplace, buy_now_msg(2 * PRICE_YOCTO));
+    }
+}
@@ -1,40 +1,56 @@
 use crate::{
-    // constants::*,
-    external::{NftMetadata, NftMutableMetadata},
+    constants::NO_DEPOSIT,
+    external::{nft_contract, JsonNft, NftMetadata, NftMutableMetadata},
     listing::{
-        constants::*,
-        secondary::{
-            config::*, internal::hash_secondary_listing_id, lib::SecondaryListingStorageKey,
-        },
+        bid::{BidBook, BidId},
+        date::DateInput,
+        secondary::{internal::hash_secondary_listing_id, lib::SecondaryListingStorageKey},
+        id::ListingId,
+        rules::JsonListingRules,
+        sale::Sale,
         status::ListingStatus,
+        terms::{ListingTerms, ListingTermsUpdate},
     },
     *,
 };
-use chrono::DateTime;
-use near_sdk::{
-    collections::Vector,
-    json_types::{U128},
-};
+use near_sdk::json_types::{U128, U64};
+use near_sdk::PromiseResult;
 use url::Url;
 
 // const NFT_MAKE_COLLECTION_GAS: Gas = Gas(5_000_000_000_000); // highest measured 3_920_035_683_889
 // const NFT_MAKE_COLLECTION_COMPLETION_GAS: Gas = Gas(6_000_000_000_000); // highest measured 5_089_357_803_858
+const NFT_TOKEN_GAS: Gas = Gas(5_000_000_000_000); // a view, the token and its metadata are read
+const NFT_TOKEN_COMPLETION_GAS: Gas = Gas(20_000_000_000_000); // writes the listing and its floor entry, or removes it with its index entries
+
+const NFT_TRANSFER_GAS: Gas = Gas(15_000_000_000_000); // TODO: measure
+const SECONDARY_LISTING_ACCEPT_BID_COMPLETION_GAS: Gas = Gas(20_000_000_000_000); // TODO: measure, refunds the remaining bids
+const SECONDARY_LISTING_CONCLUDE_COMPLETION_GAS: Gas = Gas(10_000_000_000_000); // removes the listing and its index entries, the bids are refunded before
+
+#[cfg(test)]
+#[path = "seller_tests.rs"]
+mod seller_tests;
 
 #[near_bindgen]
 impl MarketplaceContract {
+    // the listing is stored as pending and the token is looked up on the NFT contract, it only
+    // goes live once nft_token confirms the owner, the approval and the metadata
+    // for approval listings the returned promise resolves to true if the listing went live, for
+    // listings in custody it resolves to whether the token must go back (nft_on_transfer result)
     pub(crate) fn secondary_listing_add(
         &mut self,
         owner_id: AccountId,
         nft_contract_id: AccountId,
         approval_id: u64,
+        is_in_custody: bool,
         token_id: NftId,
         nft_metadata: NftMetadata,
         nft_mutable_metadata: NftMutableMetadata,
         price_yocto: Option<U128>,
         min_bid_yocto: Option<U128>, // if None, only buy now is allowed
-        start_date: Option<String>, // if missing, it'll start accepting bids when this transaction is mined
-        end_date: Option<String>,
-    ) {
+        start_date: Option<DateInput>, // if missing, it'll start accepting bids when this transaction is mined
+        end_date: Option<DateInput>,   // a duration counts from the start date
+        rules: Option<JsonListingRules>, // if missing, the marketplace defaults apply
+    ) -> Promise {
         let price_yocto = price_yocto.map(|p| p.0);
         let min_bid_yocto = min_bid_yocto.map(|b| b.0);
 
@@ -44,7 +60,7 @@ impl MarketplaceContract {
         let storage_byte_cost = env::storage_byte_cost();
         let current_deposit: Balance = self.storage_deposits.get(&owner_id).unwrap_or(0);
         let marketplace_worst_case_storage_cost =
-            SECONDARY_LISTING_ADD_STORAGE_MAX as Balance * storage_byte_cost;
+            self.config.storage_limits.secondary_listing_add_storage_max as Balance * storage_byte_cost;
         let worst_case_storage_cost = marketplace_worst_case_storage_cost;
         assert!(
             current_deposit >= worst_case_storage_cost,
@@ -54,46 +70,28 @@ impl MarketplaceContract {
 
         // Has title of ok length?
         let title = nft_metadata.title.clone().expect("Token must have a title");
+        let title_len_max = self.config.listing_limits.title_len_max;
         assert!(
-            title.len() <= MAX_LISTING_TITLE_LEN,
+            title.len() <= title_len_max as usize,
             "Title length cannot exceed {} characters",
-            MAX_LISTING_TITLE_LEN
+            title_len_max
         );
 
         // Is URL present and valid?
         let media_url = nft_metadata.media.clone().expect("Missing NFT media");
         assert!(Url::parse(&media_url).is_ok(), "NFT media URL is invalid");
 
+        // the rules are validated against the marketplace bounds, defaults apply if missing
+        let rules = self.config.listing_rules(rules);
+
         // Is the price ok?
         if let Some(price_yocto) = price_yocto {
-            assert!(
-                price_yocto >= MIN_PRICE_YOCTO,
-                "Price cannot be lower than {} yoctoNear",
-                MIN_PRICE_YOCTO
-            );
-
-            // Is the price multiple of marketplace price unit?
-            assert!(
-                price_yocto % PRICE_STEP_YOCTO == 0,
-                "Price must be integer multiple of {} yoctoNear",
-                PRICE_STEP_YOCTO
-            );
+            rules.assert_price(price_yocto);
         }
 
         // Is min bid ok?
         if let Some(min_bid_yocto) = min_bid_yocto {
-            assert!(
-                min_bid_yocto >= MIN_BID_YOCTO,
-                "Bid cannot be lower than {} yoctoNear",
-                MIN_BID_YOCTO
-            );
-
-            // Is the price multiple of marketplace price unit?
-            assert!(
-                min_bid_yocto % BID_STEP_YOCTO == 0,
-                "Bid must be integer multiple of {} yoctoNear",
-                BID_STEP_YOCTO
-            );
+            rules.assert_min_bid(min_bid_yocto);
         }
 
         // the logic here is:
@@ -105,11 +103,8 @@ impl MarketplaceContract {
         let is_accepting_bids = min_bid_yocto.is_some();
         let current_block_timestamp = env::block_timestamp() as i64;
 
-        let start_timestamp = if let Some(start_date_str) = start_date {
-            let start_datetime = DateTime::parse_from_rfc3339(&start_date_str).expect(
-                "Wrong date format. Must be ISO8601/RFC3339 (f.ex. 2022-01-22T11:20:55+08:00)",
-            );
-            let start_timestamp = start_datetime.timestamp_nanos();
+        let start_timestamp = if let Some(start_date) = start_date {
+            let start_timestamp = start_date.to_timestamp(current_block_timestamp);
             assert!(
                 start_timestamp >= current_block_timestamp,
                 "Start date into the past"
@@ -119,12 +114,8 @@ impl MarketplaceContract {
             current_block_timestamp
         };
 
-        let end_timestamp: Option<i64> = if let Some(end_date_str) = end_date {
-            let end_datetime = DateTime::parse_from_rfc3339(&end_date_str).expect(
-                "Wrong date format. Must be ISO8601/RFC3339 (f.ex. 2022-01-22T11:20:55+08:00)",
-            );
-            let end_timestamp = end_datetime.timestamp_nanos();
-            let current_block_timestamp = env::block_timestamp() as i64;
+        let end_timestamp: Option<i64> = if let Some(end_date) = end_date {
+            let end_timestamp = end_date.to_timestamp(start_timestamp);
             assert!(
                 end_timestamp >= current_block_timestamp,
                 "End date into the past"
@@ -140,12 +131,12 @@ impl MarketplaceContract {
             // end timestamp set
             let duration = end_timestamp - start_timestamp;
             assert!(
-                duration >= SECONDARY_LISTING_MIN_DURATION_NANO,
+                duration >= self.config.listing_limits.secondary_listing_min_duration_nano,
                 "Listing duration too short"
             );
             if is_accepting_bids {
                 assert!(
-                    duration <= SECONDARY_LISTING_MAX_DURATION_NANO,
+                    duration <= self.config.listing_limits.secondary_listing_max_duration_nano,
                     "Listing duration too long"
                 );
             }
@@ -161,28 +152,36 @@ impl MarketplaceContract {
             token_id,
         };
         let listing_id_hash = hash_secondary_listing_id(&listing_id);
-        let listing = SecondaryListing {
+        let mut listing = SecondaryListing {
             id: listing_id,
             seller_id: owner_id.clone(),
             approval_id,
+            is_in_custody,
+            collection_id: None,
             nft_metadata,
             nft_mutable_metadata,
             price_yocto,
             min_bid_yocto,
             start_timestamp,
             end_timestamp,
-            status: ListingStatus::Unstarted,
-            bids: Vector::new(
+            status: ListingStatus::Pending,
+            bids: BidBook::new(
                 SecondaryListingStorageKey::Bids { listing_id_hash }
                     .try_to_vec()
                     .unwrap(),
+                SecondaryListingStorageKey::BidKeys { listing_id_hash }
+                    .try_to_vec()
+                    .unwrap(),
             ),
             next_bid_id: 0,
+            version: 0,
+            seq: 0, // assigned by internal_add_secondary_listing
+            rules,
         };
 
         let marketplace_storage_before = env::storage_usage();
 
-        self.internal_add_secondary_listing(&listing);
+        self.internal_add_secondary_listing(&mut listing);
 
         let storage_byte_cost = env::storage_byte_cost();
         let marketplace_storage = env::storage_usage() - marketplace_storage_before;
@@ -199,8 +198,254 @@ impl MarketplaceContract {
             0 // should never happen; TODO: log warning to review storage deposit logic
         };
         self.storage_deposits.insert(&owner_id, &updated_deposit);
+
+        let nft_token_promise = nft_contract::nft_token(
+            listing.id.token_id.clone(),
+            listing.id.nft_contract_id.clone(),
+            NO_DEPOSIT,
+            NFT_TOKEN_GAS,
+        );
+        if is_in_custody {
+            nft_token_promise.then(ext_self_nft::secondary_listing_custody_nft_token_completion(
+                listing.id.nft_contract_id,
+                listing.id.token_id,
+                env::current_account_id(),
+                NO_DEPOSIT,
+                NFT_TOKEN_COMPLETION_GAS,
+            ))
+        } else {
+            nft_token_promise.then(ext_self_nft::secondary_listing_add_nft_token_completion(
+                listing.id.nft_contract_id,
+                listing.id.token_id,
+                env::current_account_id(),
+                NO_DEPOSIT,
+                NFT_TOKEN_COMPLETION_GAS,
+            ))
+        }
+    }
+
+    // modifies a live listing; only the fields that are passed in are changed
+    // once bids have been placed the seller can neither lower the minimum bid nor
+    // shorten the listing; returns the new listing version
+    pub fn secondary_listing_update(
+        &mut self,
+        nft_contract_id: AccountId,
+        token_id: NftId,
+        price_yocto: Option<U128>,
+        min_bid_yocto: Option<U128>,
+        end_date: Option<DateInput>, // a duration counts from the listing start
+        nft_mutable_metadata: Option<NftMutableMetadata>,
+    ) -> U64 {
+        self.internal_update_secondary_listing(
+            &env::predecessor_account_id(),
+            SecondaryListingId {
+                nft_contract_id,
+                token_id,
+            },
+            None,
+            price_yocto,
+            min_bid_yocto,
+            end_date,
+            nft_mutable_metadata,
+        )
+    }
+
+    // approval_id is set when the update comes with a new approval (via nft_on_approve)
+    pub(crate) fn internal_update_secondary_listing(
+        &mut self,
+        seller_id: &AccountId,
+        listing_id: SecondaryListingId,
+        approval_id: Option<u64>,
+        price_yocto: Option<U128>,
+        min_bid_yocto: Option<U128>,
+        end_date: Option<DateInput>, // a duration counts from the listing start
+        nft_mutable_metadata: Option<NftMutableMetadata>,
+    ) -> U64 {
+        let mut listing = self
+            .secondary_listings_by_id
+            .get(&listing_id)
+            .expect("Could not find this listing");
+        listing.update_status();
+
+        // make sure it's the seller who's calling this
+        assert!(
+            seller_id == &listing.seller_id,
+            "Only the seller can update the listing"
+        );
+
+        assert!(
+            listing.status != ListingStatus::Ended && listing.status != ListingStatus::Pending,
+            "This listing is {}",
+            listing.status.as_str()
+        );
+
+        // needed to update the listing index
+        let old_price_yocto = listing.price_yocto;
+        let old_end_timestamp = listing.end_timestamp;
+
+        let mut terms = ListingTerms {
+            price_yocto: listing.price_yocto,
+            min_bid_yocto: listing.min_bid_yocto,
+            start_timestamp: listing.start_timestamp,
+            end_timestamp: listing.end_timestamp,
+        };
+        terms.update(
+            ListingTermsUpdate {
+                price_yocto: price_yocto.map(|p| p.0),
+                min_bid_yocto: min_bid_yocto.map(|b| b.0),
+                end_date,
+            },
+            &listing.rules,
+            &listing.bids,
+            self.config.listing_limits.secondary_listing_min_duration_nano,
+            self.config.listing_limits.secondary_listing_max_duration_nano,
+        );
+        listing.price_yocto = terms.price_yocto;
+        listing.min_bid_yocto = terms.min_bid_yocto;
+        listing.end_timestamp = terms.end_timestamp;
+
+        if let Some(nft_mutable_metadata) = nft_mutable_metadata {
+            listing.nft_mutable_metadata = nft_mutable_metadata;
+        }
+
+        if let Some(approval_id) = approval_id {
+            listing.approval_id = approval_id;
+        }
+
+        listing.version += 1;
+
+        let storage_before = env::storage_usage();
+        self.secondary_listings_by_id.insert(&listing_id, &listing);
+        self.secondary_listing_index
+            .remove(listing.seq, old_price_yocto, old_end_timestamp);
+        self.secondary_listing_index.insert(
+            listing.seq,
+            listing.price_yocto,
+            listing.end_timestamp,
+            &listing_id,
+        );
+        self.internal_remove_listing_from_floor(
+            &listing_id.nft_contract_id,
+            listing.collection_id,
+            old_price_yocto,
+            listing.seq,
+        );
+        self.internal_add_listing_to_floor(
+            &ListingId::Secondary(listing_id.clone()),
+            &listing_id.nft_contract_id,
+            listing.collection_id,
+            listing.price_yocto,
+            listing.seq,
+        );
+        let storage_after = env::storage_usage();
+
+        // mutable metadata may have changed size, settle the difference with seller's deposit
+        self.internal_charge_account_storage(seller_id, storage_before, storage_after);
+
+        U64(listing.version)
+    }
+
+    // sells the token to the given bidder; tokens in custody are transferred by the marketplace
+    // as their owner, approval listings use the approval they were listed with, nft_approve with
+    // an accept_offer msg passes a fresh one instead
+    pub fn secondary_listing_accept_bid(
+        &mut self,
+        nft_contract_id: AccountId,
+        token_id: NftId,
+        bid_id: U64,
+    ) -> Promise {
+        self.internal_secondary_listing_accept_bid(
+            &env::predecessor_account_id(),
+            SecondaryListingId {
+                nft_contract_id,
+                token_id,
+            },
+            None,
+            bid_id.0,
+        )
     }
 
+    // the bid amount is already escrowed by the marketplace; the bid leaves the book right away,
+    // it's refunded if the transfer fails
+    pub(crate) fn internal_secondary_listing_accept_bid(
+        &mut self,
+        seller_id: &AccountId,
+        listing_id: SecondaryListingId,
+        approval_id: Option<u64>, // replaces the one the listing was created with
+        bid_id: BidId,
+    ) -> Promise {
+        let mut listing = self
+            .secondary_listings_by_id
+            .get(&listing_id)
+            .expect("Could not find this listing");
+        listing.update_status();
+
+        assert!(
+            seller_id == &listing.seller_id,
+            "Only the seller can accept bids"
+        );
+        assert!(
+            listing.status == ListingStatus::Running || listing.status == ListingStatus::Ended,
+            "This listing is {}",
+            listing.status.as_str()
+        );
+
+        // the bid storage was paid for by the bidder
+        let storage_before = env::storage_usage();
+        let bid = listing.bids.remove(bid_id).expect("Could not find bid");
+        self.internal_remove_bid_from_account(
+            &bid.bidder_id,
+            ListingId::Secondary(listing_id.clone()),
+            bid.id,
+        );
+        if let Some(approval_id) = approval_id {
+            assert!(
+                !listing.is_in_custody,
+                "The token is in custody, there's no approval to replace"
+            );
+            listing.approval_id = approval_id;
+        }
+        self.secondary_listings_by_id.insert(&listing_id, &listing);
+        let storage_after = env::storage_usage();
+        self.internal_charge_account_storage(&bid.bidder_id, storage_before, storage_after);
+
+        // unused escrow of a proxy bid goes back right away
+        if bid.max_amount_yocto > bid.amount_yocto {
+            Promise::new(bid.bidder_id.clone()).transfer(bid.max_amount_yocto - bid.amount_yocto);
+        }
+
+        // the marketplace owns the token in custody, no approval involved
+        let approval_id = if listing.is_in_custody {
+            None
+        } else {
+            Some(listing.approval_id)
+        };
+        nft_contract::nft_transfer(
+            bid.bidder_id.clone(),
+            listing_id.token_id.clone(),
+            approval_id,
+            None,
+            listing_id.nft_contract_id.clone(),
+            1,
+            NFT_TRANSFER_GAS,
+        )
+        .then(ext_self_nft::secondary_listing_accept_bid_completion(
+            listing_id.nft_contract_id,
+            listing_id.token_id,
+            listing.seller_id,
+            listing.collection_id.map(U64),
+            bid.bidder_id,
+            U128(bid.amount_yocto),
+            env::current_account_id(),
+            NO_DEPOSIT,
+            SECONDARY_LISTING_ACCEPT_BID_COMPLETION_GAS,
+        ))
+    }
+
+    // standing bids are refunded; tokens in custody go back to the seller, the seller must
+    // attach 1yN for nft_transfer; such a listing stays pending until the transfer resolves and
+    // is restored if the token couldn't be returned
+    #[payable]
     pub fn secondary_listing_conclude(
         &mut self,
         owner_id: AccountId,
@@ -218,24 +463,293 @@ impl MarketplaceContract {
             token_id,
         };
 
-        let listing = self
+        let mut listing = self
             .secondary_listings_by_id
             .get(&listing_id)
             .expect("Could not find this listing");
+        // the token could still be on its way back to the seller
+        assert!(
+            !(listing.is_in_custody && listing.status == ListingStatus::Pending),
+            "This listing is {}",
+            listing.status.as_str()
+        );
 
-        let storage_before = env::storage_usage();
+        if !listing.is_in_custody {
+            self.internal_close_secondary_listing(&listing_id);
+            return;
+        }
 
-        let removed_listing = self.internal_remove_secondary_listing(&listing_id);
+        assert_eq!(
+            env::attached_deposit(),
+            1,
+            "Attach 1yN to get the token back from custody"
+        );
+        let bid_ids: Vec<BidId> = listing.bids.iter().map(|bid| bid.id).collect();
+        for bid_id in bid_ids {
+            self.internal_secondary_listing_refund_bid(&mut listing, bid_id);
+        }
+        listing.status = ListingStatus::Pending;
+        self.secondary_listings_by_id.insert(&listing_id, &listing);
 
-        let storage_after = env::storage_usage();
-        let storage_freed = storage_before - storage_after;
-        let refunded_deposit = storage_freed as Balance * env::storage_byte_cost();
-        let current_deposit = self
-            .storage_deposits
-            .get(&removed_listing.seller_id)
-            .expect("Could not find seller's storage deposit record");
-        let updated_deposit = current_deposit + refunded_deposit;
-        self.storage_deposits
-            .insert(&removed_listing.seller_id, &(updated_deposit));
+        nft_contract::nft_transfer(
+            listing.seller_id,
+            listing_id.token_id.clone(),
+            None,
+            None,
+            listing_id.nft_contract_id.clone(),
+            1,
+            NFT_TRANSFER_GAS,
+        )
+        .then(ext_self_nft::secondary_listing_conclude_completion(
+            listing_id.nft_contract_id,
+            listing_id.token_id,
+            env::current_account_id(),
+            NO_DEPOSIT,
+            SECONDARY_LISTING_CONCLUDE_COMPLETION_GAS,
+        ));
+    }
+}
+
+#[ext_contract(ext_self_nft)]
+trait SecondaryListingSellerCallback {
+    fn secondary_listing_add_nft_token_completion(
+        &mut self,
+        nft_contract_id: AccountId,
+        token_id: NftId,
+    ) -> bool;
+
+    fn secondary_listing_custody_nft_token_completion(
+        &mut self,
+        nft_contract_id: AccountId,
+        token_id: NftId,
+    ) -> bool;
+
+    fn secondary_listing_accept_bid_completion(
+        &mut self,
+        nft_contract_id: AccountId,
+        token_id: NftId,
+        seller_id: AccountId,
+        collection_id: Option<U64>,
+        bidder_id: AccountId,
+        amount_yocto: U128,
+    ) -> bool;
+
+    fn secondary_listing_conclude_completion(
+        &mut self,
+        nft_contract_id: AccountId,
+        token_id: NftId,
+    ) -> bool;
+}
+
+trait SecondaryListingSellerCallback {
+    fn secondary_listing_add_nft_token_completion(
+        &mut self,
+        nft_contract_id: AccountId,
+        token_id: NftId,
+    ) -> bool;
+
+    fn secondary_listing_custody_nft_token_completion(
+        &mut self,
+        nft_contract_id: AccountId,
+        token_id: NftId,
+    ) -> bool;
+
+    fn secondary_listing_accept_bid_completion(
+        &mut self,
+        nft_contract_id: AccountId,
+        token_id: NftId,
+        seller_id: AccountId,
+        collection_id: Option<U64>,
+        bidder_id: AccountId,
+        amount_yocto: U128,
+    ) -> bool;
+
+    fn secondary_listing_conclude_completion(
+        &mut self,
+        nft_contract_id: AccountId,
+        token_id: NftId,
+    ) -> bool;
+}
+
+#[near_bindgen]
+impl SecondaryListingSellerCallback for MarketplaceContract {
+    // activates the pending listing if the token matches what the seller claimed, removes it
+    // otherwise; we don't panic on mismatch, the listing removal must not be reverted
+    #[private]
+    fn secondary_listing_add_nft_token_completion(
+        &mut self,
+        nft_contract_id: AccountId,
+        token_id: NftId,
+    ) -> bool {
+        let listing_id = SecondaryListingId {
+            nft_contract_id,
+            token_id,
+        };
+        // may have been removed already if the approval got revoked meanwhile
+        let mut listing = match self.secondary_listings_by_id.get(&listing_id) {
+            Some(listing) => listing,
+            None => return false,
+        };
+        assert!(
+            listing.status == ListingStatus::Pending,
+            "This listing is {}",
+            listing.status.as_str()
+        );
+
+        assert_eq!(env::promise_results_count(), 1, "Too many data receipts");
+        let token: Option<JsonNft> = match env::promise_result(0) {
+            PromiseResult::Successful(val) => {
+                near_sdk::serde_json::from_slice::<Option<JsonNft>>(&val).unwrap_or(None)
+            }
+            _ => None,
+        };
+
+        // a token in custody is already ours, otherwise the seller must own it and have it
+        // approved for us
+        let verified_token = token.filter(|token| {
+            let is_transferable = if listing.is_in_custody {
+                token.owner_id == env::current_account_id()
+            } else {
+                token.owner_id == listing.seller_id
+                    && token.approved_account_ids.get(&env::current_account_id())
+                        == Some(&listing.approval_id)
+            };
+            is_transferable && token.metadata == listing.nft_metadata
+        });
+
+        if let Some(token) = verified_token {
+            // store what the NFT contract says, not what the seller passed in
+            listing.collection_id = Some(token.collection_id);
+            listing.nft_metadata = token.metadata;
+            listing.nft_mutable_metadata = token.mutable_metadata;
+            listing.status = ListingStatus::Unstarted;
+            listing.update_status();
+
+            let storage_before = env::storage_usage();
+            self.secondary_listings_by_id.insert(&listing_id, &listing);
+            self.internal_add_listing_to_floor(
+                &ListingId::Secondary(listing_id.clone()),
+                &listing_id.nft_contract_id,
+                listing.collection_id,
+                listing.price_yocto,
+                listing.seq,
+            );
+            let storage_after = env::storage_usage();
+            self.internal_charge_account_storage(&listing.seller_id, storage_before, storage_after);
+            true
+        } else {
+            env::log_str("Token owner, approval or metadata mismatch, listing removed");
+
+            // return the listing storage deposit to the seller
+            let storage_before = env::storage_usage();
+            let removed_listing = self.internal_remove_secondary_listing(&listing_id);
+            let storage_after = env::storage_usage();
+            self.internal_charge_account_storage(
+                &removed_listing.seller_id,
+                storage_before,
+                storage_after,
+            );
+            false
+        }
+    }
+
+    // resolves nft_on_transfer; returns true if the NFT contract must give the token back to
+    // the seller, which is the case when the listing didn't go live
+    #[private]
+    fn secondary_listing_custody_nft_token_completion(
+        &mut self,
+        nft_contract_id: AccountId,
+        token_id: NftId,
+    ) -> bool {
+        !self.secondary_listing_add_nft_token_completion(nft_contract_id, token_id)
+    }
+
+    // returns true if the token was sold; the seller and the collection are passed in as the
+    // listing may have been delisted or replaced while the transfer was in flight, the sale is
+    // settled either way
+    #[private]
+    fn secondary_listing_accept_bid_completion(
+        &mut self,
+        nft_contract_id: AccountId,
+        token_id: NftId,
+        seller_id: AccountId,
+        collection_id: Option<U64>,
+        bidder_id: AccountId,
+        amount_yocto: U128,
+    ) -> bool {
+        let amount_yocto = amount_yocto.0;
+        let listing_id = SecondaryListingId {
+            nft_contract_id,
+            token_id,
+        };
+
+        assert_eq!(env::promise_results_count(), 1, "Too many data receipts");
+        match env::promise_result(0) {
+            PromiseResult::Successful(_) => {
+                // the seller doesn't hold the token anymore, whatever they listed it with is
+                // closed; a listing by someone else (f.ex. the bidder) stays
+                match self.secondary_listings_by_id.get(&listing_id) {
+                    Some(listing) if listing.seller_id == seller_id => {
+                        self.internal_close_secondary_listing(&listing_id);
+                    }
+                    _ => {}
+                }
+
+                // the 1yN attached to nft_transfer is taken from the bid
+                Promise::new(seller_id.clone()).transfer(amount_yocto - 1);
+                self.internal_record_sale(
+                    Sale {
+                        listing_id: Some(ListingId::Secondary(listing_id.clone())),
+                        token_id: listing_id.token_id.clone(),
+                        buyer_id: bidder_id,
+                        seller_id,
+                        price_yocto: amount_yocto,
+                        timestamp: env::block_timestamp(),
+                    },
+                    &listing_id.nft_contract_id,
+                    collection_id.map(|collection_id| collection_id.0),
+                );
+                true
+            }
+            _ => {
+                // we don't panic here, it'd revert the refund
+                Promise::new(bidder_id).transfer(amount_yocto);
+                false
+            }
+        }
+    }
+
+    // removes the listing once the token is back with the seller; if the transfer failed the
+    // token is still in custody and the listing goes live again
+    #[private]
+    fn secondary_listing_conclude_completion(
+        &mut self,
+        nft_contract_id: AccountId,
+        token_id: NftId,
+    ) -> bool {
+        let listing_id = SecondaryListingId {
+            nft_contract_id,
+            token_id,
+        };
+        let mut listing = match self.secondary_listings_by_id.get(&listing_id) {
+            Some(listing) => listing,
+            None => return false,
+        };
+
+        assert_eq!(env::promise_results_count(), 1, "Too many data receipts");
+        match env::promise_result(0) {
+            PromiseResult::Successful(_) => {
+                self.internal_close_secondary_listing(&listing_id);
+                true
+            }
+            _ => {
+                // we don't panic here, the listing would be stuck pending
+                env::log_str("Could not return the token from custody, listing restored");
+                listing.status = ListingStatus::Unstarted;
+                listing.update_status();
+                self.secondary_listings_by_id.insert(&listing_id, &listing);
+                false
+            }
+        }
     }
 }
@@ -0,0 +1,446 @@
+#[cfg(test)]
+mod seller_tests {
+    use super::super::SecondaryListingSellerCallback;
+    use crate::{listing::status::ListingStatus, test_utils::*, *};
+    use near_sdk::json_types::{U128, U64};
+    use near_sdk::serde_json::{self, json, Value};
+    use near_sdk::PromiseResult;
+
+    const TOKEN_ID: &str = "0:1";
+    const APPROVAL_ID: u64 = 0; // set by add_secondary_listing
+    const BID_ID: u64 = 0; // the first bid of the auction
+
+    // listing waiting for the NFT contract to confirm the token
+    fn setup() -> (MarketplaceContract, SecondaryListingId) {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        let listing_id =
+            add_secondary_listing(&mut marketplace, TOKEN_ID, Some(PRICE_YOCTO), None, false);
+        let mut listing = marketplace
+            .secondary_listings_by_id
+            .get(&listing_id)
+            .unwrap();
+        listing.status = ListingStatus::Pending;
+        marketplace
+            .secondary_listings_by_id
+            .insert(&listing_id, &listing);
+        (marketplace, listing_id)
+    }
+
+    fn token(owner_id: &str, title: &str, approved_account_ids: Value) -> PromiseResult {
+        PromiseResult::Successful(
+            serde_json::to_vec(&json!({
+                "token_id": TOKEN_ID,
+                "owner_id": owner_id,
+                "collection_id": 3,
+                "metadata": {"title": title, "media": "https://eneftigo.com/media/0.png"},
+                "mutable_metadata": {"aux_audio_url": "https://eneftigo.com/audio/0.mp3"},
+                "approved_account_ids": approved_account_ids
+            }))
+            .unwrap(),
+        )
+    }
+
+    fn approved() -> Value {
+        json!({ MARKETPLACE_ACCOUNT_ID: APPROVAL_ID })
+    }
+
+    fn complete(marketplace: &mut MarketplaceContract, promise_result: PromiseResult) -> bool {
+        set_callback_context(NOW, vec![promise_result]);
+        marketplace.secondary_listing_add_nft_token_completion(
+            account(NFT_CONTRACT_ID),
+            TOKEN_ID.to_string(),
+        )
+    }
+
+    fn assert_removed(marketplace: &MarketplaceContract, listing_id: &SecondaryListingId) {
+        assert!(marketplace
+            .secondary_listings_by_id
+            .get(listing_id)
+            .is_none());
+        assert!(marketplace
+            .secondary_listings_by_seller(account(SELLER_ACCOUNT_ID), None, None)
+            .is_empty());
+    }
+
+    /* add listing nft_token completion */
+
+    #[test]
+    fn test_add_completion_activates_matching_listing() {
+        let (mut marketplace, listing_id) = setup();
+
+        assert!(complete(
+            &mut marketplace,
+            token(SELLER_ACCOUNT_ID, "Collection 0", approved())
+        ));
+
+        let listing = marketplace
+            .secondary_listings_by_id
+            .get(&listing_id)
+            .unwrap();
+        assert!(listing.status == ListingStatus::Running);
+        // what the NFT contract says is stored, not what the seller passed in
+        assert_eq!(listing.collection_id, Some(3));
+        assert_eq!(
+            listing.nft_mutable_metadata.aux_audio_url,
+            Some("https://eneftigo.com/audio/0.mp3".to_string())
+        );
+        assert_eq!(marketplace.secondary_listings(None, None).len(), 1);
+    }
+
+    #[test]
+    fn test_add_completion_owner_mismatch() {
+        let (mut marketplace, listing_id) = setup();
+
+        assert!(!complete(
+            &mut marketplace,
+            token(BUYER_ACCOUNT_ID, "Collection 0", approved())
+        ));
+
+        assert_removed(&marketplace, &listing_id);
+    }
+
+    #[test]
+    fn test_add_completion_metadata_mismatch() {
+        let (mut marketplace, listing_id) = setup();
+
+        assert!(!complete(
+            &mut marketplace,
+            token(SELLER_ACCOUNT_ID, "Something else", approved())
+        ));
+
+        assert_removed(&marketplace, &listing_id);
+    }
+
+    #[test]
+    fn test_add_completion_approval_mismatch() {
+        let (mut marketplace, listing_id) = setup();
+
+        assert!(!complete(
+            &mut marketplace,
+            token(
+                SELLER_ACCOUNT_ID,
+                "Collection 0",
+                json!({ MARKETPLACE_ACCOUNT_ID: APPROVAL_ID + 1 })
+            )
+        ));
+
+        assert_removed(&marketplace, &listing_id);
+    }
+
+    #[test]
+    fn test_add_completion_failed_lookup() {
+        let (mut marketplace, listing_id) = setup();
+
+        assert!(!complete(&mut marketplace, PromiseResult::Failed));
+
+        assert_removed(&marketplace, &listing_id);
+    }
+
+    #[test]
+    fn test_add_completion_removed_meanwhile() {
+        let (mut marketplace, listing_id) = setup();
+        marketplace.internal_close_secondary_listing(&listing_id);
+
+        assert!(!complete(
+            &mut marketplace,
+            token(SELLER_ACCOUNT_ID, "Collection 0", approved())
+        ));
+    }
+
+    /* pending listings are not for sale */
+
+    #[test]
+    fn test_pending_listing_not_listed() {
+        let (marketplace, _) = setup();
+
+        assert!(marketplace.secondary_listings(None, None).is_empty());
+        assert!(marketplace
+            .secondary_listings_query(None, None, None, None)
+            .listings
+            .is_empty());
+        // the seller still sees it
+        assert_eq!(
+            marketplace
+                .secondary_listings_by_seller(account(SELLER_ACCOUNT_ID), None, None)
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = r#"This listing is Pending"#)]
+    fn test_pending_listing_cannot_be_bought() {
+        let (mut marketplace, _) = setup();
+
+        set_context(BUYER_ACCOUNT_ID, NOW, PRICE_YOCTO + 1);
+        marketplace.secondary_listing_buy(
+            account(NFT_CONTRACT_ID),
+            TOKEN_ID.to_string(),
+            None,
+            None,
+            None,
+        );
+    }
+
+    /* conclude */
+
+    // running auction with a standing bid
+    fn auction(is_in_custody: bool) -> (MarketplaceContract, SecondaryListingId) {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        let listing_id = add_secondary_listing(
+            &mut marketplace,
+            TOKEN_ID,
+            Some(PRICE_YOCTO),
+            Some(MIN_BID_YOCTO),
+            is_in_custody,
+        );
+        set_context(BIDDER_ACCOUNT_ID, NOW, MIN_BID_YOCTO);
+        marketplace.secondary_listing_place_bid(
+            account(NFT_CONTRACT_ID),
+            TOKEN_ID.to_string(),
+            U128(MIN_BID_YOCTO),
+        );
+        (marketplace, listing_id)
+    }
+
+    fn conclude(marketplace: &mut MarketplaceContract, attached_deposit: Balance) {
+        set_context(SELLER_ACCOUNT_ID, NOW, attached_deposit);
+        marketplace.secondary_listing_conclude(
+            account(SELLER_ACCOUNT_ID),
+            account(NFT_CONTRACT_ID),
+            TOKEN_ID.to_string(),
+        );
+    }
+
+    fn complete_conclude(marketplace: &mut MarketplaceContract, result: PromiseResult) -> bool {
+        set_callback_context(NOW, vec![result]);
+        marketplace
+            .secondary_listing_conclude_completion(account(NFT_CONTRACT_ID), TOKEN_ID.to_string())
+    }
+
+    fn assert_bid_refunded(marketplace: &MarketplaceContract) {
+        assert!(marketplace
+            .bids_by_account(account(BIDDER_ACCOUNT_ID), None, None)
+            .is_empty());
+        assert_eq!(
+            marketplace
+                .storage_deposits
+                .get(&account(BIDDER_ACCOUNT_ID)),
+            Some(STORAGE_DEPOSIT)
+        );
+    }
+
+    #[test]
+    fn test_conclude_refunds_bids() {
+        let (mut marketplace, listing_id) = auction(false);
+
+        conclude(&mut marketplace, 0);
+
+        assert!(marketplace
+            .secondary_listings_by_id
+            .get(&listing_id)
+            .is_none());
+        assert_bid_refunded(&marketplace);
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Attach 1yN to get the token back from custody"#)]
+    fn test_conclude_custody_without_deposit() {
+        let (mut marketplace, _) = auction(true);
+
+        conclude(&mut marketplace, 0);
+    }
+
+    #[test]
+    fn test_conclude_custody_waits_for_transfer() {
+        let (mut marketplace, listing_id) = auction(true);
+
+        conclude(&mut marketplace, 1);
+
+        // the listing stays until the token is back, the bids are refunded right away
+        let listing = marketplace
+            .secondary_listings_by_id
+            .get(&listing_id)
+            .unwrap();
+        assert!(listing.status == ListingStatus::Pending);
+        assert_bid_refunded(&marketplace);
+    }
+
+    #[test]
+    #[should_panic(expected = r#"This listing is Pending"#)]
+    fn test_conclude_custody_twice() {
+        let (mut marketplace, _) = auction(true);
+
+        conclude(&mut marketplace, 1);
+        conclude(&mut marketplace, 1);
+    }
+
+    #[test]
+    fn test_conclude_completion() {
+        let (mut marketplace, listing_id) = auction(true);
+        conclude(&mut marketplace, 1);
+
+        assert!(complete_conclude(
+            &mut marketplace,
+            PromiseResult::Successful(vec![])
+        ));
+
+        assert!(marketplace
+            .secondary_listings_by_id
+            .get(&listing_id)
+            .is_none());
+        assert!(marketplace
+            .secondary_listings_by_seller(account(SELLER_ACCOUNT_ID), None, None)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_conclude_completion_failed_transfer_restores_listing() {
+        let (mut marketplace, listing_id) = auction(true);
+        conclude(&mut marketplace, 1);
+
+        assert!(!complete_conclude(&mut marketplace, PromiseResult::Failed));
+
+        // the token is still in custody, the listing is live again
+        let listing = marketplace
+            .secondary_listings_by_id
+            .get(&listing_id)
+            .unwrap();
+        assert!(listing.status == ListingStatus::Running);
+        assert!(listing.is_in_custody);
+    }
+
+    /* accept bid */
+
+    fn accept(marketplace: &mut MarketplaceContract, listing_id: &SecondaryListingId) {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        marketplace.internal_secondary_listing_accept_bid(
+            &account(SELLER_ACCOUNT_ID),
+            listing_id.clone(),
+            Some(APPROVAL_ID),
+            BID_ID,
+        );
+    }
+
+    fn complete_accept(marketplace: &mut MarketplaceContract, result: PromiseResult) -> bool {
+        set_callback_context(NOW, vec![result]);
+        marketplace.secondary_listing_accept_bid_completion(
+            account(NFT_CONTRACT_ID),
+            TOKEN_ID.to_string(),
+            account(SELLER_ACCOUNT_ID),
+            Some(U64(0)),
+            account(BIDDER_ACCOUNT_ID),
+            U128(MIN_BID_YOCTO),
+        )
+    }
+
+    fn assert_sold_to_bidder(marketplace: &MarketplaceContract) {
+        let sales = marketplace.sales(None, None);
+        assert_eq!(sales.len(), 1);
+        assert_eq!(sales[0].buyer_id, account(BIDDER_ACCOUNT_ID));
+        assert_eq!(sales[0].seller_id, account(SELLER_ACCOUNT_ID));
+        assert_eq!(sales[0].price_yocto, U128(MIN_BID_YOCTO));
+    }
+
+    #[test]
+    fn test_accept_bid_completion() {
+        let (mut marketplace, listing_id) = auction(false);
+        accept(&mut marketplace, &listing_id);
+
+        assert!(complete_accept(
+            &mut marketplace,
+            PromiseResult::Successful(vec![])
+        ));
+
+        assert_removed(&marketplace, &listing_id);
+        assert_sold_to_bidder(&marketplace);
+    }
+
+    #[test]
+    fn test_accept_bid_completion_after_delisting() {
+        let (mut marketplace, listing_id) = auction(false);
+        accept(&mut marketplace, &listing_id);
+        conclude(&mut marketplace, 0);
+
+        // the token is with the bidder already, the seller still gets paid
+        assert!(complete_accept(
+            &mut marketplace,
+            PromiseResult::Successful(vec![])
+        ));
+
+        assert_removed(&marketplace, &listing_id);
+        assert_sold_to_bidder(&marketplace);
+    }
+
+    #[test]
+    fn test_accept_bid_completion_after_relisting() {
+        let (mut marketplace, listing_id) = auction(false);
+        accept(&mut marketplace, &listing_id);
+        conclude(&mut marketplace, 0);
+        add_secondary_listing(&mut marketplace, TOKEN_ID, Some(PRICE_YOCTO), None, false);
+
+        // the seller doesn't own the token anymore, the new listing goes too
+        assert!(complete_accept(
+            &mut marketplace,
+            PromiseResult::Successful(vec![])
+        ));
+
+        assert_removed(&marketplace, &listing_id);
+        assert_sold_to_bidder(&marketplace);
+    }
+
+    #[test]
+    fn test_accept_bid_custody_listing() {
+        let (mut marketplace, listing_id) = auction(true);
+
+        // the marketplace holds the token, the seller has nothing to approve
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        marketplace.secondary_listing_accept_bid(
+            account(NFT_CONTRACT_ID),
+            TOKEN_ID.to_string(),
+            U64(BID_ID),
+        );
+        assert!(marketplace
+            .bids_by_account(account(BIDDER_ACCOUNT_ID), None, None)
+            .is_empty());
+
+        assert!(complete_accept(
+            &mut marketplace,
+            PromiseResult::Successful(vec![])
+        ));
+
+        assert_removed(&marketplace, &listing_id);
+        assert_sold_to_bidder(&marketplace);
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Only the seller can accept bids"#)]
+    fn test_accept_bid_not_seller() {
+        let (mut marketplace, _) = auction(true);
+
+        set_context(BUYER_ACCOUNT_ID, NOW, 0);
+        marketplace.secondary_listing_accept_bid(
+            account(NFT_CONTRACT_ID),
+            TOKEN_ID.to_string(),
+            U64(BID_ID),
+        );
+    }
+
+    #[test]
+    fn test_accept_bid_completion_failed_transfer() {
+        let (mut marketplace, listing_id) = auction(false);
+        accept(&mut marketplace, &listing_id);
+
+        assert!(!complete_accept(&mut marketplace, PromiseResult::Failed));
+
+        // the bid is off the book and refunded, the listing stays
+        assert!(marketplace
+            .secondary_listings_by_id
+            .get(&listing_id)
+            .is_some());
+        assert_bid_refunded(&marketplace);
+        assert!(marketplace.sales(None, None).is_empty());
+    }
+}
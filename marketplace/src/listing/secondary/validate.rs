@@ -0,0 +1,135 @@
+use crate::{
+    constants::NO_DEPOSIT,
+    external::{nft_contract, JsonNft},
+    listing::status::ListingStatus,
+    *,
+};
+use near_sdk::PromiseResult;
+
+const NFT_IS_APPROVED_GAS: Gas = Gas(5_000_000_000_000); // TODO: measure
+const NFT_TOKEN_GAS: Gas = Gas(5_000_000_000_000); // TODO: measure
+const SECONDARY_LISTING_VALIDATE_COMPLETION_GAS: Gas = Gas(20_000_000_000_000); // TODO: measure, grows with the number of bids
+
+#[cfg(test)]
+#[path = "validate_tests.rs"]
+mod validate_tests;
+
+// anyone can ask the marketplace to re-check a listing against the NFT contract; listings whose
+// token has moved or whose approval has been revoked are removed and their bidders refunded
+#[near_bindgen]
+impl MarketplaceContract {
+    pub fn secondary_listing_validate(&mut self, nft_contract_id: AccountId, token_id: NftId) -> Promise {
+        // with too little gas the NFT calls could fail and make a live listing look dead
+        assert!(
+            env::prepaid_gas()
+                >= NFT_IS_APPROVED_GAS + NFT_TOKEN_GAS + SECONDARY_LISTING_VALIDATE_COMPLETION_GAS,
+            "Attach at least {} gas",
+            (NFT_IS_APPROVED_GAS + NFT_TOKEN_GAS + SECONDARY_LISTING_VALIDATE_COMPLETION_GAS).0
+        );
+
+        let listing_id = SecondaryListingId {
+            nft_contract_id: nft_contract_id.clone(),
+            token_id: token_id.clone(),
+        };
+        let listing = self
+            .secondary_listings_by_id
+            .get(&listing_id)
+            .expect("Could not find this listing");
+        assert!(
+            listing.status != ListingStatus::Pending,
+            "This listing is {}",
+            listing.status.as_str()
+        );
+        // the marketplace owns tokens in custody, they cannot go stale
+        assert!(!listing.is_in_custody, "This listing is in custody");
+
+        nft_contract::nft_is_approved(
+            token_id.clone(),
+            env::current_account_id(),
+            Some(listing.approval_id),
+            nft_contract_id.clone(),
+            NO_DEPOSIT,
+            NFT_IS_APPROVED_GAS,
+        )
+        .and(nft_contract::nft_token(
+            token_id.clone(),
+            nft_contract_id.clone(),
+            NO_DEPOSIT,
+            NFT_TOKEN_GAS,
+        ))
+        .then(ext_self_nft::secondary_listing_validate_completion(
+            nft_contract_id,
+            token_id,
+            env::current_account_id(),
+            NO_DEPOSIT,
+            SECONDARY_LISTING_VALIDATE_COMPLETION_GAS,
+        ))
+    }
+}
+
+#[ext_contract(ext_self_nft)]
+trait SecondaryListingValidateCallback {
+    fn secondary_listing_validate_completion(
+        &mut self,
+        nft_contract_id: AccountId,
+        token_id: NftId,
+    ) -> bool;
+}
+
+trait SecondaryListingValidateCallback {
+    fn secondary_listing_validate_completion(
+        &mut self,
+        nft_contract_id: AccountId,
+        token_id: NftId,
+    ) -> bool;
+}
+
+#[near_bindgen]
+impl SecondaryListingValidateCallback for MarketplaceContract {
+    // returns true if the listing is still live
+    #[private]
+    fn secondary_listing_validate_completion(
+        &mut self,
+        nft_contract_id: AccountId,
+        token_id: NftId,
+    ) -> bool {
+        let listing_id = SecondaryListingId {
+            nft_contract_id,
+            token_id,
+        };
+        // may have been sold or removed in the meantime
+        let listing = match self.secondary_listings_by_id.get(&listing_id) {
+            Some(listing) => listing,
+            None => return false,
+        };
+
+        assert_eq!(env::promise_results_count(), 2, "Unexpected number of data receipts");
+        // nft_is_approved panics when the token doesn't exist, nft_token tells us for sure
+        let token = match env::promise_result(1) {
+            PromiseResult::Successful(val) => {
+                near_sdk::serde_json::from_slice::<Option<JsonNft>>(&val)
+                    .expect("NFT nft_token returned unexpected value")
+            }
+            _ => {
+                env::log_str("Could not look up the token, listing left unchanged");
+                return true;
+            }
+        };
+        let is_approved = match env::promise_result(0) {
+            PromiseResult::Successful(val) => {
+                near_sdk::serde_json::from_slice::<bool>(&val)
+                    .expect("NFT nft_is_approved returned unexpected value")
+            }
+            _ => false,
+        };
+
+        let is_live = match token {
+            Some(token) => is_approved && token.owner_id == listing.seller_id,
+            None => false,
+        };
+        if !is_live {
+            self.internal_delist_secondary_listing(&listing_id);
+        }
+        is_live
+    }
+}
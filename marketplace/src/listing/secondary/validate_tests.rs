@@ -0,0 +1,180 @@
+#[cfg(test)]
+mod validate_tests {
+    use super::super::SecondaryListingValidateCallback;
+    use crate::{test_utils::*, *};
+    use near_sdk::json_types::U128;
+    use near_sdk::serde_json::{self, json};
+    use near_sdk::PromiseResult;
+
+    const TOKEN_ID: &str = "0:1";
+
+    fn setup(is_in_custody: bool) -> (MarketplaceContract, SecondaryListingId) {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        let listing_id = add_secondary_listing(
+            &mut marketplace,
+            TOKEN_ID,
+            Some(PRICE_YOCTO),
+            Some(MIN_BID_YOCTO),
+            is_in_custody,
+        );
+        set_context(BIDDER_ACCOUNT_ID, NOW, MIN_BID_YOCTO);
+        marketplace.secondary_listing_place_bid(
+            account(NFT_CONTRACT_ID),
+            TOKEN_ID.to_string(),
+            U128(MIN_BID_YOCTO),
+        );
+        (marketplace, listing_id)
+    }
+
+    fn is_approved_result(is_approved: bool) -> PromiseResult {
+        PromiseResult::Successful(serde_json::to_vec(&is_approved).unwrap())
+    }
+
+    fn token_result(owner_id: Option<&str>) -> PromiseResult {
+        let token = owner_id.map(|owner_id| {
+            json!({
+                "token_id": TOKEN_ID,
+                "owner_id": owner_id,
+                "collection_id": 0,
+                "metadata": {"title": "Collection 0"},
+                "mutable_metadata": {},
+                "approved_account_ids": {}
+            })
+        });
+        PromiseResult::Successful(serde_json::to_vec(&token).unwrap())
+    }
+
+    fn complete(
+        marketplace: &mut MarketplaceContract,
+        promise_results: Vec<PromiseResult>,
+    ) -> bool {
+        set_callback_context(NOW, promise_results);
+        marketplace
+            .secondary_listing_validate_completion(account(NFT_CONTRACT_ID), TOKEN_ID.to_string())
+    }
+
+    fn assert_delisted(marketplace: &MarketplaceContract, listing_id: &SecondaryListingId) {
+        assert!(marketplace
+            .secondary_listings_by_id
+            .get(listing_id)
+            .is_none());
+        assert!(marketplace
+            .bids_by_account(account(BIDDER_ACCOUNT_ID), None, None)
+            .is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = r#"This listing is in custody"#)]
+    fn test_validate_custody_listing() {
+        let (mut marketplace, _) = setup(true);
+        set_context(BUYER_ACCOUNT_ID, NOW, 0);
+        marketplace.secondary_listing_validate(account(NFT_CONTRACT_ID), TOKEN_ID.to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Could not find this listing"#)]
+    fn test_validate_missing_listing() {
+        let (mut marketplace, _) = setup(false);
+        set_context(BUYER_ACCOUNT_ID, NOW, 0);
+        marketplace.secondary_listing_validate(account(NFT_CONTRACT_ID), "0:2".to_string());
+    }
+
+    #[test]
+    fn test_validate_live_listing() {
+        let (mut marketplace, listing_id) = setup(false);
+
+        let is_live = complete(
+            &mut marketplace,
+            vec![
+                is_approved_result(true),
+                token_result(Some(SELLER_ACCOUNT_ID)),
+            ],
+        );
+
+        assert!(is_live);
+        let listing = marketplace
+            .secondary_listings_by_id
+            .get(&listing_id)
+            .unwrap();
+        assert_eq!(listing.bids.len(), 1);
+    }
+
+    #[test]
+    fn test_validate_revoked_approval() {
+        let (mut marketplace, listing_id) = setup(false);
+
+        let is_live = complete(
+            &mut marketplace,
+            vec![
+                is_approved_result(false),
+                token_result(Some(SELLER_ACCOUNT_ID)),
+            ],
+        );
+
+        assert!(!is_live);
+        assert_delisted(&marketplace, &listing_id);
+    }
+
+    #[test]
+    fn test_validate_moved_token() {
+        let (mut marketplace, listing_id) = setup(false);
+
+        let is_live = complete(
+            &mut marketplace,
+            vec![
+                is_approved_result(true),
+                token_result(Some(BUYER_ACCOUNT_ID)),
+            ],
+        );
+
+        assert!(!is_live);
+        assert_delisted(&marketplace, &listing_id);
+    }
+
+    #[test]
+    fn test_validate_burnt_token() {
+        let (mut marketplace, listing_id) = setup(false);
+
+        // nft_is_approved panics for tokens that don't exist
+        let is_live = complete(
+            &mut marketplace,
+            vec![PromiseResult::Failed, token_result(None)],
+        );
+
+        assert!(!is_live);
+        assert_delisted(&marketplace, &listing_id);
+    }
+
+    #[test]
+    fn test_validate_failed_lookup_leaves_listing() {
+        let (mut marketplace, listing_id) = setup(false);
+
+        let is_live = complete(
+            &mut marketplace,
+            vec![is_approved_result(false), PromiseResult::Failed],
+        );
+
+        assert!(is_live);
+        assert!(marketplace
+            .secondary_listings_by_id
+            .get(&listing_id)
+            .is_some());
+    }
+
+    #[test]
+    fn test_validate_sold_meanwhile() {
+        let (mut marketplace, listing_id) = setup(false);
+        marketplace.internal_close_secondary_listing(&listing_id);
+
+        let is_live = complete(
+            &mut marketplace,
+            vec![
+                is_approved_result(false),
+                token_result(Some(BUYER_ACCOUNT_ID)),
+            ],
+        );
+
+        assert!(!is_live);
+    }
+}
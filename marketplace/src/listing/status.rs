@@ -7,6 +7,7 @@ pub enum ListingStatus {
     Unstarted,
     Running,
     Ended,
+    Pending, // waiting for the NFT contract to confirm the token owner and metadata
 }
 
 impl ListingStatus {
@@ -15,6 +16,7 @@ impl ListingStatus {
             ListingStatus::Unstarted => "Unstarted",
             ListingStatus::Running => "Running",
             ListingStatus::Ended => "Ended",
+            ListingStatus::Pending => "Pending",
         }
     }
 }
@@ -0,0 +1,112 @@
+use crate::{
+    listing::{bid::BidBook, date::DateInput, rules::ListingRules},
+    *,
+};
+
+#[cfg(test)]
+#[path = "terms_tests.rs"]
+mod terms_tests;
+
+// the price, bid and end date terms shared by primary and secondary listings
+pub(crate) struct ListingTerms {
+    pub price_yocto: Option<u128>,
+    pub min_bid_yocto: Option<u128>,
+    pub start_timestamp: i64,
+    pub end_timestamp: Option<i64>,
+}
+
+// the changes requested by the seller, terms left as None are kept
+pub(crate) struct ListingTermsUpdate {
+    pub price_yocto: Option<u128>,
+    pub min_bid_yocto: Option<u128>,
+    pub end_date: Option<DateInput>, // a duration counts from the listing start
+}
+
+impl ListingTerms {
+    // once bids have been placed the seller can neither lower the minimum bid nor
+    // shorten the listing so that the bidders are not put at a disadvantage
+    // the duration limits are those of the listing kind, same as when adding the listing
+    pub(crate) fn update(
+        &mut self,
+        update: ListingTermsUpdate,
+        rules: &ListingRules,
+        bids: &BidBook,
+        min_duration_nano: i64,
+        max_duration_nano: i64,
+    ) {
+        let has_bids = !bids.is_empty();
+
+        // Is the price ok?
+        if let Some(price_yocto) = update.price_yocto {
+            rules.assert_price(price_yocto);
+            // bids must stay lower than buy now price
+            if has_bids {
+                let best_bid = bids.best().unwrap();
+                assert!(
+                    price_yocto > best_bid.amount_yocto,
+                    "Price must be higher than the best bid of {} yoctoNear",
+                    best_bid.amount_yocto
+                );
+            }
+            self.price_yocto = Some(price_yocto);
+        }
+
+        // Is min bid ok?
+        if let Some(min_bid_yocto) = update.min_bid_yocto {
+            rules.assert_min_bid(min_bid_yocto);
+            if has_bids {
+                let current_min_bid_yocto = self.min_bid_yocto.unwrap();
+                assert!(
+                    min_bid_yocto >= current_min_bid_yocto,
+                    "Cannot lower the minimum bid once bids have been placed"
+                );
+                // all standing bids must remain valid
+                let worst_bid = bids.worst().unwrap();
+                assert!(
+                    min_bid_yocto <= worst_bid.amount_yocto,
+                    "Minimum bid cannot exceed the lowest standing bid of {} yoctoNear",
+                    worst_bid.amount_yocto
+                );
+            }
+            self.min_bid_yocto = Some(min_bid_yocto);
+        }
+
+        if let (Some(price_yocto), Some(min_bid_yocto)) = (self.price_yocto, self.min_bid_yocto) {
+            assert!(
+                min_bid_yocto < price_yocto,
+                "Minimum bid must be lower than buy now price"
+            );
+        }
+
+        if let Some(end_date) = update.end_date {
+            let end_timestamp = end_date.to_timestamp(self.start_timestamp);
+            assert!(
+                end_timestamp >= env::block_timestamp() as i64,
+                "End date into the past"
+            );
+            if has_bids {
+                if let Some(current_end_timestamp) = self.end_timestamp {
+                    assert!(
+                        end_timestamp >= current_end_timestamp,
+                        "Cannot shorten the listing once bids have been placed"
+                    );
+                }
+            }
+            self.end_timestamp = Some(end_timestamp);
+        }
+
+        let is_accepting_bids = self.min_bid_yocto.is_some();
+        if let Some(end_timestamp) = self.end_timestamp {
+            let duration = end_timestamp - self.start_timestamp;
+            assert!(duration >= min_duration_nano, "Listing duration too short");
+            if is_accepting_bids {
+                assert!(duration <= max_duration_nano, "Listing duration too long");
+            }
+        } else {
+            assert!(
+                !is_accepting_bids,
+                "End date must be set for bid-accepting listing"
+            );
+        }
+    }
+}
@@ -0,0 +1,255 @@
+#[cfg(test)]
+mod terms_tests {
+    use crate::{listing::date::DateInput, test_utils::*, *};
+    use near_sdk::json_types::{U128, U64};
+
+    const TOKEN_ID: &str = "0:1";
+    const LOW_BID_YOCTO: Balance = MIN_BID_YOCTO + ONE_NEAR / 10;
+    const HIGH_BID_YOCTO: Balance = MIN_BID_YOCTO + 3 * ONE_NEAR / 10;
+
+    fn hours(count: u64) -> DateInput {
+        DateInput::DurationMs(U64(count * HOUR_NANO / 1_000_000))
+    }
+
+    // running primary auction of two NFTs with two standing bids
+    fn primary_auction() -> (MarketplaceContract, PrimaryListingId) {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        let listing_id = add_primary_listing(
+            &mut marketplace,
+            0,
+            2,
+            Some(PRICE_YOCTO),
+            Some(MIN_BID_YOCTO),
+            None,
+        );
+        for (bidder_id, amount_yocto) in [
+            (BIDDER_ACCOUNT_ID, LOW_BID_YOCTO),
+            (BIDDER2_ACCOUNT_ID, HIGH_BID_YOCTO),
+        ] {
+            set_context(bidder_id, NOW, amount_yocto);
+            marketplace.primary_listing_place_bid(
+                account(NFT_CONTRACT_ID),
+                U64(0),
+                U128(amount_yocto),
+                None,
+            );
+        }
+        (marketplace, listing_id)
+    }
+
+    // running secondary auction with a bid outbid by another
+    fn secondary_auction() -> (MarketplaceContract, SecondaryListingId) {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        let listing_id = add_secondary_listing(
+            &mut marketplace,
+            TOKEN_ID,
+            Some(PRICE_YOCTO),
+            Some(MIN_BID_YOCTO),
+            false,
+        );
+        for (bidder_id, amount_yocto) in [
+            (BIDDER_ACCOUNT_ID, LOW_BID_YOCTO),
+            (BIDDER2_ACCOUNT_ID, HIGH_BID_YOCTO),
+        ] {
+            set_context(bidder_id, NOW, amount_yocto);
+            marketplace.secondary_listing_place_bid(
+                account(NFT_CONTRACT_ID),
+                TOKEN_ID.to_string(),
+                U128(amount_yocto),
+            );
+        }
+        (marketplace, listing_id)
+    }
+
+    fn update_primary(
+        marketplace: &mut MarketplaceContract,
+        price_yocto: Option<Balance>,
+        min_bid_yocto: Option<Balance>,
+        end_date: Option<DateInput>,
+    ) {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        marketplace.primary_listing_update(
+            account(NFT_CONTRACT_ID),
+            U64(0),
+            price_yocto.map(U128),
+            min_bid_yocto.map(U128),
+            end_date,
+            None,
+            None,
+        );
+    }
+
+    fn update_secondary(
+        marketplace: &mut MarketplaceContract,
+        price_yocto: Option<Balance>,
+        min_bid_yocto: Option<Balance>,
+        end_date: Option<DateInput>,
+    ) {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        marketplace.secondary_listing_update(
+            account(NFT_CONTRACT_ID),
+            TOKEN_ID.to_string(),
+            price_yocto.map(U128),
+            min_bid_yocto.map(U128),
+            end_date,
+            None,
+        );
+    }
+
+    /* primary listing update with bids */
+
+    #[test]
+    fn test_primary_update_with_bids() {
+        let (mut marketplace, listing_id) = primary_auction();
+
+        update_primary(
+            &mut marketplace,
+            Some(HIGH_BID_YOCTO + ONE_NEAR / 10),
+            Some(LOW_BID_YOCTO),
+            Some(hours(48)),
+        );
+
+        let listing = marketplace.primary_listings_by_id.get(&listing_id).unwrap();
+        assert_eq!(listing.price_yocto, Some(HIGH_BID_YOCTO + ONE_NEAR / 10));
+        assert_eq!(listing.min_bid_yocto, Some(LOW_BID_YOCTO));
+        assert_eq!(listing.end_timestamp, Some((48 * HOUR_NANO) as i64));
+        assert_eq!(listing.bids.len(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "Price must be higher than the best bid")]
+    fn test_primary_price_not_above_best_bid() {
+        let (mut marketplace, _) = primary_auction();
+
+        update_primary(&mut marketplace, Some(HIGH_BID_YOCTO), None, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot lower the minimum bid once bids have been placed")]
+    fn test_primary_lower_min_bid() {
+        let (mut marketplace, _) = primary_auction();
+
+        update_primary(
+            &mut marketplace,
+            None,
+            Some(MIN_BID_YOCTO - ONE_NEAR / 10),
+            None,
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Minimum bid cannot exceed the lowest standing bid")]
+    fn test_primary_min_bid_above_worst_bid() {
+        let (mut marketplace, _) = primary_auction();
+
+        update_primary(
+            &mut marketplace,
+            None,
+            Some(LOW_BID_YOCTO + ONE_NEAR / 10),
+            None,
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot shorten the listing once bids have been placed")]
+    fn test_primary_shorten() {
+        let (mut marketplace, _) = primary_auction();
+
+        update_primary(&mut marketplace, None, None, Some(hours(12)));
+    }
+
+    /* secondary listing update with bids */
+
+    #[test]
+    fn test_secondary_update_with_bids() {
+        let (mut marketplace, listing_id) = secondary_auction();
+
+        update_secondary(
+            &mut marketplace,
+            Some(HIGH_BID_YOCTO + ONE_NEAR / 10),
+            Some(LOW_BID_YOCTO),
+            Some(hours(48)),
+        );
+
+        let listing = marketplace
+            .secondary_listings_by_id
+            .get(&listing_id)
+            .unwrap();
+        assert_eq!(listing.price_yocto, Some(HIGH_BID_YOCTO + ONE_NEAR / 10));
+        assert_eq!(listing.min_bid_yocto, Some(LOW_BID_YOCTO));
+        assert_eq!(listing.end_timestamp, Some((48 * HOUR_NANO) as i64));
+    }
+
+    #[test]
+    #[should_panic(expected = "Price must be higher than the best bid")]
+    fn test_secondary_price_not_above_best_bid() {
+        let (mut marketplace, _) = secondary_auction();
+
+        update_secondary(&mut marketplace, Some(HIGH_BID_YOCTO), None, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot lower the minimum bid once bids have been placed")]
+    fn test_secondary_lower_min_bid() {
+        let (mut marketplace, _) = secondary_auction();
+
+        update_secondary(
+            &mut marketplace,
+            None,
+            Some(MIN_BID_YOCTO - ONE_NEAR / 10),
+            None,
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Minimum bid cannot exceed the lowest standing bid")]
+    fn test_secondary_min_bid_above_worst_bid() {
+        let (mut marketplace, _) = secondary_auction();
+
+        update_secondary(
+            &mut marketplace,
+            None,
+            Some(LOW_BID_YOCTO + ONE_NEAR / 10),
+            None,
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot shorten the listing once bids have been placed")]
+    fn test_secondary_shorten() {
+        let (mut marketplace, _) = secondary_auction();
+
+        update_secondary(&mut marketplace, None, None, Some(hours(12)));
+    }
+
+    /* without bids the terms can be changed freely */
+
+    #[test]
+    fn test_update_without_bids() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        let listing_id = add_secondary_listing(
+            &mut marketplace,
+            TOKEN_ID,
+            Some(PRICE_YOCTO),
+            Some(MIN_BID_YOCTO),
+            false,
+        );
+
+        update_secondary(
+            &mut marketplace,
+            None,
+            Some(MIN_BID_YOCTO - ONE_NEAR / 10),
+            Some(hours(12)),
+        );
+
+        let listing = marketplace
+            .secondary_listings_by_id
+            .get(&listing_id)
+            .unwrap();
+        assert_eq!(listing.min_bid_yocto, Some(MIN_BID_YOCTO - ONE_NEAR / 10));
+        assert_eq!(listing.end_timestamp, Some((12 * HOUR_NANO) as i64));
+    }
+}
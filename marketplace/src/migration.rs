@@ -0,0 +1,226 @@
+use crate::{
+    external::{NftMetadata, NftMutableMetadata},
+    listing::{
+        bid::{Bid, BidBook, BidId},
+        primary::{
+            internal::hash_primary_listing_id,
+            lib::PrimaryListingStorageKey,
+            round::PrimaryCollection,
+        },
+        secondary::{internal::hash_secondary_listing_id, lib::SecondaryListingStorageKey},
+        status::ListingStatus,
+    },
+    *,
+};
+
+#[cfg(test)]
+#[path = "migration_tests.rs"]
+mod migration_tests;
+
+// contract state deployed before the listing index, offers, bundles, drafts and the config
+// were added
+#[derive(BorshDeserialize, BorshSerialize)]
+pub(crate) struct MarketplaceContractV1 {
+    pub owner_id: AccountId,
+    pub primary_listings_by_id: UnorderedMap<PrimaryListingId, PrimaryListingV1>,
+    pub primary_listings_by_seller_id: LookupMap<AccountId, UnorderedSet<PrimaryListingId>>,
+    pub secondary_listings_by_id: UnorderedMap<SecondaryListingId, SecondaryListingV1>,
+    pub secondary_listings_by_seller_id: LookupMap<AccountId, UnorderedSet<SecondaryListingId>>,
+    pub storage_deposits: LookupMap<AccountId, Balance>,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub(crate) struct BidV1 {
+    pub id: BidId,
+    pub bidder_id: AccountId,
+    pub amount_yocto: u128,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub(crate) struct PrimaryListingV1 {
+    pub id: PrimaryListingId,
+    pub seller_id: AccountId,
+    pub nft_metadata: NftMetadata,
+    pub nft_mutable_metadata: NftMutableMetadata,
+    pub supply_total: u64,
+    pub price_yocto: Option<u128>,
+    pub min_bid_yocto: Option<u128>,
+    pub start_timestamp: i64,
+    pub end_timestamp: Option<i64>,
+    pub status: ListingStatus,
+    pub supply_left: u64,
+    pub bids: Vector<BidV1>, // same prefix as the bid book which replaces it
+    pub next_bid_id: u64,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub(crate) struct SecondaryListingV1 {
+    pub id: SecondaryListingId,
+    pub seller_id: AccountId,
+    pub approval_id: u64,
+    pub nft_metadata: NftMetadata,
+    pub nft_mutable_metadata: NftMutableMetadata,
+    pub price_yocto: Option<u128>,
+    pub min_bid_yocto: Option<u128>,
+    pub start_timestamp: i64,
+    pub end_timestamp: Option<i64>,
+    pub status: ListingStatus,
+    pub bids: Vector<BidV1>, // same prefix as the bid book which replaces it
+    pub next_bid_id: u64,
+}
+
+#[near_bindgen]
+impl MarketplaceContract {
+    // upgrades the V1 state; the listings are rewritten in the current layout and indexed,
+    // everything else added since V1 starts out empty
+    #[private]
+    #[init(ignore_state)]
+    pub fn migrate() -> Self {
+        let mut old: MarketplaceContractV1 =
+            env::state_read().expect("Could not read contract state");
+
+        // the listings go back under the same keys, so the old entries are cleared first
+        let primary_listings = old.primary_listings_by_id.to_vec();
+        let secondary_listings = old.secondary_listings_by_id.to_vec();
+        old.primary_listings_by_id.clear();
+        old.secondary_listings_by_id.clear();
+
+        let mut marketplace = Self {
+            primary_listings_by_seller_id: old.primary_listings_by_seller_id,
+            secondary_listings_by_seller_id: old.secondary_listings_by_seller_id,
+            storage_deposits: old.storage_deposits,
+            ..Self::new(old.owner_id)
+        };
+        for (_, listing) in primary_listings {
+            marketplace.internal_migrate_primary_listing(listing);
+        }
+        for (_, listing) in secondary_listings {
+            marketplace.internal_migrate_secondary_listing(listing);
+        }
+        marketplace
+    }
+}
+
+impl MarketplaceContract {
+    fn internal_migrate_primary_listing(&mut self, mut old: PrimaryListingV1) {
+        let listing_id_hash = hash_primary_listing_id(&old.id, 0);
+        let bids = internal_take_bids(&mut old.bids);
+        let mut listing = PrimaryListing {
+            id: old.id,
+            seller_id: old.seller_id,
+            nft_metadata: old.nft_metadata,
+            nft_mutable_metadata: old.nft_mutable_metadata,
+            supply_total: old.supply_total,
+            price_yocto: old.price_yocto,
+            min_bid_yocto: old.min_bid_yocto,
+            start_timestamp: old.start_timestamp,
+            end_timestamp: old.end_timestamp,
+            status: old.status,
+            supply_left: old.supply_left,
+            supply_pending: 0,
+            bids: BidBook::new(
+                PrimaryListingStorageKey::Bids { listing_id_hash }
+                    .try_to_vec()
+                    .unwrap(),
+                PrimaryListingStorageKey::BidKeys { listing_id_hash }
+                    .try_to_vec()
+                    .unwrap(),
+            ),
+            next_bid_id: old.next_bid_id,
+            version: 0,
+            phases: Vector::new(
+                PrimaryListingStorageKey::Phases { listing_id_hash }
+                    .try_to_vec()
+                    .unwrap(),
+            ),
+            max_per_account: None,
+            purchases_by_account: UnorderedMap::new(
+                PrimaryListingStorageKey::Purchases { listing_id_hash }
+                    .try_to_vec()
+                    .unwrap(),
+            ),
+            seq: 0, // assigned by internal_add_primary_listing
+            rules: self.config.listing_rules_default.clone(),
+            round: 0,
+        };
+        for bid in bids.iter() {
+            listing.bids.insert(bid);
+            self.internal_add_bid_to_account(
+                &bid.bidder_id,
+                ListingId::Primary(listing.id.clone()),
+                bid.id,
+            );
+        }
+
+        // V1 collections were made for a single listing of their whole supply
+        let collection = PrimaryCollection {
+            seller_id: listing.seller_id.clone(),
+            nft_metadata: listing.nft_metadata.clone(),
+            nft_mutable_metadata: listing.nft_mutable_metadata.clone(),
+            max_supply: listing.supply_total,
+            supply_minted: listing.supply_total - listing.supply_left,
+            supply_pending: 0,
+            next_round: 1,
+        };
+        self.primary_collections.insert(
+            &(listing.id.nft_contract_id.clone(), listing.id.collection_id),
+            &collection,
+        );
+        self.internal_add_primary_listing(&mut listing);
+    }
+
+    fn internal_migrate_secondary_listing(&mut self, mut old: SecondaryListingV1) {
+        let listing_id_hash = hash_secondary_listing_id(&old.id);
+        let bids = internal_take_bids(&mut old.bids);
+        let mut listing = SecondaryListing {
+            id: old.id,
+            seller_id: old.seller_id,
+            approval_id: old.approval_id,
+            is_in_custody: false,
+            collection_id: None,
+            nft_metadata: old.nft_metadata,
+            nft_mutable_metadata: old.nft_mutable_metadata,
+            price_yocto: old.price_yocto,
+            min_bid_yocto: old.min_bid_yocto,
+            start_timestamp: old.start_timestamp,
+            end_timestamp: old.end_timestamp,
+            status: old.status,
+            bids: BidBook::new(
+                SecondaryListingStorageKey::Bids { listing_id_hash }
+                    .try_to_vec()
+                    .unwrap(),
+                SecondaryListingStorageKey::BidKeys { listing_id_hash }
+                    .try_to_vec()
+                    .unwrap(),
+            ),
+            next_bid_id: old.next_bid_id,
+            version: 0,
+            seq: 0, // assigned by internal_add_secondary_listing
+            rules: self.config.listing_rules_default.clone(),
+        };
+        for bid in bids.iter() {
+            listing.bids.insert(bid);
+            self.internal_add_bid_to_account(
+                &bid.bidder_id,
+                ListingId::Secondary(listing.id.clone()),
+                bid.id,
+            );
+        }
+        self.internal_add_secondary_listing(&mut listing);
+    }
+}
+
+// V1 bids held no proxy maximum, their escrow is the amount
+fn internal_take_bids(old_bids: &mut Vector<BidV1>) -> Vec<Bid> {
+    let bids = old_bids
+        .iter()
+        .map(|bid| Bid {
+            id: bid.id,
+            bidder_id: bid.bidder_id,
+            amount_yocto: bid.amount_yocto,
+            max_amount_yocto: bid.amount_yocto,
+        })
+        .collect();
+    old_bids.clear();
+    bids
+}
@@ -0,0 +1,224 @@
+#[cfg(test)]
+mod migration_tests {
+    use super::super::{BidV1, MarketplaceContractV1, PrimaryListingV1, SecondaryListingV1};
+    use crate::{
+        external::NftMutableMetadata,
+        listing::{
+            primary::{internal::hash_primary_listing_id, lib::PrimaryListingStorageKey},
+            secondary::{internal::hash_secondary_listing_id, lib::SecondaryListingStorageKey},
+            status::ListingStatus,
+        },
+        test_utils::*,
+        *,
+    };
+
+    const TOKEN_ID: &str = "0:1";
+
+    fn primary_listing_id() -> PrimaryListingId {
+        PrimaryListingId {
+            nft_contract_id: account(NFT_CONTRACT_ID),
+            collection_id: 0,
+        }
+    }
+
+    fn secondary_listing_id() -> SecondaryListingId {
+        SecondaryListingId {
+            nft_contract_id: account(NFT_CONTRACT_ID),
+            token_id: TOKEN_ID.to_string(),
+        }
+    }
+
+    fn bids_v1(prefix: Vec<u8>, bidder_id: &str) -> Vector<BidV1> {
+        let mut bids = Vector::new(prefix);
+        bids.push(&BidV1 {
+            id: 0,
+            bidder_id: account(bidder_id),
+            amount_yocto: MIN_BID_YOCTO,
+        });
+        bids
+    }
+
+    // V1 state with a primary and a secondary auction, each holding a bid
+    fn write_v1_state() {
+        let primary_listing_id = primary_listing_id();
+        let secondary_listing_id = secondary_listing_id();
+        let primary_listing_id_hash = hash_primary_listing_id(&primary_listing_id, 0);
+        let secondary_listing_id_hash = hash_secondary_listing_id(&secondary_listing_id);
+
+        let mut old = MarketplaceContractV1 {
+            owner_id: account(MARKETPLACE_ACCOUNT_ID),
+            primary_listings_by_id: UnorderedMap::new(MarketplaceStorageKey::PrimaryListingsById),
+            primary_listings_by_seller_id: LookupMap::new(
+                MarketplaceStorageKey::PrimaryListingsBySellerId,
+            ),
+            secondary_listings_by_id: UnorderedMap::new(
+                MarketplaceStorageKey::SecondaryListingsById,
+            ),
+            secondary_listings_by_seller_id: LookupMap::new(
+                MarketplaceStorageKey::SecondaryListingsBySellerId,
+            ),
+            storage_deposits: LookupMap::new(MarketplaceStorageKey::StorageDeposits),
+        };
+        old.storage_deposits
+            .insert(&account(SELLER_ACCOUNT_ID), &STORAGE_DEPOSIT);
+        old.primary_listings_by_id.insert(
+            &primary_listing_id,
+            &PrimaryListingV1 {
+                id: primary_listing_id.clone(),
+                seller_id: account(SELLER_ACCOUNT_ID),
+                nft_metadata: nft_metadata(0),
+                nft_mutable_metadata: NftMutableMetadata {
+                    aux_audio_url: None,
+                },
+                supply_total: 10,
+                price_yocto: Some(PRICE_YOCTO),
+                min_bid_yocto: Some(MIN_BID_YOCTO),
+                start_timestamp: 0,
+                end_timestamp: Some((24 * HOUR_NANO) as i64),
+                status: ListingStatus::Running,
+                supply_left: 8,
+                bids: bids_v1(
+                    PrimaryListingStorageKey::Bids {
+                        listing_id_hash: primary_listing_id_hash,
+                    }
+                    .try_to_vec()
+                    .unwrap(),
+                    BIDDER_ACCOUNT_ID,
+                ),
+                next_bid_id: 1,
+            },
+        );
+        old.secondary_listings_by_id.insert(
+            &secondary_listing_id,
+            &SecondaryListingV1 {
+                id: secondary_listing_id.clone(),
+                seller_id: account(SELLER_ACCOUNT_ID),
+                approval_id: 3,
+                nft_metadata: nft_metadata(0),
+                nft_mutable_metadata: NftMutableMetadata {
+                    aux_audio_url: None,
+                },
+                price_yocto: Some(PRICE_YOCTO),
+                min_bid_yocto: Some(MIN_BID_YOCTO),
+                start_timestamp: 0,
+                end_timestamp: Some((24 * HOUR_NANO) as i64),
+                status: ListingStatus::Running,
+                bids: bids_v1(
+                    SecondaryListingStorageKey::Bids {
+                        listing_id_hash: secondary_listing_id_hash,
+                    }
+                    .try_to_vec()
+                    .unwrap(),
+                    BIDDER2_ACCOUNT_ID,
+                ),
+                next_bid_id: 1,
+            },
+        );
+        env::state_write(&old);
+    }
+
+    /* migrate */
+
+    #[test]
+    fn test_migrate_keeps_deposits() {
+        set_context(MARKETPLACE_ACCOUNT_ID, NOW, 0);
+        write_v1_state();
+
+        let marketplace = MarketplaceContract::migrate();
+
+        assert_eq!(marketplace.owner_id, account(MARKETPLACE_ACCOUNT_ID));
+        assert_eq!(
+            marketplace
+                .storage_deposits
+                .get(&account(SELLER_ACCOUNT_ID)),
+            Some(STORAGE_DEPOSIT)
+        );
+        assert_eq!(marketplace.next_offer_id, 0);
+        assert_eq!(marketplace.next_bundle_id, 0);
+        assert_eq!(marketplace.sales.len(), 0);
+    }
+
+    #[test]
+    fn test_migrate_primary_listing() {
+        set_context(MARKETPLACE_ACCOUNT_ID, NOW, 0);
+        write_v1_state();
+
+        let marketplace = MarketplaceContract::migrate();
+
+        let listing = marketplace
+            .primary_listings_by_id
+            .get(&primary_listing_id())
+            .unwrap();
+        assert_eq!(listing.supply_left, 8);
+        assert_eq!(listing.next_bid_id, 1);
+        assert_eq!(listing.bids.len(), 1);
+        let bid = listing.bids.best().unwrap();
+        assert_eq!(bid.bidder_id, account(BIDDER_ACCOUNT_ID));
+        assert_eq!(bid.max_amount_yocto, MIN_BID_YOCTO);
+        assert_eq!(listing.round, 0);
+        assert!(listing.max_per_account.is_none());
+
+        let collection = marketplace
+            .primary_collections
+            .get(&(account(NFT_CONTRACT_ID), 0))
+            .unwrap();
+        assert_eq!(collection.max_supply, 10);
+        assert_eq!(collection.supply_minted, 2);
+        assert_eq!(collection.next_round, 1);
+
+        let bids = marketplace.bids_by_account(account(BIDDER_ACCOUNT_ID), None, None);
+        assert_eq!(bids.len(), 1);
+        assert_eq!(bids[0].amount_yocto.0, MIN_BID_YOCTO);
+    }
+
+    #[test]
+    fn test_migrate_secondary_listing() {
+        set_context(MARKETPLACE_ACCOUNT_ID, NOW, 0);
+        write_v1_state();
+
+        let marketplace = MarketplaceContract::migrate();
+
+        let listing = marketplace
+            .secondary_listings_by_id
+            .get(&secondary_listing_id())
+            .unwrap();
+        assert_eq!(listing.approval_id, 3);
+        assert!(!listing.is_in_custody);
+        assert!(listing.collection_id.is_none());
+        assert_eq!(listing.bids.len(), 1);
+        assert_eq!(
+            listing.bids.best().unwrap().bidder_id,
+            account(BIDDER2_ACCOUNT_ID)
+        );
+        assert_eq!(
+            marketplace
+                .bids_by_account(account(BIDDER2_ACCOUNT_ID), None, None)
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_migrate_indexes_listings() {
+        set_context(MARKETPLACE_ACCOUNT_ID, NOW, 0);
+        write_v1_state();
+
+        let marketplace = MarketplaceContract::migrate();
+
+        assert_eq!(marketplace.next_listing_seq, 2);
+        assert_eq!(marketplace.primary_listings_by_id.len(), 1);
+        assert_eq!(marketplace.secondary_listings_by_id.len(), 1);
+        assert!(marketplace
+            .primary_listings_by_seller_id
+            .get(&account(SELLER_ACCOUNT_ID))
+            .unwrap()
+            .contains(&primary_listing_id()));
+        assert!(marketplace
+            .floor_by_contract
+            .iter()
+            .any(|(_, listing_id)| matches!(
+                listing_id,
+                ListingId::Secondary(listing_id) if listing_id.token_id == TOKEN_ID
+            )));
+    }
+}
@@ -0,0 +1,510 @@
+use crate::{
+    constants::NO_DEPOSIT,
+    external::{nft_contract, JsonNft},
+    internal::hash_account_id,
+    listing::sale::Sale,
+    offer::OfferId,
+    *,
+};
+use near_sdk::json_types::{U128, U64};
+use near_sdk::{PromiseOrValue, PromiseResult};
+
+pub const COLLECTION_OFFER_QUANTITY_MAX: u64 = 100;
+
+const NFT_TOKEN_GAS: Gas = Gas(5_000_000_000_000); // TODO: measure
+const NFT_TRANSFER_GAS: Gas = Gas(15_000_000_000_000); // TODO: measure
+const COLLECTION_OFFER_ACCEPT_COMPLETION_GAS: Gas = Gas(15_000_000_000_000); // TODO: measure
+const COLLECTION_OFFER_NFT_TOKEN_COMPLETION_GAS: Gas = Gas(45_000_000_000_000); // TODO: measure, covers the transfer and its completion
+
+#[cfg(test)]
+#[path = "collection_tests.rs"]
+mod collection_tests;
+
+// (nft_contract_id, collection_id, u128::MAX - price_yocto, offer_id) so that the best offer
+// for a contract or collection comes first, among equal prices the earlier one wins
+// collection_id None means any token of the contract
+pub type CollectionOfferKey = (AccountId, Option<NftCollectionId>, u128, OfferId);
+
+// escrowed offer for any token of an NFT contract or of one of its collections
+// the full price of all the tokens wanted is deposited when the offer is placed
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct CollectionOffer {
+    pub id: OfferId,
+    pub bidder_id: AccountId,
+    pub nft_contract_id: AccountId,
+    pub collection_id: Option<NftCollectionId>, // None means any token of the contract
+    pub price_yocto: u128,                      // per token
+    pub quantity_left: u64,                     // tokens still wanted
+    pub quantity_pending: u64,                  // accepted, token transfer in flight
+    pub is_withdrawn: bool,
+    pub timestamp: u64, // nanoseconds since 1970-01-01
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct JsonCollectionOffer {
+    pub id: U64,
+    pub bidder_id: AccountId,
+    pub nft_contract_id: AccountId,
+    pub collection_id: Option<U64>,
+    pub price_yocto: U128,
+    pub quantity_left: U64,
+    pub quantity_pending: U64,
+    pub timestamp: U64,
+}
+
+impl CollectionOffer {
+    fn key(&self) -> CollectionOfferKey {
+        (
+            self.nft_contract_id.clone(),
+            self.collection_id,
+            u128::MAX - self.price_yocto,
+            self.id,
+        )
+    }
+
+    // withdrawn and filled offers stay around only until pending transfers resolve
+    fn is_open(&self) -> bool {
+        !self.is_withdrawn && self.quantity_left > 0
+    }
+
+    fn to_json(&self) -> JsonCollectionOffer {
+        JsonCollectionOffer {
+            id: U64(self.id),
+            bidder_id: self.bidder_id.clone(),
+            nft_contract_id: self.nft_contract_id.clone(),
+            collection_id: self.collection_id.map(|c| U64(c)),
+            price_yocto: U128(self.price_yocto),
+            quantity_left: U64(self.quantity_left),
+            quantity_pending: U64(self.quantity_pending),
+            timestamp: U64(self.timestamp),
+        }
+    }
+}
+
+#[near_bindgen]
+impl MarketplaceContract {
+    // we only expect the deposit equal to price times quantity plus the storage of the offer,
+    // the surplus is returned; returns the offer id
+    #[payable]
+    pub fn collection_offer_place(
+        &mut self,
+        nft_contract_id: AccountId,
+        collection_id: Option<U64>,
+        price_yocto: U128,
+        quantity: U64,
+    ) -> U64 {
+        let price_yocto = price_yocto.0;
+        let quantity = quantity.0;
+
+        // offers follow the bid rules of listings which don't set their own
+        let rules = &self.config.listing_rules_default;
+        assert!(
+            price_yocto >= rules.min_bid_yocto,
+            "Offer cannot be lower than {} yoctoNear",
+            rules.min_bid_yocto
+        );
+        assert!(
+            price_yocto % rules.bid_step_yocto == 0,
+            "Offer must be integer multiple of {} yoctoNear",
+            rules.bid_step_yocto
+        );
+        assert!(
+            quantity > 0 && quantity <= COLLECTION_OFFER_QUANTITY_MAX,
+            "Quantity must be between 1 and {}",
+            COLLECTION_OFFER_QUANTITY_MAX
+        );
+
+        let bidder_id = env::predecessor_account_id();
+        let offer = CollectionOffer {
+            id: self.next_offer_id,
+            bidder_id: bidder_id.clone(),
+            nft_contract_id,
+            collection_id: collection_id.map(|c| c.0),
+            price_yocto,
+            quantity_left: quantity,
+            quantity_pending: 0,
+            is_withdrawn: false,
+            timestamp: env::block_timestamp(),
+        };
+        self.next_offer_id += 1;
+
+        let storage_before = env::storage_usage();
+        self.collection_offers_by_id.insert(&offer.id, &offer);
+        self.collection_offer_book.insert(&offer.key(), &offer.id);
+        self.internal_add_collection_offer_to_account(&bidder_id, offer.id);
+        let storage_after = env::storage_usage();
+        let storage_cost = (storage_after - storage_before) as Balance * env::storage_byte_cost();
+
+        let required_deposit = price_yocto * quantity as u128 + storage_cost;
+        let attached_deposit = env::attached_deposit();
+        assert!(
+            attached_deposit >= required_deposit,
+            "Attached deposit of {} is insufficient to pay {} for {} token(s) and {} for storage",
+            attached_deposit,
+            price_yocto,
+            quantity,
+            storage_cost
+        );
+        let refund = attached_deposit - required_deposit;
+        if refund > 0 {
+            Promise::new(bidder_id).transfer(refund);
+        }
+
+        U64(offer.id)
+    }
+
+    // returns the deposit for the tokens not bought yet; tokens whose transfer is in flight
+    // are still paid for, or refunded if the transfer fails
+    pub fn collection_offer_withdraw(&mut self, offer_id: U64) {
+        let mut offer = self
+            .collection_offers_by_id
+            .get(&offer_id.0)
+            .expect("Could not find offer");
+        assert!(
+            offer.bidder_id == env::predecessor_account_id(),
+            "Not authorized to withdraw this offer"
+        );
+        assert!(offer.is_open(), "This offer is closed");
+
+        let refund = offer.price_yocto * offer.quantity_left as u128;
+        self.collection_offer_book.remove(&offer.key());
+        offer.quantity_left = 0;
+        offer.is_withdrawn = true;
+        self.internal_update_collection_offer(&offer);
+
+        Promise::new(offer.bidder_id).transfer(refund);
+    }
+}
+
+impl MarketplaceContract {
+    // sells the token to the author of the offer, called from nft_on_approve with the approval
+    // the token was just given; one token of the offer is reserved until the sale resolves
+    pub(crate) fn internal_accept_collection_offer(
+        &mut self,
+        owner_id: &AccountId,
+        nft_contract_id: AccountId,
+        token_id: NftId,
+        approval_id: u64,
+        offer_id: OfferId,
+    ) -> Promise {
+        let mut offer = self
+            .collection_offers_by_id
+            .get(&offer_id)
+            .expect("Could not find offer");
+        assert!(offer.is_open(), "This offer is closed");
+        assert!(
+            offer.nft_contract_id == nft_contract_id,
+            "This offer is for tokens of {}",
+            offer.nft_contract_id
+        );
+        assert!(&offer.bidder_id != owner_id, "Cannot accept your own offer");
+
+        offer.quantity_left -= 1;
+        offer.quantity_pending += 1;
+        if offer.quantity_left == 0 {
+            self.collection_offer_book.remove(&offer.key());
+        }
+        self.collection_offers_by_id.insert(&offer.id, &offer);
+
+        // the collection of the token is only known to the NFT contract
+        nft_contract::nft_token(token_id.clone(), nft_contract_id, NO_DEPOSIT, NFT_TOKEN_GAS).then(
+            ext_self_nft::collection_offer_nft_token_completion(
+                U64(offer_id),
+                owner_id.clone(),
+                token_id,
+                approval_id,
+                env::current_account_id(),
+                NO_DEPOSIT,
+                COLLECTION_OFFER_NFT_TOKEN_COMPLETION_GAS,
+            ),
+        )
+    }
+
+    // gives the token reserved by an accept back to the offer, or its price back to the bidder
+    // if the offer has been withdrawn meanwhile
+    fn internal_release_collection_offer(&mut self, offer_id: OfferId) {
+        let mut offer = self
+            .collection_offers_by_id
+            .get(&offer_id)
+            .expect("Could not find offer");
+        offer.quantity_pending -= 1;
+        if offer.is_withdrawn {
+            Promise::new(offer.bidder_id.clone()).transfer(offer.price_yocto);
+        } else {
+            if offer.quantity_left == 0 {
+                self.collection_offer_book.insert(&offer.key(), &offer.id);
+            }
+            offer.quantity_left += 1;
+        }
+        self.internal_update_collection_offer(&offer);
+    }
+
+    // stores the offer, or removes it once it's closed and nothing is pending anymore;
+    // the freed storage goes back to the bidder
+    fn internal_update_collection_offer(&mut self, offer: &CollectionOffer) {
+        if offer.is_open() || offer.quantity_pending > 0 {
+            self.collection_offers_by_id.insert(&offer.id, offer);
+            return;
+        }
+
+        let storage_before = env::storage_usage();
+        self.collection_offers_by_id.remove(&offer.id);
+        self.collection_offer_book.remove(&offer.key());
+        self.internal_remove_collection_offer_from_account(&offer.bidder_id, offer.id);
+        let storage_after = env::storage_usage();
+        let storage_refund = (storage_before - storage_after) as Balance * env::storage_byte_cost();
+        Promise::new(offer.bidder_id.clone()).transfer(storage_refund);
+    }
+
+    fn internal_add_collection_offer_to_account(
+        &mut self,
+        bidder_id: &AccountId,
+        offer_id: OfferId,
+    ) {
+        let mut offers = self
+            .collection_offers_by_account
+            .get(bidder_id)
+            .unwrap_or_else(|| {
+                UnorderedSet::new(
+                    MarketplaceStorageKey::CollectionOffersByAccountInner {
+                        account_id_hash: hash_account_id(bidder_id),
+                    }
+                    .try_to_vec()
+                    .unwrap(),
+                )
+            });
+        offers.insert(&offer_id);
+        self.collection_offers_by_account.insert(bidder_id, &offers);
+    }
+
+    fn internal_remove_collection_offer_from_account(
+        &mut self,
+        bidder_id: &AccountId,
+        offer_id: OfferId,
+    ) {
+        if let Some(mut offers) = self.collection_offers_by_account.get(bidder_id) {
+            offers.remove(&offer_id);
+            if offers.is_empty() {
+                self.collection_offers_by_account.remove(bidder_id);
+            } else {
+                self.collection_offers_by_account.insert(bidder_id, &offers);
+            }
+        }
+    }
+}
+
+#[ext_contract(ext_self_nft)]
+trait CollectionOfferCallback {
+    fn collection_offer_nft_token_completion(
+        &mut self,
+        offer_id: U64,
+        owner_id: AccountId,
+        token_id: NftId,
+        approval_id: u64,
+    ) -> PromiseOrValue<bool>;
+
+    fn collection_offer_accept_completion(
+        &mut self,
+        offer_id: U64,
+        owner_id: AccountId,
+        token_id: NftId,
+        collection_id: U64,
+    ) -> bool;
+}
+
+trait CollectionOfferCallback {
+    fn collection_offer_nft_token_completion(
+        &mut self,
+        offer_id: U64,
+        owner_id: AccountId,
+        token_id: NftId,
+        approval_id: u64,
+    ) -> PromiseOrValue<bool>;
+
+    fn collection_offer_accept_completion(
+        &mut self,
+        offer_id: U64,
+        owner_id: AccountId,
+        token_id: NftId,
+        collection_id: U64,
+    ) -> bool;
+}
+
+#[near_bindgen]
+impl CollectionOfferCallback for MarketplaceContract {
+    // transfers the token if it matches the offer; resolves to true if the token was sold
+    #[private]
+    fn collection_offer_nft_token_completion(
+        &mut self,
+        offer_id: U64,
+        owner_id: AccountId,
+        token_id: NftId,
+        approval_id: u64,
+    ) -> PromiseOrValue<bool> {
+        let offer = self
+            .collection_offers_by_id
+            .get(&offer_id.0)
+            .expect("Could not find offer");
+
+        assert_eq!(env::promise_results_count(), 1, "Too many data receipts");
+        let token: Option<JsonNft> = match env::promise_result(0) {
+            PromiseResult::Successful(val) => {
+                near_sdk::serde_json::from_slice::<Option<JsonNft>>(&val).unwrap_or(None)
+            }
+            _ => None,
+        };
+        let verified_token = token.filter(|token| {
+            token.owner_id == owner_id
+                && token.approved_account_ids.get(&env::current_account_id()) == Some(&approval_id)
+                && offer
+                    .collection_id
+                    .map_or(true, |collection_id| collection_id == token.collection_id)
+        });
+
+        if let Some(token) = verified_token {
+            PromiseOrValue::Promise(
+                nft_contract::nft_transfer(
+                    offer.bidder_id.clone(),
+                    token_id.clone(),
+                    Some(approval_id),
+                    None,
+                    offer.nft_contract_id.clone(),
+                    1,
+                    NFT_TRANSFER_GAS,
+                )
+                .then(ext_self_nft::collection_offer_accept_completion(
+                    offer_id,
+                    owner_id,
+                    token_id,
+                    U64(token.collection_id),
+                    env::current_account_id(),
+                    NO_DEPOSIT,
+                    COLLECTION_OFFER_ACCEPT_COMPLETION_GAS,
+                )),
+            )
+        } else {
+            // we don't panic here, it'd keep the token reserved
+            env::log_str("Token owner, approval or collection mismatch, offer not accepted");
+            self.internal_release_collection_offer(offer_id.0);
+            PromiseOrValue::Value(false)
+        }
+    }
+
+    // returns true if the token was sold
+    #[private]
+    fn collection_offer_accept_completion(
+        &mut self,
+        offer_id: U64,
+        owner_id: AccountId,
+        token_id: NftId,
+        collection_id: U64,
+    ) -> bool {
+        assert_eq!(env::promise_results_count(), 1, "Too many data receipts");
+        match env::promise_result(0) {
+            PromiseResult::Successful(_) => {
+                let mut offer = self
+                    .collection_offers_by_id
+                    .get(&offer_id.0)
+                    .expect("Could not find offer");
+                offer.quantity_pending -= 1;
+                self.internal_update_collection_offer(&offer);
+                // the transfer reset the approvals, listings of the token are dead
+                self.internal_delist_token(&offer.nft_contract_id, &token_id, &owner_id);
+
+                // the 1yN attached to nft_transfer is taken from the offer
+                Promise::new(owner_id.clone()).transfer(offer.price_yocto - 1);
+                self.internal_record_sale(
+                    Sale {
+                        listing_id: None,
+                        token_id,
+                        buyer_id: offer.bidder_id,
+                        seller_id: owner_id,
+                        price_yocto: offer.price_yocto,
+                        timestamp: env::block_timestamp(),
+                    },
+                    &offer.nft_contract_id,
+                    Some(collection_id.0),
+                );
+                true
+            }
+            _ => {
+                self.internal_release_collection_offer(offer_id.0);
+                false
+            }
+        }
+    }
+}
+
+// view-only methods
+
+#[near_bindgen]
+impl MarketplaceContract {
+    pub fn collection_offer(&self, offer_id: U64) -> Option<JsonCollectionOffer> {
+        self.collection_offers_by_id
+            .get(&offer_id.0)
+            .map(|offer| offer.to_json())
+    }
+
+    // open offers for the NFT contract, or for one of its collections if collection_id is given,
+    // best first; contract-wide offers are not included in the collection ones
+    // results are paginated
+    pub fn collection_offers(
+        &self,
+        nft_contract_id: AccountId,
+        collection_id: Option<U64>,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<JsonCollectionOffer> {
+        let collection_id = collection_id.map(|c| c.0);
+
+        //where to start pagination - if we have a from_index, we'll use that - otherwise start from 0 index
+        let start = u128::from(from_index.unwrap_or(U128(0))) as usize;
+        let count = limit.unwrap_or(10) as usize;
+
+        // key 0 would be an offer at u128::MAX, which cannot be deposited
+        self.collection_offer_book
+            .iter_from((nft_contract_id.clone(), collection_id, 0, 0))
+            .take_while(|(key, _)| key.0 == nft_contract_id && key.1 == collection_id)
+            .skip(start)
+            .take(count)
+            .map(|(_, offer_id)| {
+                self.collection_offers_by_id
+                    .get(&offer_id)
+                    .unwrap()
+                    .to_json()
+            })
+            .collect()
+    }
+
+    // offers placed by the account, including closed ones still waiting for transfers to resolve
+    // results are paginated
+    pub fn collection_offers_by_account(
+        &self,
+        account_id: AccountId,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<JsonCollectionOffer> {
+        let offers = self.collection_offers_by_account.get(&account_id);
+        if offers.is_none() {
+            return vec![];
+        }
+        let offers = offers.unwrap();
+
+        //where to start pagination - if we have a from_index, we'll use that - otherwise start from 0 index
+        let start = u128::from(from_index.unwrap_or(U128(0))) as usize;
+        let count = limit.unwrap_or(10) as usize;
+
+        offers
+            .iter()
+            .skip(start)
+            .take(count)
+            .map(|offer_id| {
+                self.collection_offers_by_id
+                    .get(&offer_id)
+                    .unwrap()
+                    .to_json()
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,317 @@
+#[cfg(test)]
+mod collection_tests {
+    use super::super::{CollectionOfferCallback, JsonCollectionOffer};
+    use crate::{test_utils::*, *};
+    use near_sdk::json_types::{U128, U64};
+    use near_sdk::serde_json::{self, json};
+    use near_sdk::{PromiseOrValue, PromiseResult};
+
+    const TOKEN_ID: &str = "0:1";
+
+    fn place(
+        marketplace: &mut MarketplaceContract,
+        bidder_id: &str,
+        collection_id: Option<u64>,
+        price_yocto: Balance,
+        quantity: u64,
+    ) -> u64 {
+        set_context(bidder_id, NOW, price_yocto * quantity as u128 + ONE_NEAR);
+        marketplace
+            .collection_offer_place(
+                account(NFT_CONTRACT_ID),
+                collection_id.map(U64),
+                U128(price_yocto),
+                U64(quantity),
+            )
+            .0
+    }
+
+    fn offer_ids(offers: Vec<JsonCollectionOffer>) -> Vec<u64> {
+        offers.iter().map(|offer| offer.id.0).collect()
+    }
+
+    fn token_result(owner_id: &str, collection_id: u64, approval_id: u64) -> PromiseResult {
+        PromiseResult::Successful(
+            serde_json::to_vec(&json!({
+                "token_id": TOKEN_ID,
+                "owner_id": owner_id,
+                "collection_id": collection_id,
+                "metadata": {"title": "Collection 0"},
+                "mutable_metadata": {},
+                "approved_account_ids": {MARKETPLACE_ACCOUNT_ID: approval_id}
+            }))
+            .unwrap(),
+        )
+    }
+
+    // the seller accepts the offer with the token, one token of it is reserved
+    fn accept(marketplace: &mut MarketplaceContract, offer_id: u64) {
+        set_context(NFT_CONTRACT_ID, NOW, 0);
+        marketplace.internal_accept_collection_offer(
+            &account(SELLER_ACCOUNT_ID),
+            account(NFT_CONTRACT_ID),
+            TOKEN_ID.to_string(),
+            1,
+            offer_id,
+        );
+    }
+
+    fn complete_accept(
+        marketplace: &mut MarketplaceContract,
+        offer_id: u64,
+        promise_result: PromiseResult,
+    ) -> bool {
+        set_callback_context(NOW, vec![promise_result]);
+        marketplace.collection_offer_accept_completion(
+            U64(offer_id),
+            account(SELLER_ACCOUNT_ID),
+            TOKEN_ID.to_string(),
+            U64(0),
+        )
+    }
+
+    /* offer book */
+
+    #[test]
+    fn test_collection_offers_best_first() {
+        let mut marketplace = marketplace();
+        let low = place(&mut marketplace, BIDDER_ACCOUNT_ID, Some(0), ONE_NEAR, 1);
+        let high = place(
+            &mut marketplace,
+            BIDDER2_ACCOUNT_ID,
+            Some(0),
+            2 * ONE_NEAR,
+            1,
+        );
+        let same_as_low = place(&mut marketplace, BUYER_ACCOUNT_ID, Some(0), ONE_NEAR, 1);
+
+        let offers =
+            marketplace.collection_offers(account(NFT_CONTRACT_ID), Some(U64(0)), None, None);
+
+        // among equal prices the earlier offer comes first
+        assert_eq!(offer_ids(offers), vec![high, low, same_as_low]);
+    }
+
+    #[test]
+    fn test_collection_offers_by_scope() {
+        let mut marketplace = marketplace();
+        let any = place(&mut marketplace, BIDDER_ACCOUNT_ID, None, ONE_NEAR, 1);
+        let collection_0 = place(&mut marketplace, BIDDER_ACCOUNT_ID, Some(0), ONE_NEAR, 1);
+        let collection_1 = place(&mut marketplace, BIDDER_ACCOUNT_ID, Some(1), ONE_NEAR, 1);
+
+        let nft_contract_id = account(NFT_CONTRACT_ID);
+        assert_eq!(
+            offer_ids(marketplace.collection_offers(nft_contract_id.clone(), None, None, None)),
+            vec![any]
+        );
+        assert_eq!(
+            offer_ids(marketplace.collection_offers(
+                nft_contract_id.clone(),
+                Some(U64(0)),
+                None,
+                None
+            )),
+            vec![collection_0]
+        );
+        assert_eq!(
+            offer_ids(marketplace.collection_offers(nft_contract_id, Some(U64(1)), None, None)),
+            vec![collection_1]
+        );
+        assert!(marketplace
+            .collection_offers(account(BUYER_ACCOUNT_ID), None, None, None)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_collection_offers_pagination() {
+        let mut marketplace = marketplace();
+        let offer_ids_placed: Vec<u64> = (1..=5)
+            .map(|price| {
+                place(
+                    &mut marketplace,
+                    BIDDER_ACCOUNT_ID,
+                    Some(0),
+                    price * ONE_NEAR,
+                    1,
+                )
+            })
+            .collect();
+
+        let page = marketplace.collection_offers(
+            account(NFT_CONTRACT_ID),
+            Some(U64(0)),
+            Some(U128(1)),
+            Some(2),
+        );
+
+        assert_eq!(
+            offer_ids(page),
+            vec![offer_ids_placed[3], offer_ids_placed[2]]
+        );
+        assert_eq!(
+            marketplace
+                .collection_offers_by_account(account(BIDDER_ACCOUNT_ID), None, Some(10))
+                .len(),
+            5
+        );
+        assert!(marketplace
+            .collection_offers_by_account(account(BIDDER2_ACCOUNT_ID), None, None)
+            .is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Quantity must be between 1 and"#)]
+    fn test_collection_offer_zero_quantity() {
+        let mut marketplace = marketplace();
+        place(&mut marketplace, BIDDER_ACCOUNT_ID, Some(0), ONE_NEAR, 0);
+    }
+
+    #[test]
+    fn test_collection_offer_withdraw() {
+        let mut marketplace = marketplace();
+        let offer_id = place(&mut marketplace, BIDDER_ACCOUNT_ID, Some(0), ONE_NEAR, 2);
+
+        set_context(BIDDER_ACCOUNT_ID, NOW, 0);
+        marketplace.collection_offer_withdraw(U64(offer_id));
+
+        assert!(marketplace.collection_offer(U64(offer_id)).is_none());
+        assert!(marketplace
+            .collection_offers(account(NFT_CONTRACT_ID), Some(U64(0)), None, None)
+            .is_empty());
+        assert!(marketplace
+            .collection_offers_by_account(account(BIDDER_ACCOUNT_ID), None, None)
+            .is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Not authorized to withdraw this offer"#)]
+    fn test_collection_offer_withdraw_by_other() {
+        let mut marketplace = marketplace();
+        let offer_id = place(&mut marketplace, BIDDER_ACCOUNT_ID, Some(0), ONE_NEAR, 2);
+
+        set_context(BIDDER2_ACCOUNT_ID, NOW, 0);
+        marketplace.collection_offer_withdraw(U64(offer_id));
+    }
+
+    /* accepting */
+
+    #[test]
+    fn test_collection_offer_accept_reserves_token() {
+        let mut marketplace = marketplace();
+        let offer_id = place(&mut marketplace, BIDDER_ACCOUNT_ID, Some(0), ONE_NEAR, 1);
+
+        accept(&mut marketplace, offer_id);
+
+        let offer = marketplace.collection_offer(U64(offer_id)).unwrap();
+        assert_eq!(offer.quantity_left.0, 0);
+        assert_eq!(offer.quantity_pending.0, 1);
+        // the last token is reserved so the offer is off the book
+        assert!(marketplace
+            .collection_offers(account(NFT_CONTRACT_ID), Some(U64(0)), None, None)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_collection_offer_token_mismatch_releases_token() {
+        let mut marketplace = marketplace();
+        let offer_id = place(&mut marketplace, BIDDER_ACCOUNT_ID, Some(0), ONE_NEAR, 1);
+        accept(&mut marketplace, offer_id);
+
+        // the token belongs to another collection
+        set_callback_context(NOW, vec![token_result(SELLER_ACCOUNT_ID, 1, 1)]);
+        let result = marketplace.collection_offer_nft_token_completion(
+            U64(offer_id),
+            account(SELLER_ACCOUNT_ID),
+            TOKEN_ID.to_string(),
+            1,
+        );
+
+        assert!(matches!(result, PromiseOrValue::Value(false)));
+        let offer = marketplace.collection_offer(U64(offer_id)).unwrap();
+        assert_eq!(offer.quantity_left.0, 1);
+        assert_eq!(offer.quantity_pending.0, 0);
+        assert_eq!(
+            offer_ids(marketplace.collection_offers(
+                account(NFT_CONTRACT_ID),
+                Some(U64(0)),
+                None,
+                None
+            )),
+            vec![offer_id]
+        );
+    }
+
+    #[test]
+    fn test_collection_offer_accept_completion() {
+        let mut marketplace = marketplace();
+        let offer_id = place(&mut marketplace, BIDDER_ACCOUNT_ID, Some(0), ONE_NEAR, 2);
+        accept(&mut marketplace, offer_id);
+
+        assert!(complete_accept(
+            &mut marketplace,
+            offer_id,
+            PromiseResult::Successful(vec![])
+        ));
+
+        let offer = marketplace.collection_offer(U64(offer_id)).unwrap();
+        assert_eq!(offer.quantity_left.0, 1);
+        assert_eq!(offer.quantity_pending.0, 0);
+    }
+
+    #[test]
+    fn test_collection_offer_accept_completion_removes_filled_offer() {
+        let mut marketplace = marketplace();
+        let offer_id = place(&mut marketplace, BIDDER_ACCOUNT_ID, Some(0), ONE_NEAR, 1);
+        accept(&mut marketplace, offer_id);
+
+        complete_accept(
+            &mut marketplace,
+            offer_id,
+            PromiseResult::Successful(vec![]),
+        );
+
+        assert!(marketplace.collection_offer(U64(offer_id)).is_none());
+        assert!(marketplace
+            .collection_offers_by_account(account(BIDDER_ACCOUNT_ID), None, None)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_collection_offer_accept_completion_delists_token() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        let listing_id =
+            add_secondary_listing(&mut marketplace, TOKEN_ID, Some(2 * ONE_NEAR), None, false);
+        let offer_id = place(&mut marketplace, BIDDER_ACCOUNT_ID, Some(0), ONE_NEAR, 1);
+        accept(&mut marketplace, offer_id);
+
+        complete_accept(
+            &mut marketplace,
+            offer_id,
+            PromiseResult::Successful(vec![]),
+        );
+
+        // the transfer reset the approvals, the listing would be a ghost
+        assert!(marketplace
+            .secondary_listings_by_id
+            .get(&listing_id)
+            .is_none());
+    }
+
+    #[test]
+    fn test_collection_offer_accept_completion_failed_transfer() {
+        let mut marketplace = marketplace();
+        let offer_id = place(&mut marketplace, BIDDER_ACCOUNT_ID, Some(0), ONE_NEAR, 1);
+        accept(&mut marketplace, offer_id);
+
+        assert!(!complete_accept(
+            &mut marketplace,
+            offer_id,
+            PromiseResult::Failed
+        ));
+
+        let offer = marketplace.collection_offer(U64(offer_id)).unwrap();
+        assert_eq!(offer.quantity_left.0, 1);
+        assert_eq!(offer.quantity_pending.0, 0);
+    }
+}
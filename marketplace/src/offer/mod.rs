@@ -0,0 +1,5 @@
+pub type OfferId = u64;
+
+pub mod collection;
+pub mod token;
+pub mod swap;
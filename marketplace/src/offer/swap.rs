@@ -0,0 +1,587 @@
+use crate::{
+    constants::NO_DEPOSIT,
+    delivery::{NFT_DELIVERY_COMPLETION_GAS, NFT_DELIVERY_GAS},
+    external::{nft_contract, JsonNft},
+    internal::hash_account_id,
+    listing::date::DateInput,
+    offer::OfferId,
+    *,
+};
+use near_sdk::json_types::{U128, U64};
+use near_sdk::{PromiseOrValue, PromiseResult};
+
+// each token is looked up and transferred twice within a single accept call, the gas of that
+// call caps the number of tokens
+pub const SWAP_OFFER_TOKENS_MAX: usize = 3; // per side
+pub const SWAP_OFFER_MIN_DURATION_NANO: i64 = 3600000000000; // 1 hour
+pub const SWAP_OFFER_MAX_DURATION_NANO: i64 = 3600000000000 * 24 * 30; // 30 days
+
+const NFT_TOKEN_GAS: Gas = Gas(5_000_000_000_000); // TODO: measure
+const NFT_TRANSFER_GAS: Gas = Gas(15_000_000_000_000); // TODO: measure
+const SWAP_OFFER_COMPLETION_GAS: Gas = Gas(10_000_000_000_000); // TODO: measure, excluding transfers
+
+#[cfg(test)]
+#[path = "swap_tests.rs"]
+mod swap_tests;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SwapToken {
+    pub nft_contract_id: AccountId,
+    pub token_id: NftId,
+}
+
+// proposer's tokens for the counterparty's tokens, NEAR may be added by either side; the
+// proposer's tokens and NEAR are committed when the offer is placed, the counterparty's when
+// it's accepted
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct SwapOffer {
+    pub id: OfferId,
+    pub proposer_id: AccountId,
+    pub counterparty_id: AccountId,
+    pub offered_tokens: Vec<SwapToken>,
+    pub offered_approval_ids: Vec<u64>, // approvals given to the marketplace, same order as tokens
+    pub wanted_tokens: Vec<SwapToken>,
+    pub proposer_pays_yocto: u128, // escrowed by the marketplace
+    pub counterparty_pays_yocto: u128,
+    pub expires_timestamp: i64, // nanoseconds since 1970-01-01
+    pub is_pending: bool,       // accepted, token transfers in flight
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct JsonSwapOffer {
+    pub id: U64,
+    pub proposer_id: AccountId,
+    pub counterparty_id: AccountId,
+    pub offered_tokens: Vec<SwapToken>,
+    pub wanted_tokens: Vec<SwapToken>,
+    pub proposer_pays_yocto: U128,
+    pub counterparty_pays_yocto: U128,
+    pub expires_timestamp: U64, // nanoseconds since 1970-01-01
+    pub is_expired: bool,
+    pub is_pending: bool,
+}
+
+impl SwapOffer {
+    fn is_expired(&self) -> bool {
+        env::block_timestamp() as i64 >= self.expires_timestamp
+    }
+
+    // offered tokens first, then the wanted ones
+    fn tokens(&self) -> impl Iterator<Item = &SwapToken> {
+        self.offered_tokens.iter().chain(self.wanted_tokens.iter())
+    }
+
+    fn token_count(&self) -> usize {
+        self.offered_tokens.len() + self.wanted_tokens.len()
+    }
+
+    fn to_json(&self) -> JsonSwapOffer {
+        JsonSwapOffer {
+            id: U64(self.id),
+            proposer_id: self.proposer_id.clone(),
+            counterparty_id: self.counterparty_id.clone(),
+            offered_tokens: self.offered_tokens.clone(),
+            wanted_tokens: self.wanted_tokens.clone(),
+            proposer_pays_yocto: U128(self.proposer_pays_yocto),
+            counterparty_pays_yocto: U128(self.counterparty_pays_yocto),
+            expires_timestamp: U64(self.expires_timestamp as u64),
+            is_expired: self.is_expired(),
+            is_pending: self.is_pending,
+        }
+    }
+}
+
+#[near_bindgen]
+impl MarketplaceContract {
+    // the offered tokens must be approved to the marketplace beforehand; we only expect the
+    // deposit equal to proposer_pays_yocto plus the storage of the offer, the surplus is
+    // returned; NEAR can be added by one side only; returns the offer id
+    #[payable]
+    pub fn swap_offer_place(
+        &mut self,
+        counterparty_id: AccountId,
+        offered_tokens: Vec<SwapToken>,
+        offered_approval_ids: Vec<U64>,
+        wanted_tokens: Vec<SwapToken>,
+        proposer_pays_yocto: Option<U128>,
+        counterparty_pays_yocto: Option<U128>,
+        expiry_date: DateInput, // a duration counts from now
+    ) -> U64 {
+        let proposer_id = env::predecessor_account_id();
+        assert!(proposer_id != counterparty_id, "Cannot swap with yourself");
+
+        assert!(
+            !offered_tokens.is_empty() && offered_tokens.len() <= SWAP_OFFER_TOKENS_MAX,
+            "Between 1 and {} tokens can be offered",
+            SWAP_OFFER_TOKENS_MAX
+        );
+        assert!(
+            !wanted_tokens.is_empty() && wanted_tokens.len() <= SWAP_OFFER_TOKENS_MAX,
+            "Between 1 and {} tokens can be asked for",
+            SWAP_OFFER_TOKENS_MAX
+        );
+        assert_eq!(
+            offered_approval_ids.len(),
+            offered_tokens.len(),
+            "Approval id must be given for each offered token"
+        );
+        let all_tokens: Vec<&SwapToken> =
+            offered_tokens.iter().chain(wanted_tokens.iter()).collect();
+        for (index, token) in all_tokens.iter().enumerate() {
+            assert!(
+                !all_tokens[..index].contains(token),
+                "Token {} is in the offer more than once",
+                token.token_id
+            );
+        }
+
+        let proposer_pays_yocto = proposer_pays_yocto.map_or(0, |p| p.0);
+        let counterparty_pays_yocto = counterparty_pays_yocto.map_or(0, |p| p.0);
+        assert!(
+            proposer_pays_yocto == 0 || counterparty_pays_yocto == 0,
+            "NEAR can be added by one side only"
+        );
+
+        let expires_timestamp = expiry_date.to_timestamp(env::block_timestamp() as i64);
+        let duration = expires_timestamp - env::block_timestamp() as i64;
+        assert!(
+            duration >= SWAP_OFFER_MIN_DURATION_NANO,
+            "Offer duration too short"
+        );
+        assert!(
+            duration <= SWAP_OFFER_MAX_DURATION_NANO,
+            "Offer duration too long"
+        );
+
+        let offer = SwapOffer {
+            id: self.next_offer_id,
+            proposer_id: proposer_id.clone(),
+            counterparty_id,
+            offered_tokens,
+            offered_approval_ids: offered_approval_ids.iter().map(|a| a.0).collect(),
+            wanted_tokens,
+            proposer_pays_yocto,
+            counterparty_pays_yocto,
+            expires_timestamp,
+            is_pending: false,
+        };
+        self.next_offer_id += 1;
+
+        let storage_before = env::storage_usage();
+        self.internal_add_swap_offer(&offer);
+        let storage_after = env::storage_usage();
+        let storage_cost = (storage_after - storage_before) as Balance * env::storage_byte_cost();
+
+        let required_deposit = proposer_pays_yocto + storage_cost;
+        let attached_deposit = env::attached_deposit();
+        assert!(
+            attached_deposit >= required_deposit,
+            "Attached deposit of {} is insufficient to pay {} and {} for storage",
+            attached_deposit,
+            proposer_pays_yocto,
+            storage_cost
+        );
+        let refund = attached_deposit - required_deposit;
+        if refund > 0 {
+            Promise::new(proposer_id).transfer(refund);
+        }
+
+        U64(offer.id)
+    }
+
+    // the proposer can withdraw at any time, once the offer expires anyone can return the
+    // deposit to the proposer
+    pub fn swap_offer_withdraw(&mut self, offer_id: U64) {
+        let offer = self
+            .swap_offers_by_id
+            .get(&offer_id.0)
+            .expect("Could not find offer");
+        assert!(
+            offer.proposer_id == env::predecessor_account_id() || offer.is_expired(),
+            "Not authorized to withdraw this offer"
+        );
+        assert!(!offer.is_pending, "This offer is being accepted");
+
+        self.internal_close_swap_offer(&offer);
+    }
+
+    pub fn swap_offer_reject(&mut self, offer_id: U64) {
+        let offer = self
+            .swap_offers_by_id
+            .get(&offer_id.0)
+            .expect("Could not find offer");
+        assert!(
+            offer.counterparty_id == env::predecessor_account_id(),
+            "Only the counterparty can reject this offer"
+        );
+        assert!(!offer.is_pending, "This offer is being accepted");
+
+        self.internal_close_swap_offer(&offer);
+    }
+
+    // the wanted tokens must be approved to the marketplace beforehand, approval_ids come in the
+    // order of the wanted tokens; counterparty_pays_yocto plus 2yN per token of both sides must
+    // be attached for the NFT transfers, the surplus is returned
+    // ownership and approvals of all tokens are checked first, then all of them are moved to the
+    // marketplace and only if every transfer succeeded they are passed on to the other side;
+    // otherwise they go back to their owners
+    #[payable]
+    pub fn swap_offer_accept(&mut self, offer_id: U64, approval_ids: Vec<U64>) -> Promise {
+        let mut offer = self
+            .swap_offers_by_id
+            .get(&offer_id.0)
+            .expect("Could not find offer");
+        assert!(
+            offer.counterparty_id == env::predecessor_account_id(),
+            "Only the counterparty can accept this offer"
+        );
+        assert!(!offer.is_expired(), "This offer has expired");
+        assert!(!offer.is_pending, "This offer is being accepted");
+        assert_eq!(
+            approval_ids.len(),
+            offer.wanted_tokens.len(),
+            "Approval id must be given for each wanted token"
+        );
+
+        let token_count = offer.token_count();
+        let required_deposit = offer.counterparty_pays_yocto + 2 * token_count as u128;
+        let attached_deposit = env::attached_deposit();
+        assert!(
+            attached_deposit >= required_deposit,
+            "Attached deposit of {} is insufficient to pay {} and 2yN per token for NFT transfers",
+            attached_deposit,
+            offer.counterparty_pays_yocto
+        );
+
+        offer.is_pending = true;
+        self.swap_offers_by_id.insert(&offer.id, &offer);
+
+        let mut lookups = offer.tokens().map(|token| {
+            nft_contract::nft_token(
+                token.token_id.clone(),
+                token.nft_contract_id.clone(),
+                NO_DEPOSIT,
+                NFT_TOKEN_GAS,
+            )
+        });
+        let first_lookup = lookups.next().unwrap();
+        // the verification issues the transfers and the custody completion, which delivers the
+        // tokens
+        let verify_gas = SWAP_OFFER_COMPLETION_GAS.0 * 2
+            + (NFT_TRANSFER_GAS.0 + NFT_DELIVERY_GAS.0 + NFT_DELIVERY_COMPLETION_GAS.0)
+                * token_count as u64;
+        lookups
+            .fold(first_lookup, |all, lookup| all.and(lookup))
+            .then(ext_self_nft::swap_offer_verify_completion(
+                offer_id,
+                approval_ids,
+                U128(attached_deposit),
+                env::current_account_id(),
+                NO_DEPOSIT,
+                Gas(verify_gas),
+            ))
+    }
+}
+
+impl MarketplaceContract {
+    fn internal_add_swap_offer(&mut self, offer: &SwapOffer) {
+        self.swap_offers_by_id.insert(&offer.id, offer);
+        for account_id in [&offer.proposer_id, &offer.counterparty_id] {
+            let mut account_offers =
+                self.swap_offers_by_account
+                    .get(account_id)
+                    .unwrap_or_else(|| {
+                        UnorderedSet::new(
+                            MarketplaceStorageKey::SwapOffersByAccountInner {
+                                account_id_hash: hash_account_id(account_id),
+                            }
+                            .try_to_vec()
+                            .unwrap(),
+                        )
+                    });
+            account_offers.insert(&offer.id);
+            self.swap_offers_by_account
+                .insert(account_id, &account_offers);
+        }
+    }
+
+    // removes all records of the offer and returns the escrow and the storage to the proposer
+    fn internal_close_swap_offer(&mut self, offer: &SwapOffer) {
+        let storage_before = env::storage_usage();
+        self.swap_offers_by_id.remove(&offer.id);
+        for account_id in [&offer.proposer_id, &offer.counterparty_id] {
+            if let Some(mut account_offers) = self.swap_offers_by_account.get(account_id) {
+                account_offers.remove(&offer.id);
+                if account_offers.is_empty() {
+                    self.swap_offers_by_account.remove(account_id);
+                } else {
+                    self.swap_offers_by_account
+                        .insert(account_id, &account_offers);
+                }
+            }
+        }
+        let storage_after = env::storage_usage();
+        let storage_refund = (storage_before - storage_after) as Balance * env::storage_byte_cost();
+
+        Promise::new(offer.proposer_id.clone())
+            .transfer(offer.proposer_pays_yocto + storage_refund);
+    }
+
+    // an offer made with tokens the proposer cannot give anymore is removed, otherwise it can be
+    // accepted again
+    fn internal_release_swap_offer(&mut self, offer: &mut SwapOffer, is_offer_valid: bool) {
+        if is_offer_valid {
+            offer.is_pending = false;
+            self.swap_offers_by_id.insert(&offer.id, offer);
+        } else {
+            env::log_str(&format!(
+                "Swap offer {} removed, the offered tokens are no longer available",
+                offer.id
+            ));
+            self.internal_close_swap_offer(offer);
+        }
+    }
+}
+
+#[ext_contract(ext_self_nft)]
+trait SwapOfferCallback {
+    fn swap_offer_verify_completion(
+        &mut self,
+        offer_id: U64,
+        approval_ids: Vec<U64>,
+        attached_deposit: U128,
+    ) -> PromiseOrValue<bool>;
+
+    fn swap_offer_custody_completion(&mut self, offer_id: U64, attached_deposit: U128) -> bool;
+}
+
+trait SwapOfferCallback {
+    fn swap_offer_verify_completion(
+        &mut self,
+        offer_id: U64,
+        approval_ids: Vec<U64>,
+        attached_deposit: U128,
+    ) -> PromiseOrValue<bool>;
+
+    fn swap_offer_custody_completion(&mut self, offer_id: U64, attached_deposit: U128) -> bool;
+}
+
+#[near_bindgen]
+impl SwapOfferCallback for MarketplaceContract {
+    // moves all tokens to the marketplace if both sides own their tokens and the approvals match
+    // we don't panic on mismatch, it'd revert the refund and keep the offer pending
+    #[private]
+    fn swap_offer_verify_completion(
+        &mut self,
+        offer_id: U64,
+        approval_ids: Vec<U64>,
+        attached_deposit: U128,
+    ) -> PromiseOrValue<bool> {
+        let mut offer = self
+            .swap_offers_by_id
+            .get(&offer_id.0)
+            .expect("Could not find offer");
+        assert_eq!(
+            env::promise_results_count(),
+            offer.token_count() as u64,
+            "Unexpected number of data receipts"
+        );
+
+        // (owner, approval id) expected for each token, in lookup order
+        let expected: Vec<(&AccountId, u64)> = offer
+            .offered_approval_ids
+            .iter()
+            .map(|approval_id| (&offer.proposer_id, *approval_id))
+            .chain(
+                approval_ids
+                    .iter()
+                    .map(|approval_id| (&offer.counterparty_id, approval_id.0)),
+            )
+            .collect();
+        let is_verified: Vec<bool> = expected
+            .iter()
+            .enumerate()
+            .map(|(index, (owner_id, approval_id))| {
+                let token = match env::promise_result(index as u64) {
+                    PromiseResult::Successful(val) => {
+                        near_sdk::serde_json::from_slice::<Option<JsonNft>>(&val).unwrap_or(None)
+                    }
+                    _ => None,
+                };
+                token.map_or(false, |token| {
+                    &token.owner_id == *owner_id
+                        && token.approved_account_ids.get(&env::current_account_id())
+                            == Some(approval_id)
+                })
+            })
+            .collect();
+
+        if is_verified.iter().all(|&is_ok| is_ok) {
+            let mut transfers =
+                offer
+                    .tokens()
+                    .zip(expected.iter())
+                    .map(|(token, (_, approval_id))| {
+                        nft_contract::nft_transfer(
+                            env::current_account_id(),
+                            token.token_id.clone(),
+                            Some(*approval_id),
+                            None,
+                            token.nft_contract_id.clone(),
+                            1,
+                            NFT_TRANSFER_GAS,
+                        )
+                    });
+            let first_transfer = transfers.next().unwrap();
+            let completion_gas = SWAP_OFFER_COMPLETION_GAS.0
+                + (NFT_DELIVERY_GAS.0 + NFT_DELIVERY_COMPLETION_GAS.0) * offer.token_count() as u64;
+            PromiseOrValue::Promise(
+                transfers
+                    .fold(first_transfer, |all, transfer| all.and(transfer))
+                    .then(ext_self_nft::swap_offer_custody_completion(
+                        offer_id,
+                        attached_deposit,
+                        env::current_account_id(),
+                        NO_DEPOSIT,
+                        Gas(completion_gas),
+                    )),
+            )
+        } else {
+            env::log_str("Token owner or approval mismatch, swap not performed");
+            Promise::new(offer.counterparty_id.clone()).transfer(attached_deposit.0);
+            let is_offer_valid = is_verified[..offer.offered_tokens.len()]
+                .iter()
+                .all(|&is_ok| is_ok);
+            self.internal_release_swap_offer(&mut offer, is_offer_valid);
+            PromiseOrValue::Value(false)
+        }
+    }
+
+    // returns true if the tokens were swapped
+    // we don't panic here, it'd revert the refunds and leave the tokens with the marketplace
+    #[private]
+    fn swap_offer_custody_completion(&mut self, offer_id: U64, attached_deposit: U128) -> bool {
+        let mut offer = self
+            .swap_offers_by_id
+            .get(&offer_id.0)
+            .expect("Could not find offer");
+        assert_eq!(
+            env::promise_results_count(),
+            offer.token_count() as u64,
+            "Unexpected number of data receipts"
+        );
+        let is_in_custody: Vec<bool> = (0..offer.token_count())
+            .map(|index| {
+                matches!(
+                    env::promise_result(index as u64),
+                    PromiseResult::Successful(_)
+                )
+            })
+            .collect();
+
+        let offered_count = offer.offered_tokens.len();
+        // the tokens that reached the marketplace lost their approvals, their listings are dead
+        for (index, token) in offer.tokens().enumerate() {
+            if !is_in_custody[index] {
+                continue;
+            }
+            let owner_id = if index < offered_count {
+                offer.proposer_id.clone()
+            } else {
+                offer.counterparty_id.clone()
+            };
+            self.internal_delist_token(&token.nft_contract_id, &token.token_id, &owner_id);
+        }
+
+        if is_in_custody.iter().all(|&is_ok| is_ok) {
+            // each token goes to the side that didn't own it
+            for (index, token) in offer.tokens().enumerate() {
+                let receiver_id = if index < offered_count {
+                    offer.counterparty_id.clone()
+                } else {
+                    offer.proposer_id.clone()
+                };
+                self.internal_deliver_token(&token.nft_contract_id, &token.token_id, &receiver_id);
+            }
+
+            if offer.proposer_pays_yocto > 0 {
+                Promise::new(offer.counterparty_id.clone()).transfer(offer.proposer_pays_yocto);
+            }
+            if offer.counterparty_pays_yocto > 0 {
+                Promise::new(offer.proposer_id.clone()).transfer(offer.counterparty_pays_yocto);
+            }
+            // 1yN per token was spent on each of the two transfers
+            let refund = attached_deposit.0
+                - offer.counterparty_pays_yocto
+                - 2 * offer.token_count() as u128;
+            if refund > 0 {
+                Promise::new(offer.counterparty_id.clone()).transfer(refund);
+            }
+
+            // the escrow went to the counterparty, only the storage goes back to the proposer
+            offer.proposer_pays_yocto = 0;
+            self.internal_close_swap_offer(&offer);
+            true
+        } else {
+            // the tokens that made it to the marketplace go back to their owners, the 1yN of the
+            // failed transfers came back to us
+            let mut returned_count: u128 = 0;
+            for (index, token) in offer.tokens().enumerate() {
+                if !is_in_custody[index] {
+                    continue;
+                }
+                let owner_id = if index < offered_count {
+                    offer.proposer_id.clone()
+                } else {
+                    offer.counterparty_id.clone()
+                };
+                self.internal_deliver_token(&token.nft_contract_id, &token.token_id, &owner_id);
+                returned_count += 1;
+            }
+            Promise::new(offer.counterparty_id.clone())
+                .transfer(attached_deposit.0 - 2 * returned_count);
+
+            let is_offer_valid = is_in_custody[..offered_count].iter().all(|&is_ok| is_ok);
+            self.internal_release_swap_offer(&mut offer, is_offer_valid);
+            false
+        }
+    }
+}
+
+// view-only methods
+
+#[near_bindgen]
+impl MarketplaceContract {
+    pub fn swap_offer(&self, offer_id: U64) -> Option<JsonSwapOffer> {
+        self.swap_offers_by_id
+            .get(&offer_id.0)
+            .map(|offer| offer.to_json())
+    }
+
+    // offers made by the account or to the account, results are paginated
+    pub fn swap_offers_by_account(
+        &self,
+        account_id: AccountId,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<JsonSwapOffer> {
+        let offers = self.swap_offers_by_account.get(&account_id);
+        if offers.is_none() {
+            return vec![];
+        }
+        let offers = offers.unwrap();
+
+        //where to start pagination - if we have a from_index, we'll use that - otherwise start from 0 index
+        let start = u128::from(from_index.unwrap_or(U128(0))) as usize;
+        let count = limit.unwrap_or(10) as usize;
+
+        offers
+            .iter()
+            .skip(start)
+            .take(count)
+            .map(|offer_id| self.swap_offers_by_id.get(&offer_id).unwrap().to_json())
+            .collect()
+    }
+}
@@ -0,0 +1,272 @@
+#[cfg(test)]
+mod swap_tests {
+    use super::super::{SwapOfferCallback, SwapToken};
+    use crate::{listing::date::DateInput, test_utils::*, *};
+    use near_sdk::json_types::{U128, U64};
+    use near_sdk::serde_json::{self, json};
+    use near_sdk::{PromiseOrValue, PromiseResult};
+
+    const OFFERED_TOKEN_ID: &str = "0:1";
+    const WANTED_TOKEN_ID: &str = "0:2";
+
+    fn swap_token(token_id: &str) -> SwapToken {
+        SwapToken {
+            nft_contract_id: account(NFT_CONTRACT_ID),
+            token_id: token_id.to_string(),
+        }
+    }
+
+    // the seller offers a token and NEAR for a token of the buyer
+    fn place(marketplace: &mut MarketplaceContract) -> u64 {
+        set_context(SELLER_ACCOUNT_ID, NOW, PRICE_YOCTO + ONE_NEAR);
+        marketplace
+            .swap_offer_place(
+                account(BUYER_ACCOUNT_ID),
+                vec![swap_token(OFFERED_TOKEN_ID)],
+                vec![U64(1)],
+                vec![swap_token(WANTED_TOKEN_ID)],
+                Some(U128(PRICE_YOCTO)),
+                None,
+                DateInput::DurationMs(U64(2 * 3_600_000)),
+            )
+            .0
+    }
+
+    fn token_result(token_id: &str, owner_id: &str, approval_id: u64) -> PromiseResult {
+        PromiseResult::Successful(
+            serde_json::to_vec(&json!({
+                "token_id": token_id,
+                "owner_id": owner_id,
+                "collection_id": 0,
+                "metadata": {"title": "Collection 0"},
+                "mutable_metadata": {},
+                "approved_account_ids": {MARKETPLACE_ACCOUNT_ID: approval_id}
+            }))
+            .unwrap(),
+        )
+    }
+
+    fn accept(marketplace: &mut MarketplaceContract, offer_id: u64) {
+        set_context(BUYER_ACCOUNT_ID, NOW, 4);
+        marketplace.swap_offer_accept(U64(offer_id), vec![U64(2)]);
+    }
+
+    fn complete_custody(
+        marketplace: &mut MarketplaceContract,
+        offer_id: u64,
+        promise_results: Vec<PromiseResult>,
+    ) -> bool {
+        set_callback_context(NOW, promise_results);
+        marketplace.swap_offer_custody_completion(U64(offer_id), U128(4))
+    }
+
+    /* placing */
+
+    #[test]
+    fn test_swap_offer_place() {
+        let mut marketplace = marketplace();
+        let offer_id = place(&mut marketplace);
+
+        let offer = marketplace.swap_offer(U64(offer_id)).unwrap();
+        assert_eq!(offer.proposer_id, account(SELLER_ACCOUNT_ID));
+        assert!(offer.offered_tokens == vec![swap_token(OFFERED_TOKEN_ID)]);
+        assert!(offer.wanted_tokens == vec![swap_token(WANTED_TOKEN_ID)]);
+        assert_eq!(offer.proposer_pays_yocto.0, PRICE_YOCTO);
+        // the duration counts from now
+        assert_eq!(offer.expires_timestamp.0, NOW + 2 * HOUR_NANO);
+        assert!(!offer.is_expired);
+        for account_id in [SELLER_ACCOUNT_ID, BUYER_ACCOUNT_ID] {
+            assert_eq!(
+                marketplace
+                    .swap_offers_by_account(account(account_id), None, None)
+                    .len(),
+                1
+            );
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Token 0:1 is in the offer more than once"#)]
+    fn test_swap_offer_place_duplicate_token() {
+        let mut marketplace = marketplace();
+        set_context(SELLER_ACCOUNT_ID, NOW, ONE_NEAR);
+        marketplace.swap_offer_place(
+            account(BUYER_ACCOUNT_ID),
+            vec![swap_token(OFFERED_TOKEN_ID)],
+            vec![U64(1)],
+            vec![swap_token(OFFERED_TOKEN_ID)],
+            None,
+            None,
+            DateInput::DurationMs(U64(2 * 3_600_000)),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = r#"NEAR can be added by one side only"#)]
+    fn test_swap_offer_place_both_sides_pay() {
+        let mut marketplace = marketplace();
+        set_context(SELLER_ACCOUNT_ID, NOW, PRICE_YOCTO + ONE_NEAR);
+        marketplace.swap_offer_place(
+            account(BUYER_ACCOUNT_ID),
+            vec![swap_token(OFFERED_TOKEN_ID)],
+            vec![U64(1)],
+            vec![swap_token(WANTED_TOKEN_ID)],
+            Some(U128(PRICE_YOCTO)),
+            Some(U128(PRICE_YOCTO)),
+            DateInput::DurationMs(U64(2 * 3_600_000)),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Offer duration too short"#)]
+    fn test_swap_offer_place_too_short() {
+        let mut marketplace = marketplace();
+        set_context(SELLER_ACCOUNT_ID, NOW, ONE_NEAR);
+        marketplace.swap_offer_place(
+            account(BUYER_ACCOUNT_ID),
+            vec![swap_token(OFFERED_TOKEN_ID)],
+            vec![U64(1)],
+            vec![swap_token(WANTED_TOKEN_ID)],
+            None,
+            None,
+            DateInput::UnixMs(U64(NOW / 1_000_000 + 60_000)),
+        );
+    }
+
+    #[test]
+    fn test_swap_offer_reject() {
+        let mut marketplace = marketplace();
+        let offer_id = place(&mut marketplace);
+
+        set_context(BUYER_ACCOUNT_ID, NOW, 0);
+        marketplace.swap_offer_reject(U64(offer_id));
+
+        assert!(marketplace.swap_offer(U64(offer_id)).is_none());
+        assert!(marketplace
+            .swap_offers_by_account(account(SELLER_ACCOUNT_ID), None, None)
+            .is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Only the counterparty can accept this offer"#)]
+    fn test_swap_offer_accept_by_other() {
+        let mut marketplace = marketplace();
+        let offer_id = place(&mut marketplace);
+
+        set_context(BUYER2_ACCOUNT_ID, NOW, 4);
+        marketplace.swap_offer_accept(U64(offer_id), vec![U64(2)]);
+    }
+
+    #[test]
+    #[should_panic(expected = r#"This offer is being accepted"#)]
+    fn test_swap_offer_withdraw_while_pending() {
+        let mut marketplace = marketplace();
+        let offer_id = place(&mut marketplace);
+        accept(&mut marketplace, offer_id);
+
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        marketplace.swap_offer_withdraw(U64(offer_id));
+    }
+
+    /* verification */
+
+    #[test]
+    fn test_swap_offer_verify_mismatch_keeps_valid_offer() {
+        let mut marketplace = marketplace();
+        let offer_id = place(&mut marketplace);
+        accept(&mut marketplace, offer_id);
+
+        // the counterparty's approval doesn't match
+        set_callback_context(
+            NOW,
+            vec![
+                token_result(OFFERED_TOKEN_ID, SELLER_ACCOUNT_ID, 1),
+                token_result(WANTED_TOKEN_ID, BUYER_ACCOUNT_ID, 3),
+            ],
+        );
+        let result = marketplace.swap_offer_verify_completion(U64(offer_id), vec![U64(2)], U128(4));
+
+        assert!(matches!(result, PromiseOrValue::Value(false)));
+        assert!(!marketplace.swap_offer(U64(offer_id)).unwrap().is_pending);
+    }
+
+    #[test]
+    fn test_swap_offer_verify_mismatch_removes_invalid_offer() {
+        let mut marketplace = marketplace();
+        let offer_id = place(&mut marketplace);
+        accept(&mut marketplace, offer_id);
+
+        // the proposer no longer owns the offered token
+        set_callback_context(
+            NOW,
+            vec![
+                token_result(OFFERED_TOKEN_ID, BUYER2_ACCOUNT_ID, 1),
+                token_result(WANTED_TOKEN_ID, BUYER_ACCOUNT_ID, 2),
+            ],
+        );
+        marketplace.swap_offer_verify_completion(U64(offer_id), vec![U64(2)], U128(4));
+
+        assert!(marketplace.swap_offer(U64(offer_id)).is_none());
+    }
+
+    /* custody */
+
+    #[test]
+    fn test_swap_offer_custody_completion() {
+        let mut marketplace = marketplace();
+        let listing_id = add_secondary_listing(
+            &mut marketplace,
+            OFFERED_TOKEN_ID,
+            Some(PRICE_YOCTO),
+            None,
+            false,
+        );
+        let offer_id = place(&mut marketplace);
+        accept(&mut marketplace, offer_id);
+
+        assert!(complete_custody(
+            &mut marketplace,
+            offer_id,
+            vec![
+                PromiseResult::Successful(vec![]),
+                PromiseResult::Successful(vec![])
+            ]
+        ));
+
+        assert!(marketplace.swap_offer(U64(offer_id)).is_none());
+        // the token left the seller with the marketplace's approval, its listing is gone
+        assert!(marketplace
+            .secondary_listings_by_id
+            .get(&listing_id)
+            .is_none());
+    }
+
+    #[test]
+    fn test_swap_offer_custody_failure_returns_tokens() {
+        let mut marketplace = marketplace();
+        let listing_id = add_secondary_listing(
+            &mut marketplace,
+            OFFERED_TOKEN_ID,
+            Some(PRICE_YOCTO),
+            None,
+            false,
+        );
+        let offer_id = place(&mut marketplace);
+        accept(&mut marketplace, offer_id);
+
+        // the offered token made it to the marketplace, the wanted one didn't
+        assert!(!complete_custody(
+            &mut marketplace,
+            offer_id,
+            vec![PromiseResult::Successful(vec![]), PromiseResult::Failed]
+        ));
+
+        // the offer can be accepted again
+        assert!(!marketplace.swap_offer(U64(offer_id)).unwrap().is_pending);
+        // the offered token went back without its approvals
+        assert!(marketplace
+            .secondary_listings_by_id
+            .get(&listing_id)
+            .is_none());
+    }
+}
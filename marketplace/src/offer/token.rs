@@ -0,0 +1,621 @@
+use crate::{
+    constants::NO_DEPOSIT,
+    external::{nft_contract, JsonNft},
+    internal::hash_account_id,
+    listing::{
+        date::DateInput,
+        sale::Sale,
+    },
+    offer::OfferId,
+    *,
+};
+use near_sdk::json_types::{U128, U64};
+use near_sdk::PromiseResult;
+
+pub const TOKEN_OFFER_MIN_DURATION_NANO: i64 = 3600000000000; // 1 hour
+pub const TOKEN_OFFER_MAX_DURATION_NANO: i64 = 3600000000000 * 24 * 30; // 30 days
+
+const NFT_TOKEN_GAS: Gas = Gas(5_000_000_000_000); // TODO: measure
+const NFT_TRANSFER_GAS: Gas = Gas(15_000_000_000_000); // TODO: measure
+const TOKEN_OFFER_REJECT_COMPLETION_GAS: Gas = Gas(10_000_000_000_000); // TODO: measure
+const TOKEN_OFFER_ACCEPT_COMPLETION_GAS: Gas = Gas(15_000_000_000_000); // TODO: measure
+
+#[cfg(test)]
+#[path = "token_tests.rs"]
+mod token_tests;
+
+// owner's answer to an offer, backed by an approval so that the bidder can take it alone
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct TokenCounterOffer {
+    pub owner_id: AccountId,
+    pub approval_id: u64,
+    pub price_yocto: u128,
+}
+
+// escrowed offer for a single token, listed or not
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct TokenOffer {
+    pub id: OfferId,
+    pub bidder_id: AccountId,
+    pub nft_contract_id: AccountId,
+    pub token_id: NftId,
+    pub price_yocto: u128, // escrowed by the marketplace
+    pub counter: Option<TokenCounterOffer>,
+    pub expires_timestamp: i64, // nanoseconds since 1970-01-01
+    pub is_pending: bool,       // accepted, token transfer in flight
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct JsonTokenOffer {
+    pub id: U64,
+    pub bidder_id: AccountId,
+    pub nft_contract_id: AccountId,
+    pub token_id: NftId,
+    pub price_yocto: U128,
+    pub counter_price_yocto: Option<U128>,
+    pub expires_timestamp: U64, // nanoseconds since 1970-01-01
+    pub is_expired: bool,
+    pub is_pending: bool,
+}
+
+pub(crate) fn hash_token_id(nft_contract_id: &AccountId, token_id: &NftId) -> CryptoHash {
+    let hashed_string = format!("{}.{}", nft_contract_id, token_id);
+    let mut hash = CryptoHash::default();
+    hash.copy_from_slice(&env::sha256(hashed_string.as_bytes()));
+    hash
+}
+
+impl TokenOffer {
+    fn is_expired(&self) -> bool {
+        env::block_timestamp() as i64 >= self.expires_timestamp
+    }
+
+    fn to_json(&self) -> JsonTokenOffer {
+        JsonTokenOffer {
+            id: U64(self.id),
+            bidder_id: self.bidder_id.clone(),
+            nft_contract_id: self.nft_contract_id.clone(),
+            token_id: self.token_id.clone(),
+            price_yocto: U128(self.price_yocto),
+            counter_price_yocto: self.counter.as_ref().map(|c| U128(c.price_yocto)),
+            expires_timestamp: U64(self.expires_timestamp as u64),
+            is_expired: self.is_expired(),
+            is_pending: self.is_pending,
+        }
+    }
+}
+
+#[near_bindgen]
+impl MarketplaceContract {
+    // we only expect the deposit equal to the price plus the storage of the offer, the surplus
+    // is returned; returns the offer id
+    #[payable]
+    pub fn token_offer_place(
+        &mut self,
+        nft_contract_id: AccountId,
+        token_id: NftId,
+        price_yocto: U128,
+        expiry_date: DateInput, // a duration counts from now
+    ) -> U64 {
+        let price_yocto = price_yocto.0;
+
+        // offers follow the bid rules of listings which don't set their own
+        let rules = &self.config.listing_rules_default;
+        assert!(
+            price_yocto >= rules.min_bid_yocto,
+            "Offer cannot be lower than {} yoctoNear",
+            rules.min_bid_yocto
+        );
+        assert!(
+            price_yocto % rules.bid_step_yocto == 0,
+            "Offer must be integer multiple of {} yoctoNear",
+            rules.bid_step_yocto
+        );
+
+        let expires_timestamp = expiry_date.to_timestamp(env::block_timestamp() as i64);
+        let duration = expires_timestamp - env::block_timestamp() as i64;
+        assert!(
+            duration >= TOKEN_OFFER_MIN_DURATION_NANO,
+            "Offer duration too short"
+        );
+        assert!(
+            duration <= TOKEN_OFFER_MAX_DURATION_NANO,
+            "Offer duration too long"
+        );
+
+        let bidder_id = env::predecessor_account_id();
+        let offer = TokenOffer {
+            id: self.next_offer_id,
+            bidder_id: bidder_id.clone(),
+            nft_contract_id,
+            token_id,
+            price_yocto,
+            counter: None,
+            expires_timestamp,
+            is_pending: false,
+        };
+        self.next_offer_id += 1;
+
+        let storage_before = env::storage_usage();
+        self.internal_add_token_offer(&offer);
+        let storage_after = env::storage_usage();
+        let storage_cost = (storage_after - storage_before) as Balance * env::storage_byte_cost();
+
+        let required_deposit = price_yocto + storage_cost;
+        let attached_deposit = env::attached_deposit();
+        assert!(
+            attached_deposit >= required_deposit,
+            "Attached deposit of {} is insufficient to pay the offer of {} and {} for storage",
+            attached_deposit,
+            price_yocto,
+            storage_cost
+        );
+        let refund = attached_deposit - required_deposit;
+        if refund > 0 {
+            Promise::new(bidder_id).transfer(refund);
+        }
+
+        U64(offer.id)
+    }
+
+    // the bidder can withdraw at any time, once the offer expires anyone can return the deposit
+    // to the bidder
+    pub fn token_offer_withdraw(&mut self, offer_id: U64) {
+        let offer = self
+            .token_offers_by_id
+            .get(&offer_id.0)
+            .expect("Could not find offer");
+        assert!(
+            offer.bidder_id == env::predecessor_account_id() || offer.is_expired(),
+            "Not authorized to withdraw this offer"
+        );
+        assert!(!offer.is_pending, "This offer is being accepted");
+
+        let storage_refund = self.internal_remove_token_offer(&offer);
+        Promise::new(offer.bidder_id).transfer(offer.price_yocto + storage_refund);
+    }
+
+    // the token owner turns the offer down, ownership is checked with the NFT contract
+    pub fn token_offer_reject(&mut self, offer_id: U64) -> Promise {
+        let offer = self
+            .token_offers_by_id
+            .get(&offer_id.0)
+            .expect("Could not find offer");
+        assert!(!offer.is_pending, "This offer is being accepted");
+
+        nft_contract::nft_token(
+            offer.token_id,
+            offer.nft_contract_id,
+            NO_DEPOSIT,
+            NFT_TOKEN_GAS,
+        )
+        .then(ext_self_nft::token_offer_reject_completion(
+            offer_id,
+            env::predecessor_account_id(),
+            env::current_account_id(),
+            NO_DEPOSIT,
+            TOKEN_OFFER_REJECT_COMPLETION_GAS,
+        ))
+    }
+
+    // the bidder takes the owner's counter price, the difference to the escrowed offer must be
+    // attached; the token is transferred using the approval given with the counter offer
+    #[payable]
+    pub fn token_offer_accept_counter(&mut self, offer_id: U64) -> Promise {
+        let mut offer = self
+            .token_offers_by_id
+            .get(&offer_id.0)
+            .expect("Could not find offer");
+        assert!(
+            offer.bidder_id == env::predecessor_account_id(),
+            "Only the bidder can accept the counter offer"
+        );
+        assert!(!offer.is_expired(), "This offer has expired");
+        assert!(!offer.is_pending, "This offer is being accepted");
+        let counter = offer.counter.take().expect("There is no counter offer");
+
+        let top_up_yocto = counter.price_yocto - offer.price_yocto;
+        let attached_deposit = env::attached_deposit();
+        assert!(
+            attached_deposit >= top_up_yocto,
+            "Attached deposit of {} is insufficient to top up the offer by {}",
+            attached_deposit,
+            top_up_yocto
+        );
+        let refund = attached_deposit - top_up_yocto;
+        if refund > 0 {
+            Promise::new(offer.bidder_id.clone()).transfer(refund);
+        }
+
+        // the counter is dropped, if the transfer fails the offer stands at its original price
+        let storage_before = env::storage_usage();
+        offer.is_pending = true;
+        self.token_offers_by_id.insert(&offer.id, &offer);
+        let storage_after = env::storage_usage();
+        self.internal_charge_account_storage(&counter.owner_id, storage_before, storage_after);
+
+        self.internal_transfer_offered_token(
+            &offer,
+            counter.approval_id,
+            counter.owner_id,
+            counter.price_yocto,
+            top_up_yocto,
+        )
+    }
+}
+
+impl MarketplaceContract {
+    // sells the token at the offered price, called from nft_on_approve with the approval the
+    // token was just given
+    pub(crate) fn internal_accept_token_offer(
+        &mut self,
+        owner_id: &AccountId,
+        nft_contract_id: AccountId,
+        token_id: NftId,
+        approval_id: u64,
+        offer_id: OfferId,
+    ) -> Promise {
+        let mut offer = self.internal_token_offer_for(&nft_contract_id, &token_id, offer_id);
+        assert!(&offer.bidder_id != owner_id, "Cannot accept your own offer");
+
+        offer.is_pending = true;
+        self.token_offers_by_id.insert(&offer.id, &offer);
+
+        self.internal_transfer_offered_token(
+            &offer,
+            approval_id,
+            owner_id.clone(),
+            offer.price_yocto,
+            0,
+        )
+    }
+
+    // the token is looked up alongside the transfer, its collection goes into the sale stats
+    fn internal_transfer_offered_token(
+        &self,
+        offer: &TokenOffer,
+        approval_id: u64,
+        owner_id: AccountId,
+        price_yocto: u128,
+        top_up_yocto: u128,
+    ) -> Promise {
+        nft_contract::nft_transfer(
+            offer.bidder_id.clone(),
+            offer.token_id.clone(),
+            Some(approval_id),
+            None,
+            offer.nft_contract_id.clone(),
+            1,
+            NFT_TRANSFER_GAS,
+        )
+        .and(nft_contract::nft_token(
+            offer.token_id.clone(),
+            offer.nft_contract_id.clone(),
+            NO_DEPOSIT,
+            NFT_TOKEN_GAS,
+        ))
+        .then(ext_self_nft::token_offer_accept_completion(
+            U64(offer.id),
+            owner_id,
+            U128(price_yocto),
+            U128(top_up_yocto),
+            env::current_account_id(),
+            NO_DEPOSIT,
+            TOKEN_OFFER_ACCEPT_COMPLETION_GAS,
+        ))
+    }
+
+    // called from nft_on_approve, the approval lets the bidder accept the counter offer alone;
+    // a new counter replaces the previous one
+    pub(crate) fn internal_counter_token_offer(
+        &mut self,
+        owner_id: &AccountId,
+        nft_contract_id: AccountId,
+        token_id: NftId,
+        approval_id: u64,
+        offer_id: OfferId,
+        price_yocto: u128,
+    ) {
+        let mut offer = self.internal_token_offer_for(&nft_contract_id, &token_id, offer_id);
+        assert!(
+            price_yocto > offer.price_yocto,
+            "Counter price must be higher than the offer of {}",
+            offer.price_yocto
+        );
+        let price_step_yocto = self.config.listing_rules_default.price_step_yocto;
+        assert!(
+            price_yocto % price_step_yocto == 0,
+            "Price must be integer multiple of {} yoctoNear",
+            price_step_yocto
+        );
+
+        // storage of the counter is taken from the owner's storage deposit
+        let storage_before = env::storage_usage();
+        offer.counter = Some(TokenCounterOffer {
+            owner_id: owner_id.clone(),
+            approval_id,
+            price_yocto,
+        });
+        self.token_offers_by_id.insert(&offer.id, &offer);
+        let storage_after = env::storage_usage();
+        self.internal_charge_account_storage(owner_id, storage_before, storage_after);
+    }
+
+    // the offer must be live and made for the given token
+    fn internal_token_offer_for(
+        &self,
+        nft_contract_id: &AccountId,
+        token_id: &NftId,
+        offer_id: OfferId,
+    ) -> TokenOffer {
+        let offer = self
+            .token_offers_by_id
+            .get(&offer_id)
+            .expect("Could not find offer");
+        assert!(
+            &offer.nft_contract_id == nft_contract_id && &offer.token_id == token_id,
+            "This offer is for another token"
+        );
+        assert!(!offer.is_expired(), "This offer has expired");
+        assert!(!offer.is_pending, "This offer is being accepted");
+        offer
+    }
+
+    fn internal_add_token_offer(&mut self, offer: &TokenOffer) {
+        self.token_offers_by_id.insert(&offer.id, offer);
+
+        let token_key = (offer.nft_contract_id.clone(), offer.token_id.clone());
+        let mut token_offers = self
+            .token_offers_by_token
+            .get(&token_key)
+            .unwrap_or_else(|| {
+                UnorderedSet::new(
+                    MarketplaceStorageKey::TokenOffersByTokenInner {
+                        token_id_hash: hash_token_id(&offer.nft_contract_id, &offer.token_id),
+                    }
+                    .try_to_vec()
+                    .unwrap(),
+                )
+            });
+        token_offers.insert(&offer.id);
+        self.token_offers_by_token.insert(&token_key, &token_offers);
+
+        let mut account_offers = self
+            .token_offers_by_account
+            .get(&offer.bidder_id)
+            .unwrap_or_else(|| {
+                UnorderedSet::new(
+                    MarketplaceStorageKey::TokenOffersByAccountInner {
+                        account_id_hash: hash_account_id(&offer.bidder_id),
+                    }
+                    .try_to_vec()
+                    .unwrap(),
+                )
+            });
+        account_offers.insert(&offer.id);
+        self.token_offers_by_account
+            .insert(&offer.bidder_id, &account_offers);
+    }
+
+    // removes all records of the offer without initiating any NEAR transfers, returns the
+    // storage freed in yoctoNear; the counter offer storage goes back to the owner's deposit
+    fn internal_remove_token_offer(&mut self, offer: &TokenOffer) -> Balance {
+        let storage_before = env::storage_usage();
+        self.token_offers_by_id.remove(&offer.id);
+
+        let token_key = (offer.nft_contract_id.clone(), offer.token_id.clone());
+        if let Some(mut token_offers) = self.token_offers_by_token.get(&token_key) {
+            token_offers.remove(&offer.id);
+            if token_offers.is_empty() {
+                self.token_offers_by_token.remove(&token_key);
+            } else {
+                self.token_offers_by_token.insert(&token_key, &token_offers);
+            }
+        }
+
+        if let Some(mut account_offers) = self.token_offers_by_account.get(&offer.bidder_id) {
+            account_offers.remove(&offer.id);
+            if account_offers.is_empty() {
+                self.token_offers_by_account.remove(&offer.bidder_id);
+            } else {
+                self.token_offers_by_account
+                    .insert(&offer.bidder_id, &account_offers);
+            }
+        }
+        let storage_after = env::storage_usage();
+        let storage_freed = storage_before - storage_after;
+
+        // the counter's share was paid by the owner, it's not part of the bidder's refund
+        let counter_storage = offer.counter.as_ref().map_or(0, |counter| {
+            let counter_storage = counter.try_to_vec().unwrap().len() as u64;
+            self.internal_charge_account_storage(&counter.owner_id, counter_storage, 0);
+            counter_storage
+        });
+        (storage_freed - counter_storage) as Balance * env::storage_byte_cost()
+    }
+}
+
+#[ext_contract(ext_self_nft)]
+trait TokenOfferCallback {
+    fn token_offer_reject_completion(&mut self, offer_id: U64, owner_id: AccountId) -> bool;
+
+    fn token_offer_accept_completion(
+        &mut self,
+        offer_id: U64,
+        owner_id: AccountId,
+        price_yocto: U128,
+        top_up_yocto: U128,
+    ) -> bool;
+}
+
+trait TokenOfferCallback {
+    fn token_offer_reject_completion(&mut self, offer_id: U64, owner_id: AccountId) -> bool;
+
+    fn token_offer_accept_completion(
+        &mut self,
+        offer_id: U64,
+        owner_id: AccountId,
+        price_yocto: U128,
+        top_up_yocto: U128,
+    ) -> bool;
+}
+
+#[near_bindgen]
+impl TokenOfferCallback for MarketplaceContract {
+    // returns true if the offer was rejected
+    #[private]
+    fn token_offer_reject_completion(&mut self, offer_id: U64, owner_id: AccountId) -> bool {
+        // may have been withdrawn or accepted in the meantime
+        let offer = match self.token_offers_by_id.get(&offer_id.0) {
+            Some(offer) => offer,
+            None => return false,
+        };
+        if offer.is_pending {
+            return false;
+        }
+
+        assert_eq!(env::promise_results_count(), 1, "Too many data receipts");
+        let token: Option<JsonNft> = match env::promise_result(0) {
+            PromiseResult::Successful(val) => {
+                near_sdk::serde_json::from_slice::<Option<JsonNft>>(&val).unwrap_or(None)
+            }
+            _ => None,
+        };
+        assert!(
+            token.map_or(false, |token| token.owner_id == owner_id),
+            "Only the token owner can reject offers"
+        );
+
+        let storage_refund = self.internal_remove_token_offer(&offer);
+        Promise::new(offer.bidder_id).transfer(offer.price_yocto + storage_refund);
+        true
+    }
+
+    // returns true if the token was sold
+    #[private]
+    fn token_offer_accept_completion(
+        &mut self,
+        offer_id: U64,
+        owner_id: AccountId,
+        price_yocto: U128,
+        top_up_yocto: U128,
+    ) -> bool {
+        let mut offer = self
+            .token_offers_by_id
+            .get(&offer_id.0)
+            .expect("Could not find offer");
+
+        assert_eq!(
+            env::promise_results_count(),
+            2,
+            "Unexpected number of data receipts"
+        );
+        match env::promise_result(0) {
+            PromiseResult::Successful(_) => {
+                // tokens of other NFT contracts have no collection, their sales only count
+                // towards the contract stats
+                let collection_id = match env::promise_result(1) {
+                    PromiseResult::Successful(val) => {
+                        near_sdk::serde_json::from_slice::<Option<JsonNft>>(&val)
+                            .unwrap_or(None)
+                            .map(|token| token.collection_id)
+                    }
+                    _ => None,
+                };
+
+                // the transfer reset the approvals, listings of the token are dead
+                self.internal_delist_token(&offer.nft_contract_id, &offer.token_id, &owner_id);
+                let storage_refund = self.internal_remove_token_offer(&offer);
+                Promise::new(offer.bidder_id.clone()).transfer(storage_refund);
+
+                // the 1yN attached to nft_transfer is taken from the offer
+                Promise::new(owner_id.clone()).transfer(price_yocto.0 - 1);
+                self.internal_record_sale(
+                    Sale {
+                        listing_id: None,
+                        token_id: offer.token_id,
+                        buyer_id: offer.bidder_id,
+                        seller_id: owner_id,
+                        price_yocto: price_yocto.0,
+                        timestamp: env::block_timestamp(),
+                    },
+                    &offer.nft_contract_id,
+                    collection_id,
+                );
+                true
+            }
+            _ => {
+                // we don't panic here, it'd revert the refund
+                if top_up_yocto.0 > 0 {
+                    Promise::new(offer.bidder_id.clone()).transfer(top_up_yocto.0);
+                }
+                offer.is_pending = false;
+                self.token_offers_by_id.insert(&offer.id, &offer);
+                false
+            }
+        }
+    }
+}
+
+// view-only methods
+
+#[near_bindgen]
+impl MarketplaceContract {
+    pub fn token_offer(&self, offer_id: U64) -> Option<JsonTokenOffer> {
+        self.token_offers_by_id
+            .get(&offer_id.0)
+            .map(|offer| offer.to_json())
+    }
+
+    // offers made for the token, expired ones included until withdrawn; results are paginated
+    pub fn token_offers(
+        &self,
+        nft_contract_id: AccountId,
+        token_id: NftId,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<JsonTokenOffer> {
+        let offers = self.token_offers_by_token.get(&(nft_contract_id, token_id));
+        if offers.is_none() {
+            return vec![];
+        }
+        let offers = offers.unwrap();
+
+        //where to start pagination - if we have a from_index, we'll use that - otherwise start from 0 index
+        let start = u128::from(from_index.unwrap_or(U128(0))) as usize;
+        let count = limit.unwrap_or(10) as usize;
+
+        offers
+            .iter()
+            .skip(start)
+            .take(count)
+            .map(|offer_id| self.token_offers_by_id.get(&offer_id).unwrap().to_json())
+            .collect()
+    }
+
+    // offers made by the account, results are paginated
+    pub fn token_offers_by_account(
+        &self,
+        account_id: AccountId,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<JsonTokenOffer> {
+        let offers = self.token_offers_by_account.get(&account_id);
+        if offers.is_none() {
+            return vec![];
+        }
+        let offers = offers.unwrap();
+
+        //where to start pagination - if we have a from_index, we'll use that - otherwise start from 0 index
+        let start = u128::from(from_index.unwrap_or(U128(0))) as usize;
+        let count = limit.unwrap_or(10) as usize;
+
+        offers
+            .iter()
+            .skip(start)
+            .take(count)
+            .map(|offer_id| self.token_offers_by_id.get(&offer_id).unwrap().to_json())
+            .collect()
+    }
+}
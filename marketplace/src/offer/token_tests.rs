@@ -0,0 +1,305 @@
+#[cfg(test)]
+mod token_tests {
+    use super::super::{JsonTokenOffer, TokenOfferCallback};
+    use crate::{listing::date::DateInput, test_utils::*, *};
+    use near_sdk::json_types::{U128, U64};
+    use near_sdk::serde_json::{self, json};
+    use near_sdk::PromiseResult;
+
+    const TOKEN_ID: &str = "0:1";
+    const EXPIRY_NANO: u64 = 3 * HOUR_NANO; // two hours after NOW
+
+    fn place(marketplace: &mut MarketplaceContract, bidder_id: &str, token_id: &str) -> u64 {
+        set_context(bidder_id, NOW, PRICE_YOCTO + ONE_NEAR);
+        marketplace
+            .token_offer_place(
+                account(NFT_CONTRACT_ID),
+                token_id.to_string(),
+                U128(PRICE_YOCTO),
+                DateInput::Rfc3339("1970-01-01T03:00:00+00:00".to_string()),
+            )
+            .0
+    }
+
+    fn offer_ids(offers: Vec<JsonTokenOffer>) -> Vec<u64> {
+        let mut offer_ids: Vec<u64> = offers.iter().map(|offer| offer.id.0).collect();
+        offer_ids.sort();
+        offer_ids
+    }
+
+    fn accept(marketplace: &mut MarketplaceContract, offer_id: u64) {
+        set_context(NFT_CONTRACT_ID, NOW, 0);
+        marketplace.internal_accept_token_offer(
+            &account(SELLER_ACCOUNT_ID),
+            account(NFT_CONTRACT_ID),
+            TOKEN_ID.to_string(),
+            1,
+            offer_id,
+        );
+    }
+
+    fn token_result(collection_id: u64) -> PromiseResult {
+        PromiseResult::Successful(
+            serde_json::to_vec(&json!({
+                "token_id": TOKEN_ID,
+                "owner_id": BIDDER_ACCOUNT_ID,
+                "collection_id": collection_id,
+                "metadata": {"title": "Collection 0"},
+                "mutable_metadata": {},
+                "approved_account_ids": {}
+            }))
+            .unwrap(),
+        )
+    }
+
+    fn complete_accept(
+        marketplace: &mut MarketplaceContract,
+        offer_id: u64,
+        promise_result: PromiseResult,
+    ) -> bool {
+        set_callback_context(NOW, vec![promise_result, token_result(0)]);
+        marketplace.token_offer_accept_completion(
+            U64(offer_id),
+            account(SELLER_ACCOUNT_ID),
+            U128(PRICE_YOCTO),
+            U128(0),
+        )
+    }
+
+    /* offer book */
+
+    #[test]
+    fn test_token_offer_place() {
+        let mut marketplace = marketplace();
+        let offer_id = place(&mut marketplace, BIDDER_ACCOUNT_ID, TOKEN_ID);
+
+        let offer = marketplace.token_offer(U64(offer_id)).unwrap();
+        assert_eq!(offer.bidder_id, account(BIDDER_ACCOUNT_ID));
+        assert_eq!(offer.price_yocto.0, PRICE_YOCTO);
+        assert_eq!(offer.expires_timestamp.0, EXPIRY_NANO);
+        assert!(!offer.is_expired);
+        assert!(!offer.is_pending);
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Offer duration too short"#)]
+    fn test_token_offer_place_too_short() {
+        let mut marketplace = marketplace();
+        set_context(BIDDER_ACCOUNT_ID, NOW, PRICE_YOCTO + ONE_NEAR);
+        marketplace.token_offer_place(
+            account(NFT_CONTRACT_ID),
+            TOKEN_ID.to_string(),
+            U128(PRICE_YOCTO),
+            DateInput::DurationMs(U64(30 * 60 * 1000)),
+        );
+    }
+
+    #[test]
+    fn test_token_offers_views() {
+        let mut marketplace = marketplace();
+        let first = place(&mut marketplace, BIDDER_ACCOUNT_ID, TOKEN_ID);
+        let second = place(&mut marketplace, BIDDER2_ACCOUNT_ID, TOKEN_ID);
+        let other_token = place(&mut marketplace, BIDDER_ACCOUNT_ID, "0:2");
+
+        let nft_contract_id = account(NFT_CONTRACT_ID);
+        assert_eq!(
+            offer_ids(marketplace.token_offers(
+                nft_contract_id.clone(),
+                TOKEN_ID.to_string(),
+                None,
+                None
+            )),
+            vec![first, second]
+        );
+        assert_eq!(
+            marketplace
+                .token_offers(
+                    nft_contract_id,
+                    TOKEN_ID.to_string(),
+                    Some(U128(1)),
+                    Some(5)
+                )
+                .len(),
+            1
+        );
+        assert_eq!(
+            offer_ids(marketplace.token_offers_by_account(account(BIDDER_ACCOUNT_ID), None, None)),
+            vec![first, other_token]
+        );
+    }
+
+    #[test]
+    fn test_token_offer_withdraw() {
+        let mut marketplace = marketplace();
+        let offer_id = place(&mut marketplace, BIDDER_ACCOUNT_ID, TOKEN_ID);
+
+        set_context(BIDDER_ACCOUNT_ID, NOW, 0);
+        marketplace.token_offer_withdraw(U64(offer_id));
+
+        assert!(marketplace.token_offer(U64(offer_id)).is_none());
+        assert!(marketplace
+            .token_offers(account(NFT_CONTRACT_ID), TOKEN_ID.to_string(), None, None)
+            .is_empty());
+        assert!(marketplace
+            .token_offers_by_account(account(BIDDER_ACCOUNT_ID), None, None)
+            .is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Not authorized to withdraw this offer"#)]
+    fn test_token_offer_withdraw_by_other() {
+        let mut marketplace = marketplace();
+        let offer_id = place(&mut marketplace, BIDDER_ACCOUNT_ID, TOKEN_ID);
+
+        set_context(BUYER_ACCOUNT_ID, NOW, 0);
+        marketplace.token_offer_withdraw(U64(offer_id));
+    }
+
+    #[test]
+    fn test_token_offer_withdraw_expired_by_anyone() {
+        let mut marketplace = marketplace();
+        let offer_id = place(&mut marketplace, BIDDER_ACCOUNT_ID, TOKEN_ID);
+
+        set_context(BUYER_ACCOUNT_ID, EXPIRY_NANO, 0);
+        assert!(marketplace.token_offer(U64(offer_id)).unwrap().is_expired);
+        marketplace.token_offer_withdraw(U64(offer_id));
+
+        assert!(marketplace.token_offer(U64(offer_id)).is_none());
+    }
+
+    #[test]
+    fn test_token_offer_reject_completion() {
+        let mut marketplace = marketplace();
+        let offer_id = place(&mut marketplace, BIDDER_ACCOUNT_ID, TOKEN_ID);
+
+        set_callback_context(
+            NOW,
+            vec![PromiseResult::Successful(
+                serde_json::to_vec(&json!({
+                    "token_id": TOKEN_ID,
+                    "owner_id": SELLER_ACCOUNT_ID,
+                    "collection_id": 0,
+                    "metadata": {"title": "Collection 0"},
+                    "mutable_metadata": {},
+                    "approved_account_ids": {}
+                }))
+                .unwrap(),
+            )],
+        );
+
+        assert!(
+            marketplace.token_offer_reject_completion(U64(offer_id), account(SELLER_ACCOUNT_ID))
+        );
+        assert!(marketplace.token_offer(U64(offer_id)).is_none());
+    }
+
+    /* accepting */
+
+    #[test]
+    #[should_panic(expected = r#"This offer is for another token"#)]
+    fn test_token_offer_accept_other_token() {
+        let mut marketplace = marketplace();
+        let offer_id = place(&mut marketplace, BIDDER_ACCOUNT_ID, "0:2");
+
+        accept(&mut marketplace, offer_id);
+    }
+
+    #[test]
+    #[should_panic(expected = r#"This offer is being accepted"#)]
+    fn test_token_offer_withdraw_while_pending() {
+        let mut marketplace = marketplace();
+        let offer_id = place(&mut marketplace, BIDDER_ACCOUNT_ID, TOKEN_ID);
+        accept(&mut marketplace, offer_id);
+
+        set_context(BIDDER_ACCOUNT_ID, NOW, 0);
+        marketplace.token_offer_withdraw(U64(offer_id));
+    }
+
+    #[test]
+    fn test_token_offer_accept_completion_delists_token() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        let listing_id =
+            add_secondary_listing(&mut marketplace, TOKEN_ID, Some(2 * ONE_NEAR), None, false);
+        let offer_id = place(&mut marketplace, BIDDER_ACCOUNT_ID, TOKEN_ID);
+        accept(&mut marketplace, offer_id);
+
+        assert!(complete_accept(
+            &mut marketplace,
+            offer_id,
+            PromiseResult::Successful(vec![])
+        ));
+
+        assert!(marketplace.token_offer(U64(offer_id)).is_none());
+        // the transfer reset the approvals, the listing would be a ghost
+        assert!(marketplace
+            .secondary_listings_by_id
+            .get(&listing_id)
+            .is_none());
+    }
+
+    #[test]
+    fn test_token_offer_accept_completion_failed_transfer() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        let listing_id =
+            add_secondary_listing(&mut marketplace, TOKEN_ID, Some(2 * ONE_NEAR), None, false);
+        let offer_id = place(&mut marketplace, BIDDER_ACCOUNT_ID, TOKEN_ID);
+        accept(&mut marketplace, offer_id);
+
+        assert!(!complete_accept(
+            &mut marketplace,
+            offer_id,
+            PromiseResult::Failed
+        ));
+
+        assert!(!marketplace.token_offer(U64(offer_id)).unwrap().is_pending);
+        assert!(marketplace
+            .secondary_listings_by_id
+            .get(&listing_id)
+            .is_some());
+    }
+
+    #[test]
+    fn test_token_offer_accept_completion_records_collection_sale() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        let offer_id = place(&mut marketplace, BIDDER_ACCOUNT_ID, TOKEN_ID);
+        accept(&mut marketplace, offer_id);
+
+        assert!(complete_accept(
+            &mut marketplace,
+            offer_id,
+            PromiseResult::Successful(vec![])
+        ));
+
+        let stats = marketplace.market_stats(account(NFT_CONTRACT_ID), Some(U64(0)));
+        assert_eq!(stats.sales_count.0, 1);
+        assert_eq!(stats.volume_yocto.0, PRICE_YOCTO);
+    }
+
+    #[test]
+    fn test_token_offer_accept_completion_without_token_lookup() {
+        set_context(SELLER_ACCOUNT_ID, NOW, 0);
+        let mut marketplace = marketplace();
+        let offer_id = place(&mut marketplace, BIDDER_ACCOUNT_ID, TOKEN_ID);
+        accept(&mut marketplace, offer_id);
+
+        // the sale stands, it only counts towards the contract stats
+        set_callback_context(
+            NOW,
+            vec![PromiseResult::Successful(vec![]), PromiseResult::Failed],
+        );
+        assert!(marketplace.token_offer_accept_completion(
+            U64(offer_id),
+            account(SELLER_ACCOUNT_ID),
+            U128(PRICE_YOCTO),
+            U128(0),
+        ));
+
+        let contract_stats = marketplace.market_stats(account(NFT_CONTRACT_ID), None);
+        assert_eq!(contract_stats.sales_count.0, 1);
+        let collection_stats = marketplace.market_stats(account(NFT_CONTRACT_ID), Some(U64(0)));
+        assert_eq!(collection_stats.sales_count.0, 0);
+    }
+}
@@ -0,0 +1,265 @@
+// helpers shared by the unit tests of the marketplace modules
+
+use crate::{
+    external::{NftMetadata, NftMutableMetadata},
+    listing::{
+        bid::BidBook,
+        bundle::{
+            internal::hash_bundle_id,
+            lib::{BundleId, BundleListing, BundleListingStorageKey, BundleToken},
+        },
+        primary::{internal::hash_primary_listing_id, lib::PrimaryListingStorageKey},
+        secondary::{internal::hash_secondary_listing_id, lib::SecondaryListingStorageKey},
+        status::ListingStatus,
+    },
+    *,
+};
+use near_sdk::collections::Vector;
+use near_sdk::test_utils::VMContextBuilder;
+use near_sdk::{testing_env, PromiseResult, RuntimeFeesConfig, VMConfig, VMContext};
+
+pub const MARKETPLACE_ACCOUNT_ID: &str = "marketplace.eneftigo.testnet";
+pub const NFT_CONTRACT_ID: &str = "nft.eneftigo.testnet";
+pub const SELLER_ACCOUNT_ID: &str = "seller.eneftigo.testnet";
+pub const BUYER_ACCOUNT_ID: &str = "buyer.eneftigo.testnet";
+pub const BUYER2_ACCOUNT_ID: &str = "buyer2.eneftigo.testnet";
+pub const BIDDER_ACCOUNT_ID: &str = "bidder.eneftigo.testnet";
+pub const BIDDER2_ACCOUNT_ID: &str = "bidder2.eneftigo.testnet";
+
+pub const ONE_NEAR: Balance = 1_000_000_000_000_000_000_000_000;
+pub const PRICE_YOCTO: Balance = ONE_NEAR;
+pub const MIN_BID_YOCTO: Balance = ONE_NEAR / 2;
+pub const STORAGE_DEPOSIT: Balance = 100 * ONE_NEAR;
+
+// listings start at 0, calls are made an hour later unless a test needs otherwise
+pub const HOUR_NANO: u64 = 3_600_000_000_000;
+pub const NOW: u64 = HOUR_NANO;
+
+pub fn account(account_id: &str) -> AccountId {
+    AccountId::new_unchecked(account_id.to_string())
+}
+
+pub fn get_context(
+    predecessor_account_id: &str,
+    block_timestamp: u64,
+    attached_deposit: Balance,
+) -> VMContext {
+    VMContextBuilder::new()
+        .current_account_id(account(MARKETPLACE_ACCOUNT_ID))
+        .predecessor_account_id(account(predecessor_account_id))
+        .signer_account_id(account(predecessor_account_id))
+        .block_timestamp(block_timestamp)
+        .attached_deposit(attached_deposit)
+        .account_balance(1_000_000 * ONE_NEAR)
+        .build()
+}
+
+pub fn set_context(predecessor_account_id: &str, block_timestamp: u64, attached_deposit: Balance) {
+    testing_env!(get_context(
+        predecessor_account_id,
+        block_timestamp,
+        attached_deposit
+    ));
+}
+
+// callbacks are private, they're called by the marketplace itself
+pub fn set_callback_context(block_timestamp: u64, promise_results: Vec<PromiseResult>) {
+    testing_env!(
+        get_context(MARKETPLACE_ACCOUNT_ID, block_timestamp, 0),
+        VMConfig::test(),
+        RuntimeFeesConfig::test(),
+        Default::default(),
+        promise_results
+    );
+}
+
+pub fn mint_result(token_id: &str, storage_bytes: u64) -> PromiseResult {
+    PromiseResult::Successful(
+        near_sdk::serde_json::to_vec(&(token_id.to_string(), U64(storage_bytes))).unwrap(),
+    )
+}
+
+pub fn marketplace() -> MarketplaceContract {
+    let mut marketplace = MarketplaceContract::new(account(MARKETPLACE_ACCOUNT_ID));
+    for account_id in [
+        SELLER_ACCOUNT_ID,
+        BUYER_ACCOUNT_ID,
+        BUYER2_ACCOUNT_ID,
+        BIDDER_ACCOUNT_ID,
+        BIDDER2_ACCOUNT_ID,
+    ] {
+        marketplace
+            .storage_deposits
+            .insert(&account(account_id), &STORAGE_DEPOSIT);
+    }
+    marketplace
+}
+
+pub fn nft_metadata(collection_id: NftCollectionId) -> NftMetadata {
+    NftMetadata {
+        title: Some(format!("Collection {}", collection_id)),
+        description: None,
+        media: Some(format!("https://eneftigo.com/media/{}.png", collection_id)),
+        media_hash: None,
+        copies: None,
+        issued_at: None,
+        expires_at: None,
+        starts_at: None,
+        updated_at: None,
+        extra: None,
+        reference: None,
+        reference_hash: None,
+    }
+}
+
+// running primary listing of the seller
+pub fn add_primary_listing(
+    marketplace: &mut MarketplaceContract,
+    collection_id: NftCollectionId,
+    supply_total: u64,
+    price_yocto: Option<Balance>,
+    min_bid_yocto: Option<Balance>,
+    max_per_account: Option<u64>,
+) -> PrimaryListingId {
+    let listing_id = PrimaryListingId {
+        nft_contract_id: account(NFT_CONTRACT_ID),
+        collection_id,
+    };
+    let listing_id_hash = hash_primary_listing_id(&listing_id, 0);
+    let mut listing = PrimaryListing {
+        id: listing_id.clone(),
+        seller_id: account(SELLER_ACCOUNT_ID),
+        nft_metadata: nft_metadata(collection_id),
+        nft_mutable_metadata: NftMutableMetadata {
+            aux_audio_url: None,
+        },
+        supply_total,
+        price_yocto,
+        min_bid_yocto,
+        start_timestamp: 0,
+        end_timestamp: min_bid_yocto.map(|_| (24 * HOUR_NANO) as i64),
+        status: ListingStatus::Unstarted,
+        supply_left: supply_total,
+        supply_pending: 0,
+        bids: BidBook::new(
+            PrimaryListingStorageKey::Bids { listing_id_hash }
+                .try_to_vec()
+                .unwrap(),
+            PrimaryListingStorageKey::BidKeys { listing_id_hash }
+                .try_to_vec()
+                .unwrap(),
+        ),
+        next_bid_id: 0,
+        version: 0,
+        phases: Vector::new(
+            PrimaryListingStorageKey::Phases { listing_id_hash }
+                .try_to_vec()
+                .unwrap(),
+        ),
+        max_per_account,
+        purchases_by_account: UnorderedMap::new(
+            PrimaryListingStorageKey::Purchases { listing_id_hash }
+                .try_to_vec()
+                .unwrap(),
+        ),
+        seq: 0,
+        rules: marketplace.config.listing_rules_default.clone(),
+        round: 0,
+    };
+    marketplace.internal_add_primary_listing(&mut listing);
+    listing_id
+}
+
+// running secondary listing of the seller's token
+pub fn add_secondary_listing(
+    marketplace: &mut MarketplaceContract,
+    token_id: &str,
+    price_yocto: Option<Balance>,
+    min_bid_yocto: Option<Balance>,
+    is_in_custody: bool,
+) -> SecondaryListingId {
+    let listing_id = SecondaryListingId {
+        nft_contract_id: account(NFT_CONTRACT_ID),
+        token_id: token_id.to_string(),
+    };
+    let listing_id_hash = hash_secondary_listing_id(&listing_id);
+    let mut listing = SecondaryListing {
+        id: listing_id.clone(),
+        seller_id: account(SELLER_ACCOUNT_ID),
+        approval_id: 0,
+        is_in_custody,
+        collection_id: Some(0),
+        nft_metadata: nft_metadata(0),
+        nft_mutable_metadata: NftMutableMetadata {
+            aux_audio_url: None,
+        },
+        price_yocto,
+        min_bid_yocto,
+        start_timestamp: 0,
+        end_timestamp: min_bid_yocto.map(|_| (24 * HOUR_NANO) as i64),
+        status: ListingStatus::Unstarted,
+        bids: BidBook::new(
+            SecondaryListingStorageKey::Bids { listing_id_hash }
+                .try_to_vec()
+                .unwrap(),
+            SecondaryListingStorageKey::BidKeys { listing_id_hash }
+                .try_to_vec()
+                .unwrap(),
+        ),
+        next_bid_id: 0,
+        version: 0,
+        seq: 0,
+        rules: marketplace.config.listing_rules_default.clone(),
+    };
+    marketplace.internal_add_secondary_listing(&mut listing);
+    listing_id
+}
+
+// running bundle listing of the seller's tokens, approval ids are 0
+pub fn add_bundle_listing(
+    marketplace: &mut MarketplaceContract,
+    token_ids: &[&str],
+    price_yocto: Option<Balance>,
+    min_bid_yocto: Option<Balance>,
+) -> BundleId {
+    let bundle_id = marketplace.next_bundle_id;
+    marketplace.next_bundle_id += 1;
+    let bundle_id_hash = hash_bundle_id(bundle_id);
+    let listing = BundleListing {
+        id: bundle_id,
+        seller_id: account(SELLER_ACCOUNT_ID),
+        tokens: token_ids
+            .iter()
+            .map(|token_id| BundleToken {
+                nft_contract_id: account(NFT_CONTRACT_ID),
+                token_id: token_id.to_string(),
+                approval_id: 0,
+                collection_id: Some(0),
+            })
+            .collect(),
+        price_yocto,
+        min_bid_yocto,
+        start_timestamp: 0,
+        end_timestamp: min_bid_yocto.map(|_| (24 * HOUR_NANO) as i64),
+        status: ListingStatus::Unstarted,
+        bids: BidBook::new(
+            BundleListingStorageKey::Bids { bundle_id_hash }
+                .try_to_vec()
+                .unwrap(),
+            BundleListingStorageKey::BidKeys { bundle_id_hash }
+                .try_to_vec()
+                .unwrap(),
+        ),
+        next_bid_id: 0,
+        is_selling: false,
+        rules: marketplace.config.listing_rules_default.clone(),
+    };
+    marketplace.bundle_listings_by_id.insert(&bundle_id, &listing);
+    for token in listing.tokens.iter() {
+        marketplace.bundle_id_by_token.insert(
+            &(token.nft_contract_id.clone(), token.token_id.clone()),
+            &bundle_id,
+        );
+    }
+    bundle_id
+}
@@ -1,7 +1,9 @@
 use crate::*;
-use near_sdk::{ext_contract, Gas};
+use near_sdk::{ext_contract, Gas, PromiseResult};
 
 const GAS_FOR_NFT_APPROVE: Gas = Gas(10_000_000_000_000);
+const GAS_FOR_RESOLVE_APPROVE: Gas = Gas(5_000_000_000_000); // plus the revoke hook, if it's called
+const GAS_FOR_NFT_ON_REVOKE: Gas = Gas(20_000_000_000_000); // marketplace refunds up to its bid cap
 const NO_DEPOSIT: Balance = 0;
 
 pub trait NonFungibleTokenCore {
@@ -36,6 +38,51 @@ trait NonFungibleTokenApprovalsReceiver {
     );
 }
 
+#[ext_contract(ext_marketplace)]
+trait MarketplaceApprovalReceiver {
+    //cross contract call to the marketplace (contract owner) when its approval for a token is gone
+    fn nft_on_revoke(&mut self, token_id: NftId, owner_id: AccountId);
+}
+
+#[ext_contract(ext_self_approval)]
+trait NonFungibleTokenApprovalResolver {
+    //resolves nft_on_approve; an approval the receiver rejected is revoked
+    fn nft_resolve_approve(&mut self, token_id: NftId, account_id: AccountId, approval_id: u64) -> bool;
+}
+
+trait NonFungibleTokenApprovalResolver {
+    fn nft_resolve_approve(&mut self, token_id: NftId, account_id: AccountId, approval_id: u64) -> bool;
+}
+
+impl NftContract {
+    // the gas the revoke hook takes when the token loses its approvals (on transfer, by anyone
+    // but the hooked marketplace itself, which settles its listing on its own)
+    pub(crate) fn internal_revoke_hook_gas(&self, token: &Nft, sender_id: &AccountId) -> Gas {
+        match &self.revoke_hook_account_id {
+            Some(account_id)
+                if token.approved_account_ids.contains_key(account_id) && sender_id != account_id =>
+            {
+                GAS_FOR_NFT_ON_REVOKE
+            }
+            _ => Gas(0),
+        }
+    }
+
+    // lets the marketplace drop its listing of the token right away instead of waiting for
+    // someone to try buying it; fire-and-forget, the result is not awaited
+    pub(crate) fn internal_notify_marketplace_of_revoke(&self, token_id: &NftId, owner_id: &AccountId) {
+        if let Some(account_id) = self.revoke_hook_account_id.clone() {
+            ext_marketplace::nft_on_revoke(
+                token_id.clone(),
+                owner_id.clone(),
+                account_id,
+                NO_DEPOSIT,
+                GAS_FOR_NFT_ON_REVOKE,
+            );
+        }
+    }
+}
+
 #[near_bindgen]
 impl NonFungibleTokenCore for NftContract {
 
@@ -84,15 +131,30 @@ impl NonFungibleTokenCore for NftContract {
         //if some message was passed into the function, we initiate a cross contract call on the
         // account we're giving access to. 
         if let Some(msg) = msg {
+            // if the receiver rejects the approval (e.g. the marketplace can't parse the listing
+            // msg) it gets revoked in nft_resolve_approve, with the revoke hook if it applies
+            let resolve_gas = if self.revoke_hook_account_id.as_ref() == Some(&account_id) {
+                Gas(GAS_FOR_RESOLVE_APPROVE.0 + GAS_FOR_NFT_ON_REVOKE.0)
+            } else {
+                GAS_FOR_RESOLVE_APPROVE
+            };
             ext_non_fungible_approval_receiver::nft_on_approve(
-                token_id,
+                token_id.clone(),
                 token.owner_id,
                 approval_id,
                 msg,
-                account_id, //contract account we're calling
+                account_id.clone(), //contract account we're calling
                 NO_DEPOSIT, //NEAR deposit we attach to the call
-                env::prepaid_gas() - GAS_FOR_NFT_APPROVE, //GAS we're attaching
+                env::prepaid_gas() - GAS_FOR_NFT_APPROVE - resolve_gas, //GAS we're attaching
             )
+            .then(ext_self_approval::nft_resolve_approve(
+                token_id,
+                account_id,
+                approval_id,
+                env::current_account_id(), //our own contract
+                NO_DEPOSIT,
+                resolve_gas,
+            ))
             .as_return(); // Returning this promise
         }
     }
@@ -139,6 +201,8 @@ impl NonFungibleTokenCore for NftContract {
         let predecessor_account_id = env::predecessor_account_id();
         assert_eq!(&predecessor_account_id, &token.owner_id);
 
+        let is_marketplace = self.revoke_hook_account_id.as_ref() == Some(&account_id);
+
         // if the account ID was in the token's approval, we remove it
         if token
             .approved_account_ids
@@ -150,6 +214,10 @@ impl NonFungibleTokenCore for NftContract {
 
             // insert the token back into the tokens_by_id collection with the account_id removed from the approval list
             self.tokens_by_id.insert(&token_id, &token);
+
+            if is_marketplace {
+                self.internal_notify_marketplace_of_revoke(&token_id, &token.owner_id);
+            }
         }
     }
 
@@ -168,12 +236,186 @@ impl NonFungibleTokenCore for NftContract {
 
         // only revoke if the approved account IDs for the token is not empty
         if !token.approved_account_ids.is_empty() {
+            let was_marketplace_approved = self
+                .revoke_hook_account_id
+                .as_ref()
+                .map_or(false, |account_id| token.approved_account_ids.contains_key(account_id));
             // refund the approved account IDs to the caller of the function
             refund_approved_account_ids(predecessor_account_id, &token.approved_account_ids);
             // clear the approved account IDs
             token.approved_account_ids.clear();
             // insert the token back into the tokens_by_id collection with the approved account IDs cleared
             self.tokens_by_id.insert(&token_id, &token);
+
+            if was_marketplace_approved {
+                self.internal_notify_marketplace_of_revoke(&token_id, &token.owner_id);
+            }
         }    
     }
 }
+
+#[near_bindgen]
+impl NonFungibleTokenApprovalResolver for NftContract {
+    // returns whether the approval stands; if nft_on_approve failed and the approval is still the
+    // one granted, it's removed and its storage refunded to the owner
+    #[private]
+    fn nft_resolve_approve(&mut self, token_id: NftId, account_id: AccountId, approval_id: u64) -> bool {
+        if let PromiseResult::Successful(_) = env::promise_result(0) {
+            return true;
+        }
+
+        // the token may have been transferred or approved again in the meantime
+        let mut token = match self.tokens_by_id.get(&token_id) {
+            Some(token) => token,
+            None => return false,
+        };
+        if token.approved_account_ids.get(&account_id) != Some(&approval_id) {
+            return false;
+        }
+
+        token.approved_account_ids.remove(&account_id);
+        refund_approved_account_ids_iter(token.owner_id.clone(), [account_id.clone()].iter());
+        self.tokens_by_id.insert(&token_id, &token);
+
+        // an earlier listing made through the approval is stale now
+        if self.revoke_hook_account_id.as_ref() == Some(&account_id) {
+            self.internal_notify_marketplace_of_revoke(&token_id, &token.owner_id);
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+
+    fn setup(predecessor_id: &str) -> NftContract {
+        let account_id = AccountId::new_unchecked(predecessor_id.to_string());
+        let context = VMContextBuilder::new()
+            .predecessor_account_id(account_id.clone())
+            .signer_account_id(account_id)
+            .build();
+        testing_env!(context);
+        NftContract::new_default_meta("nft.near".parse().unwrap())
+    }
+
+    fn token(approved_account_id: &str) -> Nft {
+        let mut approved_account_ids = HashMap::new();
+        approved_account_ids.insert(AccountId::new_unchecked(approved_account_id.to_string()), 0);
+        Nft {
+            minter_id: "minter.near".parse().unwrap(),
+            owner_id: "owner.near".parse().unwrap(),
+            collection_id: 0,
+            approved_account_ids,
+            next_approval_id: 1,
+            royalty: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_revoke_hook_is_off_by_default() {
+        let contract = setup("nft.near");
+        assert_eq!(contract.revoke_hook(), None);
+        assert_eq!(
+            contract.internal_revoke_hook_gas(&token("nft.near"), &"owner.near".parse().unwrap()),
+            Gas(0)
+        );
+    }
+
+    #[test]
+    fn test_revoke_hook_gas() {
+        let mut contract = setup("nft.near");
+        contract.set_revoke_hook(Some("marketplace.near".parse().unwrap()));
+        let owner_id: AccountId = "owner.near".parse().unwrap();
+        let marketplace_id: AccountId = "marketplace.near".parse().unwrap();
+
+        assert_eq!(
+            contract.internal_revoke_hook_gas(&token("marketplace.near"), &owner_id),
+            GAS_FOR_NFT_ON_REVOKE
+        );
+        // the marketplace settles its own transfers, other approvals are not its business
+        assert_eq!(
+            contract.internal_revoke_hook_gas(&token("marketplace.near"), &marketplace_id),
+            Gas(0)
+        );
+        assert_eq!(
+            contract.internal_revoke_hook_gas(&token("other.near"), &owner_id),
+            Gas(0)
+        );
+
+        contract.set_revoke_hook(None);
+        assert_eq!(
+            contract.internal_revoke_hook_gas(&token("marketplace.near"), &owner_id),
+            Gas(0)
+        );
+    }
+
+    fn set_resolve_context(promise_result: PromiseResult) {
+        let account_id: AccountId = "nft.near".parse().unwrap();
+        let context = VMContextBuilder::new()
+            .current_account_id(account_id.clone())
+            .predecessor_account_id(account_id)
+            .build();
+        testing_env!(
+            context,
+            near_sdk::VMConfig::test(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![promise_result]
+        );
+    }
+
+    #[test]
+    fn test_resolve_approve_success() {
+        let mut contract = setup("nft.near");
+        contract.tokens_by_id.insert(&"0:1".to_string(), &token("marketplace.near"));
+        set_resolve_context(PromiseResult::Successful(vec![]));
+
+        let marketplace_id: AccountId = "marketplace.near".parse().unwrap();
+        assert!(contract.nft_resolve_approve("0:1".to_string(), marketplace_id.clone(), 0));
+        let token = contract.tokens_by_id.get(&"0:1".to_string()).unwrap();
+        assert_eq!(token.approved_account_ids.get(&marketplace_id), Some(&0));
+    }
+
+    #[test]
+    fn test_resolve_approve_failure_revokes() {
+        let mut contract = setup("nft.near");
+        contract.tokens_by_id.insert(&"0:1".to_string(), &token("marketplace.near"));
+        set_resolve_context(PromiseResult::Failed);
+
+        let marketplace_id: AccountId = "marketplace.near".parse().unwrap();
+        assert!(!contract.nft_resolve_approve("0:1".to_string(), marketplace_id.clone(), 0));
+        let token = contract.tokens_by_id.get(&"0:1".to_string()).unwrap();
+        assert!(token.approved_account_ids.get(&marketplace_id).is_none());
+    }
+
+    #[test]
+    fn test_resolve_approve_failure_approved_again() {
+        let mut contract = setup("nft.near");
+        contract.tokens_by_id.insert(&"0:1".to_string(), &token("marketplace.near"));
+        set_resolve_context(PromiseResult::Failed);
+
+        // a later approval (id 1) is not the one that failed, it stays
+        let marketplace_id: AccountId = "marketplace.near".parse().unwrap();
+        let mut approved_again = contract.tokens_by_id.get(&"0:1".to_string()).unwrap();
+        approved_again.approved_account_ids.insert(marketplace_id.clone(), 1);
+        contract.tokens_by_id.insert(&"0:1".to_string(), &approved_again);
+
+        assert!(!contract.nft_resolve_approve("0:1".to_string(), marketplace_id.clone(), 0));
+        let token = contract.tokens_by_id.get(&"0:1".to_string()).unwrap();
+        assert_eq!(token.approved_account_ids.get(&marketplace_id), Some(&1));
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Only contract owner (Eneftigo Marketplace) can set the revoke hook"#)]
+    fn test_set_revoke_hook_owner_only() {
+        let mut contract = setup("nft.near");
+        let context = VMContextBuilder::new()
+            .predecessor_account_id("owner.near".parse().unwrap())
+            .build();
+        testing_env!(context);
+        contract.set_revoke_hook(Some("owner.near".parse().unwrap()));
+    }
+}
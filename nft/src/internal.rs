@@ -173,6 +173,12 @@ impl NftContract {
         // insert that new token into the tokens_by_id, replacing the old entry
         self.tokens_by_id.insert(token_id, &updated_token);
 
+        // approvals are reset so the marketplace can no longer sell the token; no need to tell it
+        // when it's the marketplace making the transfer, it settles the listing on its own
+        if self.internal_revoke_hook_gas(&token, sender_id).0 > 0 {
+            self.internal_notify_marketplace_of_revoke(token_id, &token.owner_id);
+        }
+
         // if there was some memo attached, we log it.
         if let Some(memo) = memo.as_ref() {
             env::log_str(&format!("Memo: {}", memo).to_string());
@@ -25,6 +25,7 @@ mod nft_core;
 mod royalty; 
 mod events;
 mod collection;
+mod migration;
 
 // This spec can be treated like a version of the standard.
 pub const NFT_METADATA_SPEC: &str = "1.0.0";
@@ -66,6 +67,10 @@ pub struct NftContract {
 
     //keeps track of the token mutable metadata for a given token ID
     pub token_mutable_metadata_by_id: UnorderedMap<NftId, TokenMutableMetadata>,
+
+    //marketplace notified when its approval for a token is gone (revoked or token transferred), if any;
+    //contracts deployed without it upgrade through migrate, which leaves it None until set_revoke_hook
+    pub revoke_hook_account_id: Option<AccountId>,
 }
 
 /// Helper structure for keys of the persistent collections.
@@ -127,12 +132,27 @@ impl NftContract {
             token_mutable_metadata_by_id: UnorderedMap::new(StorageKey::TokenMutableMetadataById.try_to_vec().unwrap()),
             owner_id,
             metadata: LazyOption::new(StorageKey::NFTContractMetadata.try_to_vec().unwrap(), Some(&metadata)),
+            revoke_hook_account_id: None,
         };
 
         //return the Contract object
         this
     }
 
+    // turns the revoke hook on (for the given marketplace account) or off (None)
+    pub fn set_revoke_hook(&mut self, account_id: Option<AccountId>) {
+        assert_eq!(
+            &env::predecessor_account_id(),
+            &self.owner_id,
+            "Only contract owner (Eneftigo Marketplace) can set the revoke hook"
+        );
+        self.revoke_hook_account_id = account_id;
+    }
+
+    pub fn revoke_hook(&self) -> Option<AccountId> {
+        self.revoke_hook_account_id.clone()
+    }
+
     pub fn clean(keys: Vec<Base64VecU8>) {
         for key in keys.iter() {
             env::storage_remove(&key.0);
@@ -0,0 +1,74 @@
+use crate::*;
+
+// contract state deployed before the revoke hook was added
+#[derive(BorshDeserialize, BorshSerialize)]
+pub(crate) struct NftContractV1 {
+    pub owner_id: AccountId,
+    pub metadata: LazyOption<NFTContractMetadata>,
+    pub tokens_per_owner: LookupMap<AccountId, UnorderedSet<NftId>>,
+    pub collections_by_id: LookupMap<NftCollectionId, NftCollection>,
+    pub collections_by_url: LookupMap<String, NftCollectionId>,
+    pub next_collection_id: u64,
+    pub tokens_by_id: LookupMap<NftId, Nft>,
+    pub token_metadata_by_id: UnorderedMap<NftId, TokenMetadata>,
+    pub token_mutable_metadata_by_id: UnorderedMap<NftId, TokenMutableMetadata>,
+}
+
+#[near_bindgen]
+impl NftContract {
+    // upgrades the V1 state; the collections keep their prefixes so only the root struct is
+    // rewritten, the revoke hook starts out off until the owner calls set_revoke_hook
+    #[private]
+    #[init(ignore_state)]
+    pub fn migrate() -> Self {
+        let old: NftContractV1 = env::state_read().expect("Could not read contract state");
+        Self {
+            owner_id: old.owner_id,
+            metadata: old.metadata,
+            tokens_per_owner: old.tokens_per_owner,
+            collections_by_id: old.collections_by_id,
+            collections_by_url: old.collections_by_url,
+            next_collection_id: old.next_collection_id,
+            tokens_by_id: old.tokens_by_id,
+            token_metadata_by_id: old.token_metadata_by_id,
+            token_mutable_metadata_by_id: old.token_mutable_metadata_by_id,
+            revoke_hook_account_id: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+
+    #[test]
+    fn test_migrate_v1() {
+        let owner_id: AccountId = "nft.near".parse().unwrap();
+        let context = VMContextBuilder::new()
+            .current_account_id(owner_id.clone())
+            .predecessor_account_id(owner_id.clone())
+            .build();
+        testing_env!(context);
+
+        let contract = NftContract::new_default_meta(owner_id.clone());
+        env::state_write(&NftContractV1 {
+            owner_id: contract.owner_id,
+            metadata: contract.metadata,
+            tokens_per_owner: contract.tokens_per_owner,
+            collections_by_id: contract.collections_by_id,
+            collections_by_url: contract.collections_by_url,
+            next_collection_id: 3,
+            tokens_by_id: contract.tokens_by_id,
+            token_metadata_by_id: contract.token_metadata_by_id,
+            token_mutable_metadata_by_id: contract.token_mutable_metadata_by_id,
+        });
+
+        let migrated = NftContract::migrate();
+        assert_eq!(migrated.owner_id, owner_id, "Owner not migrated");
+        assert_eq!(migrated.next_collection_id, 3, "Collection counter not migrated");
+        assert_eq!(migrated.metadata.get().unwrap().symbol, "ENEFTIGO", "Metadata not migrated");
+        assert!(migrated.revoke_hook().is_none(), "Revoke hook should start out off");
+    }
+}
@@ -142,6 +142,12 @@ impl NonFungibleTokenCore for NftContract {
         // get sender ID 
         let sender_id = env::predecessor_account_id();
 
+        // the revoke hook fired by the transfer comes out of the receiver's share
+        let revoke_hook_gas = self
+            .tokens_by_id
+            .get(&token_id)
+            .map_or(Gas(0), |token| self.internal_revoke_hook_gas(&token, &sender_id));
+
         // transfer the token and get the previous token object
         let previous_token = self.internal_transfer(
             &sender_id, 
@@ -166,7 +172,7 @@ impl NonFungibleTokenCore for NftContract {
             msg,
             receiver_id.clone(), // contract account to make the call to
             NO_DEPOSIT, // attached deposit
-            env::prepaid_gas() - GAS_FOR_NFT_TRANSFER_CALL, // attached GAS
+            env::prepaid_gas() - GAS_FOR_NFT_TRANSFER_CALL - revoke_hook_gas, // attached GAS
         )
         // we then resolve the promise and call nft_resolve_transfer on our own contract
         .then(
@@ -12,6 +12,25 @@ pub const PRIMARY_LISTING_ACCEPTING_PROPOSALS_BUY_GAS: Gas = 100_000_000_000_000
 pub const PRIMARY_LISTING_ACCEPTING_PROPOSALS_CONCLUDE_GAS: Gas = 10_000_000_000_000;    // TODO: measure when there's a lot of proposers 
 pub const PRIMARY_LISTING_ACCEPTING_PROPOSALS_REVOKE_GAS: Gas = 10_000_000_000_000; // TODO: measure
 
+/*
+    Bid book benchmark
+*/
+pub const BID_BOOK_BENCHMARK_BIDS_COUNTS: [u64; 3] = [10, 50, 100];
+pub const BID_BOOK_BENCHMARK_SUPPLY: u64 = 100; // TOTAL_SUPPLY_MAX, all bids stay acceptable
+pub const BID_BOOK_BENCHMARK_MIN_BID_YOCTO: u128 = 100_000_000_000_000_000_000_000; // MIN_BID_YOCTO
+pub const BID_BOOK_BENCHMARK_PLACE_BID_GAS: Gas = 300_000_000_000_000; // max, sorted vector grows linearly
+pub const BID_BOOK_BENCHMARK_DURATION_MS: u64 = 24 * 3600 * 1000; // bid-accepting listings must end
+// primary_listing_place_bid gas at 100 standing bids over the gas at 10, the bid book takes
+// O(log n) storage operations per bid so it must stay well below the 10x of a linear book
+pub const BID_BOOK_BENCHMARK_GAS_GROWTH_MAX: f64 = 2.0;
+
+/*
+    Buying the maximum quantity
+*/
+pub const PRIMARY_LISTING_BUY_QUANTITY_MAX: u64 = 10; // marketplace PRIMARY_LISTING_BUY_QUANTITY_MAX
+pub const PRIMARY_LISTING_BUY_QUANTITY_MAX_GAS: Gas = 300_000_000_000_000; // max, the mints and their completion must fit
+pub const PRIMARY_LISTING_BUY_QUANTITY_MAX_PRICE_YOCTO: u128 = 100_000_000_000_000_000_000_000; // 0.1N
+
 /*
     Storage
 */
@@ -22,6 +41,7 @@ pub const PRIMARY_LISTING_ACCEPTING_PROPOSALS_PLACE_STORAGE: u64 = 796;     // a
 pub const PRIMARY_LISTING_ADD_WORST_CASE_STORAGE: u64 =
 PRIMARY_LISTING_ADD_WORST_CASE_MARKETPLACE_STORAGE + NEW_COLLECTION_WORST_CASE_NFT_STORAGE;
 pub const NFT_MINT_WORST_CASE_STORAGE: u64 = 830; // actual measured was 830
+pub const NFT_MINT_STORAGE_MAX: u64 = 3317; // marketplace default, bidders must have it deposited
 
 /*
     Fees
@@ -39,6 +59,7 @@ pub const STORAGE_COST_YOCTO_PER_BYTE: u128 = 10000000000000000000;
 */
 pub const MARKETPLACE_WASM_FILEPATH: &str = "../out/marketplace.wasm";
 pub const NFT_WASM_FILEPATH: &str = "../out/nft.wasm";
+pub const MARKETPLACE_VECTOR_BIDS_WASM_FILEPATH: &str = "../out/marketplace_vector_bids.wasm";
 
 pub const MIN_DURATION_SECS: i64 = 3600; // 1 hour
 pub const MAX_DURATION_SECS: i64 = 3600 * 24 * 14; // 2 weeks
@@ -0,0 +1,201 @@
+use crate::gas_and_storage::*;
+use colored::Colorize;
+use near_units::parse_near;
+use serde_json::json;
+use workspaces::prelude::*;
+use workspaces::result::CallExecutionDetails;
+use workspaces::types::{Balance, Gas};
+
+#[allow(dead_code)]
+mod gas_and_storage;
+
+// compares primary_listing_place_bid gas of the current marketplace build against the build
+// which kept bids in a sorted vector (re-sorted and rewritten on every bid)
+// to get the baseline wasm, build the marketplace at the commit preceding the bid book and copy
+// it to MARKETPLACE_VECTOR_BIDS_WASM_FILEPATH; if it's missing only the current build is measured
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let current = measure_place_bid_gas(MARKETPLACE_WASM_FILEPATH).await?;
+    let baseline = if std::path::Path::new(MARKETPLACE_VECTOR_BIDS_WASM_FILEPATH).exists() {
+        Some(measure_place_bid_gas(MARKETPLACE_VECTOR_BIDS_WASM_FILEPATH).await?)
+    } else {
+        println!(
+            "{} not found, skipping the sorted vector baseline",
+            MARKETPLACE_VECTOR_BIDS_WASM_FILEPATH
+        );
+        None
+    };
+
+    println!("{}", "primary_listing_place_bid gas".cyan());
+    for (index, bids_count) in BID_BOOK_BENCHMARK_BIDS_COUNTS.iter().enumerate() {
+        match &baseline {
+            Some(baseline) => println!(
+                " - {:>3} bids: bid book {:>16}, sorted vector {:>16} ({:.2}x)",
+                bids_count,
+                current[index],
+                baseline[index],
+                baseline[index] as f64 / current[index] as f64
+            ),
+            None => println!(" - {:>3} bids: bid book {:>16}", bids_count, current[index]),
+        }
+    }
+
+    // the gas measured at the smallest and the largest book
+    let growth = *current.last().unwrap() as f64 / current[0] as f64;
+    assert!(
+        growth <= BID_BOOK_BENCHMARK_GAS_GROWTH_MAX,
+        "    bid book gas grew {:.2}x from {} to {} bids {}",
+        growth,
+        BID_BOOK_BENCHMARK_BIDS_COUNTS[0],
+        BID_BOOK_BENCHMARK_BIDS_COUNTS[BID_BOOK_BENCHMARK_BIDS_COUNTS.len() - 1],
+        "FAILED".red()
+    );
+    if let Some(baseline) = &baseline {
+        assert!(
+            current.last() < baseline.last(),
+            "    bid book is not cheaper than the sorted vector at {} bids {}",
+            BID_BOOK_BENCHMARK_BIDS_COUNTS[BID_BOOK_BENCHMARK_BIDS_COUNTS.len() - 1],
+            "FAILED".red()
+        );
+    }
+    println!(" - {}", "PASSED".green());
+
+    Ok(())
+}
+
+// deploys fresh contracts and places bids on a single listing, returns the gas burnt by the
+// bid which brings the number of standing bids to each of BID_BOOK_BENCHMARK_BIDS_COUNTS
+async fn measure_place_bid_gas(marketplace_wasm_filepath: &str) -> anyhow::Result<Vec<Gas>> {
+    let worker = workspaces::sandbox().await?;
+
+    let marketplace_wasm = std::fs::read(marketplace_wasm_filepath)?;
+    let marketplace_contract: workspaces::Contract = worker.dev_deploy(&marketplace_wasm).await?;
+    let outcome = marketplace_contract
+        .call(&worker, "new")
+        .args_json(json!({
+            "owner_id": marketplace_contract.id(),
+        }))?
+        .transact()
+        .await?;
+    assert!(
+        outcome.is_success(),
+        "    marketplace initialization failed: {:#?} {}",
+        outcome,
+        "FAILED".red()
+    );
+
+    let outcome = marketplace_contract
+        .as_account()
+        .create_subaccount(&worker, "nft")
+        .initial_balance(parse_near!("10 N"))
+        .transact()
+        .await?;
+    assert!(
+        outcome.details.is_success(),
+        "NFT subaccont creation failed: {:#?} {}",
+        outcome.details,
+        "FAILED".red()
+    );
+    let nft_account: workspaces::Account = outcome.result;
+    let nft_wasm = std::fs::read(&NFT_WASM_FILEPATH)?;
+    let outcome = nft_account.deploy(&worker, &nft_wasm).await?;
+    assert!(
+        outcome.details.is_success(),
+        "    nft contract deployment failed: {:#?} {}",
+        outcome.details,
+        "FAILED".red()
+    );
+    let nft_contract: workspaces::Contract = outcome.result;
+    let outcome = nft_account
+        .call(&worker, &nft_contract.id(), "new_default_meta")
+        .args_json(json!({
+            "owner_id": marketplace_contract.id(),
+        }))?
+        .transact()
+        .await?;
+    assert!(
+        outcome.is_success(),
+        "    nft contract initialization failed {:?} {}",
+        outcome,
+        "FAILED".red()
+    );
+
+    let seller_account = worker.dev_create_account().await?;
+    let bidder_account = worker.dev_create_account().await?;
+
+    // seller deposit covering the listing storage
+    let required_deposit =
+        PRIMARY_LISTING_ADD_WORST_CASE_STORAGE as Balance * STORAGE_COST_YOCTO_PER_BYTE;
+    seller_account
+        .call(&worker, marketplace_contract.id(), "place_deposit")
+        .deposit(required_deposit)
+        .transact()
+        .await?;
+
+    // bidder deposit covering the storage of a token minted for an accepted bid
+    bidder_account
+        .call(&worker, marketplace_contract.id(), "place_deposit")
+        .deposit(NFT_MINT_STORAGE_MAX as Balance * STORAGE_COST_YOCTO_PER_BYTE)
+        .transact()
+        .await?;
+
+    // supply large enough for every bid to be acceptable at the minimum bid
+    let outcome = seller_account
+        .call(&worker, marketplace_contract.id(), "primary_listing_add")
+        .args_json(json!({
+            "title": "Bored Grapes",
+            "image_url": "https://ipfs.io/ipfs/QmcRD4wkPPi6dig81r5sLj9Zm1gDCL4zgpEj9CfuRrGbzF",
+            "supply_total": BID_BOOK_BENCHMARK_SUPPLY.to_string(),
+            "min_bid_yocto": BID_BOOK_BENCHMARK_MIN_BID_YOCTO.to_string(),
+            "end_date": { "duration_ms": BID_BOOK_BENCHMARK_DURATION_MS.to_string() },
+        }))?
+        .gas(PRIMARY_LISTING_ACCEPTING_PROPOSALS_ADD_GAS)
+        .transact()
+        .await?;
+    assert!(
+        outcome.is_success(),
+        "    primary_listing_add failed: {:#?} {}",
+        outcome,
+        "FAILED".red()
+    );
+    let (collection_id, _) = outcome.json::<(String, serde_json::Value)>()?;
+
+    let mut gas_burnt: Vec<Gas> = Vec::new();
+    let bids_count_max = *BID_BOOK_BENCHMARK_BIDS_COUNTS.iter().max().unwrap();
+    for bid_index in 0..bids_count_max {
+        // amounts cycle so that new bids land in the middle of the book as well as at its ends
+        let amount_yocto = BID_BOOK_BENCHMARK_MIN_BID_YOCTO
+            + (bid_index % 5) as Balance * BID_BOOK_BENCHMARK_MIN_BID_YOCTO;
+        let outcome = bidder_account
+            .call(&worker, marketplace_contract.id(), "primary_listing_place_bid")
+            .args_json(json!({
+                "nft_contract_id": nft_account.id().clone(),
+                "collection_id": collection_id,
+                "amount_yocto": amount_yocto.to_string(),
+            }))?
+            .gas(BID_BOOK_BENCHMARK_PLACE_BID_GAS)
+            .deposit(amount_yocto)
+            .transact()
+            .await?;
+        assert!(
+            outcome.is_success(),
+            "    primary_listing_place_bid #{} failed: {:#?} {}",
+            bid_index,
+            outcome,
+            "FAILED".red()
+        );
+        if BID_BOOK_BENCHMARK_BIDS_COUNTS.contains(&(bid_index + 1)) {
+            gas_burnt.push(get_gas_burnt(&outcome));
+        }
+    }
+
+    Ok(gas_burnt)
+}
+
+fn get_gas_burnt(execution_details: &CallExecutionDetails) -> Gas {
+    execution_details
+        .receipt_outcomes()
+        .iter()
+        .fold(0, |acc, receipt| acc + receipt.gas_burnt)
+        + execution_details.outcome().gas_burnt
+}
@@ -0,0 +1,165 @@
+use crate::gas_and_storage::*;
+use colored::Colorize;
+use near_units::parse_near;
+use serde_json::json;
+use workspaces::prelude::*;
+use workspaces::types::Balance;
+
+#[allow(dead_code)]
+mod gas_and_storage;
+
+// buys PRIMARY_LISTING_BUY_QUANTITY_MAX tokens in a single primary_listing_buy; the mint
+// completion gas grows with quantity, so this is the call most likely to run out of it
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let worker = workspaces::sandbox().await?;
+
+    let marketplace_wasm = std::fs::read(MARKETPLACE_WASM_FILEPATH)?;
+    let marketplace_contract: workspaces::Contract = worker.dev_deploy(&marketplace_wasm).await?;
+    let outcome = marketplace_contract
+        .call(&worker, "new")
+        .args_json(json!({
+            "owner_id": marketplace_contract.id(),
+        }))?
+        .transact()
+        .await?;
+    assert!(
+        outcome.is_success(),
+        "    marketplace initialization failed: {:#?} {}",
+        outcome,
+        "FAILED".red()
+    );
+
+    let outcome = marketplace_contract
+        .as_account()
+        .create_subaccount(&worker, "nft")
+        .initial_balance(parse_near!("10 N"))
+        .transact()
+        .await?;
+    assert!(
+        outcome.details.is_success(),
+        "NFT subaccont creation failed: {:#?} {}",
+        outcome.details,
+        "FAILED".red()
+    );
+    let nft_account: workspaces::Account = outcome.result;
+    let nft_wasm = std::fs::read(&NFT_WASM_FILEPATH)?;
+    let outcome = nft_account.deploy(&worker, &nft_wasm).await?;
+    assert!(
+        outcome.details.is_success(),
+        "    nft contract deployment failed: {:#?} {}",
+        outcome.details,
+        "FAILED".red()
+    );
+    let nft_contract: workspaces::Contract = outcome.result;
+    let outcome = nft_account
+        .call(&worker, &nft_contract.id(), "new_default_meta")
+        .args_json(json!({
+            "owner_id": marketplace_contract.id(),
+        }))?
+        .transact()
+        .await?;
+    assert!(
+        outcome.is_success(),
+        "    nft contract initialization failed {:?} {}",
+        outcome,
+        "FAILED".red()
+    );
+
+    let seller_account = worker.dev_create_account().await?;
+    let buyer_account = worker.dev_create_account().await?;
+
+    // seller deposit covering the listing storage
+    seller_account
+        .call(&worker, marketplace_contract.id(), "place_deposit")
+        .deposit(PRIMARY_LISTING_ADD_WORST_CASE_STORAGE as Balance * STORAGE_COST_YOCTO_PER_BYTE)
+        .transact()
+        .await?;
+
+    // buyer deposit covering the storage of every token minted
+    buyer_account
+        .call(&worker, marketplace_contract.id(), "place_deposit")
+        .deposit(
+            (NFT_MINT_STORAGE_MAX * PRIMARY_LISTING_BUY_QUANTITY_MAX) as Balance
+                * STORAGE_COST_YOCTO_PER_BYTE,
+        )
+        .transact()
+        .await?;
+
+    let outcome = seller_account
+        .call(&worker, marketplace_contract.id(), "primary_listing_add")
+        .args_json(json!({
+            "title": "Bored Grapes",
+            "image_url": "https://ipfs.io/ipfs/QmcRD4wkPPi6dig81r5sLj9Zm1gDCL4zgpEj9CfuRrGbzF",
+            "supply_total": PRIMARY_LISTING_BUY_QUANTITY_MAX.to_string(),
+            "price_yocto": PRIMARY_LISTING_BUY_QUANTITY_MAX_PRICE_YOCTO.to_string(),
+        }))?
+        .gas(PRIMARY_LISTING_BUY_NOW_ONLY_ADD_GAS)
+        .transact()
+        .await?;
+    assert!(
+        outcome.is_success(),
+        "    primary_listing_add failed: {:#?} {}",
+        outcome,
+        "FAILED".red()
+    );
+    let (collection_id, _) = outcome.json::<(String, serde_json::Value)>()?;
+
+    println!(
+        "{}: Buying {} tokens at once:",
+        "primary_listing_buy".cyan(),
+        PRIMARY_LISTING_BUY_QUANTITY_MAX
+    );
+    let outcome = buyer_account
+        .call(&worker, marketplace_contract.id(), "primary_listing_buy")
+        .args_json(json!({
+            "nft_contract_id": nft_account.id().clone(),
+            "collection_id": collection_id,
+            "quantity": PRIMARY_LISTING_BUY_QUANTITY_MAX.to_string(),
+        }))?
+        .gas(PRIMARY_LISTING_BUY_QUANTITY_MAX_GAS)
+        .deposit(
+            PRIMARY_LISTING_BUY_QUANTITY_MAX_PRICE_YOCTO
+                * PRIMARY_LISTING_BUY_QUANTITY_MAX as Balance,
+        )
+        .transact()
+        .await?;
+    // the call resolves to the mint completion, so it fails if the completion ran out of gas
+    assert!(
+        outcome.is_success(),
+        "    primary_listing_buy of {} tokens failed: {:#?} {}",
+        PRIMARY_LISTING_BUY_QUANTITY_MAX,
+        outcome,
+        "FAILED".red()
+    );
+
+    // every mint went through
+    let owned = nft_contract
+        .view(
+            &worker,
+            "nft_supply_for_owner",
+            json!({ "account_id": buyer_account.id() })
+                .to_string()
+                .into_bytes(),
+        )
+        .await?
+        .json::<String>()?;
+    assert_eq!(
+        owned,
+        PRIMARY_LISTING_BUY_QUANTITY_MAX.to_string(),
+        "    buyer owns {} tokens instead of {} {}",
+        owned,
+        PRIMARY_LISTING_BUY_QUANTITY_MAX,
+        "FAILED".red()
+    );
+
+    let gas_burnt = outcome
+        .receipt_outcomes()
+        .iter()
+        .fold(0, |acc, receipt| acc + receipt.gas_burnt)
+        + outcome.outcome().gas_burnt;
+    println!(" - gas burnt {}", gas_burnt);
+    println!(" - {}", "PASSED".green());
+
+    Ok(())
+}